@@ -61,7 +61,7 @@
     CompressedPublicKey, PubkeyHash, PublicKey, TweakedPublicKey, UntweakedPublicKey,
     XOnlyPublicKey,
 };
-use crate::network::{Network, NetworkKind, Params};
+use crate::network::{CustomNetwork, Network, NetworkKind, Params};
 use crate::prelude::{String, ToOwned};
 use crate::script::witness_program::WitnessProgram;
 use crate::script::witness_version::WitnessVersion;
@@ -184,31 +184,32 @@ enum AddressInner {
     Segwit { program: WitnessProgram, hrp: KnownHrp },
 }
 
-/// Formats bech32 as upper case if alternate formatting is chosen (`{:#}`).
-impl fmt::Display for AddressInner {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+impl AddressInner {
+    /// Formats the address using the given base58 prefixes and bech32 human-readable part.
+    ///
+    /// Formats bech32 as upper case if alternate formatting is chosen (`{:#}`).
+    fn fmt_with(
+        &self,
+        fmt: &mut fmt::Formatter,
+        pubkey_prefix: u8,
+        script_prefix: u8,
+        hrp: Hrp,
+    ) -> fmt::Result {
         use AddressInner::*;
         match self {
-            P2pkh { hash, network } => {
+            P2pkh { hash, .. } => {
                 let mut prefixed = [0; 21];
-                prefixed[0] = match network {
-                    NetworkKind::Main => PUBKEY_ADDRESS_PREFIX_MAIN,
-                    NetworkKind::Test => PUBKEY_ADDRESS_PREFIX_TEST,
-                };
+                prefixed[0] = pubkey_prefix;
                 prefixed[1..].copy_from_slice(hash.as_byte_array());
                 base58::encode_check_to_fmt(fmt, &prefixed[..])
             }
-            P2sh { hash, network } => {
+            P2sh { hash, .. } => {
                 let mut prefixed = [0; 21];
-                prefixed[0] = match network {
-                    NetworkKind::Main => SCRIPT_ADDRESS_PREFIX_MAIN,
-                    NetworkKind::Test => SCRIPT_ADDRESS_PREFIX_TEST,
-                };
+                prefixed[0] = script_prefix;
                 prefixed[1..].copy_from_slice(hash.as_byte_array());
                 base58::encode_check_to_fmt(fmt, &prefixed[..])
             }
-            Segwit { program, hrp } => {
-                let hrp = hrp.to_hrp();
+            Segwit { program, .. } => {
                 let version = Fe32::try_from(program.version().to_num())
                     .expect("version nums 0-16 are valid fe32 values");
                 let program = program.program().as_ref();
@@ -223,6 +224,39 @@ fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
     }
 }
 
+/// Formats bech32 as upper case if alternate formatting is chosen (`{:#}`).
+impl fmt::Display for AddressInner {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use AddressInner::*;
+        let (pubkey_prefix, script_prefix, hrp) = match *self {
+            P2pkh { network: NetworkKind::Main, .. } | P2sh { network: NetworkKind::Main, .. } =>
+                (PUBKEY_ADDRESS_PREFIX_MAIN, SCRIPT_ADDRESS_PREFIX_MAIN, bech32::hrp::BC),
+            P2pkh { network: NetworkKind::Test, .. } | P2sh { network: NetworkKind::Test, .. } =>
+                (PUBKEY_ADDRESS_PREFIX_TEST, SCRIPT_ADDRESS_PREFIX_TEST, bech32::hrp::TB),
+            Segwit { hrp, .. } =>
+                (PUBKEY_ADDRESS_PREFIX_MAIN, SCRIPT_ADDRESS_PREFIX_MAIN, hrp.to_hrp()),
+        };
+        self.fmt_with(fmt, pubkey_prefix, script_prefix, hrp)
+    }
+}
+
+/// Displays an address using the prefixes of a custom network.
+struct DisplayCustom<'a> {
+    inner: &'a AddressInner,
+    network: &'a CustomNetwork,
+}
+
+impl fmt::Display for DisplayCustom<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt_with(
+            fmt,
+            self.network.pubkey_address_prefix,
+            self.network.script_address_prefix,
+            self.network.bech32_hrp,
+        )
+    }
+}
+
 /// Known bech32 human-readable parts.
 ///
 /// This is the human-readable part before the separator (`1`) in a bech32 encoded address e.g.,
@@ -264,7 +298,7 @@ fn from_hrp(hrp: Hrp) -> Result<Self, UnknownHrpError> {
     }
 
     /// Converts, infallibly a known HRP to a [`bech32::Hrp`].
-    fn to_hrp(self) -> Hrp {
+    pub(crate) fn to_hrp(self) -> Hrp {
         match self {
             Self::Mainnet => bech32::hrp::BC,
             Self::Testnets => bech32::hrp::TB,
@@ -731,6 +765,8 @@ pub fn script_pubkey(&self) -> ScriptBuf {
     /// parsed as case-insensitive many wallets got this wrong and don't parse correctly.
     /// [See compatibility table.](https://github.com/btcpayserver/btcpayserver/issues/2110)
     ///
+    /// For URIs with an amount or other parameters see [`PaymentUri`](crate::bip21::PaymentUri).
+    ///
     /// If you want to avoid allocation you can use alternate display instead:
     /// ```
     /// # use core::fmt::Write;
@@ -748,6 +784,78 @@ pub fn script_pubkey(&self) -> ScriptBuf {
     /// ```
     pub fn to_qr_uri(self) -> String { format!("bitcoin:{:#}", self) }
 
+    /// Returns the string encoding of the address using the prefixes of the custom `network`.
+    ///
+    /// # Errors
+    ///
+    /// If the address is not valid for the [`NetworkKind`] of `network`.
+    pub fn to_string_custom(
+        self,
+        network: &CustomNetwork,
+    ) -> Result<String, NetworkValidationError> {
+        if self.network_kind() != network.network_kind() {
+            return Err(NetworkValidationError {
+                required: network.params.network,
+                address: self.into_unchecked(),
+            });
+        }
+        Ok(format!("{}", DisplayCustom { inner: self.inner(), network }))
+    }
+
+    /// Parses an address encoded with the prefixes of the custom `network`.
+    ///
+    /// The parsed address is checked to be valid on `network`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ParseError::Bech32`] if the address begins with the bech32 human-readable part of
+    ///   `network` and is not a valid SegWit address of it.
+    ///
+    /// - [`ParseError::Base58`] if the address is not a valid base58 address with one of the
+    ///   prefixes of `network`.
+    pub fn from_str_custom(s: &str, network: &CustomNetwork) -> Result<Address, ParseError> {
+        let hrp = network.bech32_hrp;
+        let is_bech32 = s.len() > hrp.len()
+            && s.is_char_boundary(hrp.len() + 1)
+            && s[..=hrp.len()].eq_ignore_ascii_case(&format!("{}1", hrp));
+        let inner = if is_bech32 {
+            let (decoded, witness_version, data) = bech32::segwit::decode(s)
+                .map_err(|e| Bech32Error::ParseBech32(ParseBech32Error(e)))?;
+            if decoded != hrp {
+                return Err(Bech32Error::from(UnknownHrpError(decoded.to_lowercase())).into());
+            }
+            let version =
+                WitnessVersion::try_from(witness_version.to_u8()).map_err(Bech32Error::from)?;
+            let program = WitnessProgram::new(version, &data)
+                .expect("bech32 guarantees valid program length for witness");
+            AddressInner::Segwit { program, hrp: KnownHrp::from(network.params.network) }
+        } else {
+            if s.len() > 50 {
+                return Err(Base58Error::from(LegacyAddressTooLongError { length: s.len() }).into());
+            }
+            let data = base58::decode_check(s).map_err(Base58Error::from)?;
+            let data: &[u8; 21] = (&*data).try_into().map_err(|_| {
+                Base58Error::from(InvalidBase58PayloadLengthError { length: s.len() })
+            })?;
+            let (prefix, &data) = data.split_first();
+            let kind = network.network_kind();
+
+            match *prefix {
+                p if p == network.pubkey_address_prefix => {
+                    let hash = PubkeyHash::from_byte_array(data);
+                    AddressInner::P2pkh { hash, network: kind }
+                }
+                p if p == network.script_address_prefix => {
+                    let hash = ScriptHash::from_byte_array(data);
+                    AddressInner::P2sh { hash, network: kind }
+                }
+                invalid =>
+                    return Err(Base58Error::from(InvalidLegacyPrefixError { invalid }).into()),
+            }
+        };
+        Ok(Address::from_inner(inner))
+    }
+
     /// Returns true if the given pubkey is directly related to the address payload.
     ///
     /// This is determined by directly comparing the address payload with either the
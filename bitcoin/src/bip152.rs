@@ -11,14 +11,15 @@
 
 use hashes::{sha256, siphash24};
 use internals::array::ArrayExt as _;
-use internals::ToU64 as _;
+use internals::{write_err, ToU64 as _};
 use io::{BufRead, Write};
 
+use crate::block::{BlockUncheckedExt as _, InvalidBlockError};
 use crate::consensus::encode::{self, Decodable, Encodable, ReadExt, WriteExt};
 use crate::internal_macros::{
     impl_array_newtype, impl_array_newtype_stringify, impl_consensus_encoding,
 };
-use crate::prelude::Vec;
+use crate::prelude::{BTreeMap, Vec};
 use crate::transaction::TxIdentifier;
 use crate::{block, consensus, Block, BlockChecked, BlockHash, Transaction};
 
@@ -404,6 +405,234 @@ pub fn from_request(
     }
 }
 
+/// Reconstructs a block from a compact block and the transactions in the mempool.
+///
+/// The transactions not found among the candidates are requested from the peer with the
+/// [`BlockTransactionsRequest`] returned by [`BlockReconstructor::request`]. The block is
+/// completed with the [`BlockTransactions`] sent in response.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BlockReconstructor {
+    header: block::Header,
+    version: u32,
+    siphash_keys: (u64, u64),
+    /// The transactions of the block, by index.
+    slots: Vec<Slot>,
+    /// The block index of each short ID.
+    short_ids: BTreeMap<ShortId, usize>,
+}
+
+/// A transaction of a block being reconstructed.
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum Slot {
+    Missing,
+    Available(Transaction),
+    /// Several candidates match the short ID, the transaction has to be requested.
+    Collision,
+}
+
+impl BlockReconstructor {
+    /// Constructs a new [`BlockReconstructor`] from a compact block.
+    ///
+    /// The version number is the one negotiated with `sendcmpct`, version 1 short IDs are
+    /// calculated from txids and version 2 short IDs from wtxids.
+    ///
+    /// Returns [`ReconstructError::ShortIdCollision`] if two short IDs of the compact block are
+    /// the same, the full block should be requested instead.
+    pub fn new(compact: &HeaderAndShortIds, version: u32) -> Result<Self, ReconstructError> {
+        if version != 1 && version != 2 {
+            return Err(ReconstructError::UnknownVersion);
+        }
+
+        let count = compact.short_ids.len() + compact.prefilled_txs.len();
+        if count == 0 || count > u16::MAX.into() {
+            return Err(ReconstructError::InvalidTransactionCount(count));
+        }
+
+        let mut slots = vec![Slot::Missing; count];
+        let mut next_idx = 0;
+        for prefilled in &compact.prefilled_txs {
+            // Indexes are differentially encoded.
+            let idx = next_idx + usize::from(prefilled.idx);
+            if idx >= count {
+                return Err(ReconstructError::InvalidPrefill);
+            }
+            slots[idx] = Slot::Available(prefilled.tx.clone());
+            next_idx = idx + 1;
+        }
+
+        let mut short_ids = BTreeMap::new();
+        let missing = slots.iter().enumerate().filter(|(_, slot)| **slot == Slot::Missing);
+        for (short_id, (idx, _)) in compact.short_ids.iter().zip(missing) {
+            if short_ids.insert(*short_id, idx).is_some() {
+                return Err(ReconstructError::ShortIdCollision);
+            }
+        }
+
+        Ok(BlockReconstructor {
+            header: compact.header,
+            version,
+            siphash_keys: ShortId::calculate_siphash_keys(&compact.header, compact.nonce),
+            slots,
+            short_ids,
+        })
+    }
+
+    /// Returns the header of the block being reconstructed.
+    pub fn header(&self) -> &block::Header { &self.header }
+
+    /// Fills in the block transactions found among `candidates`, typically the mempool.
+    ///
+    /// A transaction matched by several candidates is left missing. Returns the number of
+    /// transactions still missing.
+    pub fn fill_from_mempool<'a, I>(&mut self, candidates: I) -> usize
+    where
+        I: IntoIterator<Item = &'a Transaction>,
+    {
+        for tx in candidates {
+            let short_id = match self.version {
+                1 => ShortId::with_siphash_keys(&tx.compute_txid(), self.siphash_keys),
+                _ => ShortId::with_siphash_keys(&tx.compute_wtxid(), self.siphash_keys),
+            };
+            if let Some(&idx) = self.short_ids.get(&short_id) {
+                let slot = &mut self.slots[idx];
+                match slot {
+                    Slot::Missing => *slot = Slot::Available(tx.clone()),
+                    Slot::Available(found) if found != tx => *slot = Slot::Collision,
+                    Slot::Available(_) | Slot::Collision => {}
+                }
+            }
+        }
+        self.missing_indexes().count()
+    }
+
+    /// Returns true if all the block transactions are available.
+    pub fn is_complete(&self) -> bool { self.missing_indexes().next().is_none() }
+
+    /// Returns the request for the missing block transactions.
+    pub fn request(&self) -> BlockTransactionsRequest {
+        BlockTransactionsRequest {
+            block_hash: self.header.block_hash(),
+            indexes: self.missing_indexes().map(|idx| idx.to_u64()).collect(),
+        }
+    }
+
+    /// Completes the block with the transactions sent in response to [`BlockReconstructor::request`].
+    pub fn complete(
+        mut self,
+        block_txn: &BlockTransactions,
+    ) -> Result<Block<BlockChecked>, ReconstructError> {
+        if block_txn.block_hash != self.header.block_hash() {
+            return Err(ReconstructError::BlockHashMismatch);
+        }
+        let missing = self.missing_indexes().collect::<Vec<_>>();
+        if block_txn.transactions.len() != missing.len() {
+            return Err(ReconstructError::TransactionCountMismatch {
+                expected: missing.len(),
+                actual: block_txn.transactions.len(),
+            });
+        }
+        for (idx, tx) in missing.into_iter().zip(&block_txn.transactions) {
+            self.slots[idx] = Slot::Available(tx.clone());
+        }
+        self.into_block()
+    }
+
+    /// Converts the reconstructor into the block if no transactions are missing.
+    ///
+    /// The Merkle root and witness commitment are checked, a mismatch means a short ID matched the
+    /// wrong transaction and the full block should be requested instead.
+    pub fn into_block(self) -> Result<Block<BlockChecked>, ReconstructError> {
+        let missing = self.missing_indexes().count();
+        let mut transactions = Vec::with_capacity(self.slots.len());
+        for slot in self.slots {
+            match slot {
+                Slot::Available(tx) => transactions.push(tx),
+                Slot::Missing | Slot::Collision =>
+                    return Err(ReconstructError::MissingTransactions(missing)),
+            }
+        }
+        Block::new_unchecked(self.header, transactions)
+            .validate()
+            .map_err(ReconstructError::InvalidBlock)
+    }
+
+    fn missing_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| !matches!(slot, Slot::Available(_)))
+            .map(|(idx, _)| idx)
+    }
+}
+
+/// An error reconstructing a block from a compact block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReconstructError {
+    /// An unknown version number was used.
+    UnknownVersion,
+    /// The compact block has no transactions or more than fit in 16-bit indexes.
+    InvalidTransactionCount(usize),
+    /// A prefilled transaction index is out of range.
+    InvalidPrefill,
+    /// The compact block contains the same short ID twice.
+    ShortIdCollision,
+    /// The block transactions are for a different block.
+    BlockHashMismatch,
+    /// The number of block transactions is not the number requested.
+    TransactionCountMismatch {
+        /// The number of transactions requested.
+        expected: usize,
+        /// The number of transactions received.
+        actual: usize,
+    },
+    /// This number of transactions is still missing.
+    MissingTransactions(usize),
+    /// The reconstructed block is invalid.
+    InvalidBlock(InvalidBlockError),
+}
+
+impl From<Infallible> for ReconstructError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for ReconstructError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ReconstructError::*;
+
+        match *self {
+            UnknownVersion => write!(f, "an unknown version number was used"),
+            InvalidTransactionCount(count) =>
+                write!(f, "invalid number of transactions in compact block: {}", count),
+            InvalidPrefill => write!(f, "prefilled transaction index out of range"),
+            ShortIdCollision => write!(f, "duplicate short ID in compact block"),
+            BlockHashMismatch => write!(f, "block transactions are for a different block"),
+            TransactionCountMismatch { expected, actual } =>
+                write!(f, "expected {} block transactions but received {}", expected, actual),
+            MissingTransactions(count) => write!(f, "{} block transactions are missing", count),
+            InvalidBlock(ref e) => write_err!(f, "reconstructed block is invalid"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReconstructError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ReconstructError::*;
+
+        match *self {
+            InvalidBlock(ref e) => Some(e),
+            UnknownVersion
+            | InvalidTransactionCount(_)
+            | InvalidPrefill
+            | ShortIdCollision
+            | BlockHashMismatch
+            | TransactionCountMismatch { .. }
+            | MissingTransactions(_) => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use hex::FromHex;
@@ -446,6 +675,13 @@ fn dummy_block() -> Block<BlockChecked> {
         Block::new_unchecked(header, transactions).assume_checked(None)
     }
 
+    /// A regtest block with a coinbase and a segwit transaction.
+    fn vector_block() -> Block<BlockChecked> {
+        let raw_block = Vec::<u8>::from_hex("000000206c750a364035aefd5f81508a08769975116d9195312ee4520dceac39e1fdc62c4dc67473b8e354358c1e610afeaff7410858bd45df43e2940f8a62bd3d5e3ac943c2975cffff7f200000000002020000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff04016b0101ffffffff020006062a0100000001510000000000000000266a24aa21a9ed4a3d9f3343dafcc0d6f6d4310f2ee5ce273ed34edca6c75db3a73e7f368734200120000000000000000000000000000000000000000000000000000000000000000000000000020000000001021fc20ba2bd745507b8e00679e3b362558f9457db374ca28ffa5243f4c23a4d5f00000000171600147c9dea14ffbcaec4b575e03f05ceb7a81cd3fcbffdffffff915d689be87b43337f42e26033df59807b768223368f189a023d0242d837768900000000171600147c9dea14ffbcaec4b575e03f05ceb7a81cd3fcbffdffffff0200cdf5050000000017a9146803c72d9154a6a20f404bed6d3dcee07986235a8700e1f5050000000017a9144e6a4c7cb5b5562904843bdf816342f4db9f5797870247304402205e9bf6e70eb0e4b495bf483fd8e6e02da64900f290ef8aaa64bb32600d973c450220670896f5d0e5f33473e5f399ab680cc1d25c2d2afd15abd722f04978f28be887012103e4e4d9312b2261af508b367d8ba9be4f01b61d6d6e78bec499845b4f410bcf2702473044022045ac80596a6ac9c8c572f94708709adaf106677221122e08daf8b9741a04f66a022003ccd52a3b78f8fd08058fc04fc0cffa5f4c196c84eae9e37e2a85babe731b57012103e4e4d9312b2261af508b367d8ba9be4f01b61d6d6e78bec499845b4f410bcf276a000000").unwrap();
+        let block: Block = deserialize(&raw_block).unwrap();
+        block.validate().unwrap()
+    }
+
     #[test]
     fn header_and_short_ids_from_block() {
         let block = dummy_block();
@@ -481,6 +717,119 @@ fn compact_block_vector() {
         assert_eq!(compact, compact_expected);
     }
 
+    #[test]
+    fn reconstruct_from_mempool() {
+        let block = vector_block();
+        let mempool = [dummy_tx(&[1]), block.transactions()[1].clone(), dummy_tx(&[2])];
+
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[]).unwrap();
+        let mut reconstructor = BlockReconstructor::new(&compact, 2).unwrap();
+        assert!(!reconstructor.is_complete());
+        assert_eq!(reconstructor.fill_from_mempool(&mempool), 0);
+        assert!(reconstructor.is_complete());
+        assert!(reconstructor.request().indexes.is_empty());
+        assert_eq!(reconstructor.into_block().unwrap(), block);
+    }
+
+    #[test]
+    fn reconstruct_from_mempool_txid_short_ids() {
+        let transactions = vec![dummy_tx(&[2]), dummy_tx(&[3]), dummy_tx(&[4])];
+        let header = block::Header {
+            merkle_root: block::compute_merkle_root(&transactions).unwrap(),
+            ..*dummy_block().header()
+        };
+        let block = Block::new_unchecked(header, transactions).validate().unwrap();
+
+        // The same transaction as in the block, but with a different wtxid.
+        let mut with_witness = block.transactions()[1].clone();
+        with_witness.input[0].witness.push([1]);
+        let mempool = [dummy_tx(&[1]), with_witness, block.transactions()[2].clone()];
+
+        // Version 2 short IDs are calculated from wtxids.
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[]).unwrap();
+        let mut reconstructor = BlockReconstructor::new(&compact, 2).unwrap();
+        assert_eq!(reconstructor.fill_from_mempool(&mempool), 1);
+        assert_eq!(reconstructor.request().indexes, [1]);
+
+        // Version 1 short IDs are calculated from txids.
+        let compact = HeaderAndShortIds::from_block(&block, 42, 1, &[]).unwrap();
+        let mut reconstructor = BlockReconstructor::new(&compact, 1).unwrap();
+        assert_eq!(reconstructor.fill_from_mempool(&mempool[..1]), 2);
+        assert_eq!(reconstructor.request().indexes, [1, 2]);
+        assert_eq!(reconstructor.fill_from_mempool(&block.transactions()[1..]), 0);
+        assert!(reconstructor.is_complete());
+        assert_eq!(reconstructor.into_block().unwrap(), block);
+
+        // A transaction matched with the wrong witness is caught validating the block.
+        let mut reconstructor = BlockReconstructor::new(&compact, 1).unwrap();
+        assert_eq!(reconstructor.fill_from_mempool(&mempool), 0);
+        assert_eq!(
+            reconstructor.into_block(),
+            Err(ReconstructError::InvalidBlock(InvalidBlockError::InvalidWitnessCommitment))
+        );
+    }
+
+    #[test]
+    fn reconstruct_with_block_transactions() {
+        let block = vector_block();
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[]).unwrap();
+        let mut reconstructor = BlockReconstructor::new(&compact, 2).unwrap();
+        assert_eq!(reconstructor.fill_from_mempool(&[dummy_tx(&[1])]), 1);
+        assert_eq!(
+            reconstructor.clone().into_block(),
+            Err(ReconstructError::MissingTransactions(1))
+        );
+
+        let request = reconstructor.request();
+        assert_eq!(request.block_hash, block.block_hash());
+        assert_eq!(request.indexes, [1]);
+
+        let block_txn = BlockTransactions::from_request(&request, &block).unwrap();
+        let mut wrong_hash = block_txn.clone();
+        wrong_hash.block_hash = BlockHash::from_byte_array([0; 32]);
+        assert_eq!(
+            reconstructor.clone().complete(&wrong_hash),
+            Err(ReconstructError::BlockHashMismatch)
+        );
+        let mut wrong_tx = block_txn.clone();
+        wrong_tx.transactions[0] = dummy_tx(&[1]);
+        assert_eq!(
+            reconstructor.clone().complete(&wrong_tx),
+            Err(ReconstructError::InvalidBlock(InvalidBlockError::InvalidMerkleRoot))
+        );
+        let mut too_many = block_txn.clone();
+        too_many.transactions.push(dummy_tx(&[1]));
+        assert_eq!(
+            reconstructor.clone().complete(&too_many),
+            Err(ReconstructError::TransactionCountMismatch { expected: 1, actual: 2 })
+        );
+        assert_eq!(reconstructor.complete(&block_txn).unwrap(), block);
+    }
+
+    #[test]
+    fn reconstruct_invalid_compact_block() {
+        let block = dummy_block();
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[]).unwrap();
+        assert_eq!(BlockReconstructor::new(&compact, 3), Err(ReconstructError::UnknownVersion));
+
+        let mut duplicate = compact.clone();
+        duplicate.short_ids[1] = duplicate.short_ids[0];
+        assert_eq!(BlockReconstructor::new(&duplicate, 2), Err(ReconstructError::ShortIdCollision));
+
+        let mut out_of_range = compact.clone();
+        out_of_range.prefilled_txs[0].idx = 3;
+        assert_eq!(
+            BlockReconstructor::new(&out_of_range, 2),
+            Err(ReconstructError::InvalidPrefill)
+        );
+
+        let empty = HeaderAndShortIds { short_ids: vec![], prefilled_txs: vec![], ..compact };
+        assert_eq!(
+            BlockReconstructor::new(&empty, 2),
+            Err(ReconstructError::InvalidTransactionCount(0))
+        );
+    }
+
     #[test]
     fn getblocktx_differential_encoding_de_and_serialization() {
         let testcases = vec![
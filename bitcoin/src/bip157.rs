@@ -0,0 +1,425 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP-157 Client Side Block Filtering.
+//!
+//! Verification of the filter headers and filters a light client receives from untrusted peers.
+//!
+//! A client first requests the filter header checkpoints with `getcfcheckpt`, ideally from several
+//! peers, then fills in the filter headers between them with `getcfheaders`. Each filter received
+//! with `getcfilters` is checked against its filter header.
+//!
+//! # Relevant BIPS
+//!
+//! * [BIP 157 - Client Side Block Filtering](https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki)
+//! * [BIP 158 - Compact Block Filters for Light Clients](https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki)
+
+use core::convert::Infallible;
+use core::fmt;
+
+use units::BlockHeight;
+
+use crate::bip158::{BlockFilter, FilterHash, FilterHeader};
+use crate::p2p::message_filter::{CFCheckpt, CFHeaders, CFilter};
+use crate::prelude::Vec;
+
+/// The filter type of the basic filter defined by BIP-158.
+pub const BASIC_FILTER_TYPE: u8 = 0;
+
+/// The number of blocks between filter header checkpoints.
+pub const CHECKPOINT_INTERVAL: u32 = 1000;
+
+/// The maximum number of filter hashes in a `cfheaders` message.
+pub const MAX_CFHEADERS: usize = 2000;
+
+/// The filter header preceding the filter header of the genesis block.
+const GENESIS_PREVIOUS_HEADER: FilterHeader = FilterHeader::from_byte_array([0; 32]);
+
+/// A chain of verified filter headers, starting at the genesis block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterHeaderChain {
+    filter_type: u8,
+    /// The filter header of every block, indexed by height.
+    headers: Vec<FilterHeader>,
+    /// The filter headers at every multiple of [`CHECKPOINT_INTERVAL`], starting with height 1000.
+    checkpoints: Vec<FilterHeader>,
+}
+
+impl FilterHeaderChain {
+    /// Constructs a new empty chain of filter headers of type `filter_type`.
+    pub fn new(filter_type: u8) -> Self {
+        FilterHeaderChain { filter_type, headers: Vec::new(), checkpoints: Vec::new() }
+    }
+
+    /// Returns the filter type.
+    pub fn filter_type(&self) -> u8 { self.filter_type }
+
+    /// Returns the height of the last filter header, or `None` if the chain is empty.
+    pub fn tip_height(&self) -> Option<BlockHeight> {
+        self.headers.len().checked_sub(1).map(|height| BlockHeight::from_u32(height as u32))
+    }
+
+    /// Returns the filter header of the block at `height`, if it is known.
+    pub fn filter_header(&self, height: BlockHeight) -> Option<FilterHeader> {
+        self.headers.get(height.to_u32() as usize).copied()
+    }
+
+    /// Returns the checkpoints, the filter headers at heights 1000, 2000 and so on.
+    pub fn checkpoints(&self) -> &[FilterHeader] { &self.checkpoints }
+
+    /// Sets the checkpoints from a `cfcheckpt` message.
+    ///
+    /// The checkpoints must agree with the filter headers and checkpoints already known, further
+    /// checkpoints are added.
+    pub fn add_checkpoints(&mut self, cfcheckpt: &CFCheckpt) -> Result<(), Error> {
+        self.check_filter_type(cfcheckpt.filter_type)?;
+        for (i, checkpoint) in cfcheckpt.filter_headers.iter().enumerate() {
+            let height = checkpoint_height(i);
+            let known = self.checkpoints.get(i).or_else(|| self.headers.get(height as usize));
+            if known.map_or(false, |known| known != checkpoint) {
+                return Err(Error::CheckpointMismatch(BlockHeight::from_u32(height)));
+            }
+        }
+        if cfcheckpt.filter_headers.len() > self.checkpoints.len() {
+            self.checkpoints = cfcheckpt.filter_headers.clone();
+        }
+        Ok(())
+    }
+
+    /// Verifies the filter headers of a `cfheaders` message and adds them to the chain.
+    ///
+    /// `start_height` is the height of the first block requested with `getcfheaders`, the
+    /// message's previous filter header must be the one at the height before. The requested range
+    /// may overlap with the known filter headers, which must then match. Checking that the stop
+    /// hash is the requested one is up to the caller.
+    pub fn connect_cfheaders(
+        &mut self,
+        start_height: BlockHeight,
+        cfheaders: &CFHeaders,
+    ) -> Result<(), Error> {
+        self.check_filter_type(cfheaders.filter_type)?;
+        if cfheaders.filter_hashes.len() > MAX_CFHEADERS {
+            return Err(Error::TooManyFilterHashes(cfheaders.filter_hashes.len()));
+        }
+        let start = start_height.to_u32() as usize;
+        if start > self.headers.len() {
+            return Err(Error::MissingFilterHeaders {
+                start_height,
+                next_height: self.next_height(),
+            });
+        }
+        if cfheaders.previous_filter_header != self.previous_header(start) {
+            return Err(Error::PreviousHeaderMismatch);
+        }
+
+        let headers = filter_headers(cfheaders.previous_filter_header, &cfheaders.filter_hashes);
+        for (height, header) in (start..).zip(&headers) {
+            let checkpoint = (height as u32 % CHECKPOINT_INTERVAL == 0 && height > 0)
+                .then(|| self.checkpoints.get(height / CHECKPOINT_INTERVAL as usize - 1))
+                .flatten();
+            if checkpoint.map_or(false, |checkpoint| checkpoint != header) {
+                return Err(Error::CheckpointMismatch(BlockHeight::from_u32(height as u32)));
+            }
+            if self.headers.get(height).map_or(false, |known| known != header) {
+                return Err(Error::HeaderMismatch(BlockHeight::from_u32(height as u32)));
+            }
+        }
+
+        let new = (start + headers.len()).saturating_sub(self.headers.len());
+        self.headers.extend_from_slice(&headers[headers.len() - new..]);
+        Ok(())
+    }
+
+    /// Verifies a filter received in a `cfilter` message for the block at `height`.
+    ///
+    /// Checking that the block hash is the one at `height` is up to the caller.
+    pub fn verify_cfilter(&self, height: BlockHeight, cfilter: &CFilter) -> Result<(), Error> {
+        self.check_filter_type(cfilter.filter_type)?;
+        let header = self.filter_header(height).ok_or(Error::UnknownFilterHeader(height))?;
+        let previous = self.previous_header(height.to_u32() as usize);
+        if BlockFilter::new(&cfilter.filter).filter_header(previous) != header {
+            return Err(Error::FilterMismatch(height));
+        }
+        Ok(())
+    }
+
+    /// Returns the filter header preceding the one at `height`.
+    fn previous_header(&self, height: usize) -> FilterHeader {
+        match height.checked_sub(1) {
+            Some(previous) => self.headers[previous],
+            None => GENESIS_PREVIOUS_HEADER,
+        }
+    }
+
+    fn next_height(&self) -> BlockHeight { BlockHeight::from_u32(self.headers.len() as u32) }
+
+    fn check_filter_type(&self, filter_type: u8) -> Result<(), Error> {
+        if filter_type != self.filter_type {
+            return Err(Error::FilterType(filter_type));
+        }
+        Ok(())
+    }
+}
+
+/// Computes the filter headers for `filter_hashes`, following `previous_filter_header`.
+pub fn filter_headers(
+    previous_filter_header: FilterHeader,
+    filter_hashes: &[FilterHash],
+) -> Vec<FilterHeader> {
+    filter_hashes
+        .iter()
+        .scan(previous_filter_header, |previous, filter_hash| {
+            *previous = filter_hash.filter_header(*previous);
+            Some(*previous)
+        })
+        .collect()
+}
+
+/// Returns the height of the checkpoint at `index`.
+fn checkpoint_height(index: usize) -> u32 { (index as u32 + 1) * CHECKPOINT_INTERVAL }
+
+/// An error verifying filter headers or filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The message is for a different filter type.
+    FilterType(u8),
+    /// A `cfheaders` message contains more than [`MAX_CFHEADERS`] filter hashes.
+    TooManyFilterHashes(usize),
+    /// A `cfheaders` message starts after the next filter header of the chain.
+    MissingFilterHeaders {
+        /// The height of the first filter header of the message.
+        start_height: BlockHeight,
+        /// The height of the next filter header of the chain.
+        next_height: BlockHeight,
+    },
+    /// The previous filter header of a `cfheaders` message is not the one in the chain.
+    PreviousHeaderMismatch,
+    /// A filter header conflicts with the checkpoint at this height.
+    CheckpointMismatch(BlockHeight),
+    /// A filter header conflicts with the known filter header at this height.
+    HeaderMismatch(BlockHeight),
+    /// The filter header at this height is not known yet.
+    UnknownFilterHeader(BlockHeight),
+    /// The filter does not match the filter header at this height.
+    FilterMismatch(BlockHeight),
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            FilterType(filter_type) => write!(f, "unexpected filter type {}", filter_type),
+            TooManyFilterHashes(n) =>
+                write!(f, "{} filter hashes exceed the maximum of {}", n, MAX_CFHEADERS),
+            MissingFilterHeaders { start_height, next_height } => write!(
+                f,
+                "filter headers start at height {} but the next height is {}",
+                start_height, next_height
+            ),
+            PreviousHeaderMismatch => f.write_str("previous filter header does not match"),
+            CheckpointMismatch(height) =>
+                write!(f, "filter header conflicts with the checkpoint at height {}", height),
+            HeaderMismatch(height) =>
+                write!(f, "filter header conflicts with the known header at height {}", height),
+            UnknownFilterHeader(height) => write!(f, "no filter header at height {}", height),
+            FilterMismatch(height) =>
+                write!(f, "filter does not match the filter header at height {}", height),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            FilterType(_)
+            | TooManyFilterHashes(_)
+            | MissingFilterHeaders { .. }
+            | PreviousHeaderMismatch
+            | CheckpointMismatch(_)
+            | HeaderMismatch(_)
+            | UnknownFilterHeader(_)
+            | FilterMismatch(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_lit::hex;
+
+    use super::*;
+    use crate::block::BlockHash;
+
+    fn filter(height: u32) -> BlockFilter { BlockFilter::new(&height.to_le_bytes()) }
+
+    fn cfheaders(
+        previous_filter_header: FilterHeader,
+        heights: core::ops::Range<u32>,
+    ) -> CFHeaders {
+        CFHeaders {
+            filter_type: BASIC_FILTER_TYPE,
+            stop_hash: BlockHash::from_byte_array([0; 32]),
+            previous_filter_header,
+            filter_hashes: heights.map(|height| filter(height).filter_hash()).collect(),
+        }
+    }
+
+    fn cfilter(height: u32) -> CFilter {
+        CFilter {
+            filter_type: BASIC_FILTER_TYPE,
+            block_hash: BlockHash::from_byte_array([0; 32]),
+            filter: filter(height).content,
+        }
+    }
+
+    /// Returns the filter headers of the blocks up to `end`.
+    fn headers(end: u32) -> Vec<FilterHeader> {
+        let hashes = (0..end).map(|height| filter(height).filter_hash()).collect::<Vec<_>>();
+        filter_headers(GENESIS_PREVIOUS_HEADER, &hashes)
+    }
+
+    #[test]
+    fn genesis() {
+        // From the BIP-158 test vectors, testnet genesis block.
+        let content = hex!("019dfca8");
+        let mut chain = FilterHeaderChain::new(BASIC_FILTER_TYPE);
+        let message = CFHeaders {
+            filter_type: BASIC_FILTER_TYPE,
+            stop_hash: BlockHash::from_byte_array([0; 32]),
+            previous_filter_header: GENESIS_PREVIOUS_HEADER,
+            filter_hashes: vec![BlockFilter::new(&content).filter_hash()],
+        };
+        chain.connect_cfheaders(BlockHeight::ZERO, &message).unwrap();
+        assert_eq!(
+            chain.filter_header(BlockHeight::ZERO).unwrap(),
+            "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750".parse().unwrap()
+        );
+
+        let mut message = CFilter {
+            filter_type: BASIC_FILTER_TYPE,
+            block_hash: BlockHash::from_byte_array([0; 32]),
+            filter: content.to_vec(),
+        };
+        chain.verify_cfilter(BlockHeight::ZERO, &message).unwrap();
+        message.filter[0] ^= 1;
+        assert_eq!(
+            chain.verify_cfilter(BlockHeight::ZERO, &message),
+            Err(Error::FilterMismatch(BlockHeight::ZERO))
+        );
+    }
+
+    #[test]
+    fn connect_cfheaders() {
+        let expected = headers(3000);
+        let mut chain = FilterHeaderChain::new(BASIC_FILTER_TYPE);
+        chain
+            .add_checkpoints(&CFCheckpt {
+                filter_type: BASIC_FILTER_TYPE,
+                stop_hash: BlockHash::from_byte_array([0; 32]),
+                filter_headers: vec![expected[1000], expected[2000]],
+            })
+            .unwrap();
+
+        chain
+            .connect_cfheaders(BlockHeight::ZERO, &cfheaders(GENESIS_PREVIOUS_HEADER, 0..2000))
+            .unwrap();
+        assert_eq!(chain.tip_height(), Some(BlockHeight::from_u32(1999)));
+        // Overlapping ranges are accepted if they agree.
+        chain
+            .connect_cfheaders(BlockHeight::from_u32(1500), &cfheaders(expected[1499], 1500..3000))
+            .unwrap();
+        assert_eq!(chain.tip_height(), Some(BlockHeight::from_u32(2999)));
+        assert_eq!(chain.filter_header(BlockHeight::from_u32(2999)), Some(expected[2999]));
+        assert_eq!(chain.filter_header(BlockHeight::from_u32(3000)), None);
+
+        for height in [0, 1000, 2999] {
+            chain.verify_cfilter(BlockHeight::from_u32(height), &cfilter(height)).unwrap();
+        }
+        assert_eq!(
+            chain.verify_cfilter(BlockHeight::from_u32(5), &cfilter(6)),
+            Err(Error::FilterMismatch(BlockHeight::from_u32(5)))
+        );
+        assert_eq!(
+            chain.verify_cfilter(BlockHeight::from_u32(3000), &cfilter(3000)),
+            Err(Error::UnknownFilterHeader(BlockHeight::from_u32(3000)))
+        );
+    }
+
+    #[test]
+    fn invalid_cfheaders() {
+        let expected = headers(1001);
+        let mut chain = FilterHeaderChain::new(BASIC_FILTER_TYPE);
+        chain
+            .add_checkpoints(&CFCheckpt {
+                filter_type: BASIC_FILTER_TYPE,
+                stop_hash: BlockHash::from_byte_array([0; 32]),
+                filter_headers: vec![expected[1000]],
+            })
+            .unwrap();
+
+        // A peer serving different filters is caught by the checkpoint.
+        let mut message = cfheaders(GENESIS_PREVIOUS_HEADER, 0..1001);
+        message.filter_hashes[10] = filter(0).filter_hash();
+        assert_eq!(
+            chain.connect_cfheaders(BlockHeight::ZERO, &message),
+            Err(Error::CheckpointMismatch(BlockHeight::from_u32(1000)))
+        );
+        assert_eq!(chain.tip_height(), None);
+
+        chain
+            .connect_cfheaders(BlockHeight::ZERO, &cfheaders(GENESIS_PREVIOUS_HEADER, 0..500))
+            .unwrap();
+        assert_eq!(
+            chain
+                .connect_cfheaders(BlockHeight::from_u32(501), &cfheaders(expected[500], 501..600)),
+            Err(Error::MissingFilterHeaders {
+                start_height: BlockHeight::from_u32(501),
+                next_height: BlockHeight::from_u32(500)
+            })
+        );
+        assert_eq!(
+            chain
+                .connect_cfheaders(BlockHeight::from_u32(500), &cfheaders(expected[498], 500..600)),
+            Err(Error::PreviousHeaderMismatch)
+        );
+        let mut message = cfheaders(expected[399], 400..600);
+        message.filter_hashes[0] = filter(0).filter_hash();
+        assert_eq!(
+            chain.connect_cfheaders(BlockHeight::from_u32(400), &message),
+            Err(Error::HeaderMismatch(BlockHeight::from_u32(400)))
+        );
+        let mut message = cfheaders(expected[499], 500..600);
+        message.filter_type = 1;
+        assert_eq!(
+            chain.connect_cfheaders(BlockHeight::from_u32(500), &message),
+            Err(Error::FilterType(1))
+        );
+        assert_eq!(
+            chain.connect_cfheaders(
+                BlockHeight::from_u32(500),
+                &cfheaders(expected[499], 500..2501)
+            ),
+            Err(Error::TooManyFilterHashes(2001))
+        );
+        assert_eq!(chain.tip_height(), Some(BlockHeight::from_u32(499)));
+
+        // Conflicting checkpoints.
+        let checkpoint = CFCheckpt {
+            filter_type: BASIC_FILTER_TYPE,
+            stop_hash: BlockHash::from_byte_array([0; 32]),
+            filter_headers: vec![expected[999]],
+        };
+        assert_eq!(
+            chain.add_checkpoints(&checkpoint),
+            Err(Error::CheckpointMismatch(BlockHeight::from_u32(1000)))
+        );
+    }
+}
@@ -0,0 +1,420 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP-21 Payment URIs.
+//!
+//! Parses and builds `bitcoin:` URIs of the form
+//! `bitcoin:<address>[?amount=<amount>][&label=<label>][&message=<message>]`, as well as any other
+//! parameter (e.g. `lightning=` or BIP-78 `pj=`). Parameter values are percent-encoded.
+//!
+//! Parameters prefixed with `req-` are required: a wallet which does not understand one of them
+//! must consider the whole URI invalid, see [`PaymentUri::check_required_params`].
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::bip21::PaymentUri;
+//! use bitcoin::{Amount, Network};
+//!
+//! let s = "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?amount=20.3&label=Luke-Jr";
+//! let uri = s.parse::<PaymentUri<_>>().unwrap().require_network(Network::Bitcoin).unwrap();
+//!
+//! assert_eq!(uri.amount, Some(Amount::from_sat(2_030_000_000).unwrap()));
+//! assert_eq!(uri.label.as_deref(), Some("Luke-Jr"));
+//! assert_eq!(uri.to_string(), s);
+//! ```
+
+use core::convert::Infallible;
+use core::fmt;
+use core::str::FromStr;
+
+use internals::write_err;
+
+use crate::address::{self, Address, NetworkChecked, NetworkUnchecked, NetworkValidation};
+use crate::amount::{Denomination, ParseAmountError};
+use crate::prelude::{String, ToString, Vec};
+use crate::{Amount, Network};
+
+/// The URI scheme, matched case-insensitively when parsing.
+const SCHEME: &str = "bitcoin:";
+
+/// A BIP-21 payment URI.
+///
+/// Like [`Address`], a URI can only be parsed as `PaymentUri<NetworkUnchecked>` and the network
+/// of its address must be checked with [`PaymentUri::require_network`] before use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentUri<V: NetworkValidation = NetworkChecked> {
+    /// The address to pay to.
+    pub address: Address<V>,
+    /// The amount requested, always encoded in BTC.
+    pub amount: Option<Amount>,
+    /// Label for the address, e.g. the name of the receiver.
+    pub label: Option<String>,
+    /// Message describing the transaction to the user.
+    pub message: Option<String>,
+    /// All other parameters, decoded, in the order they appear in the URI.
+    ///
+    /// This includes required (`req-` prefixed) parameters and extensions like `lightning` or `pj`.
+    pub params: Vec<(String, String)>,
+}
+
+impl<V: NetworkValidation> PaymentUri<V> {
+    /// Constructs a new URI paying to `address` without any parameters.
+    pub fn new(address: Address<V>) -> Self {
+        PaymentUri { address, amount: None, label: None, message: None, params: Vec::new() }
+    }
+
+    /// Returns the value of the first parameter named `key`, if any.
+    ///
+    /// Only looks into [`PaymentUri::params`], i.e. `amount`, `label` and `message` are never
+    /// returned.
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the BOLT-11 invoice of the `lightning` parameter, if any.
+    pub fn lightning(&self) -> Option<&str> { self.param("lightning") }
+
+    /// Returns the BIP-78 payjoin endpoint of the `pj` parameter, if any.
+    pub fn payjoin_endpoint(&self) -> Option<&str> { self.param("pj") }
+
+    /// Returns an iterator over the required (`req-` prefixed) parameters.
+    ///
+    /// The `req-` prefix is stripped from the returned keys.
+    pub fn required_params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().filter_map(|(k, v)| k.strip_prefix("req-").map(|k| (k, v.as_str())))
+    }
+
+    /// Checks that all required parameters are in `understood`.
+    ///
+    /// Keys in `understood` are given without the `req-` prefix. BIP-21 mandates that a URI with
+    /// a required parameter the client does not understand is rejected.
+    pub fn check_required_params(
+        &self,
+        understood: &[&str],
+    ) -> Result<(), UnknownRequiredParamError> {
+        match self.required_params().find(|(k, _)| !understood.contains(k)) {
+            Some((key, _)) => Err(UnknownRequiredParamError(key.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl PaymentUri<NetworkUnchecked> {
+    /// Checks whether the network of the address is as required.
+    ///
+    /// See [`Address::require_network`].
+    pub fn require_network(self, required: Network) -> Result<PaymentUri, address::ParseError> {
+        let address = self.address.require_network(required)?;
+        Ok(PaymentUri {
+            address,
+            amount: self.amount,
+            label: self.label,
+            message: self.message,
+            params: self.params,
+        })
+    }
+
+    /// Marks, without any additional checks, the network of the address as checked.
+    ///
+    /// See [`Address::assume_checked`].
+    pub fn assume_checked(self) -> PaymentUri {
+        PaymentUri {
+            address: self.address.assume_checked(),
+            amount: self.amount,
+            label: self.label,
+            message: self.message,
+            params: self.params,
+        }
+    }
+}
+
+/// Formats the URI, percent-encoding parameter values.
+///
+/// Alternate formatting `{:#}` uses an uppercase bech32 address, which is more compact in QR
+/// codes, see [`Address::to_qr_uri`].
+impl fmt::Display for PaymentUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}{:#}", SCHEME, self.address)?;
+        } else {
+            write!(f, "{}{}", SCHEME, self.address)?;
+        }
+
+        let mut sep = '?';
+        let mut write_param = |f: &mut fmt::Formatter, key: &str, value: &dyn fmt::Display| {
+            write!(f, "{}{}={}", sep, PercentEncoded(key), value)?;
+            sep = '&';
+            Ok(())
+        };
+        if let Some(amount) = self.amount {
+            write_param(f, "amount", &amount.display_in(Denomination::Bitcoin))?;
+        }
+        if let Some(ref label) = self.label {
+            write_param(f, "label", &PercentEncoded(label))?;
+        }
+        if let Some(ref message) = self.message {
+            write_param(f, "message", &PercentEncoded(message))?;
+        }
+        for (key, value) in &self.params {
+            write_param(f, key, &PercentEncoded(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// A URI can be parsed only with `NetworkUnchecked`.
+impl FromStr for PaymentUri<NetworkUnchecked> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let rest = match s.get(..SCHEME.len()) {
+            Some(scheme) if scheme.eq_ignore_ascii_case(SCHEME) => &s[SCHEME.len()..],
+            _ => return Err(ParseError::InvalidScheme),
+        };
+        let (address, query) = match rest.split_once('?') {
+            Some((address, query)) => (address, Some(query)),
+            None => (rest, None),
+        };
+        let address = address.parse::<Address<_>>().map_err(ParseError::Address)?;
+
+        let mut uri = PaymentUri::new(address);
+        for pair in query.into_iter().flat_map(|q| q.split('&')).filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = percent_decode(key)?;
+            let value = percent_decode(value)?;
+            match key.as_str() {
+                "amount" => {
+                    if uri.amount.is_some() {
+                        return Err(ParseError::DuplicateParam(key));
+                    }
+                    uri.amount = Some(parse_amount(&value)?);
+                }
+                "label" => {
+                    if uri.label.is_some() {
+                        return Err(ParseError::DuplicateParam(key));
+                    }
+                    uri.label = Some(value);
+                }
+                "message" => {
+                    if uri.message.is_some() {
+                        return Err(ParseError::DuplicateParam(key));
+                    }
+                    uri.message = Some(value);
+                }
+                _ => uri.params.push((key, value)),
+            }
+        }
+        Ok(uri)
+    }
+}
+
+/// Parses a BIP-21 amount: a decimal number of bitcoin, without sign, exponent or separators.
+fn parse_amount(s: &str) -> Result<Amount, ParseError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return Err(ParseError::InvalidAmountFormat);
+    }
+    Amount::from_str_in(s, Denomination::Bitcoin).map_err(ParseError::Amount)
+}
+
+/// Decodes `%XX` escapes in `s`, the result must be valid UTF-8.
+fn percent_decode(s: &str) -> Result<String, ParseError> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hi = iter.next().and_then(|c| (c as char).to_digit(16));
+            let lo = iter.next().and_then(|c| (c as char).to_digit(16));
+            match (hi, lo) {
+                (Some(hi), Some(lo)) => bytes.push((hi << 4 | lo) as u8),
+                _ => return Err(ParseError::InvalidPercentEncoding),
+            }
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| ParseError::InvalidUtf8)
+}
+
+/// Displays a string percent-encoding everything but RFC 3986 unreserved characters.
+struct PercentEncoded<'a>(&'a str);
+
+impl fmt::Display for PercentEncoded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in self.0.bytes() {
+            if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+                write!(f, "{}", b as char)?;
+            } else {
+                write!(f, "%{:02X}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error parsing a BIP-21 payment URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The URI does not start with `bitcoin:`.
+    InvalidScheme,
+    /// The address is invalid.
+    Address(address::ParseError),
+    /// The amount contains characters other than digits and a decimal point.
+    InvalidAmountFormat,
+    /// The amount is not a valid bitcoin amount.
+    Amount(ParseAmountError),
+    /// A `%` is not followed by two hex digits.
+    InvalidPercentEncoding,
+    /// A decoded key or value is not valid UTF-8.
+    InvalidUtf8,
+    /// The `amount`, `label` or `message` parameter appears more than once.
+    DuplicateParam(String),
+}
+
+impl From<Infallible> for ParseError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+
+        match *self {
+            InvalidScheme => write!(f, "URI scheme is not `bitcoin:`"),
+            Address(ref e) => write_err!(f, "invalid address"; e),
+            InvalidAmountFormat => write!(f, "amount must be a decimal number of bitcoin"),
+            Amount(ref e) => write_err!(f, "invalid amount"; e),
+            InvalidPercentEncoding => write!(f, "invalid percent-encoding"),
+            InvalidUtf8 => write!(f, "percent-decoded parameter is not valid UTF-8"),
+            DuplicateParam(ref key) => write!(f, "duplicate parameter: {}", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseError::*;
+
+        match *self {
+            Address(ref e) => Some(e),
+            Amount(ref e) => Some(e),
+            InvalidScheme
+            | InvalidAmountFormat
+            | InvalidPercentEncoding
+            | InvalidUtf8
+            | DuplicateParam(_) => None,
+        }
+    }
+}
+
+/// A required (`req-` prefixed) parameter is not understood, the URI must be rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UnknownRequiredParamError(pub String);
+
+impl fmt::Display for UnknownRequiredParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown required parameter: req-{}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownRequiredParamError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+
+    fn parse(s: &str) -> Result<PaymentUri, ParseError> {
+        s.parse::<PaymentUri<_>>().map(PaymentUri::assume_checked)
+    }
+
+    #[test]
+    fn bip21_examples() {
+        let uri = parse("bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").unwrap();
+        assert_eq!(uri.address.to_string(), ADDR);
+        assert_eq!(uri, PaymentUri::new(uri.address));
+
+        let s = "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?label=Luke-Jr";
+        assert_eq!(parse(s).unwrap().label.as_deref(), Some("Luke-Jr"));
+
+        let s = "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?amount=50&label=Luke-Jr&message=Donation%20for%20project%20xyz";
+        let uri = parse(s).unwrap();
+        assert_eq!(uri.amount, Some(Amount::from_int_btc(50_u16)));
+        assert_eq!(uri.message.as_deref(), Some("Donation for project xyz"));
+        assert_eq!(uri.to_string(), s);
+
+        let s = "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?req-somethingyoudontunderstand=50&req-somethingelseyoudontget=999";
+        let uri = parse(s).unwrap();
+        assert_eq!(uri.required_params().count(), 2);
+        assert_eq!(
+            uri.check_required_params(&["somethingelseyoudontget"]),
+            Err(UnknownRequiredParamError("somethingyoudontunderstand".to_string()))
+        );
+
+        let s = "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?somethingyoudontunderstand=50&somethingelseyoudontget=999";
+        let uri = parse(s).unwrap();
+        assert!(uri.check_required_params(&[]).is_ok());
+        assert_eq!(uri.param("somethingelseyoudontget"), Some("999"));
+    }
+
+    #[test]
+    fn extension_params() {
+        let s = "BITCOIN:BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4?amount=0.00001&pj=https://example.com/pj&lightning=LNBC10U1P3PJ257";
+        let uri = parse(s).unwrap();
+        assert_eq!(uri.amount, Some(Amount::from_sat(1000).unwrap()));
+        assert_eq!(uri.payjoin_endpoint(), Some("https://example.com/pj"));
+        assert_eq!(uri.lightning(), Some("LNBC10U1P3PJ257"));
+
+        let uri = parse(&uri.to_string()).unwrap();
+        assert_eq!(uri.payjoin_endpoint(), Some("https://example.com/pj"));
+        assert!(uri.to_string().contains("pj=https%3A%2F%2Fexample.com%2Fpj"));
+        assert!(
+            format!("{:#}", uri).starts_with("bitcoin:BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4?")
+        );
+    }
+
+    #[test]
+    fn percent_encoding_round_trip() {
+        let mut uri = parse(&format!("bitcoin:{}", ADDR)).unwrap();
+        uri.label = Some("Ünïcödé & co = 100%".to_string());
+        uri.params.push(("x-note".to_string(), "a+b?c".to_string()));
+
+        let s = uri.to_string();
+        assert!(!s[SCHEME.len()..].contains(' '));
+        assert_eq!(parse(&s).unwrap(), uri);
+
+        assert_eq!(
+            parse(&format!("bitcoin:{}?label=%4", ADDR)),
+            Err(ParseError::InvalidPercentEncoding)
+        );
+        assert_eq!(parse(&format!("bitcoin:{}?label=%FF", ADDR)), Err(ParseError::InvalidUtf8));
+    }
+
+    #[test]
+    fn invalid_uris() {
+        assert_eq!(parse(ADDR), Err(ParseError::InvalidScheme));
+        assert!(matches!(parse("bitcoin:notanaddress"), Err(ParseError::Address(_))));
+
+        for amount in ["", "1e3", "-1", "1,000", "+1"] {
+            let s = format!("bitcoin:{}?amount={}", ADDR, amount);
+            assert_eq!(parse(&s), Err(ParseError::InvalidAmountFormat));
+        }
+        let s = format!("bitcoin:{}?amount=0.000000001", ADDR);
+        assert!(matches!(parse(&s), Err(ParseError::Amount(_))));
+
+        let s = format!("bitcoin:{}?label=a&label=b", ADDR);
+        assert_eq!(parse(&s), Err(ParseError::DuplicateParam("label".to_string())));
+    }
+
+    #[test]
+    fn require_network() {
+        let uri = format!("bitcoin:{}?amount=1", ADDR).parse::<PaymentUri<_>>().unwrap();
+        assert!(uri.clone().require_network(Network::Regtest).is_err());
+        let uri = uri.require_network(Network::Bitcoin).unwrap();
+        assert_eq!(uri.amount, Some(Amount::ONE_BTC));
+    }
+}
@@ -17,17 +17,17 @@
 
 use crate::crypto::key::{CompressedPublicKey, Keypair, PrivateKey, XOnlyPublicKey};
 use crate::internal_macros::{impl_array_newtype, impl_array_newtype_stringify};
-use crate::network::NetworkKind;
+use crate::network::{CustomNetwork, NetworkKind};
 use crate::prelude::{String, Vec};
 
 /// Version bytes for extended public keys on the Bitcoin network.
-const VERSION_BYTES_MAINNET_PUBLIC: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+pub(crate) const VERSION_BYTES_MAINNET_PUBLIC: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
 /// Version bytes for extended private keys on the Bitcoin network.
-const VERSION_BYTES_MAINNET_PRIVATE: [u8; 4] = [0x04, 0x88, 0xAD, 0xE4];
+pub(crate) const VERSION_BYTES_MAINNET_PRIVATE: [u8; 4] = [0x04, 0x88, 0xAD, 0xE4];
 /// Version bytes for extended public keys on any of the testnet networks.
-const VERSION_BYTES_TESTNETS_PUBLIC: [u8; 4] = [0x04, 0x35, 0x87, 0xCF];
+pub(crate) const VERSION_BYTES_TESTNETS_PUBLIC: [u8; 4] = [0x04, 0x35, 0x87, 0xCF];
 /// Version bytes for extended private keys on any of the testnet networks.
-const VERSION_BYTES_TESTNETS_PRIVATE: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+pub(crate) const VERSION_BYTES_TESTNETS_PRIVATE: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
 
 /// The old name for xpub, extended public key.
 #[deprecated(since = "0.31.0", note = "use `Xpub` instead")]
@@ -769,14 +769,32 @@ fn ckd_priv<C: secp256k1::Signing>(
 
     /// Decoding extended private key from binary data according to BIP 32
     pub fn decode(data: &[u8]) -> Result<Xpriv, ParseError> {
-        let Common { network, depth, parent_fingerprint, child_number, chain_code, key } =
-            Common::decode(data)?;
+        Xpriv::decode_with(data, |version| match version {
+            VERSION_BYTES_MAINNET_PRIVATE => Some(NetworkKind::Main),
+            VERSION_BYTES_TESTNETS_PRIVATE => Some(NetworkKind::Test),
+            _ => None,
+        })
+    }
 
-        let network = match network {
-            VERSION_BYTES_MAINNET_PRIVATE => NetworkKind::Main,
-            VERSION_BYTES_TESTNETS_PRIVATE => NetworkKind::Test,
-            unknown => return Err(ParseError::UnknownVersion(unknown)),
-        };
+    /// Decodes an extended private key of the custom `network` from binary data.
+    pub fn decode_custom(data: &[u8], network: &CustomNetwork) -> Result<Xpriv, ParseError> {
+        Xpriv::decode_with(data, |version| {
+            if version == network.xpriv_version {
+                Some(network.network_kind())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Decodes an extended private key, `network` returns the network of the version bytes.
+    fn decode_with<F>(data: &[u8], network: F) -> Result<Xpriv, ParseError>
+    where
+        F: FnOnce([u8; 4]) -> Option<NetworkKind>,
+    {
+        let Common { network: version, depth, parent_fingerprint, child_number, chain_code, key } =
+            Common::decode(data)?;
+        let network = network(version).ok_or(ParseError::UnknownVersion(version))?;
 
         let (&zero, private_key) = key.split_first();
         if zero != 0 {
@@ -795,11 +813,31 @@ pub fn decode(data: &[u8]) -> Result<Xpriv, ParseError> {
 
     /// Extended private key binary encoding according to BIP 32
     pub fn encode(&self) -> [u8; 78] {
-        let mut ret = [0; 78];
-        ret[0..4].copy_from_slice(&match self.network {
+        self.encode_with(match self.network {
             NetworkKind::Main => VERSION_BYTES_MAINNET_PRIVATE,
             NetworkKind::Test => VERSION_BYTES_TESTNETS_PRIVATE,
-        });
+        })
+    }
+
+    /// Encodes the extended private key for the custom `network`.
+    pub fn encode_custom(&self, network: &CustomNetwork) -> [u8; 78] {
+        self.encode_with(network.xpriv_version)
+    }
+
+    /// Returns the base58 string of the extended private key for the custom `network`.
+    pub fn to_string_custom(self, network: &CustomNetwork) -> String {
+        base58::encode_check(&self.encode_custom(network)[..])
+    }
+
+    /// Parses the base58 string of an extended private key of the custom `network`.
+    pub fn from_str_custom(inp: &str, network: &CustomNetwork) -> Result<Xpriv, ParseError> {
+        Xpriv::decode_custom(&decode_base58(inp)?, network)
+    }
+
+    /// Encodes the extended private key with the `version` bytes.
+    fn encode_with(&self, version: [u8; 4]) -> [u8; 78] {
+        let mut ret = [0; 78];
+        ret[0..4].copy_from_slice(&version);
         ret[4] = self.depth;
         ret[5..9].copy_from_slice(&self.parent_fingerprint[..]);
         ret[9..13].copy_from_slice(&u32::from(self.child_number).to_be_bytes());
@@ -927,14 +965,32 @@ pub fn ckd_pub<C: secp256k1::Verification>(
 
     /// Decoding extended public key from binary data according to BIP 32
     pub fn decode(data: &[u8]) -> Result<Xpub, ParseError> {
-        let Common { network, depth, parent_fingerprint, child_number, chain_code, key } =
-            Common::decode(data)?;
+        Xpub::decode_with(data, |version| match version {
+            VERSION_BYTES_MAINNET_PUBLIC => Some(NetworkKind::Main),
+            VERSION_BYTES_TESTNETS_PUBLIC => Some(NetworkKind::Test),
+            _ => None,
+        })
+    }
 
-        let network = match network {
-            VERSION_BYTES_MAINNET_PUBLIC => NetworkKind::Main,
-            VERSION_BYTES_TESTNETS_PUBLIC => NetworkKind::Test,
-            unknown => return Err(ParseError::UnknownVersion(unknown)),
-        };
+    /// Decodes an extended public key of the custom `network` from binary data.
+    pub fn decode_custom(data: &[u8], network: &CustomNetwork) -> Result<Xpub, ParseError> {
+        Xpub::decode_with(data, |version| {
+            if version == network.xpub_version {
+                Some(network.network_kind())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Decodes an extended public key, `network` returns the network of the version bytes.
+    fn decode_with<F>(data: &[u8], network: F) -> Result<Xpub, ParseError>
+    where
+        F: FnOnce([u8; 4]) -> Option<NetworkKind>,
+    {
+        let Common { network: version, depth, parent_fingerprint, child_number, chain_code, key } =
+            Common::decode(data)?;
+        let network = network(version).ok_or(ParseError::UnknownVersion(version))?;
 
         Ok(Xpub {
             network,
@@ -948,11 +1004,31 @@ pub fn decode(data: &[u8]) -> Result<Xpub, ParseError> {
 
     /// Extended public key binary encoding according to BIP 32
     pub fn encode(&self) -> [u8; 78] {
-        let mut ret = [0; 78];
-        ret[0..4].copy_from_slice(&match self.network {
+        self.encode_with(match self.network {
             NetworkKind::Main => VERSION_BYTES_MAINNET_PUBLIC,
             NetworkKind::Test => VERSION_BYTES_TESTNETS_PUBLIC,
-        });
+        })
+    }
+
+    /// Encodes the extended public key for the custom `network`.
+    pub fn encode_custom(&self, network: &CustomNetwork) -> [u8; 78] {
+        self.encode_with(network.xpub_version)
+    }
+
+    /// Returns the base58 string of the extended public key for the custom `network`.
+    pub fn to_string_custom(self, network: &CustomNetwork) -> String {
+        base58::encode_check(&self.encode_custom(network)[..])
+    }
+
+    /// Parses the base58 string of an extended public key of the custom `network`.
+    pub fn from_str_custom(inp: &str, network: &CustomNetwork) -> Result<Xpub, ParseError> {
+        Xpub::decode_custom(&decode_base58(inp)?, network)
+    }
+
+    /// Encodes the extended public key with the `version` bytes.
+    fn encode_with(&self, version: [u8; 4]) -> [u8; 78] {
+        let mut ret = [0; 78];
+        ret[0..4].copy_from_slice(&version);
         ret[4] = self.depth;
         ret[5..9].copy_from_slice(&self.parent_fingerprint[..]);
         ret[9..13].copy_from_slice(&u32::from(self.child_number).to_be_bytes());
@@ -981,15 +1057,7 @@ fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
 impl FromStr for Xpriv {
     type Err = ParseError;
 
-    fn from_str(inp: &str) -> Result<Xpriv, ParseError> {
-        let data = base58::decode_check(inp)?;
-
-        if data.len() != 78 {
-            return Err(InvalidBase58PayloadLengthError { length: data.len() }.into());
-        }
-
-        Xpriv::decode(&data)
-    }
+    fn from_str(inp: &str) -> Result<Xpriv, ParseError> { Xpriv::decode(&decode_base58(inp)?) }
 }
 
 impl fmt::Display for Xpub {
@@ -1001,15 +1069,17 @@ fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
 impl FromStr for Xpub {
     type Err = ParseError;
 
-    fn from_str(inp: &str) -> Result<Xpub, ParseError> {
-        let data = base58::decode_check(inp)?;
+    fn from_str(inp: &str) -> Result<Xpub, ParseError> { Xpub::decode(&decode_base58(inp)?) }
+}
 
-        if data.len() != 78 {
-            return Err(InvalidBase58PayloadLengthError { length: data.len() }.into());
-        }
+/// Decodes the base58 string of an extended key.
+fn decode_base58(inp: &str) -> Result<Vec<u8>, ParseError> {
+    let data = base58::decode_check(inp)?;
 
-        Xpub::decode(&data)
+    if data.len() != 78 {
+        return Err(InvalidBase58PayloadLengthError { length: data.len() }.into());
     }
+    Ok(data)
 }
 
 impl From<Xpub> for XKeyIdentifier {
@@ -0,0 +1,773 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP-322 Generic Signed Message Format.
+//!
+//! Signs and verifies messages for any address by proving that a virtual transaction spending a
+//! virtual output locked with the address's `scriptPubKey` is valid. The virtual `to_spend`
+//! transaction commits to the message, the `to_sign` transaction spends it.
+//!
+//! Three formats are supported:
+//!
+//! * Simple: the witness of the `to_sign` transaction, for segwit addresses.
+//! * Full: the whole `to_sign` transaction, for any address.
+//! * Proof of funds: a full `to_sign` transaction which additionally spends the UTXOs proven.
+//!
+//! Signing goes through [`Psbt::sign`] and [`Psbt::finalize`]. Verification uses
+//! `libbitcoinconsensus` for pre-Taproot scripts when the `bitcoinconsensus` feature is enabled
+//! and the native [`interpreter`] otherwise.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use hashes::{hash_newtype, sha256t, sha256t_tag};
+use internals::write_err;
+use secp256k1::{Secp256k1, Signing, Verification};
+
+use crate::address::script_pubkey::{ScriptBufExt as _, ScriptExt as _};
+use crate::bip32::{DerivationPath, Fingerprint, KeySource};
+use crate::consensus::{encode, DeserializeError};
+use crate::crypto::key::{PrivateKey, PublicKey};
+use crate::locktime::absolute;
+use crate::opcodes::all::{OP_PUSHBYTES_0, OP_RETURN};
+use crate::prelude::{BTreeMap, Vec};
+use crate::psbt::{self, FinalizeError, Psbt};
+use crate::script::interpreter::{self, VerifyFlags};
+use crate::script::{Builder, Script, ScriptBuf, ScriptExt as _};
+use crate::sighash::Prevouts;
+use crate::transaction::{self, OutPointExt as _};
+use crate::{Amount, OutPoint, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+
+sha256t_tag! {
+    pub struct MessageTag = hash_str("BIP0322-signed-message");
+}
+
+hash_newtype! {
+    /// Tagged hash of a message signed with BIP-322, tag \"BIP0322-signed-message\".
+    pub struct MessageHash(sha256t::Hash<MessageTag>);
+}
+
+hashes::impl_hex_for_newtype!(MessageHash);
+
+impl MessageHash {
+    /// Computes the hash of `message`.
+    pub fn new(message: impl AsRef<[u8]>) -> Self {
+        MessageHash(sha256t::Hash::hash(message.as_ref()))
+    }
+}
+
+/// Constructs the virtual `to_spend` transaction committing to `message`.
+///
+/// Its single output, locked with `script_pubkey`, is spent by the `to_sign` transaction.
+pub fn to_spend(script_pubkey: &Script, message: impl AsRef<[u8]>) -> Transaction {
+    let message_hash = MessageHash::new(message);
+    Transaction {
+        version: transaction::Version::maybe_non_standard(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array([0; 32]), 0xFFFF_FFFF),
+            script_sig: Builder::new()
+                .push_opcode(OP_PUSHBYTES_0)
+                .push_slice(message_hash.to_byte_array())
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: script_pubkey.into() }],
+    }
+}
+
+/// Constructs the unsigned virtual `to_sign` transaction spending `to_spend`.
+///
+/// The `funds` outpoints are spent by additional inputs for a proof of funds.
+pub fn to_sign(to_spend: &Transaction, funds: &[OutPoint]) -> Transaction {
+    let input = |previous_output| TxIn {
+        previous_output,
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::ZERO,
+        witness: Witness::new(),
+    };
+    let mut inputs = vec![input(OutPoint::new(to_spend.compute_txid(), 0))];
+    inputs.extend(funds.iter().copied().map(input));
+    Transaction {
+        version: transaction::Version::maybe_non_standard(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: inputs,
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: op_return() }],
+    }
+}
+
+/// Constructs the PSBT of the `to_sign` transaction, ready to be signed.
+///
+/// The spent UTXOs are set, signing keys and scripts are left to the caller.
+pub fn to_sign_psbt(
+    script_pubkey: &Script,
+    message: impl AsRef<[u8]>,
+    funds: &[(OutPoint, TxOut)],
+) -> Psbt {
+    let to_spend = to_spend(script_pubkey, message);
+    let outpoints = funds.iter().map(|(outpoint, _)| *outpoint).collect::<Vec<_>>();
+    let mut psbt = Psbt::from_unsigned_tx(to_sign(&to_spend, &outpoints))
+        .expect("to_sign has empty script sigs and witnesses");
+
+    if script_pubkey.is_witness_program() || script_pubkey.is_p2sh() {
+        psbt.inputs[0].witness_utxo = Some(to_spend.output[0].clone());
+    } else {
+        psbt.inputs[0].non_witness_utxo = Some(to_spend);
+    }
+    for (input, (_, utxo)) in psbt.inputs[1..].iter_mut().zip(funds) {
+        input.witness_utxo = Some(utxo.clone());
+    }
+    psbt
+}
+
+/// A BIP-322 signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signature {
+    /// The witness of the `to_sign` transaction.
+    Simple(Witness),
+    /// The signed `to_sign` transaction.
+    Full(Transaction),
+}
+
+impl Signature {
+    /// Serializes the signature, the witness or the transaction are consensus encoded.
+    pub fn serialize(&self) -> Vec<u8> {
+        match *self {
+            Signature::Simple(ref witness) => encode::serialize(witness),
+            Signature::Full(ref tx) => encode::serialize(tx),
+        }
+    }
+
+    /// Deserializes a signature, trying the simple format first.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        match encode::deserialize(bytes) {
+            Ok(witness) => Ok(Signature::Simple(witness)),
+            Err(_) => encode::deserialize(bytes).map(Signature::Full),
+        }
+    }
+}
+
+#[cfg(feature = "base64")]
+mod base64_impls {
+    use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+    use super::*;
+    use crate::prelude::String;
+
+    impl Signature {
+        /// Converts a signature from base64 encoding.
+        pub fn from_base64(s: &str) -> Result<Signature, ParseSignatureError> {
+            let bytes =
+                BASE64_STANDARD.decode(s).map_err(|_| ParseSignatureError::InvalidBase64)?;
+            Signature::deserialize(&bytes).map_err(ParseSignatureError::Decode)
+        }
+
+        /// Converts to base64 encoding.
+        pub fn to_base64(&self) -> String { BASE64_STANDARD.encode(self.serialize()) }
+    }
+
+    impl fmt::Display for Signature {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let bytes = self.serialize();
+            write!(f, "{}", base64::display::Base64Display::new(&bytes, &BASE64_STANDARD))
+        }
+    }
+
+    impl core::str::FromStr for Signature {
+        type Err = ParseSignatureError;
+        fn from_str(s: &str) -> Result<Signature, ParseSignatureError> { Signature::from_base64(s) }
+    }
+}
+
+/// Signs `message` for `script_pubkey` in the simple format.
+///
+/// `script_pubkey` must be a P2WPKH or P2TR (key path, without script tree) output of
+/// `private_key`, the simple format can not carry the `scriptSig` of other scripts.
+pub fn sign_simple<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    private_key: &PrivateKey,
+    script_pubkey: &Script,
+    message: impl AsRef<[u8]>,
+) -> Result<Signature, SignError> {
+    if !script_pubkey.is_witness_program() {
+        return Err(SignError::UnsupportedScript);
+    }
+    let tx = sign_to_sign(secp, private_key, script_pubkey, message, &[])?;
+    Ok(Signature::Simple(tx.input[0].witness.clone()))
+}
+
+/// Signs `message` for `script_pubkey` in the full format.
+///
+/// `script_pubkey` must be a P2PKH, P2WPKH, P2SH-P2WPKH or P2TR (key path, without script tree)
+/// output of `private_key`.
+pub fn sign_full<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    private_key: &PrivateKey,
+    script_pubkey: &Script,
+    message: impl AsRef<[u8]>,
+) -> Result<Signature, SignError> {
+    sign_to_sign(secp, private_key, script_pubkey, message, &[]).map(Signature::Full)
+}
+
+/// Signs `message` for `script_pubkey` as a proof of funds of the `funds` UTXOs.
+///
+/// `script_pubkey` and the scripts of the UTXOs must be P2PKH, P2WPKH, P2SH-P2WPKH or P2TR (key
+/// path, without script tree) outputs of `private_key`.
+pub fn sign_proof_of_funds<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    private_key: &PrivateKey,
+    script_pubkey: &Script,
+    message: impl AsRef<[u8]>,
+    funds: &[(OutPoint, TxOut)],
+) -> Result<Signature, SignError> {
+    sign_to_sign(secp, private_key, script_pubkey, message, funds).map(Signature::Full)
+}
+
+/// Signs and finalizes the `to_sign` PSBT with `private_key`.
+fn sign_to_sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    private_key: &PrivateKey,
+    script_pubkey: &Script,
+    message: impl AsRef<[u8]>,
+    funds: &[(OutPoint, TxOut)],
+) -> Result<Transaction, SignError> {
+    let public_key = private_key.public_key(secp);
+    let mut psbt = to_sign_psbt(script_pubkey, message, funds);
+    for input_index in 0..psbt.inputs.len() {
+        let script_pubkey =
+            psbt.spend_utxo(input_index).expect("UTXOs are set").script_pubkey.clone();
+        add_key_origin(secp, &mut psbt.inputs[input_index], &script_pubkey, &public_key)?;
+    }
+
+    let mut keys = BTreeMap::new();
+    keys.insert(public_key, *private_key);
+    psbt.sign(&keys, secp).map_err(|(_, errors)| {
+        let (input_index, error) = errors.into_iter().next().expect("errors are not empty");
+        SignError::Sign { input_index, error }
+    })?;
+    psbt.finalize().map_err(|errors| {
+        let (input_index, error) = errors.into_iter().next().expect("errors are not empty");
+        SignError::Finalize { input_index, error }
+    })?;
+    Ok(psbt.extract_tx_unchecked_fee_rate())
+}
+
+/// Adds the origin of `public_key` to `input` if it can spend `script_pubkey`.
+fn add_key_origin<C: Verification>(
+    secp: &Secp256k1<C>,
+    input: &mut psbt::Input,
+    script_pubkey: &Script,
+    public_key: &PublicKey,
+) -> Result<(), SignError> {
+    let key_source: KeySource = (Fingerprint::default(), DerivationPath::master());
+
+    if script_pubkey.is_p2tr() {
+        let internal_key = public_key.inner.x_only_public_key().0.into();
+        if ScriptBuf::new_p2tr(secp, internal_key, None) != *script_pubkey {
+            return Err(SignError::KeyMismatch);
+        }
+        input.tap_internal_key = Some(internal_key);
+        input.tap_key_origins.insert(internal_key, (Vec::new(), key_source));
+        return Ok(());
+    }
+
+    let wpubkey_hash = public_key.wpubkey_hash().map_err(|_| SignError::UnsupportedScript)?;
+    let p2wpkh = ScriptBuf::new_p2wpkh(wpubkey_hash);
+    if script_pubkey.is_p2sh() {
+        if p2wpkh.to_p2sh().as_deref() != Ok(script_pubkey) {
+            return Err(SignError::KeyMismatch);
+        }
+        input.redeem_script = Some(p2wpkh);
+    } else if *script_pubkey != p2wpkh
+        && *script_pubkey != ScriptBuf::new_p2pkh(public_key.pubkey_hash())
+    {
+        return Err(SignError::KeyMismatch);
+    }
+    input.bip32_derivation.insert(public_key.inner, key_source);
+    Ok(())
+}
+
+/// The script verification flags of the inputs of the `to_sign` transaction.
+///
+/// These are the consensus rules, which `libbitcoinconsensus` supports apart from Taproot, so that
+/// the result of [`verify`] does not depend on whether the `bitcoinconsensus` feature is enabled.
+const VERIFY_FLAGS: VerifyFlags = VerifyFlags::CONSENSUS;
+
+/// Verifies a BIP-322 signature of `message` for `script_pubkey`.
+///
+/// For a proof of funds, `funds` are the UTXOs spent by the additional inputs of the `to_sign`
+/// transaction, in the same order. Time-locked proofs are accepted, checking the lock times is up
+/// to the caller.
+///
+/// The inputs are verified against the consensus rules ([`VerifyFlags::CONSENSUS`]), standardness
+/// rules such as low S values are not enforced.
+pub fn verify<C: Verification>(
+    secp: &Secp256k1<C>,
+    script_pubkey: &Script,
+    message: impl AsRef<[u8]>,
+    signature: &Signature,
+    funds: &[TxOut],
+) -> Result<(), VerifyError> {
+    let to_spend = to_spend(script_pubkey, message);
+    let to_sign = match *signature {
+        Signature::Simple(ref witness) => {
+            let mut to_sign = to_sign(&to_spend, &[]);
+            to_sign.input[0].witness = witness.clone();
+            to_sign
+        }
+        Signature::Full(ref to_sign) => to_sign.clone(),
+    };
+
+    if to_sign.input.first().map(|input| input.previous_output)
+        != Some(OutPoint::new(to_spend.compute_txid(), 0))
+    {
+        return Err(VerifyError::NotSpendingToSpend);
+    }
+    if to_sign.version != transaction::Version::maybe_non_standard(0)
+        && to_sign.version != transaction::Version::TWO
+    {
+        return Err(VerifyError::InvalidVersion(to_sign.version));
+    }
+    if to_sign.output.len() != 1
+        || to_sign.output[0].value != Amount::ZERO
+        || to_sign.output[0].script_pubkey != op_return()
+    {
+        return Err(VerifyError::InvalidOutput);
+    }
+    if to_sign.input.len() != funds.len() + 1 {
+        return Err(VerifyError::FundsMismatch { inputs: to_sign.input.len(), funds: funds.len() });
+    }
+
+    let mut prevouts = Vec::with_capacity(to_sign.input.len());
+    prevouts.push(to_spend.output[0].clone());
+    prevouts.extend_from_slice(funds);
+    #[cfg(feature = "bitcoinconsensus")]
+    let serialized = encode::serialize(&to_sign);
+    for (input_index, prevout) in prevouts.iter().enumerate() {
+        // libbitcoinconsensus does not support Taproot.
+        #[cfg(feature = "bitcoinconsensus")]
+        if !prevout.script_pubkey.is_p2tr() {
+            crate::consensus_validation::verify_script_with_flags(
+                &prevout.script_pubkey,
+                input_index,
+                prevout.value,
+                &serialized,
+                u32::from(VERIFY_FLAGS) & !u32::from(VerifyFlags::TAPROOT),
+            )
+            .map_err(|error| VerifyError::Consensus { input_index, error })?;
+            continue;
+        }
+        #[cfg(not(feature = "bitcoinconsensus"))]
+        let _ = prevout;
+
+        interpreter::verify_input(
+            secp,
+            &to_sign,
+            input_index,
+            &Prevouts::All(&prevouts),
+            VERIFY_FLAGS,
+        )
+        .map_err(|error| VerifyError::Script { input_index, error })?;
+    }
+    Ok(())
+}
+
+/// Returns the `scriptPubKey` of the output of the `to_sign` transaction.
+fn op_return() -> ScriptBuf { Builder::new().push_opcode(OP_RETURN).into_script() }
+
+/// An error signing a BIP-322 message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignError {
+    /// The script can not be signed for with a single key, or not in the requested format.
+    UnsupportedScript,
+    /// The script is not an output of the private key.
+    KeyMismatch,
+    /// Signing an input of the `to_sign` PSBT failed.
+    Sign {
+        /// The index of the input.
+        input_index: usize,
+        /// The error.
+        error: psbt::SignError,
+    },
+    /// Finalizing an input of the `to_sign` PSBT failed.
+    Finalize {
+        /// The index of the input.
+        input_index: usize,
+        /// The error.
+        error: FinalizeError,
+    },
+}
+
+impl From<Infallible> for SignError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SignError::*;
+
+        match *self {
+            UnsupportedScript => write!(f, "signing for this script is not supported"),
+            KeyMismatch => write!(f, "the script is not an output of the private key"),
+            Sign { input_index, ref error } =>
+                write_err!(f, "failed to sign input {}", input_index; error),
+            Finalize { input_index, ref error } =>
+                write_err!(f, "failed to finalize input {}", input_index; error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SignError::*;
+
+        match *self {
+            Sign { ref error, .. } => Some(error),
+            Finalize { ref error, .. } => Some(error),
+            UnsupportedScript | KeyMismatch => None,
+        }
+    }
+}
+
+/// An error verifying a BIP-322 signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifyError {
+    /// The first input of the `to_sign` transaction does not spend the `to_spend` transaction.
+    NotSpendingToSpend,
+    /// The `to_sign` transaction version is neither 0 nor 2.
+    InvalidVersion(transaction::Version),
+    /// The `to_sign` transaction does not have a single empty `OP_RETURN` output.
+    InvalidOutput,
+    /// The number of funds is not the number of additional inputs.
+    FundsMismatch {
+        /// The number of inputs of the `to_sign` transaction.
+        inputs: usize,
+        /// The number of funds.
+        funds: usize,
+    },
+    /// Script verification of an input failed.
+    Script {
+        /// The index of the input.
+        input_index: usize,
+        /// The error.
+        error: interpreter::Error,
+    },
+    /// Script verification of an input with `libbitcoinconsensus` failed.
+    #[cfg(feature = "bitcoinconsensus")]
+    Consensus {
+        /// The index of the input.
+        input_index: usize,
+        /// The error.
+        error: crate::consensus_validation::BitcoinconsensusError,
+    },
+}
+
+impl From<Infallible> for VerifyError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use VerifyError::*;
+
+        match *self {
+            NotSpendingToSpend => write!(f, "to_sign does not spend to_spend"),
+            InvalidVersion(version) => write!(f, "invalid to_sign version {}", version),
+            InvalidOutput => write!(f, "to_sign must have a single empty OP_RETURN output"),
+            FundsMismatch { inputs, funds } => write!(
+                f,
+                "{} funds provided for a to_sign transaction with {} inputs",
+                funds, inputs
+            ),
+            Script { input_index, ref error } =>
+                write_err!(f, "script verification of input {} failed", input_index; error),
+            #[cfg(feature = "bitcoinconsensus")]
+            Consensus { input_index, ref error } =>
+                write_err!(f, "script verification of input {} failed", input_index; error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use VerifyError::*;
+
+        match *self {
+            Script { ref error, .. } => Some(error),
+            #[cfg(feature = "bitcoinconsensus")]
+            Consensus { ref error, .. } => Some(error),
+            NotSpendingToSpend | InvalidVersion(_) | InvalidOutput | FundsMismatch { .. } => None,
+        }
+    }
+}
+
+/// An error parsing a base64 encoded BIP-322 signature.
+#[cfg(feature = "base64")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseSignatureError {
+    /// Invalid base64 encoding.
+    InvalidBase64,
+    /// The decoded bytes are neither a witness nor a transaction.
+    Decode(DeserializeError),
+}
+
+#[cfg(feature = "base64")]
+impl From<Infallible> for ParseSignatureError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+#[cfg(feature = "base64")]
+impl fmt::Display for ParseSignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseSignatureError::*;
+
+        match *self {
+            InvalidBase64 => write!(f, "invalid base64"),
+            Decode(ref e) => write_err!(f, "invalid signature encoding"; e),
+        }
+    }
+}
+
+#[cfg(all(feature = "base64", feature = "std"))]
+impl std::error::Error for ParseSignatureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseSignatureError::*;
+
+        match *self {
+            InvalidBase64 => None,
+            Decode(ref e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_lit::hex;
+
+    use super::*;
+    use crate::address::Address;
+    use crate::crypto::key::CompressedPublicKey;
+    use crate::network::Network;
+
+    /// The private key and address of the BIP-322 test vectors.
+    const WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+    const ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+    fn script_pubkey() -> ScriptBuf {
+        ADDRESS
+            .parse::<Address<_>>()
+            .unwrap()
+            .require_network(Network::Bitcoin)
+            .unwrap()
+            .script_pubkey()
+    }
+
+    #[test]
+    fn message_hash() {
+        assert_eq!(
+            MessageHash::new("").to_byte_array(),
+            hex!("c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1")
+        );
+        assert_eq!(
+            MessageHash::new("Hello World").to_byte_array(),
+            hex!("f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a")
+        );
+    }
+
+    #[test]
+    fn transactions() {
+        let to_spend_empty = to_spend(&script_pubkey(), "");
+        assert_eq!(
+            to_spend_empty.compute_txid().to_string(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            to_sign(&to_spend_empty, &[]).compute_txid().to_string(),
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"
+        );
+
+        let to_spend_hello = to_spend(&script_pubkey(), "Hello World");
+        assert_eq!(
+            to_spend_hello.compute_txid().to_string(),
+            "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b"
+        );
+        assert_eq!(
+            to_sign(&to_spend_hello, &[]).compute_txid().to_string(),
+            "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn simple_vectors() {
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::from_wif(WIF).unwrap();
+        let vectors = [
+            ("", "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+            ("Hello World", "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+        ];
+        for (message, expected) in vectors {
+            let signature = expected.parse::<Signature>().unwrap();
+            assert!(matches!(signature, Signature::Simple(_)));
+            assert_eq!(signature.to_string(), expected);
+            verify(&secp, &script_pubkey(), message, &signature, &[]).unwrap();
+
+            // The vectors grind for a low R value, signatures made here are not byte-identical.
+            let signature = sign_simple(&secp, &private_key, &script_pubkey(), message).unwrap();
+            let signature = signature.to_base64().parse::<Signature>().unwrap();
+            verify(&secp, &script_pubkey(), message, &signature, &[]).unwrap();
+        }
+
+        // The signature of one message does not verify another.
+        let signature = vectors[0].1.parse::<Signature>().unwrap();
+        assert!(verify(&secp, &script_pubkey(), vectors[1].0, &signature, &[]).is_err());
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::from_wif(WIF).unwrap();
+        let public_key = CompressedPublicKey::from_private_key(&secp, private_key).unwrap();
+        let scripts = [
+            ScriptBuf::new_p2pkh(public_key.pubkey_hash()),
+            ScriptBuf::new_p2wpkh(public_key.wpubkey_hash()),
+            ScriptBuf::new_p2wpkh(public_key.wpubkey_hash()).to_p2sh().unwrap(),
+            ScriptBuf::new_p2tr(&secp, public_key.0.x_only_public_key().0, None),
+        ];
+
+        for script_pubkey in &scripts {
+            let signature = sign_full(&secp, &private_key, script_pubkey, "message").unwrap();
+            verify(&secp, script_pubkey, "message", &signature, &[]).unwrap();
+            assert!(verify(&secp, script_pubkey, "other message", &signature, &[]).is_err());
+            let decoded = Signature::deserialize(&signature.serialize()).unwrap();
+            assert_eq!(decoded, signature);
+
+            if !script_pubkey.is_witness_program() {
+                assert_eq!(
+                    sign_simple(&secp, &private_key, script_pubkey, "message"),
+                    Err(SignError::UnsupportedScript)
+                );
+                continue;
+            }
+            let signature = sign_simple(&secp, &private_key, script_pubkey, "message").unwrap();
+            assert!(matches!(signature, Signature::Simple(_)));
+            assert_eq!(Signature::deserialize(&signature.serialize()).unwrap(), signature);
+            verify(&secp, script_pubkey, "message", &signature, &[]).unwrap();
+        }
+
+        let other_key = PrivateKey::from_byte_array([1; 32], Network::Bitcoin).unwrap();
+        assert_eq!(
+            sign_full(&secp, &other_key, &scripts[1], "message"),
+            Err(SignError::KeyMismatch)
+        );
+    }
+
+    #[test]
+    fn high_s_signature() {
+        use secp256k1::constants::CURVE_ORDER;
+
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::from_wif(WIF).unwrap();
+        let witness = match sign_simple(&secp, &private_key, &script_pubkey(), "message").unwrap() {
+            Signature::Simple(witness) => witness,
+            Signature::Full(_) => unreachable!(),
+        };
+
+        // Replace S by N - S, which is as valid but not standard.
+        let (der, sighash_type) = witness[0].split_at(witness[0].len() - 1);
+        let mut compact = secp256k1::ecdsa::Signature::from_der(der).unwrap().serialize_compact();
+        let mut borrow = 0;
+        for i in (32..64).rev() {
+            let difference = i16::from(CURVE_ORDER[i - 32]) - i16::from(compact[i]) - borrow;
+            compact[i] = difference.rem_euclid(256) as u8;
+            borrow = i16::from(difference < 0);
+        }
+        let mut high_s = secp256k1::ecdsa::Signature::from_compact(&compact).unwrap();
+        let mut signature = high_s.serialize_der().to_vec();
+        signature.extend_from_slice(sighash_type);
+        let witness = Witness::from_slice(&[signature, witness[1].to_vec()]);
+        high_s.normalize_s();
+        assert_eq!(high_s.serialize_der().as_ref(), der);
+
+        // Only the standardness rules reject the signature.
+        let to_spend = to_spend(&script_pubkey(), "message");
+        let mut to_sign = to_sign(&to_spend, &[]);
+        to_sign.input[0].witness = witness.clone();
+        let prevouts = Prevouts::All(&to_spend.output);
+        assert!(interpreter::verify_input(&secp, &to_sign, 0, &prevouts, VerifyFlags::STANDARD)
+            .is_err());
+
+        // Verified alike with and without the `bitcoinconsensus` feature.
+        let signature = Signature::Simple(witness);
+        verify(&secp, &script_pubkey(), "message", &signature, &[]).unwrap();
+        assert!(verify(&secp, &script_pubkey(), "other message", &signature, &[]).is_err());
+    }
+
+    #[test]
+    fn proof_of_funds() {
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::from_wif(WIF).unwrap();
+        let funds = [
+            (
+                OutPoint::new(Txid::from_byte_array([1; 32]), 0),
+                TxOut { value: Amount::from_sat_u32(50_000), script_pubkey: script_pubkey() },
+            ),
+            (
+                OutPoint::new(Txid::from_byte_array([2; 32]), 3),
+                TxOut { value: Amount::from_sat_u32(20_000), script_pubkey: script_pubkey() },
+            ),
+        ];
+        let utxos = funds.iter().map(|(_, utxo)| utxo.clone()).collect::<Vec<_>>();
+
+        let signature =
+            sign_proof_of_funds(&secp, &private_key, &script_pubkey(), "funds", &funds).unwrap();
+        match signature {
+            Signature::Full(ref tx) => assert_eq!(tx.input.len(), 3),
+            Signature::Simple(_) => panic!("proof of funds must use the full format"),
+        }
+        verify(&secp, &script_pubkey(), "funds", &signature, &utxos).unwrap();
+        assert_eq!(
+            verify(&secp, &script_pubkey(), "funds", &signature, &utxos[..1]),
+            Err(VerifyError::FundsMismatch { inputs: 3, funds: 1 })
+        );
+        // Claiming more funds than the UTXOs hold invalidates the signatures.
+        let mut inflated = utxos.clone();
+        inflated[0].value = Amount::from_sat_u32(60_000);
+        assert!(verify(&secp, &script_pubkey(), "funds", &signature, &inflated).is_err());
+    }
+
+    #[test]
+    fn invalid_to_sign() {
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::from_wif(WIF).unwrap();
+        let signature = sign_full(&secp, &private_key, &script_pubkey(), "message").unwrap();
+        let to_sign = match signature {
+            Signature::Full(tx) => tx,
+            Signature::Simple(_) => unreachable!(),
+        };
+
+        let mut tx = to_sign.clone();
+        tx.input[0].previous_output.vout = 1;
+        assert_eq!(
+            verify(&secp, &script_pubkey(), "message", &Signature::Full(tx), &[]),
+            Err(VerifyError::NotSpendingToSpend)
+        );
+        let mut tx = to_sign.clone();
+        tx.version = transaction::Version::ONE;
+        assert_eq!(
+            verify(&secp, &script_pubkey(), "message", &Signature::Full(tx), &[]),
+            Err(VerifyError::InvalidVersion(transaction::Version::ONE))
+        );
+        let mut tx = to_sign.clone();
+        tx.output[0].value = Amount::ONE_SAT;
+        assert_eq!(
+            verify(&secp, &script_pubkey(), "message", &Signature::Full(tx), &[]),
+            Err(VerifyError::InvalidOutput)
+        );
+    }
+}
@@ -0,0 +1,931 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP-352 Silent Payments.
+//!
+//! Implementation of silent payment addresses, the derivation of the outputs paying them and the
+//! scanning of transactions for received outputs.
+//!
+//! A silent payment address is static and reusable, yet every payment to it goes to a fresh
+//! taproot output that only the sender and the receiver can link to the address. The output key
+//! is derived with ECDH between the sum of the input keys of the transaction and the scan key of
+//! the address.
+
+use core::convert::Infallible;
+use core::fmt;
+use core::str::FromStr;
+
+use bech32::primitives::decode::{CheckedHrpstring, CheckedHrpstringError};
+use bech32::primitives::gf32::Fe32;
+use bech32::primitives::hrp::Hrp;
+use bech32::primitives::iter::{ByteIterExt, Fe32IterExt};
+use bech32::Bech32m;
+use hashes::{hash160, sha256t, sha256t_tag, HashEngine};
+use internals::write_err;
+use secp256k1::{Parity, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification};
+
+use crate::crypto::key::XOnlyPublicKey;
+use crate::network::NetworkKind;
+use crate::prelude::{BTreeMap, String, ToString, Vec};
+use crate::script::ScriptExt as _;
+use crate::witness::WitnessExt as _;
+use crate::{OutPoint, Script, Transaction, TxIn, TxOut};
+
+/// The human-readable part of mainnet silent payment addresses.
+const HRP_MAINNET: Hrp = Hrp::parse_unchecked("sp");
+/// The human-readable part of testnet, signet and regtest silent payment addresses.
+const HRP_TESTNET: Hrp = Hrp::parse_unchecked("tsp");
+
+/// The length of the data of a version 0 address, the scan key followed by the spend key.
+const ADDRESS_DATA_LEN: usize = 66;
+
+/// The x-coordinate of the NUMS point H from BIP-341, used as taproot internal key when only
+/// script path spends are possible.
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+sha256t_tag! {
+    struct InputsTag = hash_str("BIP0352/Inputs");
+}
+
+sha256t_tag! {
+    struct SharedSecretTag = hash_str("BIP0352/SharedSecret");
+}
+
+sha256t_tag! {
+    struct LabelTag = hash_str("BIP0352/Label");
+}
+
+/// A silent payment address.
+///
+/// Parsing accepts the versions 0 to 30, a sender uses the first 66 bytes of the data of an
+/// address with a higher version as specified by BIP-352. Addresses are always formatted as
+/// version 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SilentPaymentAddress {
+    network: NetworkKind,
+    scan_key: PublicKey,
+    spend_key: PublicKey,
+}
+
+impl SilentPaymentAddress {
+    /// Constructs a new silent payment address from the receiver's scan and spend keys.
+    ///
+    /// For a labeled address, `spend_key` is the labeled spend key.
+    pub fn new(scan_key: PublicKey, spend_key: PublicKey, network: impl Into<NetworkKind>) -> Self {
+        SilentPaymentAddress { network: network.into(), scan_key, spend_key }
+    }
+
+    /// Returns the network kind the address is for.
+    pub fn network(&self) -> NetworkKind { self.network }
+
+    /// Returns the scan key.
+    pub fn scan_key(&self) -> PublicKey { self.scan_key }
+
+    /// Returns the spend key.
+    pub fn spend_key(&self) -> PublicKey { self.spend_key }
+
+    fn hrp(&self) -> Hrp {
+        match self.network {
+            NetworkKind::Main => HRP_MAINNET,
+            NetworkKind::Test => HRP_TESTNET,
+        }
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use fmt::Write as _;
+
+        let hrp = self.hrp();
+        let scan_key = self.scan_key.serialize();
+        let spend_key = self.spend_key.serialize();
+        let chars = scan_key
+            .iter()
+            .chain(&spend_key)
+            .copied()
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp)
+            .with_witness_version(Fe32::Q)
+            .chars();
+        for c in chars {
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = ParseAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let checked = CheckedHrpstring::new::<Bech32m>(s).map_err(ParseAddressError::Bech32)?;
+        let network = match checked.hrp() {
+            hrp if hrp == HRP_MAINNET => NetworkKind::Main,
+            hrp if hrp == HRP_TESTNET => NetworkKind::Test,
+            hrp => return Err(ParseAddressError::UnknownHrp(hrp.to_string())),
+        };
+
+        let (version, data) = match checked.data_part_ascii_no_checksum().split_first() {
+            Some((version, data)) => (Fe32::from_char_unchecked(*version).to_u8(), data),
+            None => return Err(ParseAddressError::InvalidLength(0)),
+        };
+        let data =
+            data.iter().map(|c| Fe32::from_char_unchecked(*c)).fes_to_bytes().collect::<Vec<_>>();
+        match version {
+            31 => return Err(ParseAddressError::UnknownVersion(version)),
+            0 if data.len() != ADDRESS_DATA_LEN =>
+                return Err(ParseAddressError::InvalidLength(data.len())),
+            _ if data.len() < ADDRESS_DATA_LEN =>
+                return Err(ParseAddressError::InvalidLength(data.len())),
+            _ => {}
+        }
+
+        let scan_key = PublicKey::from_slice(&data[..33]).map_err(ParseAddressError::InvalidKey)?;
+        let spend_key = PublicKey::from_slice(&data[33..ADDRESS_DATA_LEN])
+            .map_err(ParseAddressError::InvalidKey)?;
+        Ok(SilentPaymentAddress { network, scan_key, spend_key })
+    }
+}
+
+/// The secret key of a transaction input, used to derive silent payment outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSecretKey {
+    /// The secret key of a P2PKH, P2WPKH or P2SH-P2WPKH input.
+    Ecdsa(SecretKey),
+    /// The tweaked secret key of a P2TR input, that is the secret key of the output key.
+    Taproot(SecretKey),
+}
+
+/// Derives the taproot output keys paying `recipients`.
+///
+/// `outpoints` are the outpoints spent by all the inputs of the transaction and `input_keys` the
+/// secret keys of the inputs eligible for silent payments: P2PKH and P2SH-P2WPKH inputs spent with
+/// a compressed public key, P2WPKH inputs and P2TR inputs. The returned output keys are in the
+/// order of `recipients`, the outputs may then be placed anywhere in the transaction.
+pub fn derive_outputs<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    outpoints: &[OutPoint],
+    input_keys: &[InputSecretKey],
+    recipients: &[SilentPaymentAddress],
+) -> Result<Vec<XOnlyPublicKey>, Error> {
+    let smallest_outpoint = smallest_outpoint(outpoints).ok_or(Error::NoInputs)?;
+
+    let mut input_secret_key: Option<SecretKey> = None;
+    for input_key in input_keys {
+        let secret_key = match *input_key {
+            InputSecretKey::Ecdsa(secret_key) => secret_key,
+            InputSecretKey::Taproot(secret_key) => match secret_key.x_only_public_key(secp).1 {
+                Parity::Even => secret_key,
+                Parity::Odd => secret_key.negate(),
+            },
+        };
+        input_secret_key = Some(match input_secret_key {
+            Some(sum) =>
+                sum.add_tweak(&secret_key.into()).map_err(|_| Error::InputKeysSumToZero)?,
+            None => secret_key,
+        });
+    }
+    let input_secret_key = input_secret_key.ok_or(Error::NoEligibleInputs)?;
+
+    let input_public_key = PublicKey::from_secret_key(secp, &input_secret_key);
+    let input_hash = input_hash(&smallest_outpoint, &input_public_key);
+    // This is statistically extremely unlikely to panic.
+    let tweak = input_secret_key.mul_tweak(&input_hash).expect("input hash is not zero");
+
+    // The output index k counts the outputs paying the same scan key.
+    let mut output_counts = BTreeMap::<PublicKey, u32>::new();
+    let mut outputs = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let shared_secret = recipient
+            .scan_key
+            .mul_tweak(secp, &tweak.into())
+            .expect("multiplication by a valid secret key");
+        let k = output_counts.entry(recipient.scan_key).or_insert(0);
+        let output_key =
+            output_key(secp, &recipient.spend_key, &shared_secret_tweak(&shared_secret, *k));
+        *k += 1;
+        outputs.push(XOnlyPublicKey::new(output_key.x_only_public_key().0));
+    }
+    Ok(outputs)
+}
+
+/// The receiver of silent payments, scanning transactions for received outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receiver {
+    network: NetworkKind,
+    scan_secret_key: SecretKey,
+    scan_key: PublicKey,
+    spend_key: PublicKey,
+    /// The label tweaks, by the label points `tweak·G`.
+    labels: BTreeMap<PublicKey, (u32, SecretKey)>,
+}
+
+impl Receiver {
+    /// The label of the change address.
+    pub const CHANGE_LABEL: u32 = 0;
+
+    /// Constructs a new receiver from its scan secret key and spend public key.
+    ///
+    /// The receiver always scans for outputs paying its change address, the address with label
+    /// [`Receiver::CHANGE_LABEL`].
+    pub fn new<C: Signing>(
+        secp: &Secp256k1<C>,
+        scan_secret_key: SecretKey,
+        spend_key: PublicKey,
+        network: impl Into<NetworkKind>,
+    ) -> Self {
+        let mut receiver = Receiver {
+            network: network.into(),
+            scan_secret_key,
+            scan_key: PublicKey::from_secret_key(secp, &scan_secret_key),
+            spend_key,
+            labels: BTreeMap::new(),
+        };
+        receiver.add_label(secp, Self::CHANGE_LABEL);
+        receiver
+    }
+
+    /// Returns the address without label.
+    pub fn address(&self) -> SilentPaymentAddress {
+        SilentPaymentAddress::new(self.scan_key, self.spend_key, self.network)
+    }
+
+    /// Adds label `m` to the labels scanned for and returns the labeled address.
+    pub fn add_label<C: Signing>(&mut self, secp: &Secp256k1<C>, m: u32) -> SilentPaymentAddress {
+        let mut engine = sha256t::Hash::<LabelTag>::engine();
+        engine.input(&self.scan_secret_key.secret_bytes());
+        engine.input(&m.to_be_bytes());
+        let hash = sha256t::Hash::<LabelTag>::from_engine(engine);
+        // This is statistically extremely unlikely to panic.
+        let tweak = SecretKey::from_byte_array(hash.as_byte_array())
+            .expect("hash value greater than curve order");
+
+        let label = PublicKey::from_secret_key(secp, &tweak);
+        self.labels.insert(label, (m, tweak));
+        let spend_key = self.spend_key.combine(&label).expect("hash value is not a discrete log");
+        SilentPaymentAddress::new(self.scan_key, spend_key, self.network)
+    }
+
+    /// Scans `tx` for the outputs paying this receiver.
+    ///
+    /// `prevouts` are the outputs spent by the inputs of `tx`, in the same order. Returns an empty
+    /// list if the transaction is not eligible for silent payments.
+    pub fn scan<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        tx: &Transaction,
+        prevouts: &[TxOut],
+    ) -> Result<Vec<ReceivedOutput>, Error> {
+        if prevouts.len() != tx.input.len() {
+            return Err(Error::PrevoutsMismatch {
+                inputs: tx.input.len(),
+                prevouts: prevouts.len(),
+            });
+        }
+        let no_outputs = Ok(Vec::new());
+
+        let mut outputs = tx
+            .output
+            .iter()
+            .zip(0..)
+            .filter(|(output, _)| output.script_pubkey.is_p2tr())
+            .filter_map(|(output, vout)| {
+                let output_key = output.script_pubkey.as_bytes()[2..].try_into().expect("32 bytes");
+                secp256k1::XOnlyPublicKey::from_byte_array(output_key).ok().map(|key| (vout, key))
+            })
+            .collect::<Vec<_>>();
+        if outputs.is_empty() {
+            return no_outputs;
+        }
+
+        // Spending a future segwit version makes the transaction ineligible.
+        if prevouts.iter().any(|prevout| {
+            prevout.script_pubkey.witness_version().map_or(false, |version| version.to_num() > 1)
+        }) {
+            return no_outputs;
+        }
+        let input_keys = tx
+            .input
+            .iter()
+            .zip(prevouts)
+            .filter_map(|(input, prevout)| input_public_key(input, &prevout.script_pubkey))
+            .collect::<Vec<_>>();
+        let input_public_key = match PublicKey::combine_keys(&input_keys.iter().collect::<Vec<_>>())
+        {
+            Ok(key) => key,
+            // No eligible inputs or their keys sum to zero.
+            Err(_) => return no_outputs,
+        };
+
+        let outpoints = tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>();
+        let smallest_outpoint = smallest_outpoint(&outpoints).ok_or(Error::NoInputs)?;
+        let input_hash = input_hash(&smallest_outpoint, &input_public_key);
+        // This is statistically extremely unlikely to panic.
+        let shared_secret = input_public_key
+            .mul_tweak(secp, &input_hash)
+            .and_then(|key| key.mul_tweak(secp, &self.scan_secret_key.into()))
+            .expect("input hash is not zero");
+
+        let mut received = Vec::new();
+        for k in 0.. {
+            let tweak = shared_secret_tweak(&shared_secret, k);
+            let output_key = output_key(secp, &self.spend_key, &tweak);
+            let negated_output_key = output_key.negate(secp);
+
+            let found = outputs.iter().enumerate().find_map(|(i, (_, key))| {
+                if *key == output_key.x_only_public_key().0 {
+                    return Some((i, tweak, None));
+                }
+                // The output key is the labeled spend key tweaked, its parity is lost.
+                let even = key.public_key(Parity::Even);
+                [even, even.negate(secp)].iter().find_map(|candidate| {
+                    let label = candidate.combine(&negated_output_key).ok()?;
+                    let (m, label_tweak) = self.labels.get(&label)?;
+                    let tweak = tweak.add_tweak(&(*label_tweak).into()).ok()?;
+                    Some((i, tweak, Some(*m)))
+                })
+            });
+            match found {
+                Some((i, tweak, label)) => {
+                    let (vout, key) = outputs.remove(i);
+                    received.push(ReceivedOutput {
+                        vout,
+                        output_key: XOnlyPublicKey::new(key),
+                        tweak,
+                        label,
+                    });
+                }
+                None => break,
+            }
+        }
+        Ok(received)
+    }
+}
+
+/// An output received with silent payments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceivedOutput {
+    /// The index of the output in the transaction.
+    pub vout: u32,
+    /// The output key of the taproot output.
+    pub output_key: XOnlyPublicKey,
+    /// The tweak to add to the spend secret key to get the secret key of the output key.
+    pub tweak: SecretKey,
+    /// The label of the address paid, if any.
+    pub label: Option<u32>,
+}
+
+/// Returns the serialization of the smallest of `outpoints`.
+///
+/// The outpoints are compared by their serialization, so the output indexes are compared as
+/// little-endian bytes.
+fn smallest_outpoint(outpoints: &[OutPoint]) -> Option<[u8; 36]> {
+    outpoints
+        .iter()
+        .map(|outpoint| {
+            let mut bytes = [0; 36];
+            bytes[..32].copy_from_slice(outpoint.txid.as_byte_array());
+            bytes[32..].copy_from_slice(&outpoint.vout.to_le_bytes());
+            bytes
+        })
+        .min()
+}
+
+/// Computes the input hash committing to the inputs of the transaction.
+fn input_hash(smallest_outpoint: &[u8; 36], input_public_key: &PublicKey) -> Scalar {
+    let mut engine = sha256t::Hash::<InputsTag>::engine();
+    engine.input(smallest_outpoint);
+    engine.input(&input_public_key.serialize());
+    let hash = sha256t::Hash::<InputsTag>::from_engine(engine);
+    // This is statistically extremely unlikely to panic.
+    Scalar::from_be_bytes(hash.to_byte_array()).expect("hash value greater than curve order")
+}
+
+/// Computes the tweak of the output with index `k` from the ECDH shared secret.
+fn shared_secret_tweak(shared_secret: &PublicKey, k: u32) -> SecretKey {
+    let mut engine = sha256t::Hash::<SharedSecretTag>::engine();
+    engine.input(&shared_secret.serialize());
+    engine.input(&k.to_be_bytes());
+    let hash = sha256t::Hash::<SharedSecretTag>::from_engine(engine);
+    // This is statistically extremely unlikely to panic.
+    SecretKey::from_byte_array(hash.as_byte_array()).expect("hash value greater than curve order")
+}
+
+/// Computes the output key `spend_key + tweak·G`.
+fn output_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    spend_key: &PublicKey,
+    tweak: &SecretKey,
+) -> PublicKey {
+    // This is statistically extremely unlikely to panic.
+    spend_key.add_exp_tweak(secp, &(*tweak).into()).expect("hash value is not a discrete log")
+}
+
+/// Returns the public key of an input eligible for silent payments.
+fn input_public_key(input: &TxIn, script_pubkey: &Script) -> Option<PublicKey> {
+    let compressed = |bytes: &[u8]| match PublicKey::from_slice(bytes) {
+        Ok(key) if bytes.len() == 33 => Some(key),
+        _ => None,
+    };
+
+    if script_pubkey.is_p2pkh() {
+        // The public key is the last push of a standard script sig, a malleated script sig is
+        // searched from the end for the public key matching the hash.
+        let pubkey_hash = &script_pubkey.as_bytes()[3..23];
+        let script_sig = input.script_sig.as_bytes();
+        return (33..=script_sig.len())
+            .rev()
+            .map(|end| &script_sig[end - 33..end])
+            .filter(|bytes| hash160::Hash::hash(bytes).as_byte_array() == pubkey_hash)
+            .find_map(compressed);
+    }
+    if script_pubkey.is_p2sh() {
+        let redeem_script = input.script_sig.as_bytes().get(1..).map(Script::from_bytes);
+        if redeem_script.map_or(false, |script| script.is_p2wpkh()) {
+            return input.witness.last().and_then(compressed);
+        }
+        return None;
+    }
+    if script_pubkey.is_p2wpkh() {
+        return input.witness.last().and_then(compressed);
+    }
+    if script_pubkey.is_p2tr() {
+        if input.witness.is_empty() {
+            return None;
+        }
+        // Outputs that can only be spent with the script path are excluded.
+        if let Some(control_block) = input.witness.taproot_control_block() {
+            if *control_block.internal_key.as_byte_array() == NUMS_H {
+                return None;
+            }
+        }
+        let output_key = script_pubkey.as_bytes()[2..].try_into().expect("32 bytes");
+        return secp256k1::XOnlyPublicKey::from_byte_array(output_key)
+            .ok()
+            .map(|key| key.public_key(Parity::Even));
+    }
+    None
+}
+
+/// An error deriving or scanning for silent payment outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The transaction has no inputs.
+    NoInputs,
+    /// No input keys were provided.
+    NoEligibleInputs,
+    /// The input secret keys sum to zero.
+    InputKeysSumToZero,
+    /// The number of prevouts is not the number of inputs.
+    PrevoutsMismatch {
+        /// The number of inputs.
+        inputs: usize,
+        /// The number of prevouts.
+        prevouts: usize,
+    },
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            NoInputs => f.write_str("transaction has no inputs"),
+            NoEligibleInputs => f.write_str("no input keys eligible for silent payments"),
+            InputKeysSumToZero => f.write_str("input secret keys sum to zero"),
+            PrevoutsMismatch { inputs, prevouts } =>
+                write!(f, "{} prevouts provided for {} inputs", prevouts, inputs),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            NoInputs | NoEligibleInputs | InputKeysSumToZero | PrevoutsMismatch { .. } => None,
+        }
+    }
+}
+
+/// An error parsing a silent payment address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseAddressError {
+    /// Invalid bech32m encoding.
+    Bech32(CheckedHrpstringError),
+    /// The human-readable part is neither `sp` nor `tsp`.
+    UnknownHrp(String),
+    /// The address version is not supported.
+    UnknownVersion(u8),
+    /// The address data has an invalid length.
+    InvalidLength(usize),
+    /// The scan or spend key is invalid.
+    InvalidKey(secp256k1::Error),
+}
+
+impl From<Infallible> for ParseAddressError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for ParseAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseAddressError::*;
+
+        match *self {
+            Bech32(ref e) => write_err!(f, "invalid bech32m encoding"; e),
+            UnknownHrp(ref hrp) => write!(f, "unknown silent payment address prefix: {}", hrp),
+            UnknownVersion(version) =>
+                write!(f, "unknown silent payment address version {}", version),
+            InvalidLength(len) => write!(f, "invalid silent payment address data length {}", len),
+            InvalidKey(ref e) => write_err!(f, "invalid silent payment address key"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseAddressError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseAddressError::*;
+
+        match *self {
+            Bech32(ref e) => Some(e),
+            InvalidKey(ref e) => Some(e),
+            UnknownHrp(_) | UnknownVersion(_) | InvalidLength(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::script_pubkey::ScriptBufExt as _;
+    use crate::key::{CompressedPublicKey, TweakedPublicKey};
+    use crate::locktime::absolute;
+    use crate::script::{PushBytesBuf, ScriptBufExt as _};
+    use crate::transaction::{self, OutPointExt as _};
+    use crate::{Amount, ScriptBuf, Sequence, Txid, Witness};
+
+    fn secret_key(byte: u8) -> SecretKey { SecretKey::from_byte_array(&[byte; 32]).unwrap() }
+
+    fn encode(hrp: Hrp, version: Fe32, data: &[u8]) -> String {
+        data.iter()
+            .copied()
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp)
+            .with_witness_version(version)
+            .chars()
+            .collect()
+    }
+
+    #[test]
+    fn address_roundtrip() {
+        let secp = Secp256k1::new();
+        let scan_key = PublicKey::from_secret_key(&secp, &secret_key(1));
+        let spend_key = PublicKey::from_secret_key(&secp, &secret_key(2));
+
+        let address = SilentPaymentAddress::new(scan_key, spend_key, NetworkKind::Main);
+        let s = address.to_string();
+        assert!(s.starts_with("sp1q"));
+        assert_eq!(s.len(), 116);
+        assert_eq!(s.parse::<SilentPaymentAddress>().unwrap(), address);
+        assert_eq!(s.to_uppercase().parse::<SilentPaymentAddress>().unwrap(), address);
+
+        let address = SilentPaymentAddress::new(scan_key, spend_key, NetworkKind::Test);
+        assert!(address.to_string().starts_with("tsp1q"));
+        assert_eq!(address.to_string().parse::<SilentPaymentAddress>().unwrap(), address);
+    }
+
+    #[test]
+    fn address_versions() {
+        let secp = Secp256k1::new();
+        let scan_key = PublicKey::from_secret_key(&secp, &secret_key(1));
+        let spend_key = PublicKey::from_secret_key(&secp, &secret_key(2));
+        let mut data = scan_key.serialize().to_vec();
+        data.extend_from_slice(&spend_key.serialize());
+        let expected = SilentPaymentAddress::new(scan_key, spend_key, NetworkKind::Main);
+
+        // Future versions may append data.
+        let mut extended = data.clone();
+        extended.extend_from_slice(&[0xab; 10]);
+        let v1 = encode(HRP_MAINNET, Fe32::P, &extended);
+        assert_eq!(v1.parse::<SilentPaymentAddress>().unwrap(), expected);
+
+        let v0 = encode(HRP_MAINNET, Fe32::Q, &extended);
+        assert_eq!(v0.parse::<SilentPaymentAddress>(), Err(ParseAddressError::InvalidLength(76)));
+        let v31 = encode(HRP_MAINNET, Fe32::L, &data);
+        assert_eq!(v31.parse::<SilentPaymentAddress>(), Err(ParseAddressError::UnknownVersion(31)));
+        let short = encode(HRP_MAINNET, Fe32::P, &data[..65]);
+        assert_eq!(
+            short.parse::<SilentPaymentAddress>(),
+            Err(ParseAddressError::InvalidLength(65))
+        );
+        let bc = encode(bech32::hrp::BC, Fe32::Q, &data);
+        assert_eq!(
+            bc.parse::<SilentPaymentAddress>(),
+            Err(ParseAddressError::UnknownHrp("bc".into()))
+        );
+    }
+
+    /// Returns a transaction spending a P2PKH, a P2WPKH and a P2TR output, and its prevouts.
+    fn spending_tx(secp: &Secp256k1<secp256k1::All>) -> (Transaction, Vec<TxOut>) {
+        let p2pkh = CompressedPublicKey(PublicKey::from_secret_key(secp, &secret_key(10)));
+        let p2wpkh = CompressedPublicKey(PublicKey::from_secret_key(secp, &secret_key(11)));
+        let (p2tr, _) = secret_key(12).x_only_public_key(secp);
+
+        let input = |vout: u32| TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array([1; 32]), vout),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let signature = [0x30; 71];
+
+        let mut p2pkh_input = input(0x100);
+        let mut script_sig = ScriptBuf::new();
+        script_sig.push_slice(PushBytesBuf::try_from(signature.to_vec()).unwrap());
+        script_sig.push_slice(p2pkh.to_bytes());
+        p2pkh_input.script_sig = script_sig;
+        let mut p2wpkh_input = input(2);
+        p2wpkh_input.witness = Witness::from_slice(&[&signature[..], &p2wpkh.to_bytes()[..]]);
+        let mut p2tr_input = input(3);
+        p2tr_input.witness = Witness::from_slice(&[&[0x01; 64][..]]);
+
+        let output = |script_pubkey| TxOut { value: Amount::from_sat_u32(1000), script_pubkey };
+        let prevouts = vec![
+            output(ScriptBuf::new_p2pkh(p2pkh.pubkey_hash())),
+            output(ScriptBuf::new_p2wpkh(p2wpkh.wpubkey_hash())),
+            output(ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+                XOnlyPublicKey::new(p2tr),
+            ))),
+        ];
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![p2pkh_input, p2wpkh_input, p2tr_input],
+            output: vec![],
+        };
+        (tx, prevouts)
+    }
+
+    fn input_keys() -> [InputSecretKey; 3] {
+        [
+            InputSecretKey::Ecdsa(secret_key(10)),
+            InputSecretKey::Ecdsa(secret_key(11)),
+            InputSecretKey::Taproot(secret_key(12)),
+        ]
+    }
+
+    fn p2tr_output(key: XOnlyPublicKey) -> TxOut {
+        TxOut {
+            value: Amount::from_sat_u32(500),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+                key,
+            )),
+        }
+    }
+
+    #[test]
+    fn send_and_receive() {
+        let secp = Secp256k1::new();
+        let spend_secret_key = secret_key(21);
+        let spend_key = PublicKey::from_secret_key(&secp, &spend_secret_key);
+        let mut receiver = Receiver::new(&secp, secret_key(20), spend_key, NetworkKind::Main);
+        let labeled = receiver.add_label(&secp, 7);
+        let change = receiver.add_label(&secp, Receiver::CHANGE_LABEL);
+        let other = Receiver::new(&secp, secret_key(30), spend_key, NetworkKind::Main);
+
+        let (mut tx, prevouts) = spending_tx(&secp);
+        let outpoints = tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>();
+        let recipients = [receiver.address(), other.address(), labeled, change, receiver.address()];
+        let outputs = derive_outputs(&secp, &outpoints, &input_keys(), &recipients).unwrap();
+        assert_eq!(outputs.len(), 5);
+        tx.output = outputs.iter().map(|key| p2tr_output(*key)).collect();
+        tx.output.push(TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([]) });
+
+        let received = receiver.scan(&secp, &tx, &prevouts).unwrap();
+        let mut found =
+            received.iter().map(|output| (output.vout, output.label)).collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, [(0, None), (2, Some(7)), (3, Some(0)), (4, None)]);
+        for output in &received {
+            let secret_key = spend_secret_key.add_tweak(&output.tweak.into()).unwrap();
+            assert_eq!(
+                XOnlyPublicKey::new(secret_key.x_only_public_key(&secp).0),
+                output.output_key
+            );
+        }
+
+        let received = other.scan(&secp, &tx, &prevouts).unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].vout, 1);
+
+        // Outpoints are compared by their serialization.
+        assert_eq!(smallest_outpoint(&outpoints).unwrap()[32..], [0x00, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn ineligible_transactions() {
+        let secp = Secp256k1::new();
+        let receiver = Receiver::new(
+            &secp,
+            secret_key(20),
+            PublicKey::from_secret_key(&secp, &secret_key(21)),
+            NetworkKind::Test,
+        );
+        let (mut tx, mut prevouts) = spending_tx(&secp);
+        let outpoints = tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>();
+        let outputs =
+            derive_outputs(&secp, &outpoints, &input_keys(), &[receiver.address()]).unwrap();
+        tx.output = vec![p2tr_output(outputs[0])];
+        assert_eq!(receiver.scan(&secp, &tx, &prevouts).unwrap().len(), 1);
+
+        assert_eq!(
+            receiver.scan(&secp, &tx, &prevouts[1..]),
+            Err(Error::PrevoutsMismatch { inputs: 3, prevouts: 2 })
+        );
+
+        // The P2TR input is spent with the script path of an output without key path.
+        let mut script_path = tx.clone();
+        let mut control_block = vec![0xc0];
+        control_block.extend_from_slice(&NUMS_H);
+        script_path.input[2].witness = Witness::from_slice(&[&[0x51][..], &control_block[..]]);
+        assert!(receiver.scan(&secp, &script_path, &prevouts).unwrap().is_empty());
+
+        // Spending a segwit version 2 output.
+        prevouts[0].script_pubkey =
+            ScriptBuf::from_bytes([&[0x52, 0x02][..], &[0; 2][..]].concat());
+        assert!(receiver.scan(&secp, &tx, &prevouts).unwrap().is_empty());
+
+        assert_eq!(
+            derive_outputs(&secp, &[], &input_keys(), &[receiver.address()]),
+            Err(Error::NoInputs)
+        );
+        assert_eq!(
+            derive_outputs(&secp, &outpoints, &[], &[receiver.address()]),
+            Err(Error::NoEligibleInputs)
+        );
+        let negated =
+            [InputSecretKey::Ecdsa(secret_key(10)), InputSecretKey::Ecdsa(secret_key(10).negate())];
+        assert_eq!(
+            derive_outputs(&secp, &outpoints, &negated, &[receiver.address()]),
+            Err(Error::InputKeysSumToZero)
+        );
+    }
+
+    #[test]
+    fn bip352_vectors() {
+        use hex::FromHex as _;
+        use serde_json::Value;
+
+        let hex = |v: &Value| Vec::<u8>::from_hex(v.as_str().unwrap()).unwrap();
+        let secret_key =
+            |v: &Value| SecretKey::from_byte_array(&hex(v).try_into().unwrap()).unwrap();
+        let output_key =
+            |v: &Value| XOnlyPublicKey::from_byte_array(&hex(v).try_into().unwrap()).unwrap();
+        // The transaction inputs and the outputs they spend.
+        let inputs = |vin: &Value| {
+            vin.as_array()
+                .unwrap()
+                .iter()
+                .map(|input| {
+                    let witness = match input["txinwitness"].as_str().unwrap() {
+                        "" => Witness::new(),
+                        _ => crate::consensus::deserialize(&hex(&input["txinwitness"])).unwrap(),
+                    };
+                    let txin = TxIn {
+                        previous_output: OutPoint::new(
+                            input["txid"].as_str().unwrap().parse().unwrap(),
+                            input["vout"].as_u64().unwrap() as u32,
+                        ),
+                        script_sig: ScriptBuf::from_bytes(hex(&input["scriptSig"])),
+                        sequence: Sequence::MAX,
+                        witness,
+                    };
+                    let prevout = TxOut {
+                        value: Amount::ZERO,
+                        script_pubkey: ScriptBuf::from_bytes(hex(
+                            &input["prevout"]["scriptPubKey"]["hex"]
+                        )),
+                    };
+                    (txin, prevout)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Test vectors from BIP-352, as of https://github.com/bitcoin/bips/pull/1458.
+        let data = include_str!("../tests/data/bip352_send_and_receive_test_vectors.json");
+        let vectors = serde_json::from_str::<Value>(data).unwrap();
+        let secp = Secp256k1::new();
+        for vector in vectors.as_array().unwrap() {
+            let comment = vector["comment"].as_str().unwrap();
+
+            for sending in vector["sending"].as_array().unwrap() {
+                let given = &sending["given"];
+                let inputs = inputs(&given["vin"]);
+                let outpoints =
+                    inputs.iter().map(|(txin, _)| txin.previous_output).collect::<Vec<_>>();
+                // The sender only uses the keys of the eligible inputs.
+                let input_keys = inputs
+                    .iter()
+                    .zip(given["vin"].as_array().unwrap())
+                    .filter(|((txin, prevout), _)| {
+                        input_public_key(txin, &prevout.script_pubkey).is_some()
+                    })
+                    .map(|((_, prevout), input)| {
+                        let key = secret_key(&input["private_key"]);
+                        if prevout.script_pubkey.is_p2tr() {
+                            InputSecretKey::Taproot(key)
+                        } else {
+                            InputSecretKey::Ecdsa(key)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let recipients = given["recipients"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|address| address.as_str().unwrap().parse().unwrap())
+                    .collect::<Vec<SilentPaymentAddress>>();
+
+                let outputs = match derive_outputs(&secp, &outpoints, &input_keys, &recipients) {
+                    Err(Error::NoEligibleInputs) => vec![],
+                    res => res.unwrap(),
+                };
+                // The expected outputs include the alternatives for recipients sharing a scan key,
+                // the outputs paying them may be derived in any order.
+                let expected = sending["expected"]["outputs"].as_array().unwrap();
+                let expected = expected.iter().map(output_key).collect::<Vec<_>>();
+                let n_outputs = sending["expected"]["n_outputs"].as_u64().unwrap();
+                assert_eq!(outputs.len() as u64, n_outputs, "{}", comment);
+                assert!(outputs.iter().all(|key| expected.contains(key)), "{}", comment);
+            }
+
+            for receiving in vector["receiving"].as_array().unwrap() {
+                let given = &receiving["given"];
+                let expected = &receiving["expected"];
+                let (input, prevouts): (Vec<_>, Vec<_>) = inputs(&given["vin"]).into_iter().unzip();
+                let tx = Transaction {
+                    version: transaction::Version::TWO,
+                    lock_time: absolute::LockTime::ZERO,
+                    input,
+                    output: given["outputs"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|key| p2tr_output(output_key(key)))
+                        .collect(),
+                };
+
+                let keys = &given["key_material"];
+                let spend_secret_key = secret_key(&keys["spend_priv_key"]);
+                let spend_key = PublicKey::from_secret_key(&secp, &spend_secret_key);
+                let mut receiver = Receiver::new(
+                    &secp,
+                    secret_key(&keys["scan_priv_key"]),
+                    spend_key,
+                    NetworkKind::Main,
+                );
+                let mut addresses = vec![receiver.address().to_string()];
+                for label in given["labels"].as_array().unwrap() {
+                    let label = label.as_u64().unwrap() as u32;
+                    addresses.push(receiver.add_label(&secp, label).to_string());
+                }
+                let expected_addresses = expected["addresses"].as_array().unwrap();
+                assert_eq!(addresses, *expected_addresses, "{}", comment);
+
+                // As for the sender, the expected outputs include the alternatives.
+                let received = receiver.scan(&secp, &tx, &prevouts).unwrap();
+                let n_outputs = expected["n_outputs"].as_u64().unwrap();
+                assert_eq!(received.len() as u64, n_outputs, "{}", comment);
+                let expected_outputs = expected["outputs"].as_array().unwrap();
+                for output in &received {
+                    let expected = expected_outputs
+                        .iter()
+                        .find(|expected| output_key(&expected["pub_key"]) == output.output_key)
+                        .unwrap_or_else(|| panic!("{}: unexpected output", comment));
+                    assert_eq!(
+                        output.tweak,
+                        secret_key(&expected["priv_key_tweak"]),
+                        "{}",
+                        comment
+                    );
+                    let secret_key = spend_secret_key.add_tweak(&output.tweak.into()).unwrap();
+                    assert_eq!(
+                        XOnlyPublicKey::new(secret_key.x_only_public_key(&secp).0),
+                        output.output_key
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,554 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP-37 Connection Bloom filtering.
+//!
+//! Implementation of the bloom filter a light client loads into its peers with the `filterload`
+//! message, so that they only relay the transactions matching it. Elements are added to a loaded
+//! filter with the `filteradd` message and the filter is removed with the `filterclear` message.
+//!
+//! # Relevant BIPS
+//!
+//! * [BIP 37 - Connection Bloom filtering](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki)
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::bip37::BloomFilter;
+//! use bitcoin::p2p::message_bloom::{BloomFlags, FilterAdd, FilterLoad};
+//!
+//! // A filter for 10 elements with a false positive rate of 0.1%.
+//! let mut filter = BloomFilter::new(10, 0.001, 0, BloomFlags::All);
+//! filter.insert(b"data");
+//! assert!(filter.contains(b"data"));
+//!
+//! // Send the filter to a peer, which loads it.
+//! let message = FilterLoad::from(filter.clone());
+//! let mut loaded = BloomFilter::try_from(message).unwrap();
+//! assert_eq!(loaded, filter);
+//!
+//! // Add an element to the filter of the peer.
+//! filter.insert(b"more data");
+//! let message = FilterAdd::try_from(&b"more data"[..]).unwrap();
+//! loaded.insert_filter_add(&message).unwrap();
+//! assert_eq!(loaded, filter);
+//! ```
+
+use core::convert::Infallible;
+use core::fmt;
+
+use crate::address::script_pubkey::ScriptExt as _;
+use crate::consensus::encode;
+use crate::constants::MAX_STACK_ELEMENT_SIZE;
+use crate::crypto::key::PublicKey;
+use crate::p2p::message_bloom::{BloomFlags, FilterAdd, FilterLoad};
+use crate::prelude::Vec;
+use crate::script::{Instruction, Script, ScriptExt as _};
+use crate::transaction::{OutPoint, Transaction};
+
+/// The maximum size of a filter in bytes.
+pub const MAX_FILTER_SIZE: usize = 36_000;
+
+/// The maximum number of hash functions of a filter.
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// The maximum size of an element added with the `filteradd` message in bytes.
+pub const MAX_ELEMENT_SIZE: usize = MAX_STACK_ELEMENT_SIZE;
+
+/// Multiplier of the hash function number in the MurmurHash3 seed.
+const SEED_MULTIPLIER: u32 = 0xFBA4_C795;
+
+/// A BIP-37 bloom filter.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BloomFilter {
+    data: Vec<u8>,
+    hash_funcs: u32,
+    tweak: u32,
+    flags: BloomFlags,
+}
+
+impl BloomFilter {
+    /// Constructs a new empty filter sized for `elements` elements with a false positive rate of
+    /// `fp_rate`, which must be between 0 and 1.
+    ///
+    /// The size of the filter is capped at [`MAX_FILTER_SIZE`] bytes and [`MAX_HASH_FUNCS`] hash
+    /// functions, the false positive rate of a capped filter is higher. `tweak` should be random,
+    /// so that peers cannot link filters to each other.
+    pub fn new(elements: u32, fp_rate: f64, tweak: u32, flags: BloomFlags) -> Self {
+        let elements = elements.max(1);
+        let bits = -1.0 / (core::f64::consts::LN_2 * core::f64::consts::LN_2)
+            * f64::from(elements)
+            * fp_rate.ln();
+        let size = (bits as usize).min(MAX_FILTER_SIZE * 8) / 8;
+        // Integer division, as in Bitcoin Core.
+        let bits_per_element = (size * 8) as u32 / elements;
+        let hash_funcs = (f64::from(bits_per_element) * core::f64::consts::LN_2) as u32;
+        BloomFilter {
+            data: vec![0; size],
+            hash_funcs: hash_funcs.min(MAX_HASH_FUNCS),
+            tweak,
+            flags,
+        }
+    }
+
+    /// Returns the filter bits.
+    pub fn as_bytes(&self) -> &[u8] { &self.data }
+
+    /// Returns the number of hash functions.
+    pub fn hash_funcs(&self) -> u32 { self.hash_funcs }
+
+    /// Returns the random value added to the hash function seeds.
+    pub fn tweak(&self) -> u32 { self.tweak }
+
+    /// Returns how the filter is updated by [`BloomFilter::is_relevant_and_update`].
+    pub fn flags(&self) -> BloomFlags { self.flags }
+
+    /// Inserts `data` into the filter.
+    pub fn insert(&mut self, data: &[u8]) {
+        if self.data.is_empty() {
+            return;
+        }
+        for n in 0..self.hash_funcs {
+            let index = self.bit_index(n, data);
+            self.data[index >> 3] |= 1 << (index & 7);
+        }
+    }
+
+    /// Inserts the element of a `filteradd` message received from a peer.
+    ///
+    /// # Errors
+    ///
+    /// If the element is larger than [`MAX_ELEMENT_SIZE`] bytes, for which Bitcoin Core treats the
+    /// peer as misbehaving. The filter is not changed.
+    pub fn insert_filter_add(&mut self, message: &FilterAdd) -> Result<(), Error> {
+        check_element_size(&message.data)?;
+        self.insert(&message.data);
+        Ok(())
+    }
+
+    /// Clears the filter, as a peer does when receiving a `filterclear` message.
+    ///
+    /// A cleared filter is empty and matches all transactions, as a peer without a filter relays
+    /// all of them.
+    pub fn clear(&mut self) {
+        *self = BloomFilter { data: Vec::new(), hash_funcs: 0, tweak: 0, flags: BloomFlags::None };
+    }
+
+    /// Inserts an outpoint into the filter, matching transactions spending it.
+    pub fn insert_outpoint(&mut self, outpoint: &OutPoint) {
+        self.insert(&encode::serialize(outpoint));
+    }
+
+    /// Inserts a public key and its hash into the filter.
+    ///
+    /// This matches transactions paying to the key in P2PK, bare multisig, P2PKH and P2WPKH
+    /// outputs, and P2PKH and P2SH-P2WPKH inputs spending with it.
+    pub fn insert_pubkey(&mut self, public_key: &PublicKey) {
+        self.insert(&public_key.to_vec());
+        self.insert(public_key.pubkey_hash().as_ref());
+    }
+
+    /// Inserts all data pushes of `script` into the filter.
+    ///
+    /// The pushes of a script pubkey, such as its script hash or witness program, are matched by
+    /// [`BloomFilter::is_relevant_and_update`].
+    pub fn insert_script(&mut self, script: &Script) {
+        for data in pushes(script) {
+            self.insert(data);
+        }
+    }
+
+    /// Returns true if `data` may have been inserted into the filter.
+    ///
+    /// An empty filter matches everything.
+    pub fn contains(&self, data: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        (0..self.hash_funcs).all(|n| {
+            let index = self.bit_index(n, data);
+            self.data[index >> 3] & (1 << (index & 7)) != 0
+        })
+    }
+
+    /// Returns true if `outpoint` may have been inserted into the filter.
+    pub fn contains_outpoint(&self, outpoint: &OutPoint) -> bool {
+        self.contains(&encode::serialize(outpoint))
+    }
+
+    /// Returns true if `public_key` may have been inserted into the filter.
+    pub fn contains_pubkey(&self, public_key: &PublicKey) -> bool {
+        self.contains(&public_key.to_vec())
+    }
+
+    /// Returns true if `tx` matches the filter, as a peer serving the filter would decide.
+    ///
+    /// A transaction matches if its txid, a data push in one of its script pubkeys, an outpoint
+    /// it spends or a data push in one of its script sigs is in the filter. If an output matches,
+    /// its outpoint is inserted depending on the [`BloomFlags`], so that transactions spending it
+    /// match as well.
+    pub fn is_relevant_and_update(&mut self, tx: &Transaction) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        let txid = tx.compute_txid();
+        let mut found = self.contains(txid.as_ref());
+
+        for (vout, output) in tx.output.iter().enumerate() {
+            if !pushes(&output.script_pubkey).any(|data| self.contains(data)) {
+                continue;
+            }
+            found = true;
+            let update = match self.flags {
+                BloomFlags::None => false,
+                BloomFlags::All => true,
+                BloomFlags::PubkeyOnly =>
+                    output.script_pubkey.is_p2pk() || output.script_pubkey.is_multisig(),
+            };
+            if update {
+                self.insert_outpoint(&OutPoint { txid, vout: vout as u32 });
+            }
+        }
+        if found {
+            return true;
+        }
+
+        tx.input.iter().any(|input| {
+            self.contains_outpoint(&input.previous_output)
+                || pushes(&input.script_sig).any(|data| self.contains(data))
+        })
+    }
+
+    /// Returns the index of the bit set by hash function number `n` for `data`.
+    fn bit_index(&self, n: u32, data: &[u8]) -> usize {
+        let seed = n.wrapping_mul(SEED_MULTIPLIER).wrapping_add(self.tweak);
+        murmur3(seed, data) as usize % (self.data.len() * 8)
+    }
+}
+
+impl From<BloomFilter> for FilterLoad {
+    fn from(filter: BloomFilter) -> Self {
+        FilterLoad {
+            filter: filter.data,
+            hash_funcs: filter.hash_funcs,
+            tweak: filter.tweak,
+            flags: filter.flags,
+        }
+    }
+}
+
+impl TryFrom<FilterLoad> for BloomFilter {
+    type Error = Error;
+
+    fn try_from(message: FilterLoad) -> Result<Self, Self::Error> {
+        if message.filter.len() > MAX_FILTER_SIZE {
+            return Err(Error::FilterTooLarge(message.filter.len()));
+        }
+        if message.hash_funcs > MAX_HASH_FUNCS {
+            return Err(Error::TooManyHashFuncs(message.hash_funcs));
+        }
+        Ok(BloomFilter {
+            data: message.filter,
+            hash_funcs: message.hash_funcs,
+            tweak: message.tweak,
+            flags: message.flags,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for FilterAdd {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        check_element_size(data)?;
+        Ok(FilterAdd { data: data.to_vec() })
+    }
+}
+
+/// Checks that `data` can be added to a filter with the `filteradd` message.
+fn check_element_size(data: &[u8]) -> Result<(), Error> {
+    if data.len() > MAX_ELEMENT_SIZE {
+        return Err(Error::ElementTooLarge(data.len()));
+    }
+    Ok(())
+}
+
+/// Returns the non-empty data pushes of `script`, up to the first invalid instruction.
+fn pushes(script: &Script) -> impl Iterator<Item = &[u8]> {
+    script.instructions().map_while(Result::ok).filter_map(|instruction| match instruction {
+        Instruction::PushBytes(bytes) if !bytes.is_empty() => Some(bytes.as_bytes()),
+        _ => None,
+    })
+}
+
+/// The 32-bit x86 variant of MurmurHash3.
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut h1 = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k1 = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k1 = 0u32;
+        for (i, byte) in tail.iter().enumerate() {
+            k1 ^= u32::from(*byte) << (8 * i);
+        }
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85eb_ca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2_ae35);
+    h1 ^ (h1 >> 16)
+}
+
+/// An error handling a `filterload` or `filteradd` message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The filter is larger than [`MAX_FILTER_SIZE`] bytes.
+    FilterTooLarge(usize),
+    /// The filter uses more than [`MAX_HASH_FUNCS`] hash functions.
+    TooManyHashFuncs(u32),
+    /// The added element is larger than [`MAX_ELEMENT_SIZE`] bytes.
+    ElementTooLarge(usize),
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            FilterTooLarge(size) =>
+                write!(f, "filter of {} bytes exceeds the maximum of {}", size, MAX_FILTER_SIZE),
+            TooManyHashFuncs(n) =>
+                write!(f, "{} hash functions exceed the maximum of {}", n, MAX_HASH_FUNCS),
+            ElementTooLarge(size) =>
+                write!(f, "element of {} bytes exceeds the maximum of {}", size, MAX_ELEMENT_SIZE),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            FilterTooLarge(_) | TooManyHashFuncs(_) | ElementTooLarge(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::FromHex as _;
+    use hex_lit::hex;
+
+    use super::*;
+    use crate::consensus::encode::serialize;
+    use crate::locktime::absolute;
+    use crate::script::ScriptBuf;
+    use crate::transaction::{self, TxIn, TxOut};
+    use crate::{Amount, Sequence, Witness};
+
+    #[test]
+    fn murmur3_vectors() {
+        // From Bitcoin Core's hash tests.
+        assert_eq!(murmur3(0x00000000, &[]), 0x00000000);
+        assert_eq!(murmur3(0xFBA4C795, &[]), 0x6a396f08);
+        assert_eq!(murmur3(0xffffffff, &[]), 0x81f16f39);
+        assert_eq!(murmur3(0x00000000, &hex!("00")), 0x514e28b7);
+        assert_eq!(murmur3(0xFBA4C795, &hex!("00")), 0xea3f0b17);
+        assert_eq!(murmur3(0x00000000, &hex!("ff")), 0xfd6cf10d);
+        assert_eq!(murmur3(0x00000000, &hex!("0011")), 0x16c6b7ab);
+        assert_eq!(murmur3(0x00000000, &hex!("001122")), 0x8eb51c3d);
+        assert_eq!(murmur3(0x00000000, &hex!("00112233")), 0xb4471bf8);
+        assert_eq!(murmur3(0x00000000, &hex!("0011223344")), 0xe2301fa8);
+        assert_eq!(murmur3(0x00000000, &hex!("001122334455")), 0xfc2e4a15);
+        assert_eq!(murmur3(0x00000000, &hex!("00112233445566")), 0xb074502c);
+        assert_eq!(murmur3(0x00000000, &hex!("0011223344556677")), 0x8034d2a0);
+        assert_eq!(murmur3(0x00000000, &hex!("001122334455667788")), 0xb4698def);
+    }
+
+    #[test]
+    fn create_insert_serialize() {
+        // From Bitcoin Core's bloom tests.
+        for (tweak, serialized) in
+            [(0, "03614e9b050000000000000001"), (2147483649, "03ce4299050000000100008001")]
+        {
+            let mut filter = BloomFilter::new(3, 0.01, tweak, BloomFlags::All);
+            filter.insert(&hex!("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+            assert!(filter.contains(&hex!("99108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+            assert!(!filter.contains(&hex!("19108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+            filter.insert(&hex!("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+            assert!(filter.contains(&hex!("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")));
+            filter.insert(&hex!("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+            assert!(filter.contains(&hex!("b9300670b4c5366e95b2699e8b18bc75e5f729c5")));
+
+            let message = FilterLoad::from(filter.clone());
+            assert_eq!(serialize(&message), Vec::from_hex(serialized).unwrap());
+            assert_eq!(BloomFilter::try_from(message).unwrap(), filter);
+        }
+    }
+
+    #[test]
+    fn size_limits() {
+        let filter = BloomFilter::new(1_000_000, 0.0001, 0, BloomFlags::None);
+        assert_eq!(filter.as_bytes().len(), MAX_FILTER_SIZE);
+        let filter = BloomFilter::new(1, 1e-30, 0, BloomFlags::None);
+        assert_eq!(filter.hash_funcs(), MAX_HASH_FUNCS);
+
+        let message = FilterLoad {
+            filter: vec![0; MAX_FILTER_SIZE + 1],
+            hash_funcs: 1,
+            tweak: 0,
+            flags: BloomFlags::None,
+        };
+        assert_eq!(BloomFilter::try_from(message), Err(Error::FilterTooLarge(MAX_FILTER_SIZE + 1)));
+        let message = FilterLoad {
+            filter: vec![0; 10],
+            hash_funcs: MAX_HASH_FUNCS + 1,
+            tweak: 0,
+            flags: BloomFlags::None,
+        };
+        assert_eq!(
+            BloomFilter::try_from(message),
+            Err(Error::TooManyHashFuncs(MAX_HASH_FUNCS + 1))
+        );
+
+        // An empty filter matches everything.
+        let mut filter = BloomFilter::new(0, 1.0, 0, BloomFlags::None);
+        assert!(filter.as_bytes().is_empty());
+        assert!(filter.contains(b"anything"));
+        assert!(filter.is_relevant_and_update(&transaction(vec![], vec![])));
+    }
+
+    #[test]
+    fn filter_add_and_clear() {
+        let mut filter = BloomFilter::new(3, 0.01, 0, BloomFlags::All);
+        let mut loaded = BloomFilter::try_from(FilterLoad::from(filter.clone())).unwrap();
+
+        let element = hex!("99108ad8ed9bb6274d3980bab5a85c048f0950c8");
+        filter.insert(&element);
+        let message = FilterAdd::try_from(&element[..]).unwrap();
+        assert_eq!(serialize(&message), hex!("1499108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+        loaded.insert_filter_add(&message).unwrap();
+        assert_eq!(loaded, filter);
+        assert!(loaded.contains(&element));
+
+        // Bitcoin Core accepts elements of up to 520 bytes.
+        let element = [0xab; MAX_ELEMENT_SIZE];
+        filter.insert(&element);
+        loaded.insert_filter_add(&FilterAdd::try_from(&element[..]).unwrap()).unwrap();
+        assert_eq!(loaded, filter);
+
+        let oversized = [0xcd; MAX_ELEMENT_SIZE + 1];
+        assert_eq!(
+            FilterAdd::try_from(&oversized[..]),
+            Err(Error::ElementTooLarge(MAX_ELEMENT_SIZE + 1))
+        );
+        let message = FilterAdd { data: oversized.to_vec() };
+        assert_eq!(
+            loaded.insert_filter_add(&message),
+            Err(Error::ElementTooLarge(MAX_ELEMENT_SIZE + 1))
+        );
+        assert_eq!(loaded, filter);
+        assert!(!loaded.contains(&oversized));
+
+        // A peer without a filter relays all transactions.
+        loaded.clear();
+        assert!(loaded.as_bytes().is_empty());
+        assert!(loaded.contains(&oversized));
+        assert!(loaded.is_relevant_and_update(&transaction(vec![], vec![])));
+    }
+
+    fn transaction(input: Vec<TxIn>, output: Vec<ScriptBuf>) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input,
+            output: output
+                .into_iter()
+                .map(|script_pubkey| TxOut { value: Amount::ONE_BTC, script_pubkey })
+                .collect(),
+        }
+    }
+
+    fn spending(outpoint: OutPoint) -> TxIn {
+        TxIn {
+            previous_output: outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }
+    }
+
+    #[test]
+    fn match_transactions() {
+        use crate::address::script_pubkey::ScriptBufExt as _;
+
+        let public_key: PublicKey =
+            "0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352".parse().unwrap();
+        let p2pkh = ScriptBuf::new_p2pkh(public_key.pubkey_hash());
+        let p2pk = ScriptBuf::new_p2pk(public_key);
+        let other = ScriptBuf::new_p2pkh(
+            "03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb"
+                .parse::<PublicKey>()
+                .unwrap()
+                .pubkey_hash(),
+        );
+        let funding = transaction(vec![], vec![other.clone(), p2pkh, p2pk]);
+        let txid = funding.compute_txid();
+        let spend =
+            |vout| transaction(vec![spending(OutPoint { txid, vout })], vec![other.clone()]);
+
+        for (flags, p2pkh_spend, p2pk_spend) in [
+            (BloomFlags::None, false, false),
+            (BloomFlags::All, true, true),
+            (BloomFlags::PubkeyOnly, false, true),
+        ] {
+            let mut filter = BloomFilter::new(10, 0.000_001, 5, flags);
+            filter.insert_pubkey(&public_key);
+            assert!(filter.contains_pubkey(&public_key));
+
+            assert!(filter.is_relevant_and_update(&funding));
+            assert!(!filter.is_relevant_and_update(&spend(0)));
+            assert_eq!(filter.is_relevant_and_update(&spend(1)), p2pkh_spend);
+            assert_eq!(filter.is_relevant_and_update(&spend(2)), p2pk_spend);
+        }
+
+        // Matching by txid, spent outpoint and script sig data.
+        let mut filter = BloomFilter::new(10, 0.000_001, 5, BloomFlags::None);
+        assert!(!filter.is_relevant_and_update(&funding));
+        filter.insert(txid.as_ref());
+        assert!(filter.is_relevant_and_update(&funding));
+
+        let mut filter = BloomFilter::new(10, 0.000_001, 5, BloomFlags::None);
+        filter.insert_outpoint(&OutPoint { txid, vout: 0 });
+        assert!(filter.is_relevant_and_update(&spend(0)));
+        assert!(!filter.is_relevant_and_update(&spend(1)));
+
+        let mut filter = BloomFilter::new(10, 0.000_001, 5, BloomFlags::None);
+        filter.insert_script(&other);
+        let mut tx = spend(1);
+        tx.output.clear();
+        assert!(!filter.is_relevant_and_update(&tx));
+        tx.input[0].script_sig = other.clone();
+        assert!(filter.is_relevant_and_update(&tx));
+    }
+}
@@ -0,0 +1,535 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP39 implementation.
+//!
+//! Implementation of mnemonic codes for generating deterministic keys, as defined
+//! at <https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki>.
+//!
+//! The seed produced by [`Mnemonic::to_seed`] is the input expected by
+//! [`Xpriv::new_master`](crate::bip32::Xpriv::new_master).
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::bip32::Xpriv;
+//! use bitcoin::bip39::Mnemonic;
+//! use bitcoin::NetworkKind;
+//!
+//! let mnemonic: Mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+//!     .parse()
+//!     .expect("valid mnemonic");
+//! let seed = mnemonic.to_seed("TREZOR");
+//! let xpriv = Xpriv::new_master(NetworkKind::Main, &seed);
+//! assert_eq!(
+//!     xpriv.to_string(),
+//!     "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF",
+//! );
+//! ```
+
+mod english;
+
+use core::convert::Infallible;
+use core::fmt;
+use core::str::FromStr;
+
+use hashes::{sha256, sha512, Hash, HashEngine, HmacEngine};
+
+use crate::prelude::{String, ToString, Vec};
+
+/// The minimum number of words in a mnemonic.
+pub const MIN_WORD_COUNT: usize = 12;
+
+/// The maximum number of words in a mnemonic.
+pub const MAX_WORD_COUNT: usize = 24;
+
+/// The number of PBKDF2 iterations used to derive the seed.
+const PBKDF2_ROUNDS: u32 = 2048;
+
+/// Language of a BIP-39 word list.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Language {
+    /// The English word list.
+    #[default]
+    English,
+}
+
+impl Language {
+    /// Returns the 2048 words of this language's word list.
+    pub fn word_list(self) -> &'static [&'static str; 2048] {
+        match self {
+            Language::English => &english::WORDS,
+        }
+    }
+
+    /// Returns the index of `word` in this language's word list.
+    pub fn find_word(self, word: &str) -> Option<u16> {
+        match self {
+            // The English word list is sorted so we can binary search it.
+            Language::English => english::WORDS.binary_search(&word).ok().map(|i| i as u16),
+        }
+    }
+}
+
+/// A BIP-39 mnemonic sentence.
+///
+/// The mnemonic encodes between 128 and 256 bits of entropy, plus a checksum, as 12 to 24
+/// words.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Mnemonic {
+    /// The language of the word list the words are taken from.
+    language: Language,
+    /// Indices of the words in the word list, only the first `word_count` are used.
+    words: [u16; MAX_WORD_COUNT],
+    /// The number of words.
+    word_count: usize,
+}
+
+impl Mnemonic {
+    /// Constructs a new English mnemonic from `entropy`.
+    ///
+    /// # Errors
+    ///
+    /// If the entropy is not between 128 and 256 bits long or not a multiple of 32 bits.
+    pub fn from_entropy(entropy: &[u8]) -> Result<Mnemonic, Error> {
+        Mnemonic::from_entropy_in(Language::English, entropy)
+    }
+
+    /// Constructs a new mnemonic in the given language from `entropy`.
+    ///
+    /// # Errors
+    ///
+    /// If the entropy is not between 128 and 256 bits long or not a multiple of 32 bits.
+    pub fn from_entropy_in(language: Language, entropy: &[u8]) -> Result<Mnemonic, Error> {
+        let bit_count = entropy.len() * 8;
+        if bit_count % 32 != 0 || !(128..=256).contains(&bit_count) {
+            return Err(Error::BadEntropyBitCount(bit_count));
+        }
+
+        // The checksum is the first `bit_count / 32` bits of the SHA256 hash of the entropy,
+        // appended to the entropy. Every 11 bits of the result are a word index.
+        let checksum = sha256::Hash::hash(entropy).to_byte_array()[0];
+        let bit = |i: usize| -> u16 {
+            let byte = if i < bit_count { entropy[i / 8] } else { checksum };
+            u16::from(byte >> (7 - i % 8) & 1)
+        };
+
+        let word_count = (bit_count + bit_count / 32) / 11;
+        let mut words = [0; MAX_WORD_COUNT];
+        for (i, word) in words[..word_count].iter_mut().enumerate() {
+            *word = (0..11).fold(0, |index, j| index << 1 | bit(i * 11 + j));
+        }
+        Ok(Mnemonic { language, words, word_count })
+    }
+
+    /// Generates a new random English mnemonic with `word_count` words.
+    ///
+    /// # Errors
+    ///
+    /// If `word_count` is not 12, 15, 18, 21 or 24.
+    #[cfg(feature = "rand-std")]
+    pub fn generate(word_count: usize) -> Result<Mnemonic, Error> {
+        use secp256k1::rand::{thread_rng, RngCore};
+
+        if word_count % 3 != 0 || !(MIN_WORD_COUNT..=MAX_WORD_COUNT).contains(&word_count) {
+            return Err(Error::BadWordCount(word_count));
+        }
+        let mut entropy = [0; 32];
+        let entropy = &mut entropy[..word_count / 3 * 4];
+        thread_rng().fill_bytes(entropy);
+        Mnemonic::from_entropy(entropy)
+    }
+
+    /// Parses a mnemonic sentence using the word list of the given language.
+    ///
+    /// Words must be separated by whitespace. The mnemonic is expected to be in Unicode
+    /// normalization form NFKD, which is always the case for the English word list.
+    ///
+    /// # Errors
+    ///
+    /// If the number of words is invalid, a word is not in the word list or the checksum does
+    /// not match.
+    pub fn parse_in(language: Language, s: &str) -> Result<Mnemonic, Error> {
+        let mut words = [0; MAX_WORD_COUNT];
+        let mut word_count = 0;
+        for (i, word) in s.split_whitespace().enumerate() {
+            if i < MAX_WORD_COUNT {
+                words[i] = language.find_word(word).ok_or(Error::UnknownWord(i))?;
+            }
+            word_count += 1;
+        }
+        if word_count % 3 != 0 || !(MIN_WORD_COUNT..=MAX_WORD_COUNT).contains(&word_count) {
+            return Err(Error::BadWordCount(word_count));
+        }
+
+        let mnemonic = Mnemonic { language, words, word_count };
+        let (bytes, entropy_len) = mnemonic.to_bytes();
+        let checksum = sha256::Hash::hash(&bytes[..entropy_len]).to_byte_array()[0];
+        // Only the first `word_count / 3` bits of the checksum are encoded.
+        if (bytes[entropy_len] ^ checksum) >> (8 - word_count / 3) != 0 {
+            return Err(Error::InvalidChecksum);
+        }
+        Ok(mnemonic)
+    }
+
+    /// Returns the language of the word list used by this mnemonic.
+    pub fn language(&self) -> Language { self.language }
+
+    /// Returns the number of words in this mnemonic.
+    pub fn word_count(&self) -> usize { self.word_count }
+
+    /// Returns an iterator over the words of this mnemonic.
+    pub fn words(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let list = self.language.word_list();
+        self.words[..self.word_count].iter().map(move |&index| list[usize::from(index)])
+    }
+
+    /// Returns the entropy encoded by this mnemonic.
+    pub fn to_entropy(&self) -> Vec<u8> {
+        let (bytes, entropy_len) = self.to_bytes();
+        bytes[..entropy_len].to_vec()
+    }
+
+    /// Derives the 64 byte seed from this mnemonic and `passphrase`.
+    ///
+    /// The seed is derived using PBKDF2-HMAC-SHA512 with 2048 iterations, the mnemonic sentence
+    /// as password and the string "mnemonic" followed by `passphrase` as salt. Pass an empty
+    /// string if no passphrase is used. The passphrase is expected to be in Unicode normalization
+    /// form NFKD, callers accepting non-ASCII passphrases need to normalize them first.
+    ///
+    /// The seed can be used with [`Xpriv::new_master`](crate::bip32::Xpriv::new_master).
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let password = self.to_string();
+        pbkdf2_hmac_sha512(password.as_bytes(), &[b"mnemonic", passphrase.as_bytes()])
+    }
+
+    /// Returns the entropy followed by the checksum, and the length of the entropy in bytes.
+    fn to_bytes(&self) -> ([u8; 33], usize) {
+        let mut bytes = [0; 33];
+        for (i, &word) in self.words[..self.word_count].iter().enumerate() {
+            for j in 0..11 {
+                if word >> (10 - j) & 1 == 1 {
+                    let bit = i * 11 + j;
+                    bytes[bit / 8] |= 1 << (7 - bit % 8);
+                }
+            }
+        }
+        (bytes, self.word_count / 3 * 4)
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, word) in self.words().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(word)?;
+        }
+        Ok(())
+    }
+}
+
+/// Does not print the words, the mnemonic is as secret as the seed derived from it.
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mnemonic")
+            .field("language", &self.language)
+            .field("word_count", &self.word_count)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Mnemonic::parse_in(Language::English, s) }
+}
+
+impl TryFrom<&str> for Mnemonic {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> { s.parse() }
+}
+
+impl TryFrom<String> for Mnemonic {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> { s.parse() }
+}
+
+/// Derives a 64 byte key using PBKDF2 with HMAC-SHA512 as the pseudorandom function.
+///
+/// The salt is the concatenation of `salt`. Since the output is exactly one HMAC-SHA512 block
+/// only the first block is computed.
+fn pbkdf2_hmac_sha512(password: &[u8], salt: &[&[u8]]) -> [u8; 64] {
+    let prf = HmacEngine::<sha512::HashEngine>::new(password);
+
+    let mut engine = prf.clone();
+    for part in salt {
+        engine.input(part);
+    }
+    engine.input(&1u32.to_be_bytes());
+    let mut block = engine.finalize().to_byte_array();
+
+    let mut key = block;
+    for _ in 1..PBKDF2_ROUNDS {
+        let mut engine = prf.clone();
+        engine.input(&block);
+        block = engine.finalize().to_byte_array();
+        key.iter_mut().zip(block.iter()).for_each(|(k, b)| *k ^= b);
+    }
+    key
+}
+
+/// Error constructing or parsing a mnemonic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Mnemonic does not have 12, 15, 18, 21 or 24 words.
+    BadWordCount(usize),
+    /// The word at the given index is not in the word list.
+    UnknownWord(usize),
+    /// Entropy is not between 128 and 256 bits long or not a multiple of 32 bits.
+    BadEntropyBitCount(usize),
+    /// The checksum encoded in the mnemonic does not match its entropy.
+    InvalidChecksum,
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            BadWordCount(count) =>
+                write!(f, "mnemonic has {} words, expected 12, 15, 18, 21 or 24", count),
+            UnknownWord(index) => write!(f, "mnemonic word {} is not in the word list", index),
+            BadEntropyBitCount(count) => write!(
+                f,
+                "entropy of {} bits is invalid, expected 128 to 256 bits in steps of 32",
+                count
+            ),
+            InvalidChecksum => f.write_str("mnemonic checksum is invalid"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            BadWordCount(_) | UnknownWord(_) | BadEntropyBitCount(_) | InvalidChecksum => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::FromHex;
+
+    use super::*;
+
+    #[test]
+    fn english_word_list() {
+        let words = Language::English.word_list();
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(Language::English.find_word(word), Some(i as u16));
+        }
+        assert_eq!(Language::English.find_word("bitcoin"), None);
+    }
+
+    #[test]
+    fn vectors() {
+        // Test vectors from BIP-39 as (entropy, mnemonic, seed), using the passphrase "TREZOR".
+        let vectors = [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+                "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+            ),
+            (
+                "80808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+                "d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+                "ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069",
+            ),
+            (
+                "000000000000000000000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+                "035895f2f481b1b0f01fcf8c289c794660b289981a78f8106447707fdd9666ca06da5a9a565181599b79f53b844d8a71dd9f439c52a3d7b3e8a79c906ac845fa",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+                "f2b94508732bcbacbcc020faefecfc89feafa6649a5491b8c952cede496c214a0c7b3c392d168748f2d4a612bada0753b52a1c7ac53c1e93abd5c6320b9e95dd",
+            ),
+            (
+                "808080808080808080808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+                "107d7c02a5aa6f38c58083ff74f04c607c2d2c0ecc55501dadd72d025b751bc27fe913ffb796f841c49b1d33b610cf0e91d3aa239027f5e99fe4ce9e5088cd65",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+                "0cd6e5d827bb62eb8fc1e262254223817fd068a74b5b449cc2f667c3f1f985a76379b43348d952e2265b4cd129090758b3e3c2c49103b5051aac2eaeb890a528",
+            ),
+            (
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+                "bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd3097170af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+                "bc09fca1804f7e69da93c2f2028eb238c227f2e9dda30cd63699232578480a4021b146ad717fbb7e451ce9eb835f43620bf5c514db0f8add49f5d121449d3e87",
+            ),
+            (
+                "8080808080808080808080808080808080808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+                "c0c519bd0e91a2ed54357d9d1ebef6f5af218a153624cf4f2da911a0ed8f7a09e2ef61af0aca007096df430022f7a2b6fb91661a9589097069720d015e4e982f",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+                "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad",
+            ),
+            (
+                "9e885d952ad362caeb4efe34a8e91bd2",
+                "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+                "274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028",
+            ),
+            (
+                "6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+                "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+                "628c3827a8823298ee685db84f55caa34b5cc195a778e52d45f59bcf75aba68e4d7590e101dc414bc1bbd5737666fbbef35d1f1903953b66624f910feef245ac",
+            ),
+            (
+                "68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+                "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+                "64c87cde7e12ecf6704ab95bb1408bef047c22db4cc7491c4271d170a1b213d20b385bc1588d9c7b38f1b39d415665b8a9030c9ec653d75e65f847d8fc1fc440",
+            ),
+            (
+                "c0ba5a8e914111210f2bd131f3d5e08d",
+                "scheme spot photo card baby mountain device kick cradle pact join borrow",
+                "ea725895aaae8d4c1cf682c1bfd2d358d52ed9f0f0591131b559e2724bb234fca05aa9c02c57407e04ee9dc3b454aa63fbff483a8b11de949624b9f1831a9612",
+            ),
+            (
+                "6d9be1ee6ebd27a258115aad99b7317b9c8d28b6d76431c3",
+                "horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+                "fd579828af3da1d32544ce4db5c73d53fc8acc4ddb1e3b251a31179cdb71e853c56d2fcb11aed39898ce6c34b10b5382772db8796e52837b54468aeb312cfc3d",
+            ),
+            (
+                "9f6a2878b2520799a44ef18bc7df394e7061a224d2c33cd015b157d746869863",
+                "panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+                "72be8e052fc4919d2adf28d5306b5474b0069df35b02303de8c1729c9538dbb6fc2d731d5f832193cd9fb6aeecbc469594a70e3dd50811b5067f3b88b28c3e8d",
+            ),
+            (
+                "23db8160a31d3e0dca3688ed941adbf3",
+                "cat swing flag economy stadium alone churn speed unique patch report train",
+                "deb5f45449e615feff5640f2e49f933ff51895de3b4381832b3139941c57b59205a42480c52175b6efcffaa58a2503887c1e8b363a707256bdd2b587b46541f5",
+            ),
+            (
+                "8197a4a47f0425faeaa69deebc05ca29c0a5b5cc76ceacc0",
+                "light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+                "4cbdff1ca2db800fd61cae72a57475fdc6bab03e441fd63f96dabd1f183ef5b782925f00105f318309a7e9c3ea6967c7801e46c8a58082674c860a37b93eda02",
+            ),
+            (
+                "066dca1a2bb7e8a1db2832148ce9933eea0f3ac9548d793112d9a95c9407efad",
+                "all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+                "26e975ec644423f4a4c4f4215ef09b4bd7ef924e85d1d17c4cf3f136c2863cf6df0a475045652c57eb5fb41513ca2a2d67722b77e954b4b3fc11f7590449191d",
+            ),
+            (
+                "f30f8c1da665478f49b001d94c5fc452",
+                "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+                "2aaa9242daafcee6aa9d7269f17d4efe271e1b9a529178d7dc139cd18747090bf9d60295d0ce74309a78852a9caadf0af48aae1c6253839624076224374bc63f",
+            ),
+            (
+                "c10ec20dc3cd9f652c7fac2f1230f7a3c828389a14392f05",
+                "scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+                "7b4a10be9d98e6cba265566db7f136718e1398c71cb581e1b2f464cac1ceedf4f3e274dc270003c670ad8d02c4558b2f8e39edea2775c9e232c7cb798b069e88",
+            ),
+            (
+                "f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+                "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+                "01f5bced59dec48e362f2c45b5de68b9fd6c92c6634f44d6d40aab69056506f0e35524a518034ddc1192e1dacd32c1ed3eaa3c3b131c88ed8e7e54c49a5d0998",
+            ),
+        ];
+
+        for (entropy, phrase, seed) in vectors {
+            let entropy = Vec::<u8>::from_hex(entropy).unwrap();
+            let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+
+            assert_eq!(mnemonic.to_string(), phrase);
+            assert_eq!(mnemonic, phrase.parse::<Mnemonic>().unwrap());
+            assert_eq!(mnemonic.word_count(), phrase.split(' ').count());
+            assert_eq!(mnemonic.to_entropy(), entropy);
+            assert_eq!(mnemonic.to_seed("TREZOR"), <[u8; 64]>::from_hex(seed).unwrap());
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let abandon = "abandon ".repeat(11);
+
+        assert_eq!(format!("{}about", abandon).parse::<Mnemonic>().map(|m| m.word_count()), Ok(12));
+        // Extra whitespace is accepted.
+        assert!(format!("  {} about\n", abandon.replace(' ', "\t ")).parse::<Mnemonic>().is_ok());
+
+        assert_eq!(format!("{}abandon", abandon).parse::<Mnemonic>(), Err(Error::InvalidChecksum));
+        assert_eq!(format!("{}bitcoin", abandon).parse::<Mnemonic>(), Err(Error::UnknownWord(11)));
+        assert_eq!(format!("{}About", abandon).parse::<Mnemonic>(), Err(Error::UnknownWord(11)));
+        assert_eq!(abandon.parse::<Mnemonic>(), Err(Error::BadWordCount(11)));
+        assert_eq!("".parse::<Mnemonic>(), Err(Error::BadWordCount(0)));
+        assert_eq!("abandon ".repeat(13).parse::<Mnemonic>(), Err(Error::BadWordCount(13)));
+        assert_eq!("abandon ".repeat(27).parse::<Mnemonic>(), Err(Error::BadWordCount(27)));
+    }
+
+    #[test]
+    fn debug_redacts_words() {
+        let mnemonic = Mnemonic::from_entropy(&[0; 16]).unwrap();
+        let debug = format!("{:?}", mnemonic);
+        assert!(!debug.contains("abandon"));
+        assert_eq!(debug, "Mnemonic { language: English, word_count: 12, .. }");
+    }
+
+    #[test]
+    fn entropy_errors() {
+        for len in [0, 12, 15, 17, 36, 40] {
+            assert_eq!(
+                Mnemonic::from_entropy(&vec![0; len]),
+                Err(Error::BadEntropyBitCount(len * 8))
+            );
+        }
+        for len in [16, 20, 24, 28, 32] {
+            let mnemonic = Mnemonic::from_entropy(&vec![0; len]).unwrap();
+            assert_eq!(mnemonic.word_count(), len * 3 / 4);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand-std")]
+    fn generate() {
+        for word_count in [12, 15, 18, 21, 24] {
+            let mnemonic = Mnemonic::generate(word_count).unwrap();
+            assert_eq!(mnemonic.word_count(), word_count);
+            assert_eq!(mnemonic.to_string().parse::<Mnemonic>(), Ok(mnemonic));
+        }
+        assert_eq!(Mnemonic::generate(13), Err(Error::BadWordCount(13)));
+    }
+}
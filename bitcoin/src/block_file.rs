@@ -0,0 +1,483 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bitcoin Core block and undo files.
+//!
+//! Bitcoin Core stores blocks in `blocks/blk?????.dat` files and the outputs spent by each block,
+//! needed to undo it during a reorg, in `blocks/rev?????.dat` files. Each record is framed by the
+//! network [`Magic`] and its size. Since Bitcoin Core 28.0 the files are XOR-ed with the key in
+//! `blocks/xor.dat`, see [`XorKey`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")] {
+//! use std::fs::File;
+//! use std::io::{BufReader, Read as _};
+//!
+//! use bitcoin::block_file::{BlockFileReader, XorKey};
+//! use bitcoin::p2p::Magic;
+//!
+//! let mut key = [0; 8];
+//! File::open("blocks/xor.dat")?.read_exact(&mut key)?;
+//!
+//! let file = BufReader::new(File::open("blocks/blk00000.dat")?);
+//! let key = XorKey::from_bytes(key);
+//! let reader = BlockFileReader::new(bitcoin::io::from_std(file), Magic::BITCOIN, key);
+//! for record in reader {
+//!     let record = record.expect("valid block file");
+//!     println!("{} at offset {}", record.block.block_hash(), record.offset);
+//! }
+//! # }
+//! # Ok::<_, std::io::Error>(())
+//! ```
+
+use core::convert::Infallible;
+use core::fmt;
+
+use hashes::{sha256d, HashEngine as _};
+use internals::write_err;
+use io::{BufRead, Read, Write};
+
+use crate::block::{BlockHash, BlockHeight, Unchecked};
+use crate::consensus::encode::{self, Decodable, Encodable, ReadExt as _, WriteExt as _};
+use crate::consensus::{compress, DeserializeError, ParseError};
+use crate::p2p::Magic;
+use crate::prelude::Vec;
+use crate::{Block, TxOut};
+
+/// The maximum size of a record, Bitcoin Core's maximum serialized object size.
+pub const MAX_RECORD_SIZE: u32 = 0x0200_0000;
+
+/// The key block and undo files are XOR-ed with, stored in `blocks/xor.dat`.
+///
+/// Byte `i` of a file is XOR-ed with byte `i % 8` of the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct XorKey([u8; 8]);
+
+impl XorKey {
+    /// The key of files which are not obfuscated, as written before Bitcoin Core 28.0.
+    pub const NONE: Self = XorKey([0; 8]);
+
+    /// Constructs a new key from the content of `xor.dat`.
+    pub const fn from_bytes(key: [u8; 8]) -> Self { XorKey(key) }
+
+    /// Returns the key bytes.
+    pub const fn to_bytes(self) -> [u8; 8] { self.0 }
+
+    /// XORs `data`, located at `offset` in a file, with the key.
+    pub fn apply(&self, offset: u64, data: &mut [u8]) {
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte ^= self.0[((offset + i as u64) % 8) as usize];
+        }
+    }
+}
+
+/// A block read from a block file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRecord {
+    /// The offset of the block data in the file, after the magic and size.
+    ///
+    /// This is the position Bitcoin Core stores in its block index.
+    pub offset: u64,
+    /// The block.
+    pub block: Block<Unchecked>,
+}
+
+/// The undo data of a block read from an undo file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoRecord {
+    /// The offset of the undo data in the file, after the magic and size.
+    pub offset: u64,
+    /// The undo data.
+    pub undo: BlockUndo,
+    /// The checksum following the undo data, see [`UndoRecord::verify_checksum`].
+    pub checksum: sha256d::Hash,
+}
+
+impl UndoRecord {
+    /// Checks the checksum against the hash of the parent of the block the undo data is for.
+    ///
+    /// Undo files don't contain the hash of the block, this can be used to match undo data with
+    /// its block.
+    pub fn verify_checksum(&self, prev_blockhash: BlockHash) -> bool {
+        self.undo.checksum(prev_blockhash) == self.checksum
+    }
+}
+
+/// An output spent by a transaction, with the metadata of the coin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpentOutput {
+    /// The spent output.
+    pub txout: TxOut,
+    /// The height of the block which created the output.
+    pub height: BlockHeight,
+    /// Whether the output was created by a coinbase transaction.
+    pub is_coinbase: bool,
+}
+
+impl Encodable for SpentOutput {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let height = u64::from(self.height.to_u32());
+        let mut len = compress::emit_varint(w, height * 2 + u64::from(self.is_coinbase))?;
+        if height > 0 {
+            // Unused transaction version, kept for compatibility.
+            len += compress::emit_varint(w, 0)?;
+        }
+        Ok(len + compress::emit_compressed_txout(w, &self.txout)?)
+    }
+}
+
+impl Decodable for SpentOutput {
+    fn consensus_decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let code = compress::read_varint(r)?;
+        let height = u32::try_from(code >> 1)
+            .map_err(|_| ParseError::ParseFailed("spent output height out of range"))?;
+        if height > 0 {
+            compress::read_varint(r)?;
+        }
+        let txout = compress::read_compressed_txout(r)?;
+        Ok(SpentOutput { txout, height: BlockHeight::from_u32(height), is_coinbase: code & 1 == 1 })
+    }
+}
+
+/// The outputs spent by a block, Bitcoin Core's `CBlockUndo`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockUndo {
+    /// The outputs spent by each transaction of the block but the coinbase, in block order.
+    ///
+    /// The outputs of a transaction are in the order of its inputs.
+    pub spent_outputs: Vec<Vec<SpentOutput>>,
+}
+
+impl BlockUndo {
+    /// Computes the checksum of the undo data of a block whose parent is `prev_blockhash`.
+    pub fn checksum(&self, prev_blockhash: BlockHash) -> sha256d::Hash {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(prev_blockhash.as_byte_array());
+        self.consensus_encode(&mut engine).expect("engines don't error");
+        sha256d::Hash::from_engine(engine)
+    }
+}
+
+impl Encodable for BlockUndo {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = w.emit_compact_size(self.spent_outputs.len())?;
+        for outputs in &self.spent_outputs {
+            len += w.emit_compact_size(outputs.len())?;
+            for output in outputs {
+                len += output.consensus_encode(w)?;
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for BlockUndo {
+    fn consensus_decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        let tx_count = r.read_compact_size()?;
+        let mut spent_outputs = Vec::new();
+        for _ in 0..tx_count {
+            let count = r.read_compact_size()?;
+            let mut outputs = Vec::new();
+            for _ in 0..count {
+                outputs.push(SpentOutput::consensus_decode(r)?);
+            }
+            spent_outputs.push(outputs);
+        }
+        Ok(BlockUndo { spent_outputs })
+    }
+}
+
+/// Reads records framed by magic and size, de-obfuscating them.
+#[derive(Debug)]
+struct RecordReader<R> {
+    inner: R,
+    magic: Magic,
+    key: XorKey,
+    position: u64,
+}
+
+impl<R: Read> RecordReader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.inner.read_exact(buf).map_err(Error::Io)?;
+        self.key.apply(self.position, buf);
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Reads the next record, returning its offset and data.
+    ///
+    /// Returns `None` at the end of the file and at the zero padding Bitcoin Core preallocates
+    /// files with.
+    fn next_record(&mut self) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let mut header = [0; 8];
+        let mut read = 0;
+        while read < header.len() {
+            match self.inner.read(&mut header[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+        // Preallocated space is not obfuscated.
+        if header[..4] == [0; 4] {
+            return Ok(None);
+        }
+        let offset = self.position;
+        self.key.apply(offset, &mut header);
+        self.position += header.len() as u64;
+
+        let magic = Magic::from_bytes([header[0], header[1], header[2], header[3]]);
+        if magic != self.magic {
+            return Err(Error::InvalidMagic { offset, magic });
+        }
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if size > MAX_RECORD_SIZE {
+            return Err(Error::RecordTooLarge { offset, size });
+        }
+
+        let mut data = vec![0; size as usize];
+        self.read_exact(&mut data)?;
+        Ok(Some((self.position - u64::from(size), data)))
+    }
+}
+
+/// Reads blocks from a `blk?????.dat` file.
+///
+/// Yields the blocks in the order they are stored, which is the order Bitcoin Core downloaded
+/// them in, not necessarily the chain order.
+#[derive(Debug)]
+pub struct BlockFileReader<R> {
+    records: RecordReader<R>,
+}
+
+impl<R: Read> BlockFileReader<R> {
+    /// Constructs a new reader of a block file for the network with `magic`.
+    pub fn new(reader: R, magic: Magic, key: XorKey) -> Self {
+        Self::with_position(reader, magic, key, 0)
+    }
+
+    /// Constructs a new reader of a block file which was already read up to `position`.
+    ///
+    /// `position` must be at the start of a record, i.e. before its magic.
+    pub fn with_position(reader: R, magic: Magic, key: XorKey, position: u64) -> Self {
+        BlockFileReader { records: RecordReader { inner: reader, magic, key, position } }
+    }
+
+    /// Returns the position in the file.
+    pub fn position(&self) -> u64 { self.records.position }
+
+    /// Reads the next block, returns `None` at the end of the file.
+    pub fn next_block(&mut self) -> Result<Option<BlockRecord>, Error> {
+        match self.records.next_record()? {
+            Some((offset, data)) => {
+                let block =
+                    encode::deserialize(&data).map_err(|error| Error::Decode { offset, error })?;
+                Ok(Some(BlockRecord { offset, block }))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<R: Read> Iterator for BlockFileReader<R> {
+    type Item = Result<BlockRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> { self.next_block().transpose() }
+}
+
+/// Reads undo data from a `rev?????.dat` file.
+#[derive(Debug)]
+pub struct UndoFileReader<R> {
+    records: RecordReader<R>,
+}
+
+impl<R: Read> UndoFileReader<R> {
+    /// Constructs a new reader of an undo file for the network with `magic`.
+    pub fn new(reader: R, magic: Magic, key: XorKey) -> Self {
+        Self::with_position(reader, magic, key, 0)
+    }
+
+    /// Constructs a new reader of an undo file which was already read up to `position`.
+    ///
+    /// `position` must be at the start of a record, i.e. before its magic.
+    pub fn with_position(reader: R, magic: Magic, key: XorKey, position: u64) -> Self {
+        UndoFileReader { records: RecordReader { inner: reader, magic, key, position } }
+    }
+
+    /// Returns the position in the file.
+    pub fn position(&self) -> u64 { self.records.position }
+
+    /// Reads the next undo data, returns `None` at the end of the file.
+    pub fn next_undo(&mut self) -> Result<Option<UndoRecord>, Error> {
+        match self.records.next_record()? {
+            Some((offset, data)) => {
+                let undo =
+                    encode::deserialize(&data).map_err(|error| Error::Decode { offset, error })?;
+                let mut checksum = [0; 32];
+                self.records.read_exact(&mut checksum)?;
+                let checksum = sha256d::Hash::from_byte_array(checksum);
+                Ok(Some(UndoRecord { offset, undo, checksum }))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<R: Read> Iterator for UndoFileReader<R> {
+    type Item = Result<UndoRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> { self.next_undo().transpose() }
+}
+
+/// Error reading a block or undo file.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// I/O error, including a file truncated in the middle of a record.
+    Io(io::Error),
+    /// A record does not start with the expected network magic.
+    InvalidMagic {
+        /// The offset of the record.
+        offset: u64,
+        /// The magic found.
+        magic: Magic,
+    },
+    /// A record is larger than [`MAX_RECORD_SIZE`].
+    RecordTooLarge {
+        /// The offset of the record.
+        offset: u64,
+        /// The size of the record.
+        size: u32,
+    },
+    /// A block or undo data is invalid.
+    Decode {
+        /// The offset of the record data.
+        offset: u64,
+        /// The decoding error.
+        error: DeserializeError,
+    },
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            Io(ref e) => write_err!(f, "I/O error"; e),
+            InvalidMagic { offset, magic } =>
+                write!(f, "invalid magic {} at offset {}", magic, offset),
+            RecordTooLarge { offset, size } =>
+                write!(f, "record of {} bytes at offset {} is too large", size, offset),
+            Decode { offset, ref error } =>
+                write_err!(f, "invalid record data at offset {}", offset; error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            Io(ref e) => Some(e),
+            Decode { ref error, .. } => Some(error),
+            InvalidMagic { .. } | RecordTooLarge { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_lit::hex;
+
+    use super::*;
+    use crate::block::BlockUncheckedExt as _;
+    use crate::constants::genesis_block;
+    use crate::{Amount, Network, ScriptBuf};
+
+    const KEY: XorKey = XorKey::from_bytes([0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+
+    fn record(magic: Magic, data: &[u8], trailer: &[u8]) -> Vec<u8> {
+        let mut record = magic.to_bytes().to_vec();
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(data);
+        record.extend_from_slice(trailer);
+        record
+    }
+
+    #[test]
+    fn read_block_file() {
+        let genesis = genesis_block(Network::Regtest);
+        let data = encode::serialize(&Block::new_unchecked(
+            *genesis.header(),
+            genesis.transactions().to_vec(),
+        ));
+        let mut file = record(Magic::REGTEST, &data, &[]);
+        file.extend(record(Magic::REGTEST, &data, &[]));
+        KEY.apply(0, &mut file);
+        file.extend_from_slice(&[0; 64]); // Preallocated space.
+
+        let records =
+            BlockFileReader::new(&file[..], Magic::REGTEST, KEY).collect::<Result<Vec<_>, _>>();
+        let records = records.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].offset, 8);
+        assert_eq!(records[1].offset, 16 + data.len() as u64);
+        assert_eq!(records[1].block.clone().validate().unwrap().block_hash(), genesis.block_hash());
+
+        // Start reading at the second record.
+        let position = 8 + data.len();
+        let mut reader =
+            BlockFileReader::with_position(&file[position..], Magic::REGTEST, KEY, position as u64);
+        assert_eq!(reader.next_block().unwrap().unwrap(), records[1]);
+
+        let mut reader = BlockFileReader::new(&file[..], Magic::BITCOIN, KEY);
+        assert!(matches!(reader.next_block(), Err(Error::InvalidMagic { offset: 0, .. })));
+
+        let mut reader = BlockFileReader::new(&file[..20], Magic::REGTEST, KEY);
+        assert!(matches!(reader.next_block(), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn read_undo_file() {
+        let p2pkh = hex!("76a914000102030405060708090a0b0c0d0e0f1011121388ac");
+        let undo = BlockUndo {
+            spent_outputs: vec![
+                vec![SpentOutput {
+                    txout: TxOut {
+                        value: Amount::from_int_btc(50_u16),
+                        script_pubkey: ScriptBuf::from_bytes(p2pkh.to_vec()),
+                    },
+                    height: BlockHeight::from_u32(1),
+                    is_coinbase: true,
+                }],
+                vec![],
+            ],
+        };
+        let data = encode::serialize(&undo);
+        // Two transactions, one spent output: height 1 coinbase, unused version, 50 BTC, P2PKH.
+        assert_eq!(data[..6], hex!("020103003200"));
+
+        let prev = BlockHash::from_byte_array([1; 32]);
+        let checksum = undo.checksum(prev);
+        let mut file = record(Magic::SIGNET, &data, checksum.as_byte_array());
+        KEY.apply(0, &mut file);
+
+        let mut reader = UndoFileReader::new(&file[..], Magic::SIGNET, KEY);
+        let record = reader.next_undo().unwrap().unwrap();
+        assert_eq!(record.offset, 8);
+        assert_eq!(record.undo, undo);
+        assert!(record.verify_checksum(prev));
+        assert!(!record.verify_checksum(BlockHash::from_byte_array([2; 32])));
+        assert!(reader.next_undo().unwrap().is_none());
+        assert_eq!(reader.position(), file.len() as u64);
+    }
+}
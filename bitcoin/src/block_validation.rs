@@ -0,0 +1,477 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Block validation.
+//!
+//! [`BlockUncheckedExt::validate`] only checks that the transactions of a block match its
+//! header. [`BlockValidator`] checks the remaining consensus rules, apart from script evaluation,
+//! in the context of the chain the block extends.
+//!
+//! [`BlockUncheckedExt::validate`]: crate::block::BlockUncheckedExt::validate
+
+use internals::ToU64 as _;
+use units::locktime::absolute::LOCK_TIME_THRESHOLD;
+use units::{BlockHeight, BlockMtp, BlockTime};
+
+use crate::block::{BlockCheckedExt as _, Checked, Header, HeaderExt as _, ValidationError};
+use crate::constants::{COINBASE_MATURITY, MAX_BLOCK_SIGOPS_COST};
+use crate::mining::{MAX_COINBASE_SCRIPT_SIG_SIZE, MIN_COINBASE_SCRIPT_SIG_SIZE};
+use crate::network::Params;
+use crate::pow::CompactTarget;
+use crate::prelude::{BTreeMap, BTreeSet};
+use crate::transaction::{OutPoint, OutPointExt as _, Transaction, TransactionExt as _, TxOut};
+use crate::{script, Amount, Block, Weight};
+
+/// The maximum number of seconds a block timestamp may be ahead of the current time.
+pub const MAX_FUTURE_BLOCK_TIME: u32 = 2 * 60 * 60;
+
+/// An unspent transaction output with the metadata of the coin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coin {
+    /// The outpoint of the output.
+    pub outpoint: OutPoint,
+    /// The unspent output.
+    pub txout: TxOut,
+    /// The height of the block which created the output.
+    pub height: BlockHeight,
+    /// Whether the output was created by a coinbase transaction.
+    pub is_coinbase: bool,
+}
+
+impl Coin {
+    /// Returns `true` if the coin can be spent by a transaction in the block at `height`.
+    ///
+    /// Coinbase outputs can only be spent once [`COINBASE_MATURITY`] blocks were mined on top of
+    /// the block which created them.
+    pub fn is_mature_at(&self, height: BlockHeight) -> bool {
+        !self.is_coinbase
+            || height.to_u32().saturating_sub(self.height.to_u32()) >= COINBASE_MATURITY
+    }
+}
+
+/// Validates blocks against the consensus rules.
+///
+/// Checks, in the order of Bitcoin Core:
+///
+/// * The proof of work and, if set, the [required target](Self::required_bits).
+/// * The timestamp against the median time past and, if set, the [current time](Self::current_time).
+/// * The version against BIP-34, BIP-65 and BIP-66.
+/// * The block weight and the structure of the coinbase transaction, including the BIP-34 height.
+/// * The transactions: non-empty inputs and outputs, duplicate inputs, values and finality.
+/// * The spent outputs and the maturity of spent coinbase outputs, fees, signature operation cost
+///   and the coinbase value against the [subsidy](Params::block_subsidy) and fees.
+///
+/// Scripts and BIP-68 relative lock times, see [`check_sequence_locks`], are not checked.
+///
+/// [`check_sequence_locks`]: crate::timelock::check_sequence_locks
+#[derive(Debug, Clone)]
+pub struct BlockValidator {
+    params: Params,
+    height: BlockHeight,
+    median_time_past: BlockMtp,
+    required_bits: Option<CompactTarget>,
+    current_time: Option<BlockTime>,
+}
+
+impl BlockValidator {
+    /// Constructs a new validator of the block at `height`, whose parent has the median time past
+    /// `median_time_past`.
+    pub fn new(
+        params: impl AsRef<Params>,
+        height: BlockHeight,
+        median_time_past: BlockMtp,
+    ) -> Self {
+        BlockValidator {
+            params: params.as_ref().clone(),
+            height,
+            median_time_past,
+            required_bits: None,
+            current_time: None,
+        }
+    }
+
+    /// Sets the target the block must commit to, see [`HeaderChain::next_work_required`].
+    ///
+    /// [`HeaderChain::next_work_required`]: crate::header_chain::HeaderChain::next_work_required
+    pub fn required_bits(mut self, bits: CompactTarget) -> Self {
+        self.required_bits = Some(bits);
+        self
+    }
+
+    /// Sets the current time, the block timestamp may be at most [`MAX_FUTURE_BLOCK_TIME`] ahead
+    /// of it.
+    pub fn current_time(mut self, time: BlockTime) -> Self {
+        self.current_time = Some(time);
+        self
+    }
+
+    /// Validates `block`, returning the total fees of its transactions.
+    ///
+    /// `spent` is called with the outpoints spent by the block, apart from the outputs created by
+    /// the block itself, and returns the unspent coin.
+    ///
+    /// # Errors
+    ///
+    /// On the first broken rule.
+    pub fn validate<S>(
+        &self,
+        block: &Block<Checked>,
+        mut spent: S,
+    ) -> Result<Amount, ValidationError>
+    where
+        S: FnMut(&OutPoint) -> Option<Coin>,
+    {
+        self.check_header(block.header())?;
+
+        let weight = block.weight();
+        if weight > Weight::MAX_BLOCK {
+            return Err(ValidationError::WeightExceeded(weight));
+        }
+        let transactions = block.transactions();
+        let coinbase = match transactions.first() {
+            Some(coinbase) if coinbase.is_coinbase() => coinbase,
+            _ => return Err(ValidationError::NoCoinbase),
+        };
+        self.check_coinbase(coinbase)?;
+        for (index, tx) in transactions.iter().enumerate() {
+            if index > 0 && tx.is_coinbase() {
+                return Err(ValidationError::UnexpectedCoinbase(index));
+            }
+            self.check_transaction(index, tx, block.header().time)?;
+        }
+
+        // Outputs created by the block, which may be spent by later transactions.
+        let mut created = BTreeMap::new();
+        let mut spent_outpoints = BTreeSet::new();
+        let mut fees = Amount::ZERO;
+        let mut sigop_cost = 0_usize;
+        for (index, tx) in transactions.iter().enumerate() {
+            let mut prevouts = BTreeMap::new();
+            if index > 0 {
+                let mut value_in = Amount::ZERO;
+                for input in &tx.input {
+                    let outpoint = input.previous_output;
+                    let coin = match created.remove(&outpoint) {
+                        Some(coin) => Some(coin),
+                        None if spent_outpoints.contains(&outpoint) => None,
+                        None => spent(&outpoint),
+                    }
+                    .ok_or(ValidationError::MissingInput(outpoint))?;
+                    if !coin.is_mature_at(self.height) {
+                        return Err(ValidationError::PrematureCoinbaseSpend(outpoint));
+                    }
+                    spent_outpoints.insert(outpoint);
+                    value_in = value_in
+                        .checked_add(coin.txout.value)
+                        .ok_or(ValidationError::ValueOutOfRange(index))?;
+                    prevouts.insert(outpoint, coin.txout);
+                }
+                let fee = value_in
+                    .checked_sub(output_value(tx).expect("checked above"))
+                    .ok_or(ValidationError::InputsBelowOutputs(index))?;
+                fees = fees.checked_add(fee).ok_or(ValidationError::ValueOutOfRange(index))?;
+            }
+
+            sigop_cost = sigop_cost
+                .saturating_add(tx.total_sigop_cost(|outpoint| prevouts.get(outpoint).cloned()));
+            if sigop_cost.to_u64() > MAX_BLOCK_SIGOPS_COST as u64 {
+                return Err(ValidationError::SigopCostExceeded(sigop_cost));
+            }
+
+            let txid = tx.compute_txid();
+            for (vout, txout) in tx.output.iter().enumerate() {
+                let outpoint = OutPoint { txid, vout: vout as u32 };
+                let coin = Coin {
+                    outpoint,
+                    txout: txout.clone(),
+                    height: self.height,
+                    is_coinbase: index == 0,
+                };
+                created.insert(outpoint, coin);
+            }
+        }
+
+        let max = self
+            .params
+            .block_subsidy(self.height)
+            .checked_add(fees)
+            .ok_or(ValidationError::ValueOutOfRange(0))?;
+        let value = output_value(coinbase).expect("checked above");
+        if value > max {
+            return Err(ValidationError::CoinbaseValueExceeded { value, max });
+        }
+        Ok(fees)
+    }
+
+    /// Checks the proof of work, timestamp and version of the header.
+    fn check_header(&self, header: &Header) -> Result<(), ValidationError> {
+        let target = header.target();
+        if self.required_bits.map_or(false, |bits| bits != header.bits)
+            || target > self.params.max_attainable_target
+        {
+            return Err(ValidationError::BadTarget);
+        }
+        if !target.is_met_by(header.block_hash()) {
+            return Err(ValidationError::BadProofOfWork);
+        }
+
+        if header.time.to_u32() <= self.median_time_past.to_u32() {
+            return Err(ValidationError::TimeTooOld {
+                time: header.time,
+                median_time_past: self.median_time_past,
+            });
+        }
+        if let Some(current_time) = self.current_time {
+            if u64::from(header.time.to_u32())
+                > u64::from(current_time.to_u32()) + u64::from(MAX_FUTURE_BLOCK_TIME)
+            {
+                return Err(ValidationError::TimeTooNew { time: header.time, current_time });
+            }
+        }
+
+        let version = header.version.to_consensus();
+        if (version < 2 && self.height >= self.params.bip34_height)
+            || (version < 3 && self.height >= self.params.bip66_height)
+            || (version < 4 && self.height >= self.params.bip65_height)
+        {
+            return Err(ValidationError::ObsoleteVersion(version));
+        }
+        Ok(())
+    }
+
+    /// Checks the `scriptSig` of the coinbase transaction.
+    fn check_coinbase(&self, coinbase: &Transaction) -> Result<(), ValidationError> {
+        let script_sig = &coinbase.input[0].script_sig;
+        if !(MIN_COINBASE_SCRIPT_SIG_SIZE..=MAX_COINBASE_SCRIPT_SIG_SIZE)
+            .contains(&script_sig.len())
+        {
+            return Err(ValidationError::CoinbaseScriptSigSize(script_sig.len()));
+        }
+        if self.height >= self.params.bip34_height {
+            let height = script::Builder::new().push_int_unchecked(self.height.to_u32().into());
+            if !script_sig.as_bytes().starts_with(height.as_bytes()) {
+                return Err(ValidationError::InvalidCoinbaseHeight);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the transaction at `index` of the block with timestamp `time`, without the outputs
+    /// it spends.
+    fn check_transaction(
+        &self,
+        index: usize,
+        tx: &Transaction,
+        time: BlockTime,
+    ) -> Result<(), ValidationError> {
+        if tx.input.is_empty() {
+            return Err(ValidationError::EmptyInputs(index));
+        }
+        if tx.output.is_empty() {
+            return Err(ValidationError::EmptyOutputs(index));
+        }
+        if output_value(tx).is_none() {
+            return Err(ValidationError::ValueOutOfRange(index));
+        }
+
+        let mut outpoints = BTreeSet::new();
+        for input in &tx.input {
+            if !outpoints.insert(input.previous_output) {
+                return Err(ValidationError::DuplicateInput {
+                    index,
+                    outpoint: input.previous_output,
+                });
+            }
+            if index > 0 && input.previous_output.is_null() {
+                return Err(ValidationError::NullPrevout(index));
+            }
+        }
+
+        // Lock times are compared to the median time past once BIP-113 is active.
+        let lock_time = tx.lock_time.to_consensus_u32();
+        let cutoff = if lock_time < LOCK_TIME_THRESHOLD {
+            self.height.to_u32()
+        } else if self.height >= self.params.csv_height {
+            self.median_time_past.to_u32()
+        } else {
+            time.to_u32()
+        };
+        if lock_time != 0 && lock_time >= cutoff && tx.is_lock_time_enabled() {
+            return Err(ValidationError::NonFinalTransaction(index));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the sum of the output values of `tx`, `None` if it exceeds [`Amount::MAX`].
+fn output_value(tx: &Transaction) -> Option<Amount> {
+    tx.output.iter().try_fold(Amount::ZERO, |acc, txout| acc.checked_add(txout.value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{self, BlockUncheckedExt as _, Version};
+    use crate::constants::genesis_block;
+    use crate::locktime::absolute;
+    use crate::{transaction, ScriptBuf, Sequence, TxIn, TxOut, Txid};
+
+    const SPENT: OutPoint = OutPoint { txid: Txid::from_byte_array([1; 32]), vout: 0 };
+
+    fn params() -> Params { Params { bip34_height: BlockHeight::from_u32(1), ..Params::REGTEST } }
+
+    fn tx(inputs: &[OutPoint], value: Amount) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: inputs
+                .iter()
+                .map(|&previous_output| TxIn { previous_output, ..TxIn::EMPTY_COINBASE })
+                .collect(),
+            output: vec![TxOut { value, script_pubkey: ScriptBuf::from_bytes(vec![0x51]) }],
+        }
+    }
+
+    fn coinbase(height: u32, value: Amount) -> Transaction {
+        let mut coinbase = tx(&[OutPoint::COINBASE_PREVOUT], value);
+        coinbase.input[0].script_sig = script::Builder::new()
+            .push_int_unchecked(height.into())
+            .push_int_unchecked(0)
+            .into_script();
+        coinbase
+    }
+
+    /// Mines a block on top of the regtest genesis block.
+    fn mine(time_offset: u32, transactions: Vec<Transaction>) -> Block<Checked> {
+        let genesis = genesis_block(params());
+        let mut header = Header {
+            version: Version::NO_SOFT_FORK_SIGNALLING,
+            prev_blockhash: genesis.block_hash(),
+            merkle_root: block::compute_merkle_root(&transactions).unwrap(),
+            time: BlockTime::from_u32(genesis.header().time.to_u32() + time_offset),
+            bits: genesis.header().bits,
+            nonce: 0,
+        };
+        while !header.target().is_met_by(header.block_hash()) {
+            header.nonce += 1;
+        }
+        Block::new_unchecked(header, transactions).validate().unwrap()
+    }
+
+    fn validator() -> BlockValidator {
+        let genesis = genesis_block(params());
+        let median_time_past = BlockMtp::from_u32(genesis.header().time.to_u32());
+        BlockValidator::new(params(), BlockHeight::from_u32(1), median_time_past)
+            .required_bits(genesis.header().bits)
+            .current_time(BlockTime::from_u32(genesis.header().time.to_u32() + 600))
+    }
+
+    /// Returns a coin for [`SPENT`] and a coinbase coin of the genesis block for its `vout` 2.
+    fn spent(outpoint: &OutPoint) -> Option<Coin> {
+        let coin = Coin {
+            outpoint: *outpoint,
+            txout: TxOut { value: Amount::ONE_BTC, script_pubkey: ScriptBuf::new() },
+            height: BlockHeight::ZERO,
+            is_coinbase: outpoint.vout == 2,
+        };
+        Some(coin).filter(|_| outpoint.txid == SPENT.txid && outpoint.vout != 1)
+    }
+
+    #[test]
+    fn valid_block() {
+        let first = tx(&[SPENT], Amount::from_sat_u32(99_990_000));
+        // Spends an output of the previous transaction in the block.
+        let second = tx(&[OutPoint { txid: first.compute_txid(), vout: 0 }], Amount::ONE_SAT);
+        let fees = Amount::from_sat_u32(99_999_999);
+        let reward = Amount::from_sat(5_099_999_999).unwrap();
+
+        let block = mine(600, vec![coinbase(1, reward), first.clone(), second]);
+        assert_eq!(validator().validate(&block, spent), Ok(fees));
+
+        let block = mine(600, vec![coinbase(1, reward), first]);
+        assert_eq!(
+            validator().validate(&block, spent),
+            Err(ValidationError::CoinbaseValueExceeded {
+                value: reward,
+                max: Amount::from_sat(5_000_010_000).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_block() {
+        let cb = coinbase(1, Amount::ZERO);
+        let validate = |time_offset, transactions| {
+            validator().validate(&mine(time_offset, transactions), spent).unwrap_err()
+        };
+
+        let err = validate(0, vec![cb.clone()]);
+        assert!(matches!(err, ValidationError::TimeTooOld { .. }));
+        let err = validate(600 + MAX_FUTURE_BLOCK_TIME + 1, vec![cb.clone()]);
+        assert!(matches!(err, ValidationError::TimeTooNew { .. }));
+        let err = validator()
+            .required_bits(CompactTarget::from_consensus(0x1d00ffff))
+            .validate(&mine(600, vec![cb.clone()]), spent);
+        assert_eq!(err, Err(ValidationError::BadTarget));
+
+        assert_eq!(
+            validate(600, vec![coinbase(2, Amount::ZERO)]),
+            ValidationError::InvalidCoinbaseHeight
+        );
+        assert_eq!(validate(600, vec![tx(&[SPENT], Amount::ZERO)]), ValidationError::NoCoinbase);
+        assert_eq!(
+            validate(600, vec![cb.clone(), coinbase(1, Amount::ZERO)]),
+            ValidationError::UnexpectedCoinbase(1)
+        );
+        assert_eq!(
+            validate(600, vec![cb.clone(), tx(&[SPENT, SPENT], Amount::ZERO)]),
+            ValidationError::DuplicateInput { index: 1, outpoint: SPENT }
+        );
+        assert_eq!(
+            validate(600, vec![cb.clone(), tx(&[SPENT], Amount::ZERO), tx(&[SPENT], Amount::ZERO)]),
+            ValidationError::MissingInput(SPENT)
+        );
+        let unknown = OutPoint { vout: 1, ..SPENT };
+        assert_eq!(
+            validate(600, vec![cb.clone(), tx(&[unknown], Amount::ZERO)]),
+            ValidationError::MissingInput(unknown)
+        );
+        let coinbase_output = OutPoint { vout: 2, ..SPENT };
+        assert_eq!(
+            validate(600, vec![cb.clone(), tx(&[coinbase_output], Amount::ZERO)]),
+            ValidationError::PrematureCoinbaseSpend(coinbase_output)
+        );
+        let coinbase_output = OutPoint { txid: cb.compute_txid(), vout: 0 };
+        assert_eq!(
+            validate(600, vec![cb.clone(), tx(&[coinbase_output], Amount::ZERO)]),
+            ValidationError::PrematureCoinbaseSpend(coinbase_output)
+        );
+        assert_eq!(
+            validate(600, vec![cb.clone(), tx(&[SPENT], Amount::from_int_btc(2_u16))]),
+            ValidationError::InputsBelowOutputs(1)
+        );
+
+        let mut locked = tx(&[SPENT], Amount::ZERO);
+        locked.lock_time = absolute::LockTime::from_consensus(1);
+        locked.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        assert_eq!(validate(600, vec![cb, locked]), ValidationError::NonFinalTransaction(1));
+    }
+
+    #[test]
+    fn bip113_lock_time() {
+        let genesis_time = genesis_block(params()).header().time.to_u32();
+        let mut locked = tx(&[SPENT], Amount::from_sat_u32(99_990_000));
+        locked.lock_time = absolute::LockTime::from_consensus(genesis_time + 300);
+        locked.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        let reward = Amount::from_sat(5_000_010_000).unwrap();
+        let block = mine(600, vec![coinbase(1, reward), locked]);
+
+        // Before CSV activation the lock time is compared to the block timestamp.
+        assert!(params().csv_height > BlockHeight::from_u32(1));
+        assert_eq!(validator().validate(&block, spent), Ok(Amount::from_sat_u32(10_000)));
+
+        let params = Params { csv_height: BlockHeight::from_u32(1), ..params() };
+        let validator =
+            BlockValidator::new(params, BlockHeight::from_u32(1), BlockMtp::from_u32(genesis_time));
+        assert_eq!(validator.validate(&block, spent), Err(ValidationError::NonFinalTransaction(1)));
+    }
+}
@@ -13,7 +13,7 @@
 use hashes::{sha256d, HashEngine};
 use internals::{compact_size, ToU64};
 use io::{BufRead, Write};
-use units::BlockTime;
+use units::{BlockMtp, BlockTime};
 
 use super::Weight;
 use crate::consensus::encode::WriteExt as _;
@@ -24,7 +24,8 @@
 use crate::pow::{Target, Work};
 use crate::prelude::Vec;
 use crate::script::{self, ScriptExt as _};
-use crate::transaction::{Transaction, TransactionExt as _, Wtxid};
+use crate::transaction::{OutPoint, Transaction, TransactionExt as _, Wtxid};
+use crate::Amount;
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
@@ -206,25 +207,27 @@ fn check_witness_commitment(transactions: &[Transaction]) -> (bool, Option<Witne
 }
 
 fn witness_commitment_from_coinbase(coinbase: &Transaction) -> Option<WitnessCommitment> {
-    // Consists of OP_RETURN, OP_PUSHBYTES_36, and four "witness header" bytes.
-    const MAGIC: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
-
     if !coinbase.is_coinbase() {
         return None;
     }
 
+    witness_commitment_index(coinbase).map(|pos| {
+        let bytes =
+            <[u8; 32]>::try_from(&coinbase.output[pos].script_pubkey.as_bytes()[6..38]).unwrap();
+        WitnessCommitment::from_byte_array(bytes)
+    })
+}
+
+/// Returns the index of the coinbase output holding the witness commitment.
+pub(crate) fn witness_commitment_index(coinbase: &Transaction) -> Option<usize> {
+    // Consists of OP_RETURN, OP_PUSHBYTES_36, and four "witness header" bytes.
+    const MAGIC: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
     // Commitment is in the last output that starts with magic bytes.
-    if let Some(pos) = coinbase
+    coinbase
         .output
         .iter()
         .rposition(|o| o.script_pubkey.len() >= 38 && o.script_pubkey.as_bytes()[0..6] == MAGIC)
-    {
-        let bytes =
-            <[u8; 32]>::try_from(&coinbase.output[pos].script_pubkey.as_bytes()[6..38]).unwrap();
-        Some(WitnessCommitment::from_byte_array(bytes))
-    } else {
-        None
-    }
 }
 
 /// Extension functionality for the [`Block<Checked>`] type.
@@ -470,6 +473,9 @@ fn to_bip34_error(err: script::Error) -> Bip34Error {
 }
 
 /// A block validation error.
+///
+/// Apart from the proof of work checks of [`HeaderExt::validate_pow`], returned by
+/// [`BlockValidator::validate`](crate::block_validation::BlockValidator::validate).
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ValidationError {
@@ -477,6 +483,70 @@ pub enum ValidationError {
     BadProofOfWork,
     /// The `target` field of a block header did not match the expected difficulty.
     BadTarget,
+    /// The header timestamp is not after the median time past of the previous blocks.
+    TimeTooOld {
+        /// The header timestamp.
+        time: BlockTime,
+        /// The median time past of the previous blocks.
+        median_time_past: BlockMtp,
+    },
+    /// The header timestamp is too far in the future.
+    TimeTooNew {
+        /// The header timestamp.
+        time: BlockTime,
+        /// The current time.
+        current_time: BlockTime,
+    },
+    /// The header version is lower than required by BIP-34, BIP-65 or BIP-66.
+    ObsoleteVersion(i32),
+    /// The block weight exceeds [`Weight::MAX_BLOCK`].
+    WeightExceeded(Weight),
+    /// The first transaction is missing or is not a coinbase transaction.
+    NoCoinbase,
+    /// The transaction at the index is a coinbase transaction but is not the first one.
+    UnexpectedCoinbase(usize),
+    /// The coinbase `scriptSig` is shorter than 2 or longer than 100 bytes.
+    CoinbaseScriptSigSize(usize),
+    /// The coinbase `scriptSig` does not start with the block height (BIP-34).
+    InvalidCoinbaseHeight,
+    /// The transaction at the index has no inputs.
+    EmptyInputs(usize),
+    /// The transaction at the index has no outputs.
+    EmptyOutputs(usize),
+    /// A transaction spends the same output twice.
+    DuplicateInput {
+        /// The index of the transaction.
+        index: usize,
+        /// The outpoint spent twice.
+        outpoint: OutPoint,
+    },
+    /// The transaction at the index, not a coinbase transaction, has an input with a null
+    /// previous output.
+    NullPrevout(usize),
+    /// The sum of the input or output values of the transaction at the index exceeds
+    /// [`Amount::MAX`].
+    ValueOutOfRange(usize),
+    /// The transaction at the index is not final at the block height and median time past.
+    NonFinalTransaction(usize),
+    /// An output spent by a transaction is unknown or already spent.
+    MissingInput(OutPoint),
+    /// A coinbase output is spent before [`COINBASE_MATURITY`] blocks were mined on top of it.
+    ///
+    /// [`COINBASE_MATURITY`]: crate::constants::COINBASE_MATURITY
+    PrematureCoinbaseSpend(OutPoint),
+    /// The outputs of the transaction at the index are worth more than its inputs.
+    InputsBelowOutputs(usize),
+    /// The total signature operation cost exceeds [`MAX_BLOCK_SIGOPS_COST`].
+    ///
+    /// [`MAX_BLOCK_SIGOPS_COST`]: crate::constants::MAX_BLOCK_SIGOPS_COST
+    SigopCostExceeded(usize),
+    /// The coinbase outputs are worth more than the block subsidy and fees.
+    CoinbaseValueExceeded {
+        /// The value of the coinbase outputs.
+        value: Amount,
+        /// The block subsidy and fees.
+        max: Amount,
+    },
 }
 
 impl From<Infallible> for ValidationError {
@@ -490,6 +560,44 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             BadProofOfWork => f.write_str("block target correct but not attained"),
             BadTarget => f.write_str("block target incorrect"),
+            TimeTooOld { time, median_time_past } => write!(
+                f,
+                "block time {} is not after the median time past {}",
+                time.to_u32(),
+                median_time_past.to_u32()
+            ),
+            TimeTooNew { time, current_time } => write!(
+                f,
+                "block time {} is too far after the current time {}",
+                time.to_u32(),
+                current_time.to_u32()
+            ),
+            ObsoleteVersion(version) => write!(f, "obsolete block version {}", version),
+            WeightExceeded(weight) => write!(f, "block weight {} exceeds the maximum", weight),
+            NoCoinbase => f.write_str("the first transaction is not a coinbase transaction"),
+            UnexpectedCoinbase(index) =>
+                write!(f, "transaction {} is an unexpected coinbase transaction", index),
+            CoinbaseScriptSigSize(size) =>
+                write!(f, "coinbase scriptSig of {} bytes is too short or too long", size),
+            InvalidCoinbaseHeight =>
+                f.write_str("coinbase scriptSig does not start with the block height"),
+            EmptyInputs(index) => write!(f, "transaction {} has no inputs", index),
+            EmptyOutputs(index) => write!(f, "transaction {} has no outputs", index),
+            DuplicateInput { index, ref outpoint } =>
+                write!(f, "transaction {} spends {} twice", index, outpoint),
+            NullPrevout(index) => write!(f, "transaction {} spends a null outpoint", index),
+            ValueOutOfRange(index) => write!(f, "values of transaction {} are out of range", index),
+            NonFinalTransaction(index) => write!(f, "transaction {} is not final", index),
+            MissingInput(ref outpoint) =>
+                write!(f, "spent output {} is missing or spent", outpoint),
+            PrematureCoinbaseSpend(ref outpoint) =>
+                write!(f, "coinbase output {} is spent before maturity", outpoint),
+            InputsBelowOutputs(index) =>
+                write!(f, "outputs of transaction {} are worth more than its inputs", index),
+            SigopCostExceeded(cost) =>
+                write!(f, "signature operation cost {} exceeds the maximum", cost),
+            CoinbaseValueExceeded { value, max } =>
+                write!(f, "coinbase value {} exceeds the subsidy and fees {}", value, max),
         }
     }
 }
@@ -500,7 +608,27 @@ fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         use self::ValidationError::*;
 
         match *self {
-            BadProofOfWork | BadTarget => None,
+            BadProofOfWork
+            | BadTarget
+            | TimeTooOld { .. }
+            | TimeTooNew { .. }
+            | ObsoleteVersion(_)
+            | WeightExceeded(_)
+            | NoCoinbase
+            | UnexpectedCoinbase(_)
+            | CoinbaseScriptSigSize(_)
+            | InvalidCoinbaseHeight
+            | EmptyInputs(_)
+            | EmptyOutputs(_)
+            | DuplicateInput { .. }
+            | NullPrevout(_)
+            | ValueOutOfRange(_)
+            | NonFinalTransaction(_)
+            | MissingInput(_)
+            | PrematureCoinbaseSpend(_)
+            | InputsBelowOutputs(_)
+            | SigopCostExceeded(_)
+            | CoinbaseValueExceeded { .. } => None,
         }
     }
 }
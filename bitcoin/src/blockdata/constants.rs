@@ -42,6 +42,7 @@
 /// The maximum allowed size of any single witness stack element.
 pub const MAX_STACK_ELEMENT_SIZE: usize = 520;
 /// How many blocks between halvings.
+#[deprecated(since = "TBD", note = "use `Params::subsidy_halving_interval` instead")]
 pub const SUBSIDY_HALVING_INTERVAL: u32 = 210_000;
 /// Maximum allowed value for an integer in Script.
 /// This constant has ambiguous semantics. Please carefully check your intended use-case and define
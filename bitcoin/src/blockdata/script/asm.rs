@@ -0,0 +1,310 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Parsing of script assembly (ASM) back into scripts.
+//!
+//! Two dialects are understood and may be mixed freely within a single string:
+//!
+//! * The output of [`Script`]'s `Display` implementation (and therefore of `fmt_asm`). Opcodes
+//!   are named as in [`opcodes::all`], a data push is written as the push opcode followed by its
+//!   payload in hex (`OP_PUSHBYTES_3 010203`, `OP_PUSHDATA1 ...`) and `OP_0` is accepted for
+//!   `OP_PUSHBYTES_0`.
+//! * The dialect used by Bitcoin Core's `script_tests.json`. Opcode names may omit the `OP_`
+//!   prefix, decimal numbers are pushed as script numbers, `0x`-prefixed hex is inserted into the
+//!   script verbatim and `'quoted'` strings are pushed as data.
+//!
+//! Tokens are separated by ASCII whitespace. Hex data must either follow a push opcode, optionally
+//! with a `0x` prefix, or be written with a `0x` prefix. A bare token made up only of decimal
+//! digits (optionally preceded by `-`) is always a number, e.g. `05` is `OP_5`, and any other bare
+//! token that is not an opcode is rejected.
+//!
+//! [`Script`]: super::Script
+
+use core::fmt;
+use core::iter::Peekable;
+
+use hex::FromHex as _;
+use internals::ToU64 as _;
+
+use super::{write_scriptint, PushBytes, ScriptBuf, ScriptBufExt as _, ScriptBufExtPriv as _};
+use crate::opcodes::all::*;
+use crate::opcodes::{self, Opcode};
+use crate::prelude::Vec;
+
+/// Largest absolute value of a decimal number accepted by Bitcoin Core's script parser.
+const MAX_DECIMAL: i64 = 0xffff_ffff;
+
+/// Parses `s` as script assembly, see the module level docs for the accepted syntax.
+pub(super) fn parse(s: &str) -> Result<ScriptBuf, ParseAsmError> {
+    let mut script = ScriptBuf::new();
+    let mut tokens = Tokens { s, pos: 0 }.peekable();
+
+    while let Some((pos, token)) = tokens.next() {
+        let err = |kind| ParseAsmError { position: pos, kind };
+
+        if is_decimal(token) {
+            let n = token
+                .parse::<i64>()
+                .ok()
+                .filter(|n| (-MAX_DECIMAL..=MAX_DECIMAL).contains(n))
+                .ok_or(err(ParseAsmErrorKind::NumberOutOfRange))?;
+            push_int(&mut script, n);
+        } else if let Some(hex) = token.strip_prefix("0x") {
+            let bytes = Vec::from_hex(hex)
+                .ok()
+                .filter(|bytes| !bytes.is_empty())
+                .ok_or(err(ParseAsmErrorKind::UnknownToken))?;
+            script.as_byte_vec().extend_from_slice(&bytes);
+        } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+            push_data(&mut script, &token.as_bytes()[1..token.len() - 1], pos)?;
+        } else if let Some(opcode) = opcode_from_name(token) {
+            script.push_opcode(opcode);
+            push_payload(&mut script, opcode, &mut tokens, pos + token.len())?;
+        } else {
+            return Err(err(ParseAsmErrorKind::UnknownToken));
+        }
+    }
+    Ok(script)
+}
+
+/// Writes the payload of the push `opcode` (which has already been written), if any.
+///
+/// `end` is the position just after the opcode token, used to report missing data at the end of
+/// the input.
+fn push_payload(
+    script: &mut ScriptBuf,
+    opcode: Opcode,
+    tokens: &mut Peekable<Tokens<'_>>,
+    end: usize,
+) -> Result<(), ParseAsmError> {
+    let len_len = match opcode {
+        OP_PUSHDATA1 => 1,
+        OP_PUSHDATA2 => 2,
+        OP_PUSHDATA4 => 4,
+        _ => match opcode.classify(opcodes::ClassifyContext::Legacy) {
+            opcodes::Class::PushBytes(n) if n > 0 =>
+                return push_fixed_payload(script, opcode, n as usize, tokens, end),
+            _ => return Ok(()),
+        },
+    };
+
+    // An `OP_PUSHDATA` with an empty payload is rendered without a data token.
+    let (pos, data) = match tokens.peek().and_then(|&(pos, token)| Some((pos, decode_hex(token)?)))
+    {
+        Some(data) => {
+            tokens.next();
+            data
+        }
+        None => (end, Vec::new()),
+    };
+    let len = data.len().to_u64().to_le_bytes();
+    if len[len_len..].iter().any(|&b| b != 0) {
+        let kind = ParseAsmErrorKind::PushTooLong { opcode, len: data.len() };
+        return Err(ParseAsmError { position: pos, kind });
+    }
+    let mut script = script.as_byte_vec();
+    script.extend_from_slice(&len[..len_len]);
+    script.extend_from_slice(&data);
+    Ok(())
+}
+
+/// Writes the payload of `OP_PUSHBYTES_n`, which must be exactly `n` bytes long.
+fn push_fixed_payload(
+    script: &mut ScriptBuf,
+    opcode: Opcode,
+    expected: usize,
+    tokens: &mut Peekable<Tokens<'_>>,
+    end: usize,
+) -> Result<(), ParseAsmError> {
+    let (pos, data) = match tokens.peek() {
+        Some(&(pos, token)) => match decode_hex(token) {
+            Some(data) => (pos, data),
+            None =>
+                return Err(ParseAsmError {
+                    position: pos,
+                    kind: ParseAsmErrorKind::MissingPushData(opcode),
+                }),
+        },
+        None =>
+            return Err(ParseAsmError {
+                position: end,
+                kind: ParseAsmErrorKind::MissingPushData(opcode),
+            }),
+    };
+    if data.len() != expected {
+        let kind = ParseAsmErrorKind::PushLengthMismatch { opcode, expected, actual: data.len() };
+        return Err(ParseAsmError { position: pos, kind });
+    }
+    tokens.next();
+    script.as_byte_vec().extend_from_slice(&data);
+    Ok(())
+}
+
+/// Pushes `n` the same way as [`Builder::push_int_unchecked`](super::Builder::push_int_unchecked).
+fn push_int(script: &mut ScriptBuf, n: i64) {
+    match n {
+        -1 => script.push_opcode(OP_PUSHNUM_NEG1),
+        0 => script.push_opcode(OP_PUSHBYTES_0),
+        1..=16 => script.push_opcode(Opcode::from(n as u8 + (OP_PUSHNUM_1.to_u8() - 1))),
+        _ => {
+            let mut buf = [0u8; 8];
+            let len = write_scriptint(&mut buf, n);
+            script.push_slice_non_minimal(&<&PushBytes>::from(&buf)[..len]);
+        }
+    }
+}
+
+/// Pushes `data` using the shortest push opcode for its length.
+fn push_data(script: &mut ScriptBuf, data: &[u8], pos: usize) -> Result<(), ParseAsmError> {
+    let data = <&PushBytes>::try_from(data).map_err(|_| ParseAsmError {
+        position: pos,
+        kind: ParseAsmErrorKind::PushTooLong { opcode: OP_PUSHDATA4, len: data.len() },
+    })?;
+    script.push_slice_non_minimal(data);
+    Ok(())
+}
+
+/// Returns true if `token` is a decimal number in Bitcoin Core's syntax.
+fn is_decimal(token: &str) -> bool {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Decodes a non-empty hex push payload, with or without a `0x` prefix.
+fn decode_hex(token: &str) -> Option<Vec<u8>> {
+    let hex = token.strip_prefix("0x").unwrap_or(token);
+    Vec::from_hex(hex).ok().filter(|bytes| !bytes.is_empty())
+}
+
+/// Looks up an opcode by any of the names it is known by, with or without the `OP_` prefix.
+fn opcode_from_name(name: &str) -> Option<Opcode> {
+    let name = name.strip_prefix("OP_").unwrap_or(name);
+    let alias = match name {
+        "0" | "FALSE" => Some(OP_PUSHBYTES_0),
+        "TRUE" => Some(OP_PUSHNUM_1),
+        "1NEGATE" => Some(OP_PUSHNUM_NEG1),
+        "NOP2" | "CHECKLOCKTIMEVERIFY" => Some(OP_CLTV),
+        "NOP3" | "CHECKSEQUENCEVERIFY" => Some(OP_CSV),
+        _ => None,
+    };
+    if alias.is_some() {
+        return alias;
+    }
+    if !name.starts_with('0') && is_decimal(name) {
+        return match name.parse::<u8>() {
+            Ok(n @ 1..=16) => Some(Opcode::from(n + (OP_PUSHNUM_1.to_u8() - 1))),
+            _ => None,
+        };
+    }
+    (0..=u8::MAX).map(Opcode::from).find(|opcode| {
+        use fmt::Write as _;
+
+        let mut cmp = NameCmp(name);
+        write!(cmp, "{}", opcode).is_ok() && cmp.0.is_empty()
+    })
+}
+
+/// Compares the `Display` output of an opcode to a name without the `OP_` prefix.
+struct NameCmp<'a>(&'a str);
+
+impl fmt::Write for NameCmp<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let s = s.strip_prefix("OP_").unwrap_or(s);
+        self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+        Ok(())
+    }
+}
+
+/// Iterator over whitespace separated tokens and their byte positions.
+struct Tokens<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.s[self.pos..];
+        let start = self.pos + (rest.len() - rest.trim_start_matches(is_separator).len());
+        let len = self.s[start..].find(is_separator).unwrap_or(self.s.len() - start);
+        self.pos = start + len;
+        if len == 0 {
+            None
+        } else {
+            Some((start, &self.s[start..start + len]))
+        }
+    }
+}
+
+fn is_separator(c: char) -> bool { c.is_ascii_whitespace() }
+
+/// Error returned when parsing script assembly fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAsmError {
+    position: usize,
+    kind: ParseAsmErrorKind,
+}
+
+impl ParseAsmError {
+    /// Returns the byte offset into the input at which the offending token starts.
+    ///
+    /// If data is missing at the end of the input this is the length of the input.
+    pub fn position(&self) -> usize { self.position }
+
+    /// Returns what went wrong.
+    pub fn kind(&self) -> &ParseAsmErrorKind { &self.kind }
+}
+
+impl fmt::Display for ParseAsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid script assembly at byte {}: {}", self.position, self.kind)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseAsmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+/// The reason parsing script assembly failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseAsmErrorKind {
+    /// The token is not an opcode, number, `0x` hex or quoted string.
+    UnknownToken,
+    /// Decimal number outside of the range `-0xffffffff..=0xffffffff` accepted by Bitcoin Core.
+    NumberOutOfRange,
+    /// A push opcode is not followed by its hex encoded payload.
+    MissingPushData(Opcode),
+    /// The payload following `OP_PUSHBYTES_n` is not `n` bytes long.
+    PushLengthMismatch {
+        /// The push opcode.
+        opcode: Opcode,
+        /// The number of bytes the opcode pushes.
+        expected: usize,
+        /// The number of bytes given.
+        actual: usize,
+    },
+    /// The payload is too long to be pushed by the opcode.
+    PushTooLong {
+        /// The push opcode.
+        opcode: Opcode,
+        /// The number of bytes given.
+        len: usize,
+    },
+}
+
+impl fmt::Display for ParseAsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseAsmErrorKind::*;
+
+        match *self {
+            UnknownToken => f.write_str("unknown token"),
+            NumberOutOfRange => f.write_str("number out of range"),
+            MissingPushData(opcode) => write!(f, "{} is not followed by hex data", opcode),
+            PushLengthMismatch { opcode, expected, actual } =>
+                write!(f, "{} expects {} bytes of data but {} were given", opcode, expected, actual),
+            PushTooLong { opcode, len } => write!(f, "{} can not push {} bytes", opcode, len),
+        }
+    }
+}
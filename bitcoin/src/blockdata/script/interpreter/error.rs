@@ -0,0 +1,320 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Script execution errors.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use hex::DisplayHex as _;
+use internals::write_err;
+
+use crate::opcodes::Opcode;
+use crate::prelude::Vec;
+use crate::OutPoint;
+
+/// Error returned when script execution fails.
+///
+/// Besides the reason for the failure this carries, where available, the opcode that was being
+/// executed, its byte position within the script and a snapshot of the main stack at the time of
+/// the failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    opcode: Option<Opcode>,
+    position: Option<usize>,
+    stack: Vec<Vec<u8>>,
+}
+
+impl Error {
+    /// Constructs a new error that is not attributable to a single opcode.
+    pub(super) fn new(kind: ErrorKind) -> Self {
+        Error { kind, opcode: None, position: None, stack: Vec::new() }
+    }
+
+    /// Constructs a new error raised while executing `opcode` at byte `position`.
+    pub(super) fn at_opcode(
+        kind: ErrorKind,
+        opcode: Opcode,
+        position: usize,
+        stack: &[Vec<u8>],
+    ) -> Self {
+        Error { kind, opcode: Some(opcode), position: Some(position), stack: stack.to_vec() }
+    }
+
+    /// Returns the reason the script failed.
+    pub fn kind(&self) -> ErrorKind { self.kind }
+
+    /// Returns the opcode that was being executed when the script failed, if any.
+    pub fn opcode(&self) -> Option<Opcode> { self.opcode }
+
+    /// Returns the byte position of the failing opcode within the executed script, if any.
+    pub fn position(&self) -> Option<usize> { self.position }
+
+    /// Returns the main stack (bottom first) at the time the script failed.
+    ///
+    /// This is empty for failures that are not attributable to a single opcode.
+    pub fn stack(&self) -> &[Vec<u8>] { &self.stack }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self { Error::new(kind) }
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.opcode, self.position) {
+            (Some(op), Some(pos)) => {
+                write!(f, "script failed at {} (position {}): {}, stack: [", op, pos, self.kind)?;
+                for (i, item) in self.stack.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{:x}", item.as_hex())?;
+                }
+                f.write_str("]")
+            }
+            _ => write!(f, "script failed: {}", self.kind),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+/// The reason a script failed, mirroring Bitcoin Core's `ScriptError_t`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Script evaluated without error but finished with a false/empty top stack element.
+    EvalFalse,
+    /// `OP_RETURN` was executed.
+    OpReturn,
+    /// Script is larger than the maximum allowed size.
+    ScriptSize,
+    /// Push or stack element is larger than the maximum allowed size.
+    PushSize,
+    /// Script executes more than the maximum allowed number of non-push opcodes.
+    OpCount,
+    /// The combined size of stack and altstack exceeds the maximum.
+    StackSize,
+    /// Signature count for `OP_CHECKMULTISIG` is negative or larger than the key count.
+    SigCount,
+    /// Public key count for `OP_CHECKMULTISIG` is negative or larger than the maximum.
+    PubkeyCount,
+    /// `OP_VERIFY` failed.
+    Verify,
+    /// `OP_EQUALVERIFY` failed.
+    EqualVerify,
+    /// `OP_CHECKMULTISIGVERIFY` failed.
+    CheckMultisigVerify,
+    /// `OP_CHECKSIGVERIFY` failed.
+    CheckSigVerify,
+    /// `OP_NUMEQUALVERIFY` failed.
+    NumEqualVerify,
+    /// Script contains an invalid opcode or a truncated push.
+    BadOpcode,
+    /// Script contains a disabled opcode.
+    DisabledOpcode,
+    /// Operation requires more items than there are on the stack.
+    InvalidStackOperation,
+    /// `OP_FROMALTSTACK` executed with an empty altstack.
+    InvalidAltstackOperation,
+    /// `OP_ELSE`/`OP_ENDIF` without matching `OP_IF`, or an `OP_IF` left open.
+    UnbalancedConditional,
+    /// `OP_CHECKLOCKTIMEVERIFY` or `OP_CHECKSEQUENCEVERIFY` with a negative argument.
+    NegativeLockTime,
+    /// Lock time required by `OP_CHECKLOCKTIMEVERIFY`/`OP_CHECKSEQUENCEVERIFY` is not satisfied.
+    UnsatisfiedLockTime,
+    /// Signature has an undefined sighash type.
+    SigHashType,
+    /// Signature is not strictly DER encoded.
+    SigDer,
+    /// Data push or number is not minimally encoded.
+    MinimalData,
+    /// Signature script contains non-push operations.
+    SigPushOnly,
+    /// Signature has a high S value.
+    SigHighS,
+    /// Dummy `OP_CHECKMULTISIG` argument is not empty.
+    SigNullDummy,
+    /// Public key is neither compressed nor uncompressed.
+    PubkeyType,
+    /// Stack does not contain exactly one element after execution.
+    CleanStack,
+    /// `OP_IF`/`OP_NOTIF` argument is not minimal.
+    MinimalIf,
+    /// Failing signature check with a non-empty signature.
+    SigNullFail,
+    /// Reserved `OP_NOPx` executed.
+    DiscourageUpgradableNops,
+    /// Witness program with an unknown version.
+    DiscourageUpgradableWitnessProgram,
+    /// Taproot script path spend with an unknown leaf version.
+    DiscourageUpgradableTaprootVersion,
+    /// Tapscript contains an `OP_SUCCESSx` opcode.
+    DiscourageOpSuccess,
+    /// Tapscript signature check with an unknown public key type.
+    DiscourageUpgradablePubkeyType,
+    /// Witness program has an invalid length.
+    WitnessProgramWrongLength,
+    /// Witness program was spent with an empty witness.
+    WitnessProgramWitnessEmpty,
+    /// Witness does not match the witness program.
+    WitnessProgramMismatch,
+    /// Native witness program was spent with a non-empty script signature.
+    WitnessMalleated,
+    /// P2SH-wrapped witness program was spent with a script signature that is not a single push.
+    WitnessMalleatedP2sh,
+    /// Witness provided for a non-witness script.
+    WitnessUnexpected,
+    /// Public key used in a SegWit v0 script is not compressed.
+    WitnessPubkeyType,
+    /// Schnorr signature has an invalid size.
+    SchnorrSigSize,
+    /// Schnorr signature has an invalid sighash type.
+    SchnorrSigHashType,
+    /// Schnorr signature is invalid.
+    SchnorrSig,
+    /// Taproot control block has an invalid size.
+    TaprootWrongControlSize,
+    /// Tapscript exceeds its signature validation weight budget.
+    TapscriptValidationWeight,
+    /// `OP_CHECKMULTISIG` used in tapscript.
+    TapscriptCheckMultisig,
+    /// `OP_IF`/`OP_NOTIF` argument in tapscript is not minimal.
+    TapscriptMinimalIf,
+    /// `OP_CODESEPARATOR` used in a non-SegWit script.
+    OpCodeSeparator,
+    /// Signature found in the script code of a legacy signature check.
+    SigFindAndDelete,
+    /// Number on the stack is longer than allowed for the operation.
+    NumericOverflow,
+    /// Number on the stack is not minimally encoded.
+    NonMinimalNumber,
+    /// The spent outputs needed to compute a signature hash were not provided.
+    MissingPrevouts,
+    /// The index of the verified input is out of bounds of the transaction inputs.
+    InputIndex {
+        /// The index of the verified input.
+        index: usize,
+        /// The number of transaction inputs.
+        length: usize,
+    },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ErrorKind::*;
+
+        let s = match *self {
+            EvalFalse =>
+                "script evaluated without error but finished with a false/empty top stack element",
+            OpReturn => "OP_RETURN was encountered",
+            ScriptSize => "script is too big",
+            PushSize => "push value size limit exceeded",
+            OpCount => "operation limit exceeded",
+            StackSize => "stack size limit exceeded",
+            SigCount => "signature count negative or greater than pubkey count",
+            PubkeyCount => "pubkey count negative or limit exceeded",
+            Verify => "script failed an OP_VERIFY operation",
+            EqualVerify => "script failed an OP_EQUALVERIFY operation",
+            CheckMultisigVerify => "script failed an OP_CHECKMULTISIGVERIFY operation",
+            CheckSigVerify => "script failed an OP_CHECKSIGVERIFY operation",
+            NumEqualVerify => "script failed an OP_NUMEQUALVERIFY operation",
+            BadOpcode => "opcode missing or not understood",
+            DisabledOpcode => "attempted to use a disabled opcode",
+            InvalidStackOperation => "operation not valid with the current stack size",
+            InvalidAltstackOperation => "operation not valid with the current altstack size",
+            UnbalancedConditional => "invalid OP_IF construction",
+            NegativeLockTime => "negative locktime",
+            UnsatisfiedLockTime => "locktime requirement not satisfied",
+            SigHashType => "signature hash type missing or not understood",
+            SigDer => "non-canonical DER signature",
+            MinimalData => "data push larger than necessary",
+            SigPushOnly => "only push operators allowed in signatures",
+            SigHighS => "non-canonical signature: S value is unnecessarily high",
+            SigNullDummy => "dummy CHECKMULTISIG argument must be zero",
+            PubkeyType => "public key type not understood",
+            CleanStack => "stack size must be exactly one after execution",
+            MinimalIf => "OP_IF/NOTIF argument must be minimal",
+            SigNullFail => "signature must be zero for failed CHECK(MULTI)SIG operation",
+            DiscourageUpgradableNops => "NOPx reserved for soft-fork upgrades",
+            DiscourageUpgradableWitnessProgram => "witness version reserved for soft-fork upgrades",
+            DiscourageUpgradableTaprootVersion => "taproot version reserved for soft-fork upgrades",
+            DiscourageOpSuccess => "OP_SUCCESSx reserved for soft-fork upgrades",
+            DiscourageUpgradablePubkeyType => "public key version reserved for soft-fork upgrades",
+            WitnessProgramWrongLength => "witness program has incorrect length",
+            WitnessProgramWitnessEmpty => "witness program was passed an empty witness",
+            WitnessProgramMismatch => "witness program hash mismatch",
+            WitnessMalleated => "witness requires empty scriptSig",
+            WitnessMalleatedP2sh => "witness requires only-redeemscript scriptSig",
+            WitnessUnexpected => "witness provided for non-witness script",
+            WitnessPubkeyType => "using non-compressed keys in segwit",
+            SchnorrSigSize => "invalid Schnorr signature size",
+            SchnorrSigHashType => "invalid Schnorr signature hash type",
+            SchnorrSig => "invalid Schnorr signature",
+            TaprootWrongControlSize => "invalid taproot control block size",
+            TapscriptValidationWeight => "too much signature validation relative to witness weight",
+            TapscriptCheckMultisig => "OP_CHECKMULTISIG(VERIFY) is not available in tapscript",
+            TapscriptMinimalIf => "OP_IF/NOTIF argument must be minimal in tapscript",
+            OpCodeSeparator => "using OP_CODESEPARATOR in non-witness script",
+            SigFindAndDelete => "signature is found in scriptCode",
+            NumericOverflow => "script number overflow",
+            NonMinimalNumber => "non-minimally encoded script number",
+            MissingPrevouts => "spent outputs required for the signature hash are not available",
+            InputIndex { index, length } =>
+                return write!(f, "input index {} out of bounds of {} inputs", index, length),
+        };
+        f.write_str(s)
+    }
+}
+
+/// An error during transaction validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TxVerifyError {
+    /// Script execution failed for the input at the given index.
+    ScriptVerification {
+        /// The index of the failing input.
+        input_index: usize,
+        /// The script execution error.
+        error: Error,
+    },
+    /// Can not find the spent output.
+    UnknownSpentOutput(OutPoint),
+}
+
+impl From<Infallible> for TxVerifyError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for TxVerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TxVerifyError::*;
+
+        match *self {
+            ScriptVerification { input_index, ref error } => {
+                write_err!(f, "script verification failed for input {}", input_index; error)
+            }
+            UnknownSpentOutput(ref p) => write!(f, "unknown spent output: {}", p),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TxVerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use TxVerifyError::*;
+
+        match *self {
+            ScriptVerification { ref error, .. } => Some(error),
+            UnknownSpentOutput(_) => None,
+        }
+    }
+}
@@ -0,0 +1,1202 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Script execution.
+//!
+//! This module contains the core of the interpreter, a port of Bitcoin Core's `EvalScript` and
+//! the signature checking logic it relies on.
+
+use hashes::{hash160, ripemd160, sha1, sha256, sha256d};
+use secp256k1::{ecdsa, schnorr, Message, Secp256k1, Verification};
+use units::locktime::absolute::LOCK_TIME_THRESHOLD;
+
+use super::{
+    Error, ErrorKind, VerifyFlags, MAX_OPS_PER_SCRIPT, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_SIZE,
+    MAX_STACK_SIZE, VALIDATION_WEIGHT_PER_SIGOP_PASSED,
+};
+use crate::blockdata::script::{read_scriptbool, scriptint_parse, write_scriptint};
+use crate::constants::MAX_STACK_ELEMENT_SIZE;
+use crate::opcodes::all::*;
+use crate::opcodes::{Class, ClassifyContext, Opcode};
+use crate::prelude::{Borrow, ToOwned, Vec};
+use crate::script::{Instruction, PushBytes, Script, ScriptBuf, ScriptExt as _};
+use crate::sighash::{Annex, Prevouts, SighashCache, TaprootError};
+use crate::taproot::TapLeafHash;
+use crate::{Amount, Sequence, TapSighashType, Transaction, TxOut};
+
+/// If set, `OP_CHECKSEQUENCEVERIFY` behaves as a NOP (BIP-112).
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+
+/// If set, the relative lock time is time based rather than height based (BIP-68).
+const SEQUENCE_LOCKTIME_TYPE_FLAG: i64 = 1 << 22;
+
+/// The bits of a sequence number that make up the relative lock time (BIP-68).
+const SEQUENCE_LOCKTIME_MASK: i64 = 0x0000_ffff;
+
+/// The script context a script is executed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SigVersion {
+    /// Legacy and P2SH scripts.
+    Base,
+    /// SegWit v0 scripts (BIP-143).
+    WitnessV0,
+    /// Taproot key path spends (BIP-341).
+    Taproot,
+    /// Tapscript leaves (BIP-342).
+    Tapscript,
+}
+
+/// Data gathered during verification that is needed for tapscript execution and signature hashes.
+#[derive(Debug, Clone)]
+pub(super) struct ExecData {
+    /// The annex, including the `0x50` prefix.
+    pub(super) annex: Option<Vec<u8>>,
+    /// The leaf hash of the executed tapscript.
+    pub(super) tapleaf_hash: Option<TapLeafHash>,
+    /// Opcode index of the last executed `OP_CODESEPARATOR` (`u32::MAX` if none).
+    pub(super) codesep_pos: u32,
+    /// Remaining signature validation budget of a tapscript.
+    pub(super) validation_weight_left: i64,
+}
+
+impl Default for ExecData {
+    fn default() -> Self {
+        ExecData {
+            annex: None,
+            tapleaf_hash: None,
+            codesep_pos: u32::MAX,
+            validation_weight_left: 0,
+        }
+    }
+}
+
+/// Signature, lock time and sequence checks against the spending transaction.
+pub(super) struct Checker<'a, C: Verification, T: Borrow<TxOut>> {
+    secp: &'a Secp256k1<C>,
+    cache: SighashCache<&'a Transaction>,
+    input_index: usize,
+    prevouts: &'a Prevouts<'a, T>,
+    amount: Amount,
+}
+
+impl<'a, C: Verification, T: Borrow<TxOut>> Checker<'a, C, T> {
+    pub(super) fn new(
+        secp: &'a Secp256k1<C>,
+        tx: &'a Transaction,
+        input_index: usize,
+        prevouts: &'a Prevouts<'a, T>,
+        amount: Amount,
+    ) -> Self {
+        Checker { secp, cache: SighashCache::new(tx), input_index, prevouts, amount }
+    }
+
+    pub(super) fn secp(&self) -> &'a Secp256k1<C> { self.secp }
+
+    /// Checks an ECDSA signature (including its sighash byte) against `pubkey`.
+    fn check_ecdsa_signature(
+        &mut self,
+        sig: &[u8],
+        pubkey: &[u8],
+        script_code: &Script,
+        sigversion: SigVersion,
+    ) -> bool {
+        let pubkey = match secp256k1::PublicKey::from_slice(pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return false,
+        };
+        let (&sighash_type, sig) = match sig.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        let sighash = match sigversion {
+            SigVersion::Base => self
+                .cache
+                .legacy_signature_hash(
+                    self.input_index,
+                    &remove_codeseparators(script_code),
+                    u32::from(sighash_type),
+                )
+                .map(|sighash| sighash.to_byte_array()),
+            SigVersion::WitnessV0 => self
+                .cache
+                .segwit_v0_signature_hash_consensus(
+                    self.input_index,
+                    script_code,
+                    self.amount,
+                    u32::from(sighash_type),
+                )
+                .map(|sighash| sighash.to_byte_array()),
+            SigVersion::Taproot | SigVersion::Tapscript => {
+                unreachable!("ECDSA signatures are not used in taproot")
+            }
+        };
+        let sighash = match sighash {
+            Ok(sighash) => sighash,
+            Err(_) => return false,
+        };
+        // Like Bitcoin Core, accept any signature the lax DER parser accepts and any S value.
+        let mut sig = match ecdsa::Signature::from_der_lax(sig) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+        sig.normalize_s();
+        self.secp.verify_ecdsa(&Message::from_digest(sighash), &sig, &pubkey).is_ok()
+    }
+
+    /// Checks a BIP-340 signature (including an optional sighash byte) against `pubkey`.
+    pub(super) fn check_schnorr_signature(
+        &mut self,
+        sig: &[u8],
+        pubkey: &[u8],
+        sigversion: SigVersion,
+        execdata: &ExecData,
+    ) -> Result<(), ErrorKind> {
+        let (sig, sighash_type) = match sig.len() {
+            64 => (sig, TapSighashType::Default),
+            65 => {
+                // The default sighash type must be implied by a 64 byte signature.
+                if sig[64] == 0x00 {
+                    return Err(ErrorKind::SchnorrSigHashType);
+                }
+                let sighash_type = TapSighashType::from_consensus_u8(sig[64])
+                    .map_err(|_| ErrorKind::SchnorrSigHashType)?;
+                (&sig[..64], sighash_type)
+            }
+            _ => return Err(ErrorKind::SchnorrSigSize),
+        };
+
+        let annex = execdata
+            .annex
+            .as_deref()
+            .map(|annex| Annex::new(annex).expect("annex is only set if it has the right prefix"));
+        let leaf_hash_code_separator = match sigversion {
+            SigVersion::Taproot => None,
+            SigVersion::Tapscript => Some((
+                execdata.tapleaf_hash.expect("leaf hash is set before executing tapscript"),
+                execdata.codesep_pos,
+            )),
+            SigVersion::Base | SigVersion::WitnessV0 => {
+                unreachable!("Schnorr signatures are only used in taproot")
+            }
+        };
+        let sighash = self
+            .cache
+            .taproot_signature_hash(
+                self.input_index,
+                self.prevouts,
+                annex,
+                leaf_hash_code_separator,
+                sighash_type,
+            )
+            .map_err(|e| match e {
+                TaprootError::SingleMissingOutput(_) | TaprootError::InvalidSighashType(_) =>
+                    ErrorKind::SchnorrSigHashType,
+                _ => ErrorKind::MissingPrevouts,
+            })?;
+
+        let pubkey = pubkey
+            .try_into()
+            .ok()
+            .and_then(|pubkey| secp256k1::XOnlyPublicKey::from_byte_array(pubkey).ok())
+            .ok_or(ErrorKind::SchnorrSig)?;
+        let sig = schnorr::Signature::from_byte_array(sig.try_into().expect("checked length"));
+        self.secp
+            .verify_schnorr(&sig, sighash.as_byte_array(), &pubkey)
+            .map_err(|_| ErrorKind::SchnorrSig)
+    }
+
+    /// Checks an `OP_CHECKLOCKTIMEVERIFY` argument against the transaction (BIP-65).
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let tx = self.cache.transaction();
+        let threshold = i64::from(LOCK_TIME_THRESHOLD);
+        let tx_lock_time = i64::from(tx.lock_time.to_consensus_u32());
+
+        // Both lock times must be of the same kind, either heights or timestamps.
+        if (tx_lock_time < threshold) != (lock_time < threshold) {
+            return false;
+        }
+        if lock_time > tx_lock_time {
+            return false;
+        }
+        // The lock time is not enforced at all if the input is final.
+        tx.input[self.input_index].sequence != Sequence::MAX
+    }
+
+    /// Checks an `OP_CHECKSEQUENCEVERIFY` argument against the transaction (BIP-112).
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let tx = self.cache.transaction();
+        let tx_sequence = i64::from(tx.input[self.input_index].sequence.to_consensus_u32());
+
+        // Relative lock times are only enforced for version 2 transactions and later.
+        if tx.version.to_u32() < 2 {
+            return false;
+        }
+        if tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return false;
+        }
+
+        let mask = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+        let tx_sequence = tx_sequence & mask;
+        let sequence = sequence & mask;
+        if (tx_sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) != (sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) {
+            return false;
+        }
+        sequence <= tx_sequence
+    }
+}
+
+/// Tracks the nesting of `OP_IF`/`OP_ELSE`/`OP_ENDIF`.
+///
+/// Only the depth and the position of the first false condition are needed, which makes every
+/// operation constant time (Bitcoin Core's `ConditionStack`).
+#[derive(Debug, Default)]
+struct ConditionStack {
+    size: usize,
+    first_false_pos: Option<usize>,
+}
+
+impl ConditionStack {
+    fn is_empty(&self) -> bool { self.size == 0 }
+
+    fn all_true(&self) -> bool { self.first_false_pos.is_none() }
+
+    fn push(&mut self, value: bool) {
+        if self.first_false_pos.is_none() && !value {
+            self.first_false_pos = Some(self.size);
+        }
+        self.size += 1;
+    }
+
+    fn pop(&mut self) {
+        self.size -= 1;
+        if self.first_false_pos == Some(self.size) {
+            self.first_false_pos = None;
+        }
+    }
+
+    fn toggle_top(&mut self) {
+        match self.first_false_pos {
+            None => self.first_false_pos = Some(self.size - 1),
+            Some(pos) if pos == self.size - 1 => self.first_false_pos = None,
+            // There is a false condition below the top, toggling the top has no effect.
+            Some(_) => {}
+        }
+    }
+}
+
+/// Returns `true` if `opcode` is an `OP_SUCCESSx` opcode in tapscript (BIP-342).
+pub(super) fn is_op_success(opcode: Opcode) -> bool {
+    matches!(opcode.classify(ClassifyContext::TapScript), Class::SuccessOp)
+}
+
+/// Executes `script` on top of `stack` (Bitcoin Core's `EvalScript`).
+pub(super) fn eval_script<C: Verification, T: Borrow<TxOut>>(
+    stack: &mut Vec<Vec<u8>>,
+    script: &Script,
+    flags: VerifyFlags,
+    checker: &mut Checker<'_, C, T>,
+    sigversion: SigVersion,
+    execdata: &mut ExecData,
+) -> Result<(), Error> {
+    if matches!(sigversion, SigVersion::Base | SigVersion::WitnessV0)
+        && script.len() > MAX_SCRIPT_SIZE
+    {
+        return Err(ErrorKind::ScriptSize.into());
+    }
+
+    execdata.codesep_pos = u32::MAX;
+    let mut exec = Exec {
+        stack,
+        altstack: Vec::new(),
+        conditions: ConditionStack::default(),
+        op_count: 0,
+        script,
+        codesep_byte_pos: 0,
+        flags,
+        sigversion,
+        checker,
+        execdata,
+    };
+
+    let bytes = script.as_bytes();
+    let mut instructions = script.instructions();
+    let mut opcode_pos = 0u32;
+    loop {
+        let position = bytes.len() - instructions.as_script().len();
+        let result = match instructions.next() {
+            None => break,
+            Some(Ok(instruction)) => {
+                let next_position = bytes.len() - instructions.as_script().len();
+                exec.step(instruction, Opcode::from(bytes[position]), next_position, opcode_pos)
+            }
+            Some(Err(_)) => Err(ErrorKind::BadOpcode),
+        };
+        result.map_err(|kind| {
+            Error::at_opcode(kind, Opcode::from(bytes[position]), position, exec.stack.as_slice())
+        })?;
+        opcode_pos = opcode_pos.wrapping_add(1);
+    }
+
+    if !exec.conditions.is_empty() {
+        return Err(ErrorKind::UnbalancedConditional.into());
+    }
+    Ok(())
+}
+
+/// The execution state of a single script.
+struct Exec<'e, 'a, C: Verification, T: Borrow<TxOut>> {
+    stack: &'e mut Vec<Vec<u8>>,
+    altstack: Vec<Vec<u8>>,
+    conditions: ConditionStack,
+    op_count: usize,
+    script: &'e Script,
+    /// Byte position just after the last executed `OP_CODESEPARATOR`.
+    codesep_byte_pos: usize,
+    flags: VerifyFlags,
+    sigversion: SigVersion,
+    checker: &'e mut Checker<'a, C, T>,
+    execdata: &'e mut ExecData,
+}
+
+impl<C: Verification, T: Borrow<TxOut>> Exec<'_, '_, C, T> {
+    /// Executes a single instruction.
+    ///
+    /// `next_position` is the byte position of the following instruction and `opcode_pos` the
+    /// index of this instruction in the script.
+    fn step(
+        &mut self,
+        instruction: Instruction,
+        opcode: Opcode,
+        next_position: usize,
+        opcode_pos: u32,
+    ) -> Result<(), ErrorKind> {
+        let executing = self.conditions.all_true();
+
+        if let Instruction::PushBytes(push) = instruction {
+            if push.len() > MAX_STACK_ELEMENT_SIZE {
+                return Err(ErrorKind::PushSize);
+            }
+        }
+
+        // Note how OP_RESERVED does not count towards the opcode limit.
+        if matches!(self.sigversion, SigVersion::Base | SigVersion::WitnessV0)
+            && opcode.to_u8() > OP_PUSHNUM_16.to_u8()
+        {
+            self.op_count += 1;
+            if self.op_count > MAX_OPS_PER_SCRIPT {
+                return Err(ErrorKind::OpCount);
+            }
+        }
+
+        // Disabled opcodes fail the script even in an unexecuted branch.
+        if is_disabled(opcode) {
+            return Err(ErrorKind::DisabledOpcode);
+        }
+
+        if opcode == OP_CODESEPARATOR
+            && self.sigversion == SigVersion::Base
+            && self.flags.has(VerifyFlags::CONST_SCRIPTCODE)
+        {
+            return Err(ErrorKind::OpCodeSeparator);
+        }
+
+        match instruction {
+            Instruction::PushBytes(push) if executing => {
+                if self.flags.has(VerifyFlags::MINIMALDATA) && !is_minimal_push(push, opcode) {
+                    return Err(ErrorKind::MinimalData);
+                }
+                self.stack.push(push.as_bytes().to_vec());
+            }
+            Instruction::PushBytes(_) => {}
+            Instruction::Op(op)
+                if executing || (OP_IF.to_u8()..=OP_ENDIF.to_u8()).contains(&op.to_u8()) =>
+                self.execute_opcode(op, executing, next_position, opcode_pos)?,
+            Instruction::Op(_) => {}
+        }
+
+        if self.stack.len() + self.altstack.len() > MAX_STACK_SIZE {
+            return Err(ErrorKind::StackSize);
+        }
+        Ok(())
+    }
+
+    fn require_minimal(&self) -> bool { self.flags.has(VerifyFlags::MINIMALDATA) }
+
+    /// Fails with `InvalidStackOperation` if the stack has less than `n` elements.
+    fn need(&self, n: usize) -> Result<(), ErrorKind> {
+        if self.stack.len() < n {
+            Err(ErrorKind::InvalidStackOperation)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the stack element `depth` positions from the top (`1` being the top element).
+    ///
+    /// Callers must ensure the stack is deep enough using [`Self::need`].
+    fn top(&self, depth: usize) -> &Vec<u8> { &self.stack[self.stack.len() - depth] }
+
+    fn pop(&mut self) -> Vec<u8> { self.stack.pop().expect("callers check stack depth") }
+
+    fn top_num(&self, depth: usize) -> Result<i64, ErrorKind> {
+        read_num(self.top(depth), self.require_minimal(), 4)
+    }
+
+    fn push_bool(&mut self, value: bool) { self.stack.push(if value { vec![1] } else { vec![] }) }
+
+    fn push_num(&mut self, n: i64) { self.stack.push(encode_num(n)) }
+
+    fn execute_opcode(
+        &mut self,
+        op: Opcode,
+        executing: bool,
+        next_position: usize,
+        opcode_pos: u32,
+    ) -> Result<(), ErrorKind> {
+        match op {
+            OP_PUSHNUM_NEG1 => self.push_num(-1),
+            _ if op.decode_pushnum().is_some() =>
+                self.push_num(i64::from(op.decode_pushnum().expect("checked above"))),
+
+            OP_NOP => {}
+
+            OP_CLTV => {
+                if !self.flags.has(VerifyFlags::CHECKLOCKTIMEVERIFY) {
+                    // Not enabled, treat as OP_NOP2.
+                    return Ok(());
+                }
+                self.need(1)?;
+                // Lock times may be up to 5 bytes long to support timestamps past 2038.
+                let lock_time = read_num(self.top(1), self.require_minimal(), 5)?;
+                if lock_time < 0 {
+                    return Err(ErrorKind::NegativeLockTime);
+                }
+                if !self.checker.check_lock_time(lock_time) {
+                    return Err(ErrorKind::UnsatisfiedLockTime);
+                }
+            }
+
+            OP_CSV => {
+                if !self.flags.has(VerifyFlags::CHECKSEQUENCEVERIFY) {
+                    // Not enabled, treat as OP_NOP3.
+                    return Ok(());
+                }
+                self.need(1)?;
+                let sequence = read_num(self.top(1), self.require_minimal(), 5)?;
+                if sequence < 0 {
+                    return Err(ErrorKind::NegativeLockTime);
+                }
+                // The disable flag turns the opcode into a NOP, reserved for future soft forks.
+                if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+                    return Ok(());
+                }
+                if !self.checker.check_sequence(sequence) {
+                    return Err(ErrorKind::UnsatisfiedLockTime);
+                }
+            }
+
+            OP_NOP1 | OP_NOP4 | OP_NOP5 | OP_NOP6 | OP_NOP7 | OP_NOP8 | OP_NOP9 | OP_NOP10 =>
+                if self.flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                    return Err(ErrorKind::DiscourageUpgradableNops);
+                },
+
+            OP_IF | OP_NOTIF => {
+                let mut value = false;
+                if executing {
+                    let top = self.stack.last().ok_or(ErrorKind::UnbalancedConditional)?;
+                    let minimal = top.is_empty() || top[..] == [1];
+                    if self.sigversion == SigVersion::Tapscript && !minimal {
+                        return Err(ErrorKind::TapscriptMinimalIf);
+                    }
+                    if self.sigversion == SigVersion::WitnessV0
+                        && self.flags.has(VerifyFlags::MINIMALIF)
+                        && !minimal
+                    {
+                        return Err(ErrorKind::MinimalIf);
+                    }
+                    value = read_scriptbool(top) != (op == OP_NOTIF);
+                    self.pop();
+                }
+                self.conditions.push(value);
+            }
+
+            OP_ELSE => {
+                if self.conditions.is_empty() {
+                    return Err(ErrorKind::UnbalancedConditional);
+                }
+                self.conditions.toggle_top();
+            }
+
+            OP_ENDIF => {
+                if self.conditions.is_empty() {
+                    return Err(ErrorKind::UnbalancedConditional);
+                }
+                self.conditions.pop();
+            }
+
+            OP_VERIFY => {
+                self.need(1)?;
+                if !read_scriptbool(self.top(1)) {
+                    return Err(ErrorKind::Verify);
+                }
+                self.pop();
+            }
+
+            OP_RETURN => return Err(ErrorKind::OpReturn),
+
+            OP_TOALTSTACK => {
+                self.need(1)?;
+                let item = self.pop();
+                self.altstack.push(item);
+            }
+
+            OP_FROMALTSTACK => {
+                let item = self.altstack.pop().ok_or(ErrorKind::InvalidAltstackOperation)?;
+                self.stack.push(item);
+            }
+
+            OP_2DROP => {
+                self.need(2)?;
+                self.pop();
+                self.pop();
+            }
+
+            OP_2DUP => {
+                self.need(2)?;
+                let (a, b) = (self.top(2).clone(), self.top(1).clone());
+                self.stack.push(a);
+                self.stack.push(b);
+            }
+
+            OP_3DUP => {
+                self.need(3)?;
+                let (a, b, c) = (self.top(3).clone(), self.top(2).clone(), self.top(1).clone());
+                self.stack.push(a);
+                self.stack.push(b);
+                self.stack.push(c);
+            }
+
+            OP_2OVER => {
+                self.need(4)?;
+                let (a, b) = (self.top(4).clone(), self.top(3).clone());
+                self.stack.push(a);
+                self.stack.push(b);
+            }
+
+            OP_2ROT => {
+                self.need(6)?;
+                let len = self.stack.len();
+                let moved: Vec<_> = self.stack.drain(len - 6..len - 4).collect();
+                self.stack.extend(moved);
+            }
+
+            OP_2SWAP => {
+                self.need(4)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 4, len - 2);
+                self.stack.swap(len - 3, len - 1);
+            }
+
+            OP_IFDUP => {
+                self.need(1)?;
+                if read_scriptbool(self.top(1)) {
+                    let top = self.top(1).clone();
+                    self.stack.push(top);
+                }
+            }
+
+            OP_DEPTH => self.push_num(self.stack.len() as i64),
+
+            OP_DROP => {
+                self.need(1)?;
+                self.pop();
+            }
+
+            OP_DUP => {
+                self.need(1)?;
+                let top = self.top(1).clone();
+                self.stack.push(top);
+            }
+
+            OP_NIP => {
+                self.need(2)?;
+                let len = self.stack.len();
+                self.stack.remove(len - 2);
+            }
+
+            OP_OVER => {
+                self.need(2)?;
+                let item = self.top(2).clone();
+                self.stack.push(item);
+            }
+
+            OP_PICK | OP_ROLL => {
+                self.need(2)?;
+                let n = self.top_num(1)?;
+                self.pop();
+                if n < 0 || n as u64 >= self.stack.len() as u64 {
+                    return Err(ErrorKind::InvalidStackOperation);
+                }
+                let index = self.stack.len() - 1 - n as usize;
+                let item = if op == OP_ROLL {
+                    self.stack.remove(index)
+                } else {
+                    self.stack[index].clone()
+                };
+                self.stack.push(item);
+            }
+
+            OP_ROT => {
+                self.need(3)?;
+                let len = self.stack.len();
+                self.stack[len - 3..].rotate_left(1);
+            }
+
+            OP_SWAP => {
+                self.need(2)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 2, len - 1);
+            }
+
+            OP_TUCK => {
+                self.need(2)?;
+                let top = self.top(1).clone();
+                let len = self.stack.len();
+                self.stack.insert(len - 2, top);
+            }
+
+            OP_SIZE => {
+                self.need(1)?;
+                self.push_num(self.top(1).len() as i64);
+            }
+
+            OP_EQUAL | OP_EQUALVERIFY => {
+                self.need(2)?;
+                let equal = self.top(2) == self.top(1);
+                self.pop();
+                self.pop();
+                self.push_bool(equal);
+                if op == OP_EQUALVERIFY {
+                    if !equal {
+                        return Err(ErrorKind::EqualVerify);
+                    }
+                    self.pop();
+                }
+            }
+
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                self.need(1)?;
+                let n = self.top_num(1)?;
+                let result = match op {
+                    OP_1ADD => n + 1,
+                    OP_1SUB => n - 1,
+                    OP_NEGATE => -n,
+                    OP_ABS => n.abs(),
+                    OP_NOT => i64::from(n == 0),
+                    _ => i64::from(n != 0),
+                };
+                self.pop();
+                self.push_num(result);
+            }
+
+            OP_ADD
+            | OP_SUB
+            | OP_BOOLAND
+            | OP_BOOLOR
+            | OP_NUMEQUAL
+            | OP_NUMEQUALVERIFY
+            | OP_NUMNOTEQUAL
+            | OP_LESSTHAN
+            | OP_GREATERTHAN
+            | OP_LESSTHANOREQUAL
+            | OP_GREATERTHANOREQUAL
+            | OP_MIN
+            | OP_MAX => {
+                self.need(2)?;
+                let a = self.top_num(2)?;
+                let b = self.top_num(1)?;
+                let result = match op {
+                    OP_ADD => a + b,
+                    OP_SUB => a - b,
+                    OP_BOOLAND => i64::from(a != 0 && b != 0),
+                    OP_BOOLOR => i64::from(a != 0 || b != 0),
+                    OP_NUMEQUAL | OP_NUMEQUALVERIFY => i64::from(a == b),
+                    OP_NUMNOTEQUAL => i64::from(a != b),
+                    OP_LESSTHAN => i64::from(a < b),
+                    OP_GREATERTHAN => i64::from(a > b),
+                    OP_LESSTHANOREQUAL => i64::from(a <= b),
+                    OP_GREATERTHANOREQUAL => i64::from(a >= b),
+                    OP_MIN => a.min(b),
+                    _ => a.max(b),
+                };
+                self.pop();
+                self.pop();
+                self.push_num(result);
+                if op == OP_NUMEQUALVERIFY {
+                    if result == 0 {
+                        return Err(ErrorKind::NumEqualVerify);
+                    }
+                    self.pop();
+                }
+            }
+
+            OP_WITHIN => {
+                self.need(3)?;
+                let x = self.top_num(3)?;
+                let min = self.top_num(2)?;
+                let max = self.top_num(1)?;
+                self.pop();
+                self.pop();
+                self.pop();
+                self.push_bool(min <= x && x < max);
+            }
+
+            OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                self.need(1)?;
+                let data = self.pop();
+                let hash = match op {
+                    OP_RIPEMD160 => ripemd160::Hash::hash(&data).to_byte_array().to_vec(),
+                    OP_SHA1 => sha1::Hash::hash(&data).to_byte_array().to_vec(),
+                    OP_SHA256 => sha256::Hash::hash(&data).to_byte_array().to_vec(),
+                    OP_HASH160 => hash160::Hash::hash(&data).to_byte_array().to_vec(),
+                    _ => sha256d::Hash::hash(&data).to_byte_array().to_vec(),
+                };
+                self.stack.push(hash);
+            }
+
+            OP_CODESEPARATOR => {
+                // Hash starts after the code separator.
+                self.codesep_byte_pos = next_position;
+                self.execdata.codesep_pos = opcode_pos;
+            }
+
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                self.need(2)?;
+                let (sig, pubkey) = (self.top(2).clone(), self.top(1).clone());
+                let success = self.eval_checksig(&sig, &pubkey)?;
+                self.pop();
+                self.pop();
+                self.push_bool(success);
+                if op == OP_CHECKSIGVERIFY {
+                    if !success {
+                        return Err(ErrorKind::CheckSigVerify);
+                    }
+                    self.pop();
+                }
+            }
+
+            OP_CHECKSIGADD => {
+                // OP_CHECKSIGADD is only available in tapscript.
+                if matches!(self.sigversion, SigVersion::Base | SigVersion::WitnessV0) {
+                    return Err(ErrorKind::BadOpcode);
+                }
+                self.need(3)?;
+                let sig = self.top(3).clone();
+                let n = self.top_num(2)?;
+                let pubkey = self.top(1).clone();
+                let success = self.eval_checksig(&sig, &pubkey)?;
+                self.pop();
+                self.pop();
+                self.pop();
+                self.push_num(n + i64::from(success));
+            }
+
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                if self.sigversion == SigVersion::Tapscript {
+                    return Err(ErrorKind::TapscriptCheckMultisig);
+                }
+                let success = self.eval_checkmultisig()?;
+                self.push_bool(success);
+                if op == OP_CHECKMULTISIGVERIFY {
+                    if !success {
+                        return Err(ErrorKind::CheckMultisigVerify);
+                    }
+                    self.pop();
+                }
+            }
+
+            _ => return Err(ErrorKind::BadOpcode),
+        }
+        Ok(())
+    }
+
+    /// The script code signatures commit to, everything after the last `OP_CODESEPARATOR`.
+    fn script_code(&self) -> &Script {
+        Script::from_bytes(&self.script.as_bytes()[self.codesep_byte_pos..])
+    }
+
+    /// Evaluates a signature check, returning whether the signature was valid.
+    ///
+    /// Errors are returned for checks that make the whole script fail rather than push false.
+    fn eval_checksig(&mut self, sig: &[u8], pubkey: &[u8]) -> Result<bool, ErrorKind> {
+        match self.sigversion {
+            SigVersion::Base | SigVersion::WitnessV0 => {
+                let mut script_code = self.script_code().to_owned();
+                if self.sigversion == SigVersion::Base {
+                    let found = find_and_delete(&mut script_code, &push_encoding(sig));
+                    if found > 0 && self.flags.has(VerifyFlags::CONST_SCRIPTCODE) {
+                        return Err(ErrorKind::SigFindAndDelete);
+                    }
+                }
+                check_signature_encoding(sig, self.flags)?;
+                check_pubkey_encoding(pubkey, self.flags, self.sigversion)?;
+                let success =
+                    self.checker.check_ecdsa_signature(sig, pubkey, &script_code, self.sigversion);
+                if !success && self.flags.has(VerifyFlags::NULLFAIL) && !sig.is_empty() {
+                    return Err(ErrorKind::SigNullFail);
+                }
+                Ok(success)
+            }
+            SigVersion::Tapscript => {
+                // An empty signature is the only way to make a signature check fail without
+                // failing the script.
+                let success = !sig.is_empty();
+                if success {
+                    self.execdata.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+                    if self.execdata.validation_weight_left < 0 {
+                        return Err(ErrorKind::TapscriptValidationWeight);
+                    }
+                }
+                match pubkey.len() {
+                    0 => return Err(ErrorKind::PubkeyType),
+                    32 =>
+                        if success {
+                            self.checker.check_schnorr_signature(
+                                sig,
+                                pubkey,
+                                self.sigversion,
+                                self.execdata,
+                            )?;
+                        },
+                    // Unknown public key types are reserved for future soft forks.
+                    _ =>
+                        if self.flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE) {
+                            return Err(ErrorKind::DiscourageUpgradablePubkeyType);
+                        },
+                }
+                Ok(success)
+            }
+            SigVersion::Taproot => unreachable!("key path spends do not execute scripts"),
+        }
+    }
+
+    /// Evaluates `OP_CHECKMULTISIG`, consuming its arguments and returning the result.
+    fn eval_checkmultisig(&mut self) -> Result<bool, ErrorKind> {
+        let mut i = 1;
+        self.need(i)?;
+
+        let n_keys = self.top_num(i)?;
+        if n_keys < 0 || n_keys > MAX_PUBKEYS_PER_MULTISIG as i64 {
+            return Err(ErrorKind::PubkeyCount);
+        }
+        let mut n_keys = n_keys as usize;
+        self.op_count += n_keys;
+        if self.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(ErrorKind::OpCount);
+        }
+        i += 1;
+        let mut ikey = i;
+        // Index of the last key that needs to be checked for the NULLFAIL rule.
+        let mut ikey2 = n_keys + 2;
+        i += n_keys;
+        self.need(i)?;
+
+        let n_sigs = self.top_num(i)?;
+        if n_sigs < 0 || n_sigs > n_keys as i64 {
+            return Err(ErrorKind::SigCount);
+        }
+        let mut n_sigs = n_sigs as usize;
+        i += 1;
+        let mut isig = i;
+        i += n_sigs;
+        self.need(i)?;
+
+        // Remove all signatures from the script code, signatures can't sign themselves.
+        let mut script_code = self.script_code().to_owned();
+        if self.sigversion == SigVersion::Base {
+            for k in 0..n_sigs {
+                let found = find_and_delete(&mut script_code, &push_encoding(self.top(isig + k)));
+                if found > 0 && self.flags.has(VerifyFlags::CONST_SCRIPTCODE) {
+                    return Err(ErrorKind::SigFindAndDelete);
+                }
+            }
+        }
+
+        let mut success = true;
+        while success && n_sigs > 0 {
+            let sig = self.top(isig).clone();
+            let pubkey = self.top(ikey).clone();
+            // Note how this makes the exact order of pubkey/signature evaluation distinguishable
+            // by CHECKMULTISIG NOT if the STRICTENC flag is set.
+            check_signature_encoding(&sig, self.flags)?;
+            check_pubkey_encoding(&pubkey, self.flags, self.sigversion)?;
+
+            if self.checker.check_ecdsa_signature(&sig, &pubkey, &script_code, self.sigversion) {
+                isig += 1;
+                n_sigs -= 1;
+            }
+            ikey += 1;
+            n_keys -= 1;
+
+            // If there are more signatures left than keys left, then too many signatures have
+            // failed. Exit early, without checking any further signatures.
+            if n_sigs > n_keys {
+                success = false;
+            }
+        }
+
+        // Clean up stack of actual arguments.
+        while i > 1 {
+            i -= 1;
+            // If the operation failed, all signatures must be empty for NULLFAIL.
+            if !success
+                && self.flags.has(VerifyFlags::NULLFAIL)
+                && ikey2 == 0
+                && !self.top(1).is_empty()
+            {
+                return Err(ErrorKind::SigNullFail);
+            }
+            ikey2 = ikey2.saturating_sub(1);
+            self.pop();
+        }
+
+        // A bug causes CHECKMULTISIG to consume one extra argument whose contents were not
+        // checked in any way. BIP-147 requires it to be empty.
+        self.need(1)?;
+        if self.flags.has(VerifyFlags::NULLDUMMY) && !self.top(1).is_empty() {
+            return Err(ErrorKind::SigNullDummy);
+        }
+        self.pop();
+        Ok(success)
+    }
+}
+
+/// Returns `true` for the opcodes disabled since CVE-2010-5137.
+fn is_disabled(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        OP_CAT
+            | OP_SUBSTR
+            | OP_LEFT
+            | OP_RIGHT
+            | OP_INVERT
+            | OP_AND
+            | OP_OR
+            | OP_XOR
+            | OP_2MUL
+            | OP_2DIV
+            | OP_MUL
+            | OP_DIV
+            | OP_MOD
+            | OP_LSHIFT
+            | OP_RSHIFT
+    )
+}
+
+/// Returns `true` if `push` uses the smallest possible encoding (Bitcoin Core's `CheckMinimalPush`).
+fn is_minimal_push(push: &PushBytes, opcode: Opcode) -> bool {
+    let data = push.as_bytes();
+    match data.len() {
+        // Should have used OP_0.
+        0 => opcode == OP_PUSHBYTES_0,
+        // Should have used OP_1 .. OP_16 or OP_1NEGATE.
+        1 if (1..=16).contains(&data[0]) || data[0] == 0x81 => false,
+        len @ 1..=75 => usize::from(opcode.to_u8()) == len,
+        76..=255 => opcode == OP_PUSHDATA1,
+        256..=65535 => opcode == OP_PUSHDATA2,
+        _ => true,
+    }
+}
+
+/// Decodes a script number of at most `max_len` bytes (Bitcoin Core's `CScriptNum`).
+fn read_num(bytes: &[u8], require_minimal: bool, max_len: usize) -> Result<i64, ErrorKind> {
+    if bytes.len() > max_len {
+        return Err(ErrorKind::NumericOverflow);
+    }
+    if require_minimal {
+        if let Some((&last, rest)) = bytes.split_last() {
+            // The most significant byte may only be zero (apart from the sign bit) if the
+            // next byte needs its high bit for the magnitude.
+            if last & 0x7f == 0 && rest.last().map_or(true, |&b| b & 0x80 == 0) {
+                return Err(ErrorKind::NonMinimalNumber);
+            }
+        }
+    }
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    Ok(scriptint_parse(bytes))
+}
+
+/// Encodes a script number minimally.
+fn encode_num(n: i64) -> Vec<u8> {
+    let mut buf = [0u8; 8];
+    let len = write_scriptint(&mut buf, n);
+    buf[..len].to_vec()
+}
+
+/// Encodes `data` as a push the way Bitcoin Core's `CScript::operator<<` does.
+///
+/// Unlike [`crate::script::Builder::push_slice`] this never uses `OP_0` or `OP_PUSHNUM_x`.
+fn push_encoding(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len() + 5);
+    match data.len() {
+        len @ 0..=75 => encoded.push(len as u8),
+        len @ 76..=0xff => encoded.extend_from_slice(&[OP_PUSHDATA1.to_u8(), len as u8]),
+        len @ 0x100..=0xffff => {
+            encoded.push(OP_PUSHDATA2.to_u8());
+            encoded.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        len => {
+            encoded.push(OP_PUSHDATA4.to_u8());
+            encoded.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+    }
+    encoded.extend_from_slice(data);
+    encoded
+}
+
+/// Returns the byte position after the instruction starting at `position`.
+///
+/// Returns `None` at the end of the script or if the instruction is malformed.
+fn next_instruction_end(script: &[u8], position: usize) -> Option<usize> {
+    let mut instructions = Script::from_bytes(&script[position..]).instructions();
+    match instructions.next() {
+        Some(Ok(_)) => Some(script.len() - instructions.as_script().len()),
+        _ => None,
+    }
+}
+
+/// Removes all occurrences of `pattern` at instruction boundaries (Bitcoin Core's
+/// `FindAndDelete`), returning the number of removed occurrences.
+fn find_and_delete(script: &mut ScriptBuf, pattern: &[u8]) -> usize {
+    let bytes = script.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut found = 0;
+    let mut position = 0;
+    let mut copied_until = 0;
+    loop {
+        result.extend_from_slice(&bytes[copied_until..position]);
+        while bytes[position..].starts_with(pattern) {
+            position += pattern.len();
+            found += 1;
+        }
+        copied_until = position;
+        match next_instruction_end(bytes, position) {
+            Some(end) => position = end,
+            None => break,
+        }
+    }
+    if found > 0 {
+        result.extend_from_slice(&bytes[copied_until..]);
+        *script = ScriptBuf::from_bytes(result);
+    }
+    found
+}
+
+/// Removes all `OP_CODESEPARATOR`s from legacy script code before hashing it.
+fn remove_codeseparators(script_code: &Script) -> ScriptBuf {
+    let bytes = script_code.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+    let mut copied_until = 0;
+    while let Some(end) = next_instruction_end(bytes, position) {
+        if bytes[position] == OP_CODESEPARATOR.to_u8() {
+            result.extend_from_slice(&bytes[copied_until..position]);
+            copied_until = end;
+        }
+        position = end;
+    }
+    result.extend_from_slice(&bytes[copied_until..]);
+    ScriptBuf::from_bytes(result)
+}
+
+/// Checks the encoding of an ECDSA signature according to `flags`.
+fn check_signature_encoding(sig: &[u8], flags: VerifyFlags) -> Result<(), ErrorKind> {
+    // Empty signatures are always allowed, they provide a compact way to fail a signature check.
+    if sig.is_empty() {
+        return Ok(());
+    }
+    let strict_der = VerifyFlags::DERSIG | VerifyFlags::LOW_S | VerifyFlags::STRICTENC;
+    if flags.to_u32() & strict_der.to_u32() != 0 && !is_valid_signature_encoding(sig) {
+        return Err(ErrorKind::SigDer);
+    }
+    if flags.has(VerifyFlags::LOW_S) && !is_low_s(&sig[..sig.len() - 1]) {
+        return Err(ErrorKind::SigHighS);
+    }
+    if flags.has(VerifyFlags::STRICTENC) {
+        let sighash_type = sig[sig.len() - 1] & !0x80;
+        if !(0x01..=0x03).contains(&sighash_type) {
+            return Err(ErrorKind::SigHashType);
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether `sig` (including the sighash byte) is strictly DER encoded (BIP-66).
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    // Format: 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S] [sighash]
+    if sig.len() < 9 || sig.len() > 73 {
+        return false;
+    }
+    if sig[0] != 0x30 || usize::from(sig[1]) != sig.len() - 3 {
+        return false;
+    }
+    let len_r = usize::from(sig[3]);
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = usize::from(sig[5 + len_r]);
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+
+    // R must be a positive integer without excess padding.
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+
+    // Same for S.
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+/// Checks whether a DER signature (without the sighash byte) has a low S value (BIP-146).
+fn is_low_s(sig: &[u8]) -> bool {
+    match ecdsa::Signature::from_der_lax(sig) {
+        Ok(sig) => {
+            let mut normalized = sig;
+            normalized.normalize_s();
+            normalized == sig
+        }
+        Err(_) => false,
+    }
+}
+
+/// Checks the encoding of a public key according to `flags`.
+fn check_pubkey_encoding(
+    pubkey: &[u8],
+    flags: VerifyFlags,
+    sigversion: SigVersion,
+) -> Result<(), ErrorKind> {
+    let compressed = pubkey.len() == 33 && (pubkey[0] == 0x02 || pubkey[0] == 0x03);
+    let uncompressed = pubkey.len() == 65 && pubkey[0] == 0x04;
+    if flags.has(VerifyFlags::STRICTENC) && !compressed && !uncompressed {
+        return Err(ErrorKind::PubkeyType);
+    }
+    // Only compressed keys are accepted in SegWit.
+    if flags.has(VerifyFlags::WITNESS_PUBKEYTYPE)
+        && sigversion == SigVersion::WitnessV0
+        && !compressed
+    {
+        return Err(ErrorKind::WitnessPubkeyType);
+    }
+    Ok(())
+}
@@ -0,0 +1,622 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Native script interpreter.
+//!
+//! A pure-Rust implementation of Bitcoin Core's script verification (`VerifyScript`) covering
+//! legacy, P2SH, SegWit v0 and Taproot (key path and tapscript) spends. Unlike the
+//! `consensus_validation` module (enabled by the `bitcoinconsensus` feature) this does not depend
+//! on `libbitcoinconsensus` so it is available on all targets, including `no_std`.
+//!
+//! Which rules are enforced is controlled by [`VerifyFlags`], the flag values are the same as
+//! Bitcoin Core's `SCRIPT_VERIFY_*` constants.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bitcoin::{Transaction, TxOut};
+//! # use bitcoin::secp256k1::Secp256k1;
+//! use bitcoin::script::interpreter::{self, VerifyFlags};
+//! use bitcoin::sighash::Prevouts;
+//!
+//! # fn verify(tx: &Transaction, spent: &[TxOut]) -> Result<(), interpreter::Error> {
+//! let secp = Secp256k1::verification_only();
+//! for index in 0..tx.input.len() {
+//!     interpreter::verify_input(&secp, tx, index, &Prevouts::All(spent), VerifyFlags::CONSENSUS)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+mod eval;
+#[cfg(test)]
+mod tests;
+
+use core::{fmt, ops};
+
+use hashes::{sha256, sha256t, HashEngine as _};
+use secp256k1::{Secp256k1, Verification};
+
+use self::eval::{Checker, ExecData, SigVersion};
+use super::witness_version::WitnessVersion;
+use super::{read_scriptbool, Script, ScriptExt as _};
+use crate::consensus::Encodable;
+use crate::crypto::key::XOnlyPublicKey;
+use crate::prelude::{Borrow, Vec};
+use crate::sighash::Prevouts;
+use crate::taproot::{
+    TapLeafHash, TapLeafTag, TapNodeHash, TapTweakHash, TAPROOT_ANNEX_PREFIX,
+    TAPROOT_CONTROL_BASE_SIZE, TAPROOT_CONTROL_MAX_SIZE, TAPROOT_CONTROL_NODE_SIZE,
+    TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT,
+};
+use crate::witness::Witness;
+use crate::{OutPoint, Transaction, TxOut};
+
+#[rustfmt::skip]                // Keep public re-exports separate.
+#[doc(inline)]
+pub use self::error::{Error, ErrorKind, TxVerifyError};
+
+/// Maximum number of bytes of a legacy or SegWit v0 script.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Maximum number of non-push operations per legacy or SegWit v0 script.
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+
+/// Maximum number of public keys per `OP_CHECKMULTISIG`.
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+/// Maximum combined number of elements on the stack and the altstack.
+pub const MAX_STACK_SIZE: usize = 1000;
+
+/// Validation weight consumed by each executed tapscript signature check (BIP-342).
+pub const VALIDATION_WEIGHT_PER_SIGOP_PASSED: i64 = 50;
+
+/// Validation weight granted to each tapscript on top of its witness size (BIP-342).
+pub const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+
+/// Script verification flags.
+///
+/// The values are identical to Bitcoin Core's `SCRIPT_VERIFY_*` flags (and the flags used by
+/// `libbitcoinconsensus`) so they can be converted to and from `u32` freely.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    /// No flags, only the rules of the original script system are enforced.
+    pub const NONE: VerifyFlags = VerifyFlags(0);
+
+    /// Evaluate P2SH subscripts (BIP-16).
+    pub const P2SH: VerifyFlags = VerifyFlags(1 << 0);
+
+    /// Enforce strict signature and public key encoding, and defined sighash types.
+    pub const STRICTENC: VerifyFlags = VerifyFlags(1 << 1);
+
+    /// Enforce strict DER signatures (BIP-66).
+    pub const DERSIG: VerifyFlags = VerifyFlags(1 << 2);
+
+    /// Enforce low S values in signatures (BIP-146).
+    pub const LOW_S: VerifyFlags = VerifyFlags(1 << 3);
+
+    /// Require the dummy `OP_CHECKMULTISIG` argument to be empty (BIP-147).
+    pub const NULLDUMMY: VerifyFlags = VerifyFlags(1 << 4);
+
+    /// Require the script signature to consist only of pushes.
+    pub const SIGPUSHONLY: VerifyFlags = VerifyFlags(1 << 5);
+
+    /// Require minimal encoding of pushes and numbers.
+    pub const MINIMALDATA: VerifyFlags = VerifyFlags(1 << 6);
+
+    /// Fail on executing the reserved `OP_NOPx` opcodes.
+    pub const DISCOURAGE_UPGRADABLE_NOPS: VerifyFlags = VerifyFlags(1 << 7);
+
+    /// Require exactly one stack element after execution.
+    ///
+    /// Only meaningful together with [`Self::P2SH`] and [`Self::WITNESS`].
+    pub const CLEANSTACK: VerifyFlags = VerifyFlags(1 << 8);
+
+    /// Enforce `OP_CHECKLOCKTIMEVERIFY` (BIP-65).
+    pub const CHECKLOCKTIMEVERIFY: VerifyFlags = VerifyFlags(1 << 9);
+
+    /// Enforce `OP_CHECKSEQUENCEVERIFY` (BIP-112).
+    pub const CHECKSEQUENCEVERIFY: VerifyFlags = VerifyFlags(1 << 10);
+
+    /// Evaluate SegWit witness programs (BIP-141).
+    pub const WITNESS: VerifyFlags = VerifyFlags(1 << 11);
+
+    /// Fail on spending witness programs with unknown versions.
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: VerifyFlags = VerifyFlags(1 << 12);
+
+    /// Require minimal `OP_IF`/`OP_NOTIF` arguments in SegWit v0 scripts.
+    pub const MINIMALIF: VerifyFlags = VerifyFlags(1 << 13);
+
+    /// Require failing signature checks to use an empty signature (BIP-146).
+    pub const NULLFAIL: VerifyFlags = VerifyFlags(1 << 14);
+
+    /// Require compressed public keys in SegWit v0 scripts.
+    pub const WITNESS_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 15);
+
+    /// Fail on `OP_CODESEPARATOR` and signatures found in legacy script code.
+    pub const CONST_SCRIPTCODE: VerifyFlags = VerifyFlags(1 << 16);
+
+    /// Evaluate Taproot spends (BIP-341 and BIP-342).
+    pub const TAPROOT: VerifyFlags = VerifyFlags(1 << 17);
+
+    /// Fail on spending tapscript leaves with unknown leaf versions.
+    pub const DISCOURAGE_UPGRADABLE_TAPROOT_VERSION: VerifyFlags = VerifyFlags(1 << 18);
+
+    /// Fail on tapscripts containing `OP_SUCCESSx` opcodes.
+    pub const DISCOURAGE_OP_SUCCESS: VerifyFlags = VerifyFlags(1 << 19);
+
+    /// Fail on tapscript signature checks with unknown public key types.
+    pub const DISCOURAGE_UPGRADABLE_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 20);
+
+    /// All consensus rules that are in force today.
+    ///
+    /// Equivalent to Bitcoin Core's `MANDATORY_SCRIPT_VERIFY_FLAGS` plus Taproot.
+    pub const CONSENSUS: VerifyFlags = VerifyFlags(
+        Self::P2SH.0
+            | Self::DERSIG.0
+            | Self::NULLDUMMY.0
+            | Self::CHECKLOCKTIMEVERIFY.0
+            | Self::CHECKSEQUENCEVERIFY.0
+            | Self::WITNESS.0
+            | Self::TAPROOT.0,
+    );
+
+    /// Consensus rules plus Bitcoin Core's standardness rules (`STANDARD_SCRIPT_VERIFY_FLAGS`).
+    ///
+    /// What is considered standard may change without warning pending Bitcoin Core changes.
+    pub const STANDARD: VerifyFlags = VerifyFlags(
+        Self::CONSENSUS.0
+            | Self::STRICTENC.0
+            | Self::MINIMALDATA.0
+            | Self::DISCOURAGE_UPGRADABLE_NOPS.0
+            | Self::CLEANSTACK.0
+            | Self::MINIMALIF.0
+            | Self::NULLFAIL.0
+            | Self::LOW_S.0
+            | Self::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM.0
+            | Self::WITNESS_PUBKEYTYPE.0
+            | Self::CONST_SCRIPTCODE.0
+            | Self::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION.0
+            | Self::DISCOURAGE_OP_SUCCESS.0
+            | Self::DISCOURAGE_UPGRADABLE_PUBKEYTYPE.0,
+    );
+
+    /// Add [`VerifyFlags`] together.
+    ///
+    /// Returns itself.
+    pub fn add(&mut self, other: VerifyFlags) -> VerifyFlags {
+        self.0 |= other.0;
+        *self
+    }
+
+    /// Remove [`VerifyFlags`] from this.
+    ///
+    /// Returns itself.
+    pub fn remove(&mut self, other: VerifyFlags) -> VerifyFlags {
+        self.0 &= !other.0;
+        *self
+    }
+
+    /// Check whether [`VerifyFlags`] are included in this one.
+    pub fn has(self, flags: VerifyFlags) -> bool { (self.0 | flags.0) == self.0 }
+
+    /// Gets the integer representation of this [`VerifyFlags`].
+    pub fn to_u32(self) -> u32 { self.0 }
+}
+
+impl fmt::LowerHex for VerifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::LowerHex::fmt(&self.0, f) }
+}
+
+impl fmt::Display for VerifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut flags = *self;
+        if flags == VerifyFlags::NONE {
+            return write!(f, "VerifyFlags(NONE)");
+        }
+        let mut first = true;
+        macro_rules! write_flag {
+            ($f:ident) => {
+                if flags.has(VerifyFlags::$f) {
+                    if !first {
+                        write!(f, "|")?;
+                    }
+                    first = false;
+                    write!(f, stringify!($f))?;
+                    flags.remove(VerifyFlags::$f);
+                }
+            };
+        }
+        write!(f, "VerifyFlags(")?;
+        write_flag!(P2SH);
+        write_flag!(STRICTENC);
+        write_flag!(DERSIG);
+        write_flag!(LOW_S);
+        write_flag!(NULLDUMMY);
+        write_flag!(SIGPUSHONLY);
+        write_flag!(MINIMALDATA);
+        write_flag!(DISCOURAGE_UPGRADABLE_NOPS);
+        write_flag!(CLEANSTACK);
+        write_flag!(CHECKLOCKTIMEVERIFY);
+        write_flag!(CHECKSEQUENCEVERIFY);
+        write_flag!(WITNESS);
+        write_flag!(DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM);
+        write_flag!(MINIMALIF);
+        write_flag!(NULLFAIL);
+        write_flag!(WITNESS_PUBKEYTYPE);
+        write_flag!(CONST_SCRIPTCODE);
+        write_flag!(TAPROOT);
+        write_flag!(DISCOURAGE_UPGRADABLE_TAPROOT_VERSION);
+        write_flag!(DISCOURAGE_OP_SUCCESS);
+        write_flag!(DISCOURAGE_UPGRADABLE_PUBKEYTYPE);
+        // If there are unknown flags left, we append them in hex.
+        if flags != VerifyFlags::NONE {
+            if !first {
+                write!(f, "|")?;
+            }
+            write!(f, "0x{:x}", flags)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl From<u32> for VerifyFlags {
+    fn from(f: u32) -> Self { VerifyFlags(f) }
+}
+
+impl From<VerifyFlags> for u32 {
+    fn from(flags: VerifyFlags) -> Self { flags.0 }
+}
+
+impl ops::BitOr for VerifyFlags {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self { self.add(rhs) }
+}
+
+impl ops::BitOrAssign for VerifyFlags {
+    fn bitor_assign(&mut self, rhs: Self) { self.add(rhs); }
+}
+
+impl ops::BitXor for VerifyFlags {
+    type Output = Self;
+
+    fn bitxor(mut self, rhs: Self) -> Self { self.remove(rhs) }
+}
+
+impl ops::BitXorAssign for VerifyFlags {
+    fn bitxor_assign(&mut self, rhs: Self) { self.remove(rhs); }
+}
+
+/// Verifies that input `input_index` of `tx` correctly spends its previous output.
+///
+/// `prevouts` must contain the output spent by `input_index`. Taproot spends that do not use
+/// `SIGHASH_ANYONECANPAY` additionally require all spent outputs ([`Prevouts::All`]).
+///
+/// # Errors
+///
+/// If the input index is out of bounds, the spent output is not available or script execution
+/// fails. The returned [`Error`] names the failing opcode and the stack state where applicable.
+pub fn verify_input<C: Verification, T: Borrow<TxOut>>(
+    secp: &Secp256k1<C>,
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &Prevouts<T>,
+    flags: VerifyFlags,
+) -> Result<(), Error> {
+    let input = tx
+        .input
+        .get(input_index)
+        .ok_or(ErrorKind::InputIndex { index: input_index, length: tx.input.len() })?;
+    let spent = prevouts.get(input_index).map_err(|_| ErrorKind::MissingPrevouts)?;
+    let mut checker = Checker::new(secp, tx, input_index, prevouts, spent.value);
+    verify_script(&input.script_sig, &spent.script_pubkey, &input.witness, flags, &mut checker)
+}
+
+/// Verifies that `tx` is able to spend its inputs.
+///
+/// The `spent` closure should not return the same [`TxOut`] twice!
+pub fn verify_transaction<C, S>(
+    secp: &Secp256k1<C>,
+    tx: &Transaction,
+    mut spent: S,
+    flags: VerifyFlags,
+) -> Result<(), TxVerifyError>
+where
+    C: Verification,
+    S: FnMut(&OutPoint) -> Option<TxOut>,
+{
+    let spent_outputs = tx
+        .input
+        .iter()
+        .map(|input| {
+            spent(&input.previous_output)
+                .ok_or(TxVerifyError::UnknownSpentOutput(input.previous_output))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let prevouts = Prevouts::All(&spent_outputs);
+    for input_index in 0..tx.input.len() {
+        verify_input(secp, tx, input_index, &prevouts, flags)
+            .map_err(|error| TxVerifyError::ScriptVerification { input_index, error })?;
+    }
+    Ok(())
+}
+
+/// Verifies a script signature, script pubkey and witness triple (Core's `VerifyScript`).
+fn verify_script<C: Verification, T: Borrow<TxOut>>(
+    script_sig: &Script,
+    script_pubkey: &Script,
+    witness: &Witness,
+    flags: VerifyFlags,
+    checker: &mut Checker<C, T>,
+) -> Result<(), Error> {
+    if flags.has(VerifyFlags::SIGPUSHONLY) && !script_sig.is_push_only() {
+        return Err(ErrorKind::SigPushOnly.into());
+    }
+
+    let mut stack = Vec::new();
+    let mut execdata = ExecData::default();
+    eval::eval_script(&mut stack, script_sig, flags, checker, SigVersion::Base, &mut execdata)?;
+    let p2sh_stack = if flags.has(VerifyFlags::P2SH) { Some(stack.clone()) } else { None };
+    eval::eval_script(&mut stack, script_pubkey, flags, checker, SigVersion::Base, &mut execdata)?;
+    if !stack.last().map_or(false, |top| read_scriptbool(top)) {
+        return Err(ErrorKind::EvalFalse.into());
+    }
+
+    let mut had_witness = false;
+    if flags.has(VerifyFlags::WITNESS) {
+        if let Some((version, program)) = witness_program(script_pubkey) {
+            had_witness = true;
+            if !script_sig.is_empty() {
+                return Err(ErrorKind::WitnessMalleated.into());
+            }
+            verify_witness_program(witness, version, program, flags, checker, false)?;
+            // Bypass the clean stack check.
+            stack.truncate(1);
+        }
+    }
+
+    if flags.has(VerifyFlags::P2SH) && script_pubkey.is_p2sh() {
+        // scriptSig must be literals-only or validation fails.
+        if !script_sig.is_push_only() {
+            return Err(ErrorKind::SigPushOnly.into());
+        }
+        stack = p2sh_stack.expect("copied above if P2SH is enabled");
+        // The stack can't be empty here, evaluating the P2SH script pubkey would have failed.
+        let serialized = stack.pop().expect("script pubkey consumes an element");
+        let redeem_script = Script::from_bytes(&serialized);
+
+        eval::eval_script(
+            &mut stack,
+            redeem_script,
+            flags,
+            checker,
+            SigVersion::Base,
+            &mut execdata,
+        )?;
+        if !stack.last().map_or(false, |top| read_scriptbool(top)) {
+            return Err(ErrorKind::EvalFalse.into());
+        }
+
+        if flags.has(VerifyFlags::WITNESS) {
+            if let Some((version, program)) = witness_program(redeem_script) {
+                had_witness = true;
+                // The script signature must be exactly a single push of the redeem script.
+                let sig_bytes = script_sig.as_bytes();
+                if sig_bytes.first() != Some(&(serialized.len() as u8))
+                    || &sig_bytes[1..] != serialized.as_slice()
+                {
+                    return Err(ErrorKind::WitnessMalleatedP2sh.into());
+                }
+                verify_witness_program(witness, version, program, flags, checker, true)?;
+                stack.truncate(1);
+            }
+        }
+    }
+
+    if flags.has(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+        return Err(ErrorKind::CleanStack.into());
+    }
+
+    if flags.has(VerifyFlags::WITNESS) && !had_witness && !witness.is_empty() {
+        return Err(ErrorKind::WitnessUnexpected.into());
+    }
+
+    Ok(())
+}
+
+/// Returns the witness version and program if `script` is a witness program.
+fn witness_program(script: &Script) -> Option<(WitnessVersion, &[u8])> {
+    script.witness_version().map(|version| (version, &script.as_bytes()[2..]))
+}
+
+/// Verifies the witness of a SegWit spend (Core's `VerifyWitnessProgram`).
+fn verify_witness_program<C: Verification, T: Borrow<TxOut>>(
+    witness: &Witness,
+    version: WitnessVersion,
+    program: &[u8],
+    flags: VerifyFlags,
+    checker: &mut Checker<C, T>,
+    is_p2sh: bool,
+) -> Result<(), Error> {
+    let mut stack = witness.to_vec();
+
+    match (version, program.len()) {
+        (WitnessVersion::V0, 32) => {
+            let witness_script = stack.pop().ok_or(ErrorKind::WitnessProgramWitnessEmpty)?;
+            if sha256::Hash::hash(&witness_script).as_byte_array() != program {
+                return Err(ErrorKind::WitnessProgramMismatch.into());
+            }
+            let script = Script::from_bytes(&witness_script);
+            execute_witness_script(
+                stack,
+                script,
+                flags,
+                checker,
+                SigVersion::WitnessV0,
+                ExecData::default(),
+            )
+        }
+        (WitnessVersion::V0, 20) => {
+            if stack.len() != 2 {
+                return Err(ErrorKind::WitnessProgramMismatch.into());
+            }
+            let wpkh = program.try_into().expect("checked length above");
+            let script = super::p2wpkh_script_code(crate::key::WPubkeyHash::from_byte_array(wpkh));
+            execute_witness_script(
+                stack,
+                &script,
+                flags,
+                checker,
+                SigVersion::WitnessV0,
+                ExecData::default(),
+            )
+        }
+        (WitnessVersion::V0, _) => Err(ErrorKind::WitnessProgramWrongLength.into()),
+        (WitnessVersion::V1, 32) if !is_p2sh => {
+            if !flags.has(VerifyFlags::TAPROOT) {
+                return Ok(());
+            }
+            if stack.is_empty() {
+                return Err(ErrorKind::WitnessProgramWitnessEmpty.into());
+            }
+            let mut execdata = ExecData::default();
+            if stack.len() >= 2
+                && stack.last().and_then(|last| last.first()) == Some(&TAPROOT_ANNEX_PREFIX)
+            {
+                execdata.annex = stack.pop();
+            }
+
+            if stack.len() == 1 {
+                // Key path spending: the only remaining element is a signature.
+                return checker
+                    .check_schnorr_signature(&stack[0], program, SigVersion::Taproot, &execdata)
+                    .map_err(Error::from);
+            }
+
+            // Script path spending: the last two elements are the control block and the script.
+            let control = stack.pop().expect("at least two elements");
+            let script = stack.pop().expect("at least two elements");
+            if control.len() < TAPROOT_CONTROL_BASE_SIZE
+                || control.len() > TAPROOT_CONTROL_MAX_SIZE
+                || (control.len() - TAPROOT_CONTROL_BASE_SIZE) % TAPROOT_CONTROL_NODE_SIZE != 0
+            {
+                return Err(ErrorKind::TaprootWrongControlSize.into());
+            }
+            let leaf_version = control[0] & TAPROOT_LEAF_MASK;
+            let script = Script::from_bytes(&script);
+            let leaf_hash = tapleaf_hash(leaf_version, script);
+            if !verify_taproot_commitment(checker.secp(), &control, program, leaf_hash) {
+                return Err(ErrorKind::WitnessProgramMismatch.into());
+            }
+            execdata.tapleaf_hash = Some(leaf_hash);
+
+            if leaf_version == TAPROOT_LEAF_TAPSCRIPT {
+                execdata.validation_weight_left = witness.size() as i64 + VALIDATION_WEIGHT_OFFSET;
+                return execute_witness_script(
+                    stack,
+                    script,
+                    flags,
+                    checker,
+                    SigVersion::Tapscript,
+                    execdata,
+                );
+            }
+            if flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION) {
+                return Err(ErrorKind::DiscourageUpgradableTaprootVersion.into());
+            }
+            Ok(())
+        }
+        // Pay-to-anchor outputs are always spendable with an empty witness.
+        (WitnessVersion::V1, 2) if !is_p2sh && program == [0x4e, 0x73] => Ok(()),
+        _ => {
+            if flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM) {
+                return Err(ErrorKind::DiscourageUpgradableWitnessProgram.into());
+            }
+            // Other version/size/P2SH combinations are left for future soft forks.
+            Ok(())
+        }
+    }
+}
+
+/// Executes a witness script or tapscript (Core's `ExecuteWitnessScript`).
+fn execute_witness_script<C: Verification, T: Borrow<TxOut>>(
+    mut stack: Vec<Vec<u8>>,
+    script: &Script,
+    flags: VerifyFlags,
+    checker: &mut Checker<C, T>,
+    sigversion: SigVersion,
+    mut execdata: ExecData,
+) -> Result<(), Error> {
+    if sigversion == SigVersion::Tapscript {
+        // OP_SUCCESSx processing overrides everything, including stack element size limits.
+        for instruction in script.instructions() {
+            let op = match instruction {
+                Ok(instruction) => instruction.opcode(),
+                // Note how this is not reached if an OP_SUCCESSx precedes the malformed push.
+                Err(_) => return Err(ErrorKind::BadOpcode.into()),
+            };
+            if op.map_or(false, eval::is_op_success) {
+                if flags.has(VerifyFlags::DISCOURAGE_OP_SUCCESS) {
+                    return Err(ErrorKind::DiscourageOpSuccess.into());
+                }
+                return Ok(());
+            }
+        }
+        // Tapscript enforces initial stack size limits (altstack is empty here).
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(ErrorKind::StackSize.into());
+        }
+    }
+
+    if stack.iter().any(|item| item.len() > crate::constants::MAX_STACK_ELEMENT_SIZE) {
+        return Err(ErrorKind::PushSize.into());
+    }
+
+    eval::eval_script(&mut stack, script, flags, checker, sigversion, &mut execdata)?;
+
+    // Scripts inside witness implicitly require clean stack behaviour.
+    if stack.len() != 1 {
+        return Err(ErrorKind::CleanStack.into());
+    }
+    if !read_scriptbool(&stack[0]) {
+        return Err(ErrorKind::EvalFalse.into());
+    }
+    Ok(())
+}
+
+/// Computes the leaf hash for an arbitrary (possibly unknown) leaf version.
+fn tapleaf_hash(leaf_version: u8, script: &Script) -> TapLeafHash {
+    let mut eng = sha256t::Hash::<TapLeafTag>::engine();
+    eng.input(&[leaf_version]);
+    script.consensus_encode(&mut eng).expect("engines don't error");
+    let inner = sha256t::Hash::<TapLeafTag>::from_engine(eng);
+    TapLeafHash::from_byte_array(inner.to_byte_array())
+}
+
+/// Checks that `control` proves `leaf_hash` is committed to by the output key `program`.
+fn verify_taproot_commitment<C: Verification>(
+    secp: &Secp256k1<C>,
+    control: &[u8],
+    program: &[u8],
+    leaf_hash: TapLeafHash,
+) -> bool {
+    let parse_key = |bytes: &[u8]| {
+        bytes.try_into().ok().and_then(|bytes| XOnlyPublicKey::from_byte_array(bytes).ok())
+    };
+    let (internal_key, output_key) = match (parse_key(&control[1..33]), parse_key(program)) {
+        (Some(internal_key), Some(output_key)) => (internal_key, output_key),
+        _ => return false,
+    };
+
+    let merkle_root = control[TAPROOT_CONTROL_BASE_SIZE..]
+        .chunks_exact(TAPROOT_CONTROL_NODE_SIZE)
+        .map(|node| TapNodeHash::from_byte_array(node.try_into().expect("chunks are 32 bytes")))
+        .fold(TapNodeHash::from(leaf_hash), TapNodeHash::from_node_hashes);
+    let tweak = TapTweakHash::from_key_and_merkle_root(internal_key, Some(merkle_root));
+    let parity = if control[0] & 1 == 1 { secp256k1::Parity::Odd } else { secp256k1::Parity::Even };
+    internal_key.tweak_add_check(secp, &output_key, parity, tweak.to_scalar())
+}
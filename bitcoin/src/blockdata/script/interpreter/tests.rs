@@ -0,0 +1,691 @@
+// SPDX-License-Identifier: CC0-1.0
+
+use hex::FromHex as _;
+use secp256k1::{Keypair, Message, SecretKey};
+
+use super::*;
+use crate::address::script_pubkey::{BuilderExt as _, ScriptBufExt as _, ScriptExt as _};
+use crate::consensus::encode::deserialize;
+use crate::crypto::key::{PublicKey, TapTweak as _};
+use crate::opcodes::all::*;
+use crate::script::{Builder, PushBytes, ScriptBuf, ScriptBufExt as _};
+use crate::sighash::SighashCache;
+use crate::taproot::{LeafVersion, TaprootBuilder};
+use crate::transaction::{self, TxIn};
+use crate::{absolute, Amount, EcdsaSighashType, Sequence, TapSighashType};
+
+const AMOUNT: Amount = Amount::from_sat_u32(100_000);
+
+/// Returns a deterministic key pair.
+fn key(n: u8) -> (SecretKey, PublicKey) {
+    let secp = Secp256k1::signing_only();
+    let sk = SecretKey::from_byte_array(&[n; 32]).unwrap();
+    (sk, PublicKey::new(sk.public_key(&secp)))
+}
+
+/// Returns a transaction spending an output with `script_pubkey` and the spent output.
+fn spending_tx(script_pubkey: ScriptBuf) -> (Transaction, TxOut) {
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: "2222222222222222222222222222222222222222222222222222222222222222"
+                    .parse()
+                    .unwrap(),
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat_u32(90_000),
+            script_pubkey: ScriptBuf::new(),
+        }],
+    };
+    (tx, TxOut { value: AMOUNT, script_pubkey })
+}
+
+fn verify(tx: &Transaction, spent: &TxOut, flags: VerifyFlags) -> Result<(), Error> {
+    let secp = Secp256k1::verification_only();
+    verify_input(&secp, tx, 0, &Prevouts::All(&[spent]), flags)
+}
+
+/// Executes `script_sig` and `script_pubkey` without a meaningful transaction.
+fn eval(script_sig: ScriptBuf, script_pubkey: ScriptBuf, flags: VerifyFlags) -> Result<(), Error> {
+    let (mut tx, spent) = spending_tx(script_pubkey);
+    tx.input[0].script_sig = script_sig;
+    verify(&tx, &spent, flags)
+}
+
+fn push_bytes(data: &[u8]) -> &PushBytes { data.try_into().unwrap() }
+
+fn sign_ecdsa(sk: &SecretKey, sighash: [u8; 32], sighash_type: EcdsaSighashType) -> Vec<u8> {
+    let secp = Secp256k1::signing_only();
+    let signature = secp.sign_ecdsa(&Message::from_digest(sighash), sk);
+    crate::ecdsa::Signature { signature, sighash_type }.to_vec()
+}
+
+#[test]
+fn arithmetic() {
+    let script_sig = Builder::new().push_int_unchecked(2).push_int_unchecked(3).into_script();
+    let script_pubkey = Builder::new()
+        .push_opcode(OP_ADD)
+        .push_opcode(OP_DUP)
+        .push_int_unchecked(5)
+        .push_opcode(OP_EQUALVERIFY)
+        .push_int_unchecked(1)
+        .push_opcode(OP_SUB)
+        .push_int_unchecked(4)
+        .push_opcode(OP_NUMEQUAL)
+        .into_script();
+    assert!(eval(script_sig, script_pubkey, VerifyFlags::STANDARD).is_ok());
+}
+
+#[test]
+fn error_reports_opcode_and_stack() {
+    let script_sig = Builder::new().push_int_unchecked(2).push_int_unchecked(3).into_script();
+    let script_pubkey = Builder::new()
+        .push_opcode(OP_ADD)
+        .push_int_unchecked(6)
+        .push_opcode(OP_EQUALVERIFY)
+        .into_script();
+
+    let err = eval(script_sig, script_pubkey, VerifyFlags::NONE).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EqualVerify);
+    assert_eq!(err.opcode(), Some(OP_EQUALVERIFY));
+    assert_eq!(err.position(), Some(2));
+    assert_eq!(err.stack(), &[Vec::<u8>::new()]);
+}
+
+#[test]
+fn eval_false() {
+    let script_sig = Builder::new().push_int_unchecked(0).into_script();
+    let err = eval(script_sig, ScriptBuf::new(), VerifyFlags::NONE).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EvalFalse);
+    assert_eq!(err.opcode(), None);
+}
+
+#[test]
+fn conditionals() {
+    let script_pubkey = Builder::new()
+        .push_opcode(OP_IF)
+        .push_int_unchecked(2)
+        .push_opcode(OP_ELSE)
+        .push_int_unchecked(3)
+        .push_opcode(OP_ENDIF)
+        .push_int_unchecked(3)
+        .push_opcode(OP_EQUAL)
+        .into_script();
+    let script_sig = Builder::new().push_int_unchecked(0).into_script();
+    assert!(eval(script_sig, script_pubkey.clone(), VerifyFlags::NONE).is_ok());
+    let script_sig = Builder::new().push_int_unchecked(1).into_script();
+    assert_eq!(
+        eval(script_sig, script_pubkey, VerifyFlags::NONE).unwrap_err().kind(),
+        ErrorKind::EvalFalse
+    );
+
+    let unbalanced = Builder::new().push_int_unchecked(1).push_opcode(OP_IF).into_script();
+    assert_eq!(
+        eval(ScriptBuf::new(), unbalanced, VerifyFlags::NONE).unwrap_err().kind(),
+        ErrorKind::UnbalancedConditional
+    );
+}
+
+#[test]
+fn disabled_opcode_in_unexecuted_branch() {
+    let script_pubkey = Builder::new()
+        .push_int_unchecked(0)
+        .push_opcode(OP_IF)
+        .push_opcode(OP_CAT)
+        .push_opcode(OP_ENDIF)
+        .push_int_unchecked(1)
+        .into_script();
+    let err = eval(ScriptBuf::new(), script_pubkey, VerifyFlags::NONE).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DisabledOpcode);
+    assert_eq!(err.opcode(), Some(OP_CAT));
+}
+
+#[test]
+fn minimal_data() {
+    // Pushing 1 with OP_PUSHBYTES_1 instead of OP_PUSHNUM_1.
+    let script_sig = ScriptBuf::from_bytes(vec![0x01, 0x01]);
+    assert!(eval(script_sig.clone(), ScriptBuf::new(), VerifyFlags::NONE).is_ok());
+    let err = eval(script_sig, ScriptBuf::new(), VerifyFlags::MINIMALDATA).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MinimalData);
+    assert_eq!(err.opcode(), Some(OP_PUSHBYTES_1));
+}
+
+#[test]
+fn flags() {
+    assert!(VerifyFlags::STANDARD.has(VerifyFlags::CONSENSUS));
+    assert!(!VerifyFlags::CONSENSUS.has(VerifyFlags::STANDARD));
+    assert_eq!(u32::from(VerifyFlags::P2SH | VerifyFlags::WITNESS), (1 << 0) | (1 << 11));
+    assert_eq!(VerifyFlags::from(1 << 17), VerifyFlags::TAPROOT);
+    assert_eq!(VerifyFlags::NONE.to_string(), "VerifyFlags(NONE)");
+    assert_eq!(
+        (VerifyFlags::P2SH | VerifyFlags::from(1 << 30)).to_string(),
+        "VerifyFlags(P2SH|0x40000000)"
+    );
+}
+
+#[test]
+fn p2pkh() {
+    let (sk, pk) = key(1);
+    let (mut tx, spent) = spending_tx(ScriptBuf::new_p2pkh(pk.pubkey_hash()));
+
+    let sighash = SighashCache::new(&tx)
+        .legacy_signature_hash(0, &spent.script_pubkey, EcdsaSighashType::All.to_u32())
+        .unwrap();
+    let sig = sign_ecdsa(&sk, sighash.to_byte_array(), EcdsaSighashType::All);
+    tx.input[0].script_sig = Builder::new().push_slice(push_bytes(&sig)).push_key(pk).into_script();
+    assert!(verify(&tx, &spent, VerifyFlags::STANDARD).is_ok());
+
+    // A different key fails the hash check.
+    let (_, other) = key(2);
+    let (mut tx, spent) = spending_tx(ScriptBuf::new_p2pkh(other.pubkey_hash()));
+    tx.input[0].script_sig = Builder::new().push_slice(push_bytes(&sig)).push_key(pk).into_script();
+    let err = verify(&tx, &spent, VerifyFlags::STANDARD).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EqualVerify);
+}
+
+#[test]
+fn p2pkh_invalid_signature() {
+    let (sk, pk) = key(1);
+    let (mut tx, spent) = spending_tx(ScriptBuf::new_p2pkh(pk.pubkey_hash()));
+    let sig = sign_ecdsa(&sk, [0xab; 32], EcdsaSighashType::All);
+    tx.input[0].script_sig = Builder::new().push_slice(push_bytes(&sig)).push_key(pk).into_script();
+
+    let err = verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EvalFalse);
+    let err = verify(&tx, &spent, VerifyFlags::STANDARD).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SigNullFail);
+    assert_eq!(err.opcode(), Some(OP_CHECKSIG));
+}
+
+#[test]
+fn p2wpkh() {
+    let (sk, pk) = key(3);
+    let wpkh = pk.wpubkey_hash().unwrap();
+    let (mut tx, spent) = spending_tx(ScriptBuf::new_p2wpkh(wpkh));
+
+    let sighash = SighashCache::new(&tx)
+        .p2wpkh_signature_hash(0, &spent.script_pubkey, AMOUNT, EcdsaSighashType::All)
+        .unwrap();
+    let sig = sign_ecdsa(&sk, sighash.to_byte_array(), EcdsaSighashType::All);
+    tx.input[0].witness = Witness::from_slice(&[sig.clone(), pk.to_vec()]);
+    assert!(verify(&tx, &spent, VerifyFlags::STANDARD).is_ok());
+
+    // Witness is ignored without the witness flag, and unexpected with it for non-SegWit outputs.
+    let (mut tx, spent) = spending_tx(Builder::new().push_int_unchecked(1).into_script());
+    tx.input[0].witness = Witness::from_slice(&[sig, pk.to_vec()]);
+    assert!(verify(&tx, &spent, VerifyFlags::P2SH).is_ok());
+    let err = verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WitnessUnexpected);
+}
+
+#[test]
+fn p2wsh_uncompressed_key() {
+    let secp = Secp256k1::signing_only();
+    let (sk, _) = key(4);
+    let pk = PublicKey::new_uncompressed(sk.public_key(&secp));
+    let witness_script = Builder::new().push_key(pk).push_opcode(OP_CHECKSIG).into_script();
+    let (mut tx, spent) = spending_tx(witness_script.to_p2wsh().unwrap());
+
+    let sighash = SighashCache::new(&tx)
+        .p2wsh_signature_hash(0, &witness_script, AMOUNT, EcdsaSighashType::All)
+        .unwrap();
+    let sig = sign_ecdsa(&sk, sighash.to_byte_array(), EcdsaSighashType::All);
+    tx.input[0].witness = Witness::from_slice(&[sig, witness_script.to_vec()]);
+
+    assert!(verify(&tx, &spent, VerifyFlags::CONSENSUS).is_ok());
+    let err = verify(&tx, &spent, VerifyFlags::STANDARD).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WitnessPubkeyType);
+}
+
+#[test]
+fn p2sh_multisig() {
+    let keys = [key(5), key(6), key(7)];
+    let redeem_script = Builder::new()
+        .push_int_unchecked(2)
+        .push_key(keys[0].1)
+        .push_key(keys[1].1)
+        .push_key(keys[2].1)
+        .push_int_unchecked(3)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script();
+    let (mut tx, spent) = spending_tx(redeem_script.to_p2sh().unwrap());
+
+    let sighash = SighashCache::new(&tx)
+        .legacy_signature_hash(0, &redeem_script, EcdsaSighashType::All.to_u32())
+        .unwrap();
+    let sig0 = sign_ecdsa(&keys[0].0, sighash.to_byte_array(), EcdsaSighashType::All);
+    let sig2 = sign_ecdsa(&keys[2].0, sighash.to_byte_array(), EcdsaSighashType::All);
+    let script_sig = |dummy: i64| {
+        Builder::new()
+            .push_int_unchecked(dummy)
+            .push_slice(push_bytes(&sig0))
+            .push_slice(push_bytes(&sig2))
+            .push_slice(push_bytes(redeem_script.as_bytes()))
+            .into_script()
+    };
+
+    tx.input[0].script_sig = script_sig(0);
+    assert!(verify(&tx, &spent, VerifyFlags::STANDARD).is_ok());
+
+    // Without P2SH only the script hash is checked.
+    let mut tx_without_sigs = tx.clone();
+    tx_without_sigs.input[0].script_sig =
+        Builder::new().push_slice(push_bytes(redeem_script.as_bytes())).into_script();
+    assert!(verify(&tx_without_sigs, &spent, VerifyFlags::NONE).is_ok());
+    assert_eq!(
+        verify(&tx_without_sigs, &spent, VerifyFlags::P2SH).unwrap_err().kind(),
+        ErrorKind::InvalidStackOperation
+    );
+
+    tx.input[0].script_sig = script_sig(1);
+    assert!(verify(&tx, &spent, VerifyFlags::P2SH).is_ok());
+    let err = verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SigNullDummy);
+    assert_eq!(err.opcode(), Some(OP_CHECKMULTISIG));
+}
+
+#[test]
+fn lock_time() {
+    let script_pubkey = Builder::new()
+        .push_int_unchecked(500)
+        .push_opcode(OP_CLTV)
+        .push_opcode(OP_DROP)
+        .push_int_unchecked(1)
+        .into_script();
+    let (mut tx, spent) = spending_tx(script_pubkey);
+
+    // Treated as a NOP without the flag.
+    assert!(verify(&tx, &spent, VerifyFlags::P2SH).is_ok());
+    assert_eq!(
+        verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err().kind(),
+        ErrorKind::UnsatisfiedLockTime
+    );
+
+    tx.lock_time = absolute::LockTime::from_consensus(500);
+    // The lock time of the transaction isn't enforced if the input is final.
+    assert_eq!(
+        verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err().kind(),
+        ErrorKind::UnsatisfiedLockTime
+    );
+    tx.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+    assert!(verify(&tx, &spent, VerifyFlags::CONSENSUS).is_ok());
+}
+
+#[test]
+fn relative_lock_time() {
+    let script_pubkey = Builder::new()
+        .push_int_unchecked(10)
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_DROP)
+        .push_int_unchecked(1)
+        .into_script();
+    let (mut tx, spent) = spending_tx(script_pubkey);
+
+    tx.input[0].sequence = Sequence::from_height(9);
+    assert_eq!(
+        verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err().kind(),
+        ErrorKind::UnsatisfiedLockTime
+    );
+    tx.input[0].sequence = Sequence::from_height(10);
+    assert!(verify(&tx, &spent, VerifyFlags::CONSENSUS).is_ok());
+    tx.version = transaction::Version::ONE;
+    assert_eq!(
+        verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err().kind(),
+        ErrorKind::UnsatisfiedLockTime
+    );
+}
+
+#[test]
+fn taproot_key_path_bip341() {
+    let data: serde_json::Value =
+        serde_json::from_str(include_str!("../../../../tests/data/bip341_tests.json")).unwrap();
+    let key_path = &data["keyPathSpending"][0];
+
+    let tx: Transaction = deserialize(
+        &Vec::<u8>::from_hex(key_path["auxiliary"]["fullySignedTx"].as_str().unwrap()).unwrap(),
+    )
+    .unwrap();
+    let spent = key_path["given"]["utxosSpent"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|utxo| TxOut {
+            value: Amount::from_sat(utxo["amountSats"].as_u64().unwrap()).unwrap(),
+            script_pubkey: ScriptBuf::from_bytes(
+                Vec::from_hex(utxo["scriptPubKey"].as_str().unwrap()).unwrap(),
+            ),
+        })
+        .collect::<Vec<_>>();
+
+    let secp = Secp256k1::verification_only();
+    for input in key_path["inputSpending"].as_array().unwrap() {
+        let index = input["given"]["txinIndex"].as_u64().unwrap() as usize;
+        verify_input(&secp, &tx, index, &Prevouts::All(&spent), VerifyFlags::STANDARD).unwrap();
+
+        // Signatures commit to all spent outputs unless ANYONECANPAY is used.
+        let err = verify_input(
+            &secp,
+            &tx,
+            index,
+            &Prevouts::One(index, &spent[index]),
+            VerifyFlags::STANDARD,
+        );
+        let hash_type = input["given"]["hashType"].as_u64().unwrap();
+        assert_eq!(err.is_ok(), hash_type & 0x80 == 0x80);
+    }
+
+    let length = tx.input.len();
+    let err = verify_input(&secp, &tx, length, &Prevouts::All(&spent), VerifyFlags::STANDARD);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::InputIndex { index: length, length });
+}
+
+#[test]
+fn tapscript() {
+    let secp = Secp256k1::new();
+    let (internal_sk, _) = key(8);
+    let (leaf_sk, _) = key(9);
+    let internal_key = Keypair::from_secret_key(&secp, &internal_sk).x_only_public_key().0;
+    let leaf_keypair = Keypair::from_secret_key(&secp, &leaf_sk);
+    let leaf_key = XOnlyPublicKey::from(leaf_keypair.x_only_public_key().0);
+
+    let leaf_script =
+        Builder::new().push_x_only_key(leaf_key).push_opcode(OP_CHECKSIG).into_script();
+    let multisig_script = Builder::new()
+        .push_int_unchecked(1)
+        .push_x_only_key(leaf_key)
+        .push_int_unchecked(1)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script();
+    let spend_info = TaprootBuilder::new()
+        .add_leaf(1, leaf_script.clone())
+        .unwrap()
+        .add_leaf(1, multisig_script.clone())
+        .unwrap()
+        .finalize(&secp, XOnlyPublicKey::from(internal_key))
+        .unwrap();
+    let script_pubkey = ScriptBuf::new_p2tr_tweaked(spend_info.output_key());
+    let (mut tx, spent) = spending_tx(script_pubkey);
+
+    let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+    let sighash = SighashCache::new(&tx)
+        .taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[&spent]),
+            leaf_hash,
+            TapSighashType::Default,
+        )
+        .unwrap();
+    let sig = secp.sign_schnorr_no_aux_rand(sighash.as_byte_array(), &leaf_keypair);
+    let control_block =
+        spend_info.control_block(&(leaf_script.clone(), LeafVersion::TapScript)).unwrap();
+    tx.input[0].witness = Witness::from_slice(&[
+        sig.as_ref().to_vec(),
+        leaf_script.to_vec(),
+        control_block.serialize(),
+    ]);
+    assert!(verify(&tx, &spent, VerifyFlags::STANDARD).is_ok());
+
+    // Taproot outputs are anyone-can-spend before activation.
+    let mut witness = tx.input[0].witness.to_vec();
+    witness[0][0] ^= 1;
+    tx.input[0].witness = Witness::from_slice(&witness);
+    assert!(verify(&tx, &spent, VerifyFlags::P2SH | VerifyFlags::WITNESS).is_ok());
+    let err = verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SchnorrSig);
+    assert_eq!(err.opcode(), Some(OP_CHECKSIG));
+
+    // OP_CHECKMULTISIG is not available in tapscript.
+    let control_block =
+        spend_info.control_block(&(multisig_script.clone(), LeafVersion::TapScript)).unwrap();
+    tx.input[0].witness =
+        Witness::from_slice(&[vec![], multisig_script.to_vec(), control_block.serialize()]);
+    let err = verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TapscriptCheckMultisig);
+
+    // Wrong merkle path.
+    let mut control_block = control_block.serialize();
+    control_block[40] ^= 1;
+    tx.input[0].witness = Witness::from_slice(&[vec![], multisig_script.to_vec(), control_block]);
+    let err = verify(&tx, &spent, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WitnessProgramMismatch);
+}
+
+#[test]
+fn taproot_key_path_tweaked() {
+    let secp = Secp256k1::new();
+    let (sk, _) = key(10);
+    let keypair = Keypair::from_secret_key(&secp, &sk);
+    let tweaked = keypair.tap_tweak(&secp, None);
+    let script_pubkey = ScriptBuf::new_p2tr_tweaked(tweaked.public_parts().0);
+    let (mut tx, spent) = spending_tx(script_pubkey);
+
+    let sighash = SighashCache::new(&tx)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(&[&spent]), TapSighashType::All)
+        .unwrap();
+    let sig = secp.sign_schnorr_no_aux_rand(sighash.as_byte_array(), &tweaked.to_keypair());
+    let mut sig = sig.as_ref().to_vec();
+    sig.push(TapSighashType::All as u8);
+    tx.input[0].witness = Witness::from_slice(&[sig.clone()]);
+    assert!(verify(&tx, &spent, VerifyFlags::STANDARD).is_ok());
+
+    // The annex is committed to by the signature.
+    tx.input[0].witness = Witness::from_slice(&[sig.clone(), vec![TAPROOT_ANNEX_PREFIX]]);
+    assert_eq!(
+        verify(&tx, &spent, VerifyFlags::STANDARD).unwrap_err().kind(),
+        ErrorKind::SchnorrSig
+    );
+
+    // The explicit default sighash type is invalid.
+    sig[64] = 0x00;
+    tx.input[0].witness = Witness::from_slice(&[sig]);
+    assert_eq!(
+        verify(&tx, &spent, VerifyFlags::STANDARD).unwrap_err().kind(),
+        ErrorKind::SchnorrSigHashType
+    );
+}
+
+#[test]
+fn unknown_spent_output() {
+    let (tx, _) = spending_tx(ScriptBuf::new());
+    let secp = Secp256k1::verification_only();
+    let err = verify_transaction(&secp, &tx, |_| None, VerifyFlags::CONSENSUS).unwrap_err();
+    assert_eq!(err, TxVerifyError::UnknownSpentOutput(tx.input[0].previous_output));
+}
+
+/// Parses a comma separated list of flag names as used in Bitcoin Core's test vectors.
+fn core_flags(names: &str) -> VerifyFlags {
+    names.split(',').filter(|name| !name.is_empty()).fold(VerifyFlags::NONE, |flags, name| {
+        flags
+            | match name {
+                "NONE" => VerifyFlags::NONE,
+                "P2SH" => VerifyFlags::P2SH,
+                "STRICTENC" => VerifyFlags::STRICTENC,
+                "DERSIG" => VerifyFlags::DERSIG,
+                "LOW_S" => VerifyFlags::LOW_S,
+                "NULLDUMMY" => VerifyFlags::NULLDUMMY,
+                "SIGPUSHONLY" => VerifyFlags::SIGPUSHONLY,
+                "MINIMALDATA" => VerifyFlags::MINIMALDATA,
+                "DISCOURAGE_UPGRADABLE_NOPS" => VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS,
+                "CLEANSTACK" => VerifyFlags::CLEANSTACK,
+                "CHECKLOCKTIMEVERIFY" => VerifyFlags::CHECKLOCKTIMEVERIFY,
+                "CHECKSEQUENCEVERIFY" => VerifyFlags::CHECKSEQUENCEVERIFY,
+                "WITNESS" => VerifyFlags::WITNESS,
+                "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM" =>
+                    VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+                "MINIMALIF" => VerifyFlags::MINIMALIF,
+                "NULLFAIL" => VerifyFlags::NULLFAIL,
+                "WITNESS_PUBKEYTYPE" => VerifyFlags::WITNESS_PUBKEYTYPE,
+                "CONST_SCRIPTCODE" => VerifyFlags::CONST_SCRIPTCODE,
+                "TAPROOT" => VerifyFlags::TAPROOT,
+                "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION" =>
+                    VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION,
+                "DISCOURAGE_OP_SUCCESS" => VerifyFlags::DISCOURAGE_OP_SUCCESS,
+                "DISCOURAGE_UPGRADABLE_PUBKEYTYPE" => VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE,
+                _ => panic!("unknown flag {}", name),
+            }
+    })
+}
+
+/// Returns the name Bitcoin Core's test vectors use for `kind`.
+fn core_error_name(kind: ErrorKind) -> &'static str {
+    use ErrorKind::*;
+
+    match kind {
+        EvalFalse => "EVAL_FALSE",
+        OpReturn => "OP_RETURN",
+        ScriptSize => "SCRIPT_SIZE",
+        PushSize => "PUSH_SIZE",
+        OpCount => "OP_COUNT",
+        StackSize => "STACK_SIZE",
+        SigCount => "SIG_COUNT",
+        PubkeyCount => "PUBKEY_COUNT",
+        Verify => "VERIFY",
+        EqualVerify => "EQUALVERIFY",
+        CheckMultisigVerify => "CHECKMULTISIGVERIFY",
+        CheckSigVerify => "CHECKSIGVERIFY",
+        NumEqualVerify => "NUMEQUALVERIFY",
+        BadOpcode => "BAD_OPCODE",
+        DisabledOpcode => "DISABLED_OPCODE",
+        InvalidStackOperation => "INVALID_STACK_OPERATION",
+        InvalidAltstackOperation => "INVALID_ALTSTACK_OPERATION",
+        UnbalancedConditional => "UNBALANCED_CONDITIONAL",
+        NegativeLockTime => "NEGATIVE_LOCKTIME",
+        UnsatisfiedLockTime => "UNSATISFIED_LOCKTIME",
+        SigHashType => "SIG_HASHTYPE",
+        SigDer => "SIG_DER",
+        MinimalData => "MINIMALDATA",
+        SigPushOnly => "SIG_PUSHONLY",
+        SigHighS => "SIG_HIGH_S",
+        SigNullDummy => "SIG_NULLDUMMY",
+        PubkeyType => "PUBKEYTYPE",
+        CleanStack => "CLEANSTACK",
+        MinimalIf => "MINIMALIF",
+        SigNullFail => "NULLFAIL",
+        DiscourageUpgradableNops => "DISCOURAGE_UPGRADABLE_NOPS",
+        DiscourageUpgradableWitnessProgram => "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+        DiscourageUpgradableTaprootVersion => "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION",
+        DiscourageOpSuccess => "DISCOURAGE_OP_SUCCESS",
+        DiscourageUpgradablePubkeyType => "DISCOURAGE_UPGRADABLE_PUBKEYTYPE",
+        WitnessProgramWrongLength => "WITNESS_PROGRAM_WRONG_LENGTH",
+        WitnessProgramWitnessEmpty => "WITNESS_PROGRAM_WITNESS_EMPTY",
+        WitnessProgramMismatch => "WITNESS_PROGRAM_MISMATCH",
+        WitnessMalleated => "WITNESS_MALLEATED",
+        WitnessMalleatedP2sh => "WITNESS_MALLEATED_P2SH",
+        WitnessUnexpected => "WITNESS_UNEXPECTED",
+        WitnessPubkeyType => "WITNESS_PUBKEYTYPE",
+        SchnorrSigSize => "SCHNORR_SIG_SIZE",
+        SchnorrSigHashType => "SCHNORR_SIG_HASHTYPE",
+        SchnorrSig => "SCHNORR_SIG",
+        TaprootWrongControlSize => "TAPROOT_WRONG_CONTROL_SIZE",
+        TapscriptValidationWeight => "TAPSCRIPT_VALIDATION_WEIGHT",
+        TapscriptCheckMultisig => "TAPSCRIPT_CHECKMULTISIG",
+        TapscriptMinimalIf => "TAPSCRIPT_MINIMALIF",
+        OpCodeSeparator => "OP_CODESEPARATOR",
+        SigFindAndDelete => "SIG_FINDANDDELETE",
+        // Core throws `scriptnum_error` which ends up as `SCRIPT_ERR_UNKNOWN_ERROR`.
+        NumericOverflow | NonMinimalNumber => "UNKNOWN_ERROR",
+        MissingPrevouts => "MISSING_PREVOUTS",
+        InputIndex { .. } => "INPUT_INDEX",
+    }
+}
+
+/// Runs Bitcoin Core's `script_tests.json`, parsing the scripts with `ScriptBuf::from_asm`.
+#[test]
+fn core_script_tests() {
+    let secp = Secp256k1::new();
+    // Internal key used by Core for `#TAPROOTOUTPUT#`.
+    let nums: XOnlyPublicKey =
+        "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0".parse().unwrap();
+    let tests: serde_json::Value =
+        serde_json::from_str(include_str!("../../../../tests/data/script_tests.json")).unwrap();
+
+    let mut ran = 0;
+    for test in tests.as_array().unwrap() {
+        let mut test = test.as_array().unwrap().as_slice();
+        if test.len() == 1 {
+            continue; // Comment.
+        }
+        let parse = |asm: &serde_json::Value| ScriptBuf::from_asm(asm.as_str().unwrap()).unwrap();
+
+        let mut witness = Witness::new();
+        let mut amount = Amount::ZERO;
+        let mut tapscript = None;
+        if let Some(items) = test[0].as_array() {
+            let (last, items) = items.split_last().unwrap();
+            amount = Amount::from_sat((last.as_f64().unwrap() * 1e8).round() as u64).unwrap();
+            for item in items {
+                let item = item.as_str().unwrap();
+                if let Some(asm) = item.strip_prefix("#SCRIPT#") {
+                    let script = ScriptBuf::from_asm(asm).unwrap();
+                    witness.push(script.as_bytes());
+                    tapscript = Some(script);
+                } else if item == "#CONTROLBLOCK#" {
+                    let script = tapscript.clone().unwrap();
+                    let info = TaprootBuilder::new()
+                        .add_leaf(0, script.clone())
+                        .unwrap()
+                        .finalize(&secp, nums)
+                        .unwrap();
+                    let control_block =
+                        info.control_block(&(script, LeafVersion::TapScript)).unwrap();
+                    witness.push(control_block.serialize());
+                } else {
+                    witness.push(Vec::from_hex(item).unwrap());
+                }
+            }
+            test = &test[1..];
+        }
+
+        let script_sig = parse(&test[0]);
+        let script_pubkey = match test[1].as_str().unwrap().strip_suffix(" #TAPROOTOUTPUT#") {
+            Some(_) => {
+                let info = TaprootBuilder::new()
+                    .add_leaf(0, tapscript.clone().unwrap())
+                    .unwrap()
+                    .finalize(&secp, nums)
+                    .unwrap();
+                ScriptBuf::new_p2tr_tweaked(info.output_key())
+            }
+            None => parse(&test[1]),
+        };
+        let flags = core_flags(test[2].as_str().unwrap());
+        let expected = test[3].as_str().unwrap();
+
+        // Core evaluates the scripts as if there was a crediting coinbase transaction.
+        let credit = Transaction {
+            version: transaction::Version::ONE,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::COINBASE_PREVOUT,
+                script_sig: ScriptBuf::from_bytes(vec![0, 0]),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut { value: amount, script_pubkey }],
+        };
+        let spend = Transaction {
+            version: transaction::Version::ONE,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: credit.compute_txid(), vout: 0 },
+                script_sig,
+                sequence: Sequence::MAX,
+                witness,
+            }],
+            output: vec![TxOut { value: amount, script_pubkey: ScriptBuf::new() }],
+        };
+
+        let result = verify_input(&secp, &spend, 0, &Prevouts::All(&credit.output), flags);
+        let got = result.as_ref().map_or_else(|e| core_error_name(e.kind()), |()| "OK");
+        assert_eq!(got, expected, "{:?}: {:?}", test, result);
+        ran += 1;
+    }
+    assert_eq!(ran, 1212);
+}
@@ -47,9 +47,11 @@
 //! At the time of writing there's only one operation using the cache - `push_verify`, so the cache
 //! is minimal but we may extend it in the future if needed.
 
+mod asm;
 mod borrowed;
 mod builder;
 mod instruction;
+pub mod interpreter;
 mod owned;
 mod push_bytes;
 #[cfg(test)]
@@ -73,6 +75,7 @@
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
+    asm::{ParseAsmError, ParseAsmErrorKind},
     borrowed::ScriptExt,
     builder::Builder,
     instruction::{Instruction, Instructions, InstructionIndices},
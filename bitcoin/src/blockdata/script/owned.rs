@@ -6,7 +6,9 @@
 use hex::FromHex as _;
 use internals::ToU64 as _;
 
-use super::{opcode_to_verify, Builder, Instruction, PushBytes, ScriptExtPriv as _};
+use super::{
+    asm, opcode_to_verify, Builder, Instruction, ParseAsmError, PushBytes, ScriptExtPriv as _,
+};
 use crate::consensus;
 use crate::opcodes::all::*;
 use crate::opcodes::{self, Opcode};
@@ -49,6 +51,21 @@ fn from_hex_no_length_prefix(s: &str) -> Result<ScriptBuf, hex::HexToBytesError>
             Ok(ScriptBuf::from_bytes(v))
         }
 
+        /// Constructs a new [`ScriptBuf`] from its assembly (ASM) representation.
+        ///
+        /// Accepts the output of [`fmt_asm`] and `Display` as well as the dialect used by Bitcoin
+        /// Core's `script_tests.json` (opcodes without the `OP_` prefix, decimal numbers, raw `0x`
+        /// hex and `'quoted'` strings). Hex data that does not follow a push opcode must be
+        /// `0x`-prefixed, bare digits are always decimal numbers. Parsing the ASM of any script
+        /// that contains no truncated push returns the original script byte for byte.
+        ///
+        /// # Errors
+        ///
+        /// If a token can not be parsed, the error reports its byte offset in `s`.
+        ///
+        /// [`fmt_asm`]: super::ScriptExt::fmt_asm
+        fn from_asm(s: &str) -> Result<ScriptBuf, ParseAsmError> { asm::parse(s) }
+
         /// Adds a single opcode to the script.
         fn push_opcode(&mut self, data: Opcode) { self.as_byte_vec().push(data.to_u8()); }
 
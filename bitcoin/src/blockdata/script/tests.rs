@@ -561,6 +561,132 @@ fn script_asm() {
     );
 }
 
+#[test]
+fn script_from_asm_round_trip() {
+    let scripts = [
+        "",
+        "6363636363686868686800",
+        "76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac",
+        "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+        // non-minimal pushes
+        "0105", "4c0105", "4d0100ff", "4e01000000ff",
+        // `OP_PUSHDATA` without payload, at the end and followed by another opcode
+        "4c00", "4d0000", "4e00000000", "4c0051",
+        // OP_RETURN_187, OP_RETURN_254, OP_INVALIDOPCODE
+        "bbfeff",
+        "0047304402202457e78cc1b7f50d0543863c27de75d07982bde8359b9e3316adec0aec165f2f02200203fd331c4e4a4a02f48cf1c291e2c0d6b2f7078a784b5b3649fca41f8794d401004cf1552103244e602b46755f24327142a0517288cebd159eccb6ccf41ea6edf1f601e9af952103bbbacc302d19d29dbfa62d23f37944ae19853cf260c745c2bea739c95328fcb721039227e83246bd51140fe93538b2301c9048be82ef2fb3c7fc5d78426ed6f609ad210229bf310c379b90033e2ecb07f77ecf9b8d59acb623ab7be25a0caed539e2e6472103703e2ed676936f10b3ce9149fa2d4a32060fb86fa9a70a4efe3f21d7ab90611921031e9b7c6022400a6bb0424bbcde14cff6c016b91ee3803926f3440abf5c146d05210334667f975f55a8455d515a2ef1c94fdfa3315f12319a14515d2a13d82831f62f57ae",
+    ];
+    for hex in scripts {
+        let script = ScriptBuf::from_hex_no_length_prefix(hex).unwrap();
+        assert_eq!(ScriptBuf::from_asm(&script.to_string()).unwrap(), script, "{}", script);
+    }
+
+    // Every opcode, with a payload where it takes one.
+    for byte in 0..=u8::MAX {
+        let mut bytes = vec![byte];
+        match Opcode::from(byte) {
+            OP_PUSHDATA1 => bytes.extend([0x02, 0xab, 0xcd]),
+            OP_PUSHDATA2 => bytes.extend([0x02, 0x00, 0xab, 0xcd]),
+            OP_PUSHDATA4 => bytes.extend([0x02, 0x00, 0x00, 0x00, 0xab, 0xcd]),
+            _ if byte < OP_PUSHDATA1.to_u8() => bytes.extend((0..byte).map(|i| i ^ 0x5a)),
+            _ => {}
+        }
+        let script = ScriptBuf::from_bytes(bytes);
+        assert_eq!(ScriptBuf::from_asm(&script.to_string()).unwrap(), script, "{}", script);
+    }
+
+    let data = PushBytesBuf::try_from(vec![0x2a; 300]).unwrap();
+    let script = Builder::new().push_slice(data).push_opcode(OP_DROP).into_script();
+    assert_eq!(ScriptBuf::from_asm(&script.to_string()).unwrap(), script);
+}
+
+#[test]
+fn script_from_asm_hex() {
+    let script = ScriptBuf::from_asm(
+        "OP_DUP OP_HASH160 OP_PUSHBYTES_20 0x162c5ea71c0b23f5b9022ef047c4a86470a5b070 \
+         OP_EQUALVERIFY 0xac",
+    )
+    .unwrap();
+    assert_eq!(
+        script.to_hex_string_no_length_prefix(),
+        "76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac"
+    );
+
+    // Bare digits are always numbers, bare hex is not data.
+    assert_eq!(ScriptBuf::from_asm("05").unwrap().as_bytes(), [0x55]);
+    assert_eq!(ScriptBuf::from_asm("1234").unwrap().as_bytes(), [0x02, 0xd2, 0x04]);
+    assert_eq!(ScriptBuf::from_asm("0x0105").unwrap().as_bytes(), [0x01, 0x05]);
+    assert!(ScriptBuf::from_asm("0a").is_err());
+    assert!(ScriptBuf::from_asm("162c5ea71c0b23f5b9022ef047c4a86470a5b070").is_err());
+}
+
+#[test]
+fn script_from_asm_core_dialect() {
+    let script = ScriptBuf::from_asm(
+        "0 -1 1 16 17 -2 1000 0x4c01 0x07 'abc' '' ADD OP_ADD 2DROP NOP2 CHECKLOCKTIMEVERIFY NOP3 \
+         CHECKSEQUENCEVERIFY OP_TRUE OP_FALSE OP_1NEGATE OP_16 RESERVED CHECKSIGADD",
+    )
+    .unwrap();
+    assert_eq!(
+        script.to_hex_string_no_length_prefix(),
+        "004f51600111018202e8034c0107036162630093936db1b1b2b251004f6050ba"
+    );
+    // Tabs and newlines separate tokens too.
+    assert_eq!(ScriptBuf::from_asm(" 1\t2\n ADD ").unwrap().as_bytes(), [0x51, 0x52, 0x93]);
+    assert_eq!(
+        ScriptBuf::from_asm("4294967295 -4294967295").unwrap().to_hex_string_no_length_prefix(),
+        "05ffffffff0005ffffffff80"
+    );
+}
+
+#[test]
+fn script_from_asm_errors() {
+    fn err(asm: &str) -> (usize, ParseAsmErrorKind) {
+        let e = ScriptBuf::from_asm(asm).unwrap_err();
+        (e.position(), e.kind().clone())
+    }
+
+    assert_eq!(err("OP_DUP OP_FOO"), (7, ParseAsmErrorKind::UnknownToken));
+    assert_eq!(err("OP_DUP  abc"), (8, ParseAsmErrorKind::UnknownToken));
+    assert_eq!(err("0x"), (0, ParseAsmErrorKind::UnknownToken));
+    assert_eq!(err("1 0xabc"), (2, ParseAsmErrorKind::UnknownToken));
+    assert_eq!(err("'abc"), (0, ParseAsmErrorKind::UnknownToken));
+    assert_eq!(err("OP_PUSHBYTES_2"), (14, ParseAsmErrorKind::MissingPushData(OP_PUSHBYTES_2)));
+    assert_eq!(
+        err("OP_PUSHBYTES_2 OP_ADD"),
+        (15, ParseAsmErrorKind::MissingPushData(OP_PUSHBYTES_2))
+    );
+    // Truncated pushes can not be parsed back.
+    assert_eq!(
+        err("OP_PUSHBYTES_1 <push past end>"),
+        (15, ParseAsmErrorKind::MissingPushData(OP_PUSHBYTES_1))
+    );
+    assert_eq!(
+        err("OP_1 OP_PUSHBYTES_2 abcdef"),
+        (
+            20,
+            ParseAsmErrorKind::PushLengthMismatch {
+                opcode: OP_PUSHBYTES_2,
+                expected: 2,
+                actual: 3
+            }
+        )
+    );
+    assert_eq!(
+        err(&format!("OP_PUSHDATA1 {}", "00".repeat(256))),
+        (13, ParseAsmErrorKind::PushTooLong { opcode: OP_PUSHDATA1, len: 256 })
+    );
+    assert_eq!(err("4294967296"), (0, ParseAsmErrorKind::NumberOutOfRange));
+    assert_eq!(err("1 -99999999999999999999"), (2, ParseAsmErrorKind::NumberOutOfRange));
+    // Positions are byte offsets.
+    assert_eq!(err("'\u{e9}' OP_FOO"), (5, ParseAsmErrorKind::UnknownToken));
+
+    assert_eq!(
+        ScriptBuf::from_asm("OP_DUP OP_FOO").unwrap_err().to_string(),
+        "invalid script assembly at byte 7: unknown token"
+    );
+}
+
 #[test]
 fn script_buf_collect() {
     assert_eq!(&core::iter::empty::<Instruction<'_>>().collect::<ScriptBuf>(), Script::new());
@@ -1130,6 +1130,9 @@ pub const fn witness_weight(&self) -> Weight {
         let wu = wu as u64; // Can't use `ToU64` in const context.
         Weight::from_wu(wu)
     }
+
+    /// Returns true if the input has witness data.
+    pub(crate) const fn has_witness(&self) -> bool { self.witness_size > 0 }
 }
 
 mod sealed {
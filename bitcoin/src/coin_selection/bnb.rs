@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Branch and Bound coin selection.
+//!
+//! Depth first search for a selection whose effective value is within the cost of change of the
+//! target, such that no change output is needed. This is the algorithm used by Bitcoin Core as
+//! described in Murch's thesis "An Evaluation of Coin Selection Strategies".
+
+use super::Candidate;
+use crate::prelude::Vec;
+
+/// The maximum number of nodes visited before giving up.
+const TOTAL_TRIES: usize = 100_000;
+
+/// Selects candidates with a total effective value in `target..=target + cost_of_change`.
+///
+/// If there are multiple such selections, the one with the least waste is returned.
+/// `high_fee_rate` is true if the current fee rate is higher than the long term fee rate, in
+/// which case branches that already waste more than the best selection are cut.
+///
+/// Returns indices into `candidates`, or `None` if no selection was found.
+pub(super) fn select(
+    candidates: &[Candidate],
+    target: u64,
+    cost_of_change: u64,
+    high_fee_rate: bool,
+) -> Option<Vec<usize>> {
+    let mut pool = (0..candidates.len()).collect::<Vec<_>>();
+    pool.sort_by(|&a, &b| candidates[b].effective_value.cmp(&candidates[a].effective_value));
+    let pool: Vec<(&Candidate, usize)> = pool.iter().map(|&i| (&candidates[i], i)).collect();
+
+    let mut available = pool.iter().map(|(c, _)| c.effective_value).sum::<u64>();
+    if available < target {
+        return None;
+    }
+
+    let mut selection: Vec<usize> = Vec::new();
+    let mut best_selection = None;
+    let (mut value, mut waste, mut best_waste) = (0u64, 0i64, i64::MAX);
+    let mut index = 0;
+    for _ in 0..TOTAL_TRIES {
+        let mut backtrack = false;
+        if value + available < target
+            || value > target + cost_of_change
+            || (waste > best_waste && high_fee_rate)
+        {
+            backtrack = true;
+        } else if value >= target {
+            // Without change the excess is paid as fee, which counts as waste.
+            let total_waste = waste + (value - target) as i64;
+            if total_waste <= best_waste {
+                best_selection = Some(selection.clone());
+                best_waste = total_waste;
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let last = match selection.last() {
+                Some(&last) => last,
+                None => break,
+            };
+            // Omitted candidates after the last selected one are available again.
+            available += pool[last + 1..index].iter().map(|(c, _)| c.effective_value).sum::<u64>();
+            // Explore the branch omitting the last selected candidate.
+            selection.pop();
+            value -= pool[last].0.effective_value;
+            waste -= pool[last].0.waste();
+            index = last + 1;
+            continue;
+        }
+
+        let candidate = pool[index].0;
+        available -= candidate.effective_value;
+        // Selecting a candidate equivalent to the previous, omitted one leads to the same results.
+        let is_equivalent = index > 0
+            && selection.last() != Some(&(index - 1))
+            && candidate.effective_value == pool[index - 1].0.effective_value
+            && candidate.fee == pool[index - 1].0.fee;
+        if !is_equivalent {
+            selection.push(index);
+            value += candidate.effective_value;
+            waste += candidate.waste();
+        }
+        index += 1;
+    }
+
+    best_selection.map(|selection| selection.into_iter().map(|i| pool[i].1).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(values: &[u64]) -> Vec<Candidate> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| Candidate {
+                index,
+                effective_value: value,
+                fee: 100,
+                long_term_fee: 50,
+            })
+            .collect()
+    }
+
+    fn selected_values(candidates: &[Candidate], selection: &[usize]) -> Vec<u64> {
+        let mut values =
+            selection.iter().map(|&i| candidates[i].effective_value).collect::<Vec<_>>();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn exact_match() {
+        let pool = candidates(&[1_000, 2_000, 3_000, 4_000]);
+
+        let selection = select(&pool, 1_000, 0, true).unwrap();
+        assert_eq!(selected_values(&pool, &selection), [1_000]);
+
+        let selection = select(&pool, 7_000, 0, true).unwrap();
+        assert_eq!(selected_values(&pool, &selection), [3_000, 4_000]);
+
+        let selection = select(&pool, 10_000, 0, true).unwrap();
+        assert_eq!(selected_values(&pool, &selection), [1_000, 2_000, 3_000, 4_000]);
+    }
+
+    #[test]
+    fn within_cost_of_change() {
+        let pool = candidates(&[1_000, 2_000, 3_000, 4_000]);
+
+        assert_eq!(select(&pool, 4_500, 0, true), None);
+        let selection = select(&pool, 4_500, 500, true).unwrap();
+        assert_eq!(selected_values(&pool, &selection).iter().sum::<u64>(), 5_000);
+    }
+
+    #[test]
+    fn least_waste() {
+        // Both {5_000} and {2_000, 3_000} match, with a high fee rate every input is waste.
+        let pool = candidates(&[2_000, 3_000, 5_000]);
+        let selection = select(&pool, 5_000, 0, true).unwrap();
+        assert_eq!(selected_values(&pool, &selection), [5_000]);
+    }
+
+    #[test]
+    fn no_match() {
+        let pool = candidates(&[1_000, 2_000, 3_000, 4_000]);
+        assert_eq!(select(&pool, 11_000, 500, true), None);
+
+        let pool = candidates(&[4_000, 4_000, 4_000]);
+        assert_eq!(select(&pool, 6_000, 1_000, true), None);
+        assert_eq!(select(&pool, 8_000, 0, true).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Knapsack coin selection.
+//!
+//! The stochastic approximation of the subset sum problem used by Bitcoin Core before Branch
+//! and Bound, it tries to find a selection just above the target plus a minimum change.
+
+use secp256k1::rand::seq::SliceRandom;
+use secp256k1::rand::Rng;
+
+use super::Candidate;
+use crate::prelude::Vec;
+
+/// The number of random subsets tried.
+const ITERATIONS: usize = 1000;
+
+/// Selects candidates with a total effective value of at least `target`.
+///
+/// Prefers an exact match, otherwise a selection leaving at least `min_change`.
+///
+/// Returns indices into `candidates`, or `None` if the candidates do not cover `target`.
+pub(super) fn select<R: Rng + ?Sized>(
+    rng: &mut R,
+    candidates: &[Candidate],
+    target: u64,
+    min_change: u64,
+) -> Option<Vec<usize>> {
+    let mut order = (0..candidates.len()).collect::<Vec<_>>();
+    order.shuffle(rng);
+
+    let mut applicable = Vec::new();
+    let mut total_lower = 0;
+    let mut lowest_larger: Option<usize> = None;
+    for i in order {
+        let value = candidates[i].effective_value;
+        if value == target {
+            return Some(vec![i]);
+        } else if value < target + min_change {
+            applicable.push(i);
+            total_lower += value;
+        } else if lowest_larger.map_or(true, |l| value < candidates[l].effective_value) {
+            lowest_larger = Some(i);
+        }
+    }
+
+    if total_lower == target {
+        return Some(applicable);
+    }
+    if total_lower < target {
+        return lowest_larger.map(|i| vec![i]);
+    }
+
+    applicable.sort_by(|&a, &b| candidates[b].effective_value.cmp(&candidates[a].effective_value));
+    let values = applicable.iter().map(|&i| candidates[i].effective_value).collect::<Vec<_>>();
+    let (mut best, mut best_value) = approximate_best_subset(rng, &values, total_lower, target);
+    if best_value != target && total_lower >= target + min_change {
+        (best, best_value) =
+            approximate_best_subset(rng, &values, total_lower, target + min_change);
+    }
+
+    // The smallest larger candidate is preferred if it does not leave less change.
+    if let Some(l) = lowest_larger {
+        let value = candidates[l].effective_value;
+        if (best_value != target && best_value < target + min_change) || value <= best_value {
+            return Some(vec![l]);
+        }
+    }
+    Some(
+        applicable
+            .into_iter()
+            .zip(best)
+            .filter_map(|(i, included)| included.then_some(i))
+            .collect(),
+    )
+}
+
+/// Randomly includes `values` to find the subset with the smallest sum of at least `target`.
+///
+/// `total` is the sum of all values, which must be at least `target`.
+fn approximate_best_subset<R: Rng + ?Sized>(
+    rng: &mut R,
+    values: &[u64],
+    total: u64,
+    target: u64,
+) -> (Vec<bool>, u64) {
+    let mut best = vec![true; values.len()];
+    let mut best_value = total;
+
+    for _ in 0..ITERATIONS {
+        if best_value == target {
+            break;
+        }
+        let mut included = vec![false; values.len()];
+        let mut value = 0;
+        let mut reached_target = false;
+        // First include values at random, then add the remaining ones in order.
+        for pass in 0..2 {
+            if reached_target {
+                break;
+            }
+            for i in 0..values.len() {
+                let include = if pass == 0 { rng.gen::<bool>() } else { !included[i] };
+                if !include {
+                    continue;
+                }
+                value += values[i];
+                included[i] = true;
+                if value >= target {
+                    reached_target = true;
+                    if value < best_value {
+                        best_value = value;
+                        best.clone_from(&included);
+                    }
+                    // Try to find a smaller subset by removing the value again.
+                    value -= values[i];
+                    included[i] = false;
+                }
+            }
+        }
+    }
+    (best, best_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::rand::rngs::mock::StepRng;
+
+    use super::*;
+
+    fn candidates(values: &[u64]) -> Vec<Candidate> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| Candidate {
+                index,
+                effective_value: value,
+                fee: 100,
+                long_term_fee: 100,
+            })
+            .collect()
+    }
+
+    fn total(candidates: &[Candidate], selection: &[usize]) -> u64 {
+        selection.iter().map(|&i| candidates[i].effective_value).sum()
+    }
+
+    #[test]
+    fn knapsack() {
+        let mut rng = StepRng::new(0, 0x9E37_79B9_7F4A_7C15);
+        let pool = candidates(&[1_000, 2_000, 3_000, 5_000, 20_000]);
+
+        // Exact match with a single candidate.
+        assert_eq!(select(&mut rng, &pool, 3_000, 1_000), Some(vec![2]));
+        // Exact match with all smaller candidates.
+        let selection = select(&mut rng, &pool, 11_000, 10_000).unwrap();
+        assert_eq!(total(&pool, &selection), 11_000);
+        // Smaller candidates do not cover the target.
+        assert_eq!(select(&mut rng, &pool, 12_000, 1_000), Some(vec![4]));
+        // A subset leaving at least the minimum change.
+        let selection = select(&mut rng, &pool, 4_500, 500).unwrap();
+        assert!(total(&pool, &selection) >= 5_000);
+        assert!(total(&pool, &selection) < 20_000);
+
+        assert_eq!(select(&mut rng, &pool, 32_000, 0), None);
+    }
+}
@@ -0,0 +1,534 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Coin selection.
+//!
+//! A [`Builder`] assembles an unsigned transaction paying a set of recipients from a pool of
+//! candidate UTXOs. Inputs are selected by the same algorithms Bitcoin Core uses: Branch and
+//! Bound, which searches for a selection that needs no change output, knapsack and single random
+//! draw. Of the selections found the one with the least waste is used.
+//!
+//! The waste of a selection is the fee paid for its inputs at the current fee rate minus the fee
+//! they would cost at the long term fee rate, plus either the cost of creating and later spending
+//! the change output or, if there is no change, the excess paid as fee.
+
+mod bnb;
+mod knapsack;
+mod srd;
+
+use core::convert::Infallible;
+use core::fmt;
+
+use secp256k1::rand::Rng;
+
+use crate::locktime::absolute;
+use crate::prelude::Vec;
+use crate::psbt::Psbt;
+use crate::script::ScriptExt as _;
+use crate::transaction::{
+    self, predict_weight, InputWeightPrediction, OutPoint, Transaction, TxIn, TxOut, TxOutExt as _,
+};
+use crate::{Amount, FeeRate, Script, ScriptBuf, Sequence, Weight, Witness};
+
+/// A UTXO that can be spent by the transaction being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedUtxo {
+    /// The outpoint of the UTXO.
+    pub outpoint: OutPoint,
+    /// The UTXO.
+    pub txout: TxOut,
+    /// The predicted weight of the input spending the UTXO, once satisfied.
+    pub satisfaction_weight: InputWeightPrediction,
+}
+
+/// Builds an unsigned transaction, selecting inputs from a pool of UTXOs.
+///
+/// # Examples
+///
+/// ```
+/// use bitcoin::address::script_pubkey::ScriptBufExt as _;
+/// use bitcoin::coin_selection::{Builder, WeightedUtxo};
+/// use bitcoin::key::WPubkeyHash;
+/// use bitcoin::secp256k1::rand::rngs::mock::StepRng;
+/// use bitcoin::transaction::InputWeightPrediction;
+/// use bitcoin::{Amount, FeeRate, OutPoint, ScriptBuf, TxOut};
+///
+/// let script_pubkey = ScriptBuf::new_p2wpkh(WPubkeyHash::from_byte_array([0; 20]));
+/// let utxo = WeightedUtxo {
+///     outpoint: OutPoint::COINBASE_PREVOUT,
+///     txout: TxOut { value: Amount::from_sat_u32(100_000), script_pubkey: script_pubkey.clone() },
+///     satisfaction_weight: InputWeightPrediction::P2WPKH_MAX,
+/// };
+///
+/// let tx = Builder::new(FeeRate::from_sat_per_vb_u32(2), script_pubkey.clone())
+///     .add_utxo(utxo)
+///     .add_recipient(script_pubkey, Amount::from_sat_u32(50_000))
+///     .build_tx_with_rng(&mut StepRng::new(0, 1))
+///     .expect("enough funds");
+/// assert_eq!(tx.output.len(), 2); // The recipient and change.
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    change_script: ScriptBuf,
+    change_satisfaction_weight: Option<InputWeightPrediction>,
+    utxos: Vec<WeightedUtxo>,
+    recipients: Vec<TxOut>,
+    version: transaction::Version,
+    lock_time: absolute::LockTime,
+    sequence: Sequence,
+}
+
+impl Builder {
+    /// Constructs a new builder paying `fee_rate`, sending change to `change_script`.
+    ///
+    /// The long term fee rate defaults to `fee_rate`. The satisfaction weight of the change
+    /// output is known for P2PKH, P2WPKH and P2TR key path spends, for other scripts set it with
+    /// [`Builder::change_satisfaction_weight`].
+    pub fn new(fee_rate: FeeRate, change_script: ScriptBuf) -> Self {
+        Builder {
+            fee_rate,
+            long_term_fee_rate: fee_rate,
+            change_satisfaction_weight: default_satisfaction_weight(&change_script),
+            change_script,
+            utxos: Vec::new(),
+            recipients: Vec::new(),
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            sequence: Sequence::ENABLE_LOCKTIME_AND_RBF,
+        }
+    }
+
+    /// Sets the fee rate expected to spend UTXOs in the future.
+    ///
+    /// If the current fee rate is higher, selections with fewer inputs are preferred, otherwise
+    /// selections consolidating more inputs.
+    pub fn long_term_fee_rate(mut self, fee_rate: FeeRate) -> Self {
+        self.long_term_fee_rate = fee_rate;
+        self
+    }
+
+    /// Sets the predicted weight of an input spending the change output.
+    pub fn change_satisfaction_weight(mut self, prediction: InputWeightPrediction) -> Self {
+        self.change_satisfaction_weight = Some(prediction);
+        self
+    }
+
+    /// Adds a UTXO the transaction may spend.
+    pub fn add_utxo(mut self, utxo: WeightedUtxo) -> Self {
+        self.utxos.push(utxo);
+        self
+    }
+
+    /// Adds UTXOs the transaction may spend.
+    pub fn add_utxos<I: IntoIterator<Item = WeightedUtxo>>(mut self, utxos: I) -> Self {
+        self.utxos.extend(utxos);
+        self
+    }
+
+    /// Adds an output paying `amount` to `script_pubkey`.
+    pub fn add_recipient(mut self, script_pubkey: ScriptBuf, amount: Amount) -> Self {
+        self.recipients.push(TxOut { value: amount, script_pubkey });
+        self
+    }
+
+    /// Sets the transaction version, defaults to version 2.
+    pub fn version(mut self, version: transaction::Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the lock time of the transaction, defaults to zero.
+    pub fn lock_time(mut self, lock_time: absolute::LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Sets the sequence number of all inputs, defaults to [`Sequence::ENABLE_LOCKTIME_AND_RBF`].
+    pub fn sequence(mut self, sequence: Sequence) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Selects inputs and builds the unsigned transaction using the thread local random number
+    /// generator.
+    ///
+    /// See [`Builder::build_tx_with_rng`].
+    #[cfg(feature = "rand-std")]
+    pub fn build_tx(&self) -> Result<Transaction, Error> {
+        self.build_tx_with_rng(&mut secp256k1::rand::thread_rng())
+    }
+
+    /// Selects inputs and builds the unsigned transaction.
+    ///
+    /// The inputs are in the order the UTXOs were added, the change output, if any, is inserted
+    /// at a random position among the recipients. If there is a change output the fee
+    /// is exactly the fee rate times the predicted weight of the transaction, otherwise the fee
+    /// is slightly higher because the excess is too small to pay for a change output.
+    ///
+    /// # Errors
+    ///
+    /// If no recipients were added, a recipient's amount is dust or the UTXOs do not cover the
+    /// recipients and fee.
+    pub fn build_tx_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Transaction, Error> {
+        self.build(rng).map(|(tx, _)| tx)
+    }
+
+    /// Selects inputs and builds an unsigned PSBT using the thread local random number
+    /// generator.
+    ///
+    /// See [`Builder::build_psbt_with_rng`].
+    #[cfg(feature = "rand-std")]
+    pub fn build_psbt(&self) -> Result<Psbt, Error> {
+        self.build_psbt_with_rng(&mut secp256k1::rand::thread_rng())
+    }
+
+    /// Selects inputs and builds an unsigned PSBT.
+    ///
+    /// The witness UTXO is set for inputs spending a witness program. Inputs spending other
+    /// scripts need the full previous transaction which the caller must add.
+    ///
+    /// # Errors
+    ///
+    /// See [`Builder::build_tx_with_rng`].
+    pub fn build_psbt_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Psbt, Error> {
+        let (tx, selected) = self.build(rng)?;
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("the transaction is unsigned");
+        for (input, utxo) in psbt.inputs.iter_mut().zip(selected) {
+            if utxo.txout.script_pubkey.is_witness_program() {
+                input.witness_utxo = Some(utxo.txout.clone());
+            }
+        }
+        Ok(psbt)
+    }
+
+    /// Selects inputs and builds the transaction, also returning the selected UTXOs.
+    fn build<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<(Transaction, Vec<&WeightedUtxo>), Error> {
+        if self.recipients.is_empty() {
+            return Err(Error::NoRecipients);
+        }
+        if let Some(index) = self
+            .recipients
+            .iter()
+            .position(|txout| txout.value < txout.script_pubkey.minimal_non_dust())
+        {
+            return Err(Error::DustOutput(index));
+        }
+        let recipients_value =
+            self.recipients.iter().map(|txout| txout.value.to_sat()).sum::<u64>();
+
+        // Fees are estimated assuming a segwit transaction, the final fee is computed exactly.
+        let base_weight =
+            predict_weight([], self.recipients.iter().map(|txout| txout.script_pubkey.len()))
+                + SEGWIT_MARKER_WEIGHT;
+        let target = recipients_value + fee(self.fee_rate, base_weight)?;
+
+        let change_weight =
+            TxOut { value: Amount::ZERO, script_pubkey: self.change_script.clone() }.weight();
+        let change_fee = fee(self.fee_rate, change_weight)?;
+        let change_spend_fee = match self.change_satisfaction_weight {
+            Some(prediction) => fee(self.long_term_fee_rate, prediction.total_weight())?,
+            None => 0,
+        };
+        let cost_of_change = change_fee + change_spend_fee;
+        let min_change = self.change_script.minimal_non_dust().to_sat();
+
+        let mut candidates = Vec::with_capacity(self.utxos.len());
+        for (index, utxo) in self.utxos.iter().enumerate() {
+            let mut weight = utxo.satisfaction_weight.total_weight();
+            if !utxo.satisfaction_weight.has_witness() {
+                // The empty witness of a non-segwit input in a segwit transaction.
+                weight += Weight::from_wu(1);
+            }
+            let input_fee = fee(self.fee_rate, weight)?;
+            // Spending UTXOs worth less than their fee only lowers the value of the selection.
+            if let Some(effective_value) =
+                utxo.txout.value.to_sat().checked_sub(input_fee).filter(|v| *v > 0)
+            {
+                let long_term_fee = fee(self.long_term_fee_rate, weight)?;
+                candidates.push(Candidate {
+                    index,
+                    effective_value,
+                    fee: input_fee,
+                    long_term_fee,
+                });
+            }
+        }
+        let available = candidates.iter().map(|c| c.effective_value).sum::<u64>();
+        if available < target {
+            return Err(insufficient_funds(available, target));
+        }
+
+        let high_fee_rate = self.fee_rate > self.long_term_fee_rate;
+        let waste = |selection: &[usize]| -> i64 {
+            let selected = selection.iter().map(|&i| &candidates[i]);
+            let excess = selected.clone().map(|c| c.effective_value).sum::<u64>() - target;
+            let change_waste =
+                if excess >= change_fee + min_change { cost_of_change } else { excess };
+            selected.map(Candidate::waste).sum::<i64>() + change_waste as i64
+        };
+        let mut selection = [
+            bnb::select(&candidates, target, cost_of_change, high_fee_rate),
+            knapsack::select(rng, &candidates, target + change_fee, min_change),
+            srd::select(rng, &candidates, target + change_fee + min_change),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by_key(|selection| waste(selection))
+        .ok_or_else(|| insufficient_funds(available, target))?;
+        selection.sort_unstable();
+
+        let selected =
+            selection.iter().map(|&i| &self.utxos[candidates[i].index]).collect::<Vec<_>>();
+        let input = selected
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: self.sequence,
+                witness: Witness::new(),
+            })
+            .collect();
+        let mut tx = Transaction {
+            version: self.version,
+            lock_time: self.lock_time,
+            input,
+            output: self.recipients.clone(),
+        };
+
+        let input_value = selected.iter().map(|utxo| utxo.txout.value.to_sat()).sum::<u64>();
+        let predictions = selected.iter().map(|utxo| utxo.satisfaction_weight);
+        let script_lens = self.recipients.iter().map(|txout| txout.script_pubkey.len());
+
+        let weight = predict_weight(
+            predictions.clone(),
+            script_lens.clone().chain(core::iter::once(self.change_script.len())),
+        );
+        let change = input_value
+            .checked_sub(recipients_value + fee(self.fee_rate, weight)?)
+            .filter(|change| *change >= min_change);
+        match change {
+            Some(change) => {
+                let change = TxOut {
+                    value: Amount::from_sat(change).map_err(|_| Error::Overflow)?,
+                    script_pubkey: self.change_script.clone(),
+                };
+                let position = rng.gen_range(0..=tx.output.len());
+                tx.output.insert(position, change);
+            }
+            None => {
+                let required = recipients_value
+                    + fee(self.fee_rate, predict_weight(predictions, script_lens))?;
+                if input_value < required {
+                    return Err(insufficient_funds(input_value, required));
+                }
+            }
+        }
+        Ok((tx, selected))
+    }
+}
+
+/// The weight of the segwit marker and flag.
+const SEGWIT_MARKER_WEIGHT: Weight = Weight::from_wu(2);
+
+/// A UTXO with a positive effective value, candidate for selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Candidate {
+    /// Index of the UTXO in the builder.
+    index: usize,
+    /// The value of the UTXO minus the fee to spend it.
+    effective_value: u64,
+    /// The fee to spend the UTXO at the current fee rate.
+    fee: u64,
+    /// The fee to spend the UTXO at the long term fee rate.
+    long_term_fee: u64,
+}
+
+impl Candidate {
+    /// Returns the fee paid now over the fee this UTXO would cost at the long term fee rate.
+    fn waste(&self) -> i64 { self.fee as i64 - self.long_term_fee as i64 }
+}
+
+/// Returns the fee in satoshis for `weight` at `fee_rate`.
+fn fee(fee_rate: FeeRate, weight: Weight) -> Result<u64, Error> {
+    fee_rate.to_fee(weight).map(Amount::to_sat).ok().ok_or(Error::Overflow)
+}
+
+/// Returns the predicted weight of an input spending a standard `script_pubkey`.
+fn default_satisfaction_weight(script_pubkey: &Script) -> Option<InputWeightPrediction> {
+    if script_pubkey.is_p2wpkh() {
+        Some(InputWeightPrediction::P2WPKH_MAX)
+    } else if script_pubkey.is_p2tr() {
+        Some(InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH)
+    } else if script_pubkey.is_p2pkh() {
+        Some(InputWeightPrediction::P2PKH_COMPRESSED_MAX)
+    } else {
+        None
+    }
+}
+
+fn insufficient_funds(available: u64, required: u64) -> Error {
+    // Sums of amounts of the UTXO set can not exceed the supply, saturate just in case.
+    let to_amount = |sat| Amount::from_sat(sat).unwrap_or(Amount::MAX);
+    Error::InsufficientFunds { available: to_amount(available), required: to_amount(required) }
+}
+
+/// Error building a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// No recipients were added.
+    NoRecipients,
+    /// The amount of the recipient at the given index is dust.
+    DustOutput(usize),
+    /// The UTXOs do not cover the recipients and fee.
+    InsufficientFunds {
+        /// The effective value of the UTXOs.
+        available: Amount,
+        /// The amount required to pay the recipients and fee.
+        required: Amount,
+    },
+    /// Calculating an amount or fee overflowed.
+    Overflow,
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            NoRecipients => f.write_str("the transaction has no recipients"),
+            DustOutput(index) => write!(f, "the amount of recipient {} is dust", index),
+            InsufficientFunds { available, required } =>
+                write!(f, "insufficient funds: {} available, {} required", available, required),
+            Overflow => f.write_str("amount or fee calculation overflowed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            NoRecipients | DustOutput(_) | InsufficientFunds { .. } | Overflow => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::rand::rngs::mock::StepRng;
+
+    use super::*;
+    use crate::address::script_pubkey::ScriptBufExt as _;
+    use crate::key::WPubkeyHash;
+    use crate::Txid;
+
+    fn p2wpkh(byte: u8) -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(WPubkeyHash::from_byte_array([byte; 20]))
+    }
+
+    fn utxo(vout: u32, sat: u64) -> WeightedUtxo {
+        WeightedUtxo {
+            outpoint: OutPoint { txid: Txid::from_byte_array([0; 32]), vout },
+            txout: TxOut { value: Amount::from_sat(sat).unwrap(), script_pubkey: p2wpkh(0) },
+            satisfaction_weight: InputWeightPrediction::P2WPKH_MAX,
+        }
+    }
+
+    fn fee_paid(builder: &Builder, tx: &Transaction) -> Amount {
+        let input_value = tx
+            .input
+            .iter()
+            .map(|txin| builder.utxos.iter().find(|u| u.outpoint == txin.previous_output).unwrap())
+            .map(|utxo| utxo.txout.value.to_sat())
+            .sum::<u64>();
+        let output_value = tx.output.iter().map(|txout| txout.value.to_sat()).sum::<u64>();
+        Amount::from_sat(input_value - output_value).unwrap()
+    }
+
+    fn predicted_fee(builder: &Builder, tx: &Transaction) -> Amount {
+        let weight = predict_weight(
+            tx.input.iter().map(|_| InputWeightPrediction::P2WPKH_MAX),
+            tx.output.iter().map(|txout| txout.script_pubkey.len()),
+        );
+        builder.fee_rate.to_fee(weight).unwrap()
+    }
+
+    #[test]
+    fn build_with_change() {
+        let mut rng = StepRng::new(0, 0x9E37_79B9_7F4A_7C15);
+        let builder = Builder::new(FeeRate::from_sat_per_vb_u32(10), p2wpkh(1))
+            .add_utxos((0..10).map(|vout| utxo(vout, 10_000 * (u64::from(vout) + 1))))
+            .add_recipient(p2wpkh(2), Amount::from_sat_u32(123_456));
+
+        let tx = builder.build_tx_with_rng(&mut rng).unwrap();
+        assert_eq!(tx.output.len(), 2);
+        assert!(tx.output.iter().any(|txout| txout.script_pubkey == p2wpkh(1)));
+        assert!(tx.output.iter().any(|txout| txout.value == Amount::from_sat_u32(123_456)));
+        assert_eq!(fee_paid(&builder, &tx), predicted_fee(&builder, &tx));
+        assert!(tx.input.iter().all(|txin| txin.sequence == Sequence::ENABLE_LOCKTIME_AND_RBF));
+    }
+
+    #[test]
+    fn build_without_change() {
+        let mut rng = StepRng::new(0, 0x9E37_79B9_7F4A_7C15);
+        let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+        let builder = Builder::new(fee_rate, p2wpkh(1))
+            .add_utxos([utxo(0, 50_000), utxo(1, 100_000), utxo(2, 200_000)])
+            .add_recipient(p2wpkh(2), Amount::from_sat_u32(98_500));
+
+        // The 100_000 sat UTXO pays the recipient and fee, too little is left for change.
+        let tx = builder.build_tx_with_rng(&mut rng).unwrap();
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output.vout, 1);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(fee_paid(&builder, &tx), Amount::from_sat_u32(1_500));
+        assert!(fee_paid(&builder, &tx) >= predicted_fee(&builder, &tx));
+    }
+
+    #[test]
+    fn build_psbt() {
+        let mut rng = StepRng::new(0, 0x9E37_79B9_7F4A_7C15);
+        let builder = Builder::new(FeeRate::from_sat_per_vb_u32(1), p2wpkh(1))
+            .add_utxos([utxo(0, 30_000), utxo(1, 30_000)])
+            .add_recipient(p2wpkh(2), Amount::from_sat_u32(50_000));
+
+        let psbt = builder.build_psbt_with_rng(&mut rng).unwrap();
+        assert_eq!(psbt.inputs.len(), 2);
+        for (input, txin) in psbt.inputs.iter().zip(&psbt.unsigned_tx.input) {
+            let utxo = &builder.utxos[txin.previous_output.vout as usize];
+            assert_eq!(input.witness_utxo.as_ref(), Some(&utxo.txout));
+        }
+    }
+
+    #[test]
+    fn build_errors() {
+        let mut rng = StepRng::new(0, 0x9E37_79B9_7F4A_7C15);
+        let builder = Builder::new(FeeRate::from_sat_per_vb_u32(10), p2wpkh(1))
+            .add_utxos([utxo(0, 10_000), utxo(1, 500)]);
+        assert_eq!(builder.build_tx_with_rng(&mut rng), Err(Error::NoRecipients));
+
+        let dust = builder.clone().add_recipient(p2wpkh(2), Amount::from_sat_u32(293));
+        assert_eq!(dust.build_tx_with_rng(&mut rng), Err(Error::DustOutput(0)));
+
+        // The 500 sat UTXO costs more to spend than it is worth.
+        let builder = builder.add_recipient(p2wpkh(2), Amount::from_sat_u32(10_000));
+        match builder.build_tx_with_rng(&mut rng) {
+            Err(Error::InsufficientFunds { available, required }) => {
+                assert_eq!(available, Amount::from_sat_u32(10_000 - 680));
+                assert!(required > Amount::from_sat_u32(10_000));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
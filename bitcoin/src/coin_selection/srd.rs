@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Single random draw coin selection.
+//!
+//! Selects candidates in random order until the target is reached.
+
+use secp256k1::rand::seq::SliceRandom;
+use secp256k1::rand::Rng;
+
+use super::Candidate;
+use crate::prelude::Vec;
+
+/// Selects random candidates with a total effective value of at least `target`.
+///
+/// Returns indices into `candidates`, or `None` if the candidates do not cover `target`.
+pub(super) fn select<R: Rng + ?Sized>(
+    rng: &mut R,
+    candidates: &[Candidate],
+    target: u64,
+) -> Option<Vec<usize>> {
+    let mut order = (0..candidates.len()).collect::<Vec<_>>();
+    order.shuffle(rng);
+
+    let mut value = 0;
+    for (count, &i) in order.iter().enumerate() {
+        value += candidates[i].effective_value;
+        if value >= target {
+            order.truncate(count + 1);
+            return Some(order);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::rand::rngs::mock::StepRng;
+
+    use super::*;
+
+    #[test]
+    fn single_random_draw() {
+        let mut rng = StepRng::new(0, 0x9E37_79B9_7F4A_7C15);
+        let pool = [1_000, 2_000, 3_000, 4_000]
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| Candidate {
+                index,
+                effective_value: value,
+                fee: 100,
+                long_term_fee: 100,
+            })
+            .collect::<Vec<_>>();
+
+        for target in [1, 2_500, 5_000, 10_000] {
+            let selection = select(&mut rng, &pool, target).unwrap();
+            let value = selection.iter().map(|&i| pool[i].effective_value).sum::<u64>();
+            assert!(value >= target);
+            // Removing the last selected candidate does not reach the target.
+            assert!(value - pool[*selection.last().unwrap()].effective_value < target);
+        }
+        assert_eq!(select(&mut rng, &pool, 10_001), None);
+    }
+}
@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bitcoin Core chainstate compression.
+//!
+//! Bitcoin Core stores coins in undo files, the chainstate database and UTXO snapshots using a
+//! compact encoding which is not part of the consensus encoding:
+//!
+//! * The MSB base-128 `VARINT`, see [`MsbVarInt`].
+//! * Compressed amounts, see [`CompressedAmount`].
+//! * Compressed scripts, see [`CompressedScript`].
+//!
+//! The types are re-exported from [`encode`].
+
+use io::{BufRead, Write};
+
+use super::encode::{self, Decodable, Encodable, ReadExt as _, WriteExt as _};
+use super::ParseError;
+use crate::opcodes::all::{OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_RETURN};
+use crate::prelude::Vec;
+use crate::script::interpreter::MAX_SCRIPT_SIZE;
+use crate::script::{Script, ScriptBuf, ScriptExt as _};
+use crate::{Amount, TxOut};
+
+/// The number of special script types, raw scripts have their length offset by this number.
+const SPECIAL_SCRIPTS: u64 = 6;
+
+/// Writes `n` as an MSB base-128 `VARINT`, returning the number of bytes written.
+pub(crate) fn emit_varint<W: Write + ?Sized>(w: &mut W, mut n: u64) -> Result<usize, io::Error> {
+    let mut buf = [0u8; 10];
+    let mut len = 0;
+    loop {
+        buf[len] = (n & 0x7f) as u8 | if len > 0 { 0x80 } else { 0x00 };
+        if n <= 0x7f {
+            break;
+        }
+        n = (n >> 7) - 1;
+        len += 1;
+    }
+    let encoded = &mut buf[..=len];
+    encoded.reverse();
+    w.write_all(encoded)?;
+    Ok(encoded.len())
+}
+
+/// Reads an MSB base-128 `VARINT`.
+pub(crate) fn read_varint<R: BufRead + ?Sized>(r: &mut R) -> Result<u64, encode::Error> {
+    let mut n = 0_u64;
+    loop {
+        let byte = r.read_u8()?;
+        if n > u64::MAX >> 7 {
+            return Err(ParseError::ParseFailed("VARINT overflow").into());
+        }
+        n = (n << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        n = n.checked_add(1).ok_or(ParseError::ParseFailed("VARINT overflow"))?;
+    }
+}
+
+/// Compresses an amount, making amounts with many trailing decimal zeros small.
+///
+/// This is Bitcoin Core's `CompressAmount`.
+pub fn compress_amount(amount: Amount) -> u64 {
+    let mut n = amount.to_sat();
+    if n == 0 {
+        return 0;
+    }
+    let mut e = 0;
+    while n % 10 == 0 && e < 9 {
+        n /= 10;
+        e += 1;
+    }
+    if e < 9 {
+        let d = n % 10;
+        n /= 10;
+        1 + (n * 9 + d - 1) * 10 + e
+    } else {
+        1 + (n - 1) * 10 + 9
+    }
+}
+
+/// Decompresses an amount compressed with [`compress_amount`].
+///
+/// This is Bitcoin Core's `DecompressAmount`.
+///
+/// # Errors
+///
+/// If the decompressed amount is larger than [`Amount::MAX`].
+pub fn decompress_amount(mut x: u64) -> Result<Amount, ParseError> {
+    const ERR: ParseError = ParseError::ParseFailed("compressed amount out of range");
+
+    if x == 0 {
+        return Ok(Amount::ZERO);
+    }
+    x -= 1;
+    let mut e = x % 10;
+    x /= 10;
+    let mut n = if e < 9 {
+        let d = (x % 9) + 1;
+        x /= 9;
+        x.checked_mul(10).and_then(|n| n.checked_add(d)).ok_or(ERR)?
+    } else {
+        x + 1
+    };
+    while e > 0 {
+        n = n.checked_mul(10).ok_or(ERR)?;
+        e -= 1;
+    }
+    Amount::from_sat(n).map_err(|_| ERR)
+}
+
+/// Writes a compressed script, returning the number of bytes written.
+pub(crate) fn emit_compressed_script<W: Write + ?Sized>(
+    w: &mut W,
+    script: &Script,
+) -> Result<usize, io::Error> {
+    let bytes = script.as_bytes();
+    match bytes.len() {
+        25 if script.is_p2pkh() => {
+            w.emit_u8(0x00)?;
+            w.emit_slice(&bytes[3..23])?;
+            Ok(21)
+        }
+        23 if script.is_p2sh() => {
+            w.emit_u8(0x01)?;
+            w.emit_slice(&bytes[2..22])?;
+            Ok(21)
+        }
+        35 if bytes[0] == 33
+            && matches!(bytes[1], 0x02 | 0x03)
+            && bytes[34] == OP_CHECKSIG.to_u8() =>
+        {
+            w.emit_slice(&bytes[1..34])?;
+            Ok(33)
+        }
+        67 if bytes[0] == 65
+            && bytes[1] == 0x04
+            && bytes[66] == OP_CHECKSIG.to_u8()
+            && secp256k1::PublicKey::from_slice(&bytes[1..66]).is_ok() =>
+        {
+            w.emit_u8(0x04 | (bytes[65] & 0x01))?;
+            w.emit_slice(&bytes[2..34])?;
+            Ok(33)
+        }
+        len => {
+            let size = emit_varint(w, len as u64 + SPECIAL_SCRIPTS)?;
+            Ok(size + w.emit_slice(bytes)?)
+        }
+    }
+}
+
+/// Reads a script compressed with [`emit_compressed_script`].
+///
+/// Like Bitcoin Core, scripts larger than the maximum script size are replaced by `OP_RETURN`.
+pub(crate) fn read_compressed_script<R: BufRead + ?Sized>(
+    r: &mut R,
+) -> Result<ScriptBuf, encode::Error> {
+    let size = read_varint(r)?;
+    let mut bytes = Vec::new();
+    match size {
+        0x00 => {
+            let mut hash = [0; 20];
+            r.read_slice(&mut hash)?;
+            bytes.extend_from_slice(&[OP_DUP.to_u8(), OP_HASH160.to_u8(), 20]);
+            bytes.extend_from_slice(&hash);
+            bytes.extend_from_slice(&[OP_EQUALVERIFY.to_u8(), OP_CHECKSIG.to_u8()]);
+        }
+        0x01 => {
+            let mut hash = [0; 20];
+            r.read_slice(&mut hash)?;
+            bytes.extend_from_slice(&[OP_HASH160.to_u8(), 20]);
+            bytes.extend_from_slice(&hash);
+            bytes.push(OP_EQUAL.to_u8());
+        }
+        0x02 | 0x03 => {
+            let mut key = [size as u8; 33];
+            r.read_slice(&mut key[1..])?;
+            bytes.push(33);
+            bytes.extend_from_slice(&key);
+            bytes.push(OP_CHECKSIG.to_u8());
+        }
+        0x04 | 0x05 => {
+            let mut key = [size as u8 - 2; 33];
+            r.read_slice(&mut key[1..])?;
+            let key = secp256k1::PublicKey::from_slice(&key)
+                .map_err(|_| ParseError::ParseFailed("invalid compressed public key"))?;
+            bytes.push(65);
+            bytes.extend_from_slice(&key.serialize_uncompressed());
+            bytes.push(OP_CHECKSIG.to_u8());
+        }
+        size => {
+            let mut len = size - SPECIAL_SCRIPTS;
+            if len > MAX_SCRIPT_SIZE as u64 {
+                let mut buf = [0; 256];
+                while len > 0 {
+                    let chunk = core::cmp::min(len, buf.len() as u64) as usize;
+                    r.read_slice(&mut buf[..chunk])?;
+                    len -= chunk as u64;
+                }
+                return Ok(ScriptBuf::from_bytes(vec![OP_RETURN.to_u8()]));
+            }
+            bytes.resize(len as usize, 0);
+            r.read_slice(&mut bytes)?;
+        }
+    }
+    Ok(ScriptBuf::from_bytes(bytes))
+}
+
+/// Writes a transaction output with a compressed amount and script.
+pub(crate) fn emit_compressed_txout<W: Write + ?Sized>(
+    w: &mut W,
+    txout: &TxOut,
+) -> Result<usize, io::Error> {
+    let len = emit_varint(w, compress_amount(txout.value))?;
+    Ok(len + emit_compressed_script(w, &txout.script_pubkey)?)
+}
+
+/// Reads a transaction output written with [`emit_compressed_txout`].
+pub(crate) fn read_compressed_txout<R: BufRead + ?Sized>(
+    r: &mut R,
+) -> Result<TxOut, encode::Error> {
+    let value = decompress_amount(read_varint(r)?)?;
+    let script_pubkey = read_compressed_script(r)?;
+    Ok(TxOut { value, script_pubkey })
+}
+
+/// An integer encoded as Bitcoin Core's MSB base-128 `VARINT`.
+///
+/// Unlike the [`compact_size`](internals::compact_size) encoding used by the consensus encoding,
+/// each integer has a single encoding and integers up to 127 take a single byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MsbVarInt(pub u64);
+
+impl Encodable for MsbVarInt {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        emit_varint(w, self.0)
+    }
+}
+
+impl Decodable for MsbVarInt {
+    fn consensus_decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        read_varint(r).map(MsbVarInt)
+    }
+}
+
+/// An amount encoded as the [`MsbVarInt`] of its [`compress_amount`] value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CompressedAmount(pub Amount);
+
+impl Encodable for CompressedAmount {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        emit_varint(w, compress_amount(self.0))
+    }
+}
+
+impl Decodable for CompressedAmount {
+    fn consensus_decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        Ok(CompressedAmount(decompress_amount(read_varint(r)?)?))
+    }
+}
+
+/// A script encoded as Bitcoin Core's `ScriptCompression`.
+///
+/// P2PKH and P2SH scripts are encoded as their hash and P2PK scripts as their compressed public
+/// key, using 21 and 33 bytes. Other scripts are encoded with their [`MsbVarInt`] length.
+///
+/// Like Bitcoin Core, scripts larger than [`MAX_SCRIPT_SIZE`] are decoded as `OP_RETURN`, they
+/// are unspendable anyway.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CompressedScript(pub ScriptBuf);
+
+impl Encodable for CompressedScript {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        emit_compressed_script(w, &self.0)
+    }
+}
+
+impl Decodable for CompressedScript {
+    fn consensus_decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        read_compressed_script(r).map(CompressedScript)
+    }
+}
+
+/// A transaction output encoded as its [`CompressedAmount`] and [`CompressedScript`], Bitcoin
+/// Core's `TxOutCompression`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompressedTxOut(pub TxOut);
+
+impl Encodable for CompressedTxOut {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        emit_compressed_txout(w, &self.0)
+    }
+}
+
+impl Decodable for CompressedTxOut {
+    fn consensus_decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, encode::Error> {
+        read_compressed_txout(r).map(CompressedTxOut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_lit::hex;
+
+    use super::*;
+
+    #[test]
+    fn varint() {
+        // From Bitcoin Core's `serialize_tests`.
+        for (n, encoded) in [
+            (0, &hex!("00")[..]),
+            (0x7f, &hex!("7f")),
+            (0x80, &hex!("8000")),
+            (0x1234, &hex!("a334")),
+            (0xffff, &hex!("82fe7f")),
+            (0x123456, &hex!("c7e756")),
+            (0x80123456, &hex!("86ffc7e756")),
+            (0xffffffff, &hex!("8efefefe7f")),
+            (u64::MAX, &hex!("80fefefefefefefefe7f")),
+        ] {
+            let mut buf = Vec::new();
+            assert_eq!(emit_varint(&mut buf, n).unwrap(), encoded.len());
+            assert_eq!(buf, encoded);
+            assert_eq!(read_varint(&mut &encoded[..]).unwrap(), n);
+        }
+        assert!(read_varint(&mut &hex!("80fefefefefefefeff00")[..]).is_err());
+    }
+
+    #[test]
+    fn amount() {
+        // From Bitcoin Core's `compress_tests`.
+        for (sat, compressed) in [
+            (0, 0x0),
+            (1, 0x1),
+            (1_000_000, 0x7),
+            (100_000_000, 0x9),
+            (5_000_000_000, 0x32),
+            (2_100_000_000_000_000, 0x1406f40),
+        ] {
+            let amount = Amount::from_sat(sat).unwrap();
+            assert_eq!(compress_amount(amount), compressed);
+            assert_eq!(decompress_amount(compressed).unwrap(), amount);
+        }
+        for sat in (0..100_000).chain((0..100_000).map(|n| n * 1_000_000)) {
+            let amount = Amount::from_sat(sat).unwrap();
+            assert_eq!(decompress_amount(compress_amount(amount)).unwrap(), amount);
+        }
+        assert!(decompress_amount(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn script() {
+        let uncompressed_key = hex!("04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f");
+        let compressed_key =
+            hex!("02678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb6");
+        for (script, size) in [
+            (hex!("76a914000102030405060708090a0b0c0d0e0f1011121388ac").to_vec(), 21),
+            (hex!("a914000102030405060708090a0b0c0d0e0f1011121387").to_vec(), 21),
+            ([&[0x21][..], &compressed_key, &[0xac]].concat(), 33),
+            ([&[0x41][..], &uncompressed_key, &[0xac]].concat(), 33),
+            (hex!("0014000102030405060708090a0b0c0d0e0f10111213").to_vec(), 23),
+            (vec![], 1),
+        ] {
+            let script = ScriptBuf::from_bytes(script);
+            let mut buf = Vec::new();
+            assert_eq!(emit_compressed_script(&mut buf, &script).unwrap(), size);
+            assert_eq!(buf.len(), size);
+            assert_eq!(read_compressed_script(&mut &buf[..]).unwrap(), script);
+        }
+    }
+
+    #[test]
+    fn encodable() {
+        let txout = TxOut {
+            value: Amount::from_int_btc(50_u16),
+            script_pubkey: ScriptBuf::from_bytes(
+                hex!("76a914000102030405060708090a0b0c0d0e0f1011121388ac").to_vec(),
+            ),
+        };
+        let data = encode::serialize(&CompressedTxOut(txout.clone()));
+        assert_eq!(data[..2], hex!("3200"));
+        assert_eq!(data.len(), 22);
+        assert_eq!(encode::deserialize::<CompressedTxOut>(&data).unwrap().0, txout);
+
+        assert_eq!(encode::serialize(&MsbVarInt(0x80)), hex!("8000"));
+        assert_eq!(encode::deserialize::<MsbVarInt>(&hex!("8000")).unwrap(), MsbVarInt(0x80));
+        assert_eq!(encode::serialize(&CompressedAmount(txout.value)), hex!("32"));
+        let script = encode::serialize(&CompressedScript(txout.script_pubkey.clone()));
+        assert_eq!(script, data[1..]);
+    }
+}
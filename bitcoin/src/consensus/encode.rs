@@ -32,6 +32,11 @@
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 pub use super::{Error, FromHexError, ParseError, DeserializeError};
+#[rustfmt::skip]
+pub use super::compress::{
+    compress_amount, decompress_amount, CompressedAmount, CompressedScript, CompressedTxOut,
+    MsbVarInt,
+};
 
 /// Encodes an object into a vector.
 pub fn serialize<T: Encodable + ?Sized>(data: &T) -> Vec<u8> {
@@ -5,6 +5,7 @@
 //! This module defines structures, functions, and traits that are needed to
 //! conform to Bitcoin consensus.
 
+pub(crate) mod compress;
 pub mod encode;
 mod error;
 #[cfg(feature = "serde")]
@@ -19,7 +19,7 @@
 
 use crate::crypto::ecdsa;
 use crate::internal_macros::impl_asref_push_bytes;
-use crate::network::NetworkKind;
+use crate::network::{CustomNetwork, NetworkKind};
 use crate::prelude::{DisplayHex, String, Vec};
 use crate::script::{self, ScriptBuf};
 use crate::taproot::{TapNodeHash, TapTweakHash};
@@ -30,6 +30,11 @@
 pub use secp256k1::rand;
 pub use serialized_x_only::SerializedXOnlyPublicKey;
 
+/// The WIF version byte of private keys on the Bitcoin network.
+pub(crate) const WIF_PREFIX_MAIN: u8 = 128;
+/// The WIF version byte of private keys on any of the test networks.
+pub(crate) const WIF_PREFIX_TEST: u8 = 239;
+
 /// A Bitcoin Schnorr X-only public key used for BIP340 signatures.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -588,10 +593,16 @@ pub fn from_slice(
     }
 
     /// Formats the private key to WIF format.
-    #[rustfmt::skip]
     pub fn fmt_wif(&self, fmt: &mut dyn fmt::Write) -> fmt::Result {
+        let prefix = if self.network.is_mainnet() { WIF_PREFIX_MAIN } else { WIF_PREFIX_TEST };
+        self.fmt_wif_with_prefix(fmt, prefix)
+    }
+
+    /// Formats the private key to WIF format with the version byte `prefix`.
+    #[rustfmt::skip]
+    fn fmt_wif_with_prefix(&self, fmt: &mut dyn fmt::Write, prefix: u8) -> fmt::Result {
         let mut ret = [0; 34];
-        ret[0] = if self.network.is_mainnet() { 128 } else { 239 };
+        ret[0] = prefix;
 
         ret[1..33].copy_from_slice(&self.inner[..]);
         let privkey = if self.compressed {
@@ -611,8 +622,38 @@ pub fn to_wif(self) -> String {
         buf
     }
 
+    /// Gets the WIF encoding of this private key on the custom `network`.
+    pub fn to_wif_custom(self, network: &CustomNetwork) -> String {
+        let mut buf = String::new();
+        self.fmt_wif_with_prefix(&mut buf, network.wif_prefix).unwrap();
+        buf
+    }
+
     /// Parses the WIF encoded private key.
     pub fn from_wif(wif: &str) -> Result<PrivateKey, FromWifError> {
+        PrivateKey::decode_wif(wif, |prefix| match prefix {
+            WIF_PREFIX_MAIN => Some(NetworkKind::Main),
+            WIF_PREFIX_TEST => Some(NetworkKind::Test),
+            _ => None,
+        })
+    }
+
+    /// Parses the WIF encoded private key of the custom `network`.
+    pub fn from_wif_custom(wif: &str, network: &CustomNetwork) -> Result<PrivateKey, FromWifError> {
+        PrivateKey::decode_wif(wif, |prefix| {
+            if prefix == network.wif_prefix {
+                Some(network.network_kind())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Parses the WIF encoded private key, `network` returns the network of a version byte.
+    fn decode_wif<F>(wif: &str, network: F) -> Result<PrivateKey, FromWifError>
+    where
+        F: FnOnce(u8) -> Option<NetworkKind>,
+    {
         let data = base58::decode_check(wif)?;
 
         let (compressed, data) = if let Ok(data) = <&[u8; 33]>::try_from(&*data) {
@@ -627,14 +668,8 @@ pub fn from_wif(wif: &str) -> Result<PrivateKey, FromWifError> {
             return Err(InvalidBase58PayloadLengthError { length: data.len() }.into());
         };
 
-        let (network, key) = data.split_first();
-        let network = match *network {
-            128 => NetworkKind::Main,
-            239 => NetworkKind::Test,
-            invalid => {
-                return Err(InvalidAddressVersionError { invalid }.into());
-            }
-        };
+        let (&prefix, key) = data.split_first();
+        let network = network(prefix).ok_or(InvalidAddressVersionError { invalid: prefix })?;
 
         Ok(PrivateKey { compressed, network, inner: secp256k1::SecretKey::from_byte_array(key)? })
     }
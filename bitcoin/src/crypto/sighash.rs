@@ -245,7 +245,7 @@ fn get_all(&self) -> Result<&[T], PrevoutsKindError> {
         }
     }
 
-    fn get(&self, input_index: usize) -> Result<&TxOut, PrevoutsIndexError> {
+    pub(crate) fn get(&self, input_index: usize) -> Result<&TxOut, PrevoutsIndexError> {
         match self {
             Prevouts::One(index, prevout) =>
                 if input_index == *index {
@@ -821,10 +821,34 @@ pub fn segwit_v0_encode_signing_data_to<W: Write + ?Sized>(
         script_code: &Script,
         value: Amount,
         sighash_type: EcdsaSighashType,
+    ) -> Result<(), SigningDataError<transaction::InputsIndexError>> {
+        self.segwit_v0_encode_signing_data_to_consensus(
+            writer,
+            input_index,
+            script_code,
+            value,
+            sighash_type.to_u32(),
+        )
+    }
+
+    /// Encodes the BIP143 signing data for a raw `u32` sighash flag.
+    ///
+    /// Like Bitcoin Core, the flag is only masked when deciding which parts of the transaction to
+    /// commit to and is otherwise hashed as is, so this also works for non-standard flags found in
+    /// consensus-valid signatures.
+    fn segwit_v0_encode_signing_data_to_consensus<W: Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        input_index: usize,
+        script_code: &Script,
+        value: Amount,
+        sighash_type: u32,
     ) -> Result<(), SigningDataError<transaction::InputsIndexError>> {
         let zero_hash = [0; 32];
 
-        let (sighash, anyone_can_pay) = sighash_type.split_anyonecanpay_flag();
+        let anyone_can_pay = sighash_type & 0x80 == 0x80;
+        let single = sighash_type & 0x1f == EcdsaSighashType::Single.to_u32();
+        let none = sighash_type & 0x1f == EcdsaSighashType::None.to_u32();
 
         self.tx.borrow().version.consensus_encode(writer)?;
 
@@ -834,10 +858,7 @@ pub fn segwit_v0_encode_signing_data_to<W: Write + ?Sized>(
             zero_hash.consensus_encode(writer)?;
         }
 
-        if !anyone_can_pay
-            && sighash != EcdsaSighashType::Single
-            && sighash != EcdsaSighashType::None
-        {
+        if !anyone_can_pay && !single && !none {
             self.segwit_cache().sequences.consensus_encode(writer)?;
         } else {
             zero_hash.consensus_encode(writer)?;
@@ -851,10 +872,9 @@ pub fn segwit_v0_encode_signing_data_to<W: Write + ?Sized>(
             txin.sequence.consensus_encode(writer)?;
         }
 
-        if sighash != EcdsaSighashType::Single && sighash != EcdsaSighashType::None {
+        if !single && !none {
             self.segwit_cache().outputs.consensus_encode(writer)?;
-        } else if sighash == EcdsaSighashType::Single && input_index < self.tx.borrow().output.len()
-        {
+        } else if single && input_index < self.tx.borrow().output.len() {
             let mut single_enc = LegacySighash::engine();
             self.tx.borrow().output[input_index].consensus_encode(&mut single_enc)?;
             let hash = LegacySighash::from_engine(single_enc);
@@ -864,7 +884,7 @@ pub fn segwit_v0_encode_signing_data_to<W: Write + ?Sized>(
         }
 
         self.tx.borrow().lock_time.consensus_encode(writer)?;
-        sighash_type.to_u32().consensus_encode(writer)?;
+        sighash_type.consensus_encode(writer)?;
         Ok(())
     }
 
@@ -916,6 +936,26 @@ pub fn p2wsh_signature_hash(
         Ok(SegwitV0Sighash::from_engine(enc))
     }
 
+    /// Computes the BIP143 sighash for a raw `u32` sighash flag, as done during script execution.
+    pub(crate) fn segwit_v0_signature_hash_consensus(
+        &mut self,
+        input_index: usize,
+        script_code: &Script,
+        value: Amount,
+        sighash_type: u32,
+    ) -> Result<SegwitV0Sighash, transaction::InputsIndexError> {
+        let mut enc = SegwitV0Sighash::engine();
+        self.segwit_v0_encode_signing_data_to_consensus(
+            &mut enc,
+            input_index,
+            script_code,
+            value,
+            sighash_type,
+        )
+        .map_err(SigningDataError::unwrap_sighash)?;
+        Ok(SegwitV0Sighash::from_engine(enc))
+    }
+
     /// Encodes the legacy signing data from which a signature hash for a given input index with a
     /// given sighash flag can be computed.
     ///
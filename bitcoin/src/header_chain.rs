@@ -0,0 +1,631 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Block header chain validation.
+//!
+//! Computes the proof of work required of the next block header, following the difficulty
+//! adjustment rules of each network, and verifies chains of block headers.
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::block::{Header, HeaderExt as _, Version};
+//! use bitcoin::constants::genesis_block;
+//! use bitcoin::header_chain::HeaderChain;
+//! use bitcoin::network::Params;
+//! use bitcoin::{BlockTime, TxMerkleNode};
+//!
+//! let mut chain = HeaderChain::new(&Params::REGTEST);
+//! assert_eq!(chain.tip_hash(), genesis_block(&Params::REGTEST).block_hash());
+//!
+//! // Mine a header on top of the genesis block.
+//! let time = BlockTime::from_u32(chain.tip().time.to_u32() + 600);
+//! let mut header = Header {
+//!     version: Version::NO_SOFT_FORK_SIGNALLING,
+//!     prev_blockhash: chain.tip_hash(),
+//!     merkle_root: TxMerkleNode::from_byte_array([0; 32]),
+//!     time,
+//!     bits: chain.next_work_required(time),
+//!     nonce: 0,
+//! };
+//! while !header.target().is_met_by(header.block_hash()) {
+//!     header.nonce += 1;
+//! }
+//!
+//! // Headers received from a peer are connected to the tip.
+//! let work = chain.connect_headers(vec![header]).expect("valid headers");
+//! assert_eq!(work, chain.chain_work());
+//! assert_eq!(chain.tip_hash(), header.block_hash());
+//! ```
+
+use core::fmt;
+
+use units::{BlockHeight, BlockTime};
+
+use crate::block::{BlockHash, Header, HeaderExt as _};
+use crate::constants::genesis_block;
+use crate::network::Params;
+use crate::pow::{CompactTarget, CompactTargetExt as _, Work};
+use crate::prelude::Vec;
+use crate::timelock::{MtpTracker, MEDIAN_TIME_SPAN};
+
+/// The maximum number of seconds the first header of a difficulty adjustment period may be older
+/// than the previous header (BIP-94).
+pub const MAX_TIMEWARP: u32 = 600;
+
+/// Computes the target required of the header following the last one in `ancestors`.
+///
+/// `ancestors` ends with the tip, at height `tip_height`, and must reach back to the first header
+/// of the tip's difficulty adjustment period. `next_time` is the timestamp of the next header, on
+/// networks allowing minimum difficulty blocks it determines whether one may be mined.
+///
+/// # Errors
+///
+/// If `ancestors` does not reach back far enough.
+pub fn next_work_required(
+    ancestors: &[Header],
+    tip_height: BlockHeight,
+    next_time: BlockTime,
+    params: impl AsRef<Params>,
+) -> Result<CompactTarget, MissingAncestorsError> {
+    let params = params.as_ref();
+    let interval = params.difficulty_adjustment_interval() as u32;
+    let tip_height = tip_height.to_u32();
+
+    // The headers of the tip's difficulty adjustment period.
+    let required = (tip_height % interval) as usize + 1;
+    if ancestors.len() < required {
+        return Err(MissingAncestorsError { required, available: ancestors.len() });
+    }
+    let period = &ancestors[ancestors.len() - required..];
+    let tip = period[period.len() - 1];
+
+    if (tip_height + 1) % interval != 0 {
+        if !params.allow_min_difficulty_blocks {
+            return Ok(tip.bits);
+        }
+        let max_target = params.max_attainable_target.to_compact_lossy();
+        // A header more than twice the target spacing after the tip may use the minimum difficulty.
+        if u64::from(next_time.to_u32())
+            > u64::from(tip.time.to_u32()) + 2 * params.pow_target_spacing
+        {
+            return Ok(max_target);
+        }
+        // Otherwise the last target not set by the rule above applies.
+        let last = period[1..].iter().rev().find(|header| header.bits != max_target);
+        return Ok(last.unwrap_or(&period[0]).bits);
+    }
+
+    if params.no_pow_retargeting {
+        return Ok(tip.bits);
+    }
+    let first = period[0];
+    let timespan = i64::from(tip.time.to_u32()) - i64::from(first.time.to_u32());
+    // BIP-94 adjusts the first target of the period, which cannot be lowered by the rule above.
+    let bits = if params.enforce_bip94 { first.bits } else { tip.bits };
+    Ok(CompactTarget::from_next_work_required(bits, timespan, params))
+}
+
+/// A chain of validated block headers.
+///
+/// Only the most recent headers are kept, enough to validate the next header.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    params: Params,
+    /// The most recent headers, ending with the tip.
+    headers: Vec<Header>,
+    tip_height: BlockHeight,
+    tip_hash: BlockHash,
+    chain_work: Work,
+    mtp: MtpTracker,
+}
+
+impl HeaderChain {
+    /// Constructs a new chain consisting of the genesis block header of the network.
+    pub fn new(params: impl AsRef<Params>) -> Self {
+        let params = params.as_ref();
+        let genesis = *genesis_block(params).header();
+        let mut mtp = MtpTracker::new();
+        mtp.push(&genesis);
+        HeaderChain {
+            params: params.clone(),
+            headers: vec![genesis],
+            tip_height: BlockHeight::ZERO,
+            tip_hash: genesis.block_hash(),
+            chain_work: genesis.work(),
+            mtp,
+        }
+    }
+
+    /// Constructs a new chain from trusted headers, ending with the tip at height `tip_height`.
+    ///
+    /// `headers` must reach back to the first header of the tip's difficulty adjustment period,
+    /// and include at least the eleven most recent headers. `chain_work` is the total work of the
+    /// chain up to and including the tip.
+    ///
+    /// # Errors
+    ///
+    /// If `headers` does not reach back far enough.
+    pub fn from_headers(
+        headers: Vec<Header>,
+        tip_height: BlockHeight,
+        chain_work: Work,
+        params: impl AsRef<Params>,
+    ) -> Result<Self, MissingAncestorsError> {
+        let params = params.as_ref();
+        let interval = params.difficulty_adjustment_interval() as u32;
+        let tip_height_u32 = tip_height.to_u32();
+        let required = ((tip_height_u32 % interval) as usize + 1)
+            .max(MEDIAN_TIME_SPAN.min(tip_height_u32 as usize + 1));
+        let tip = match headers.last() {
+            Some(tip) if headers.len() >= required => *tip,
+            _ => return Err(MissingAncestorsError { required, available: headers.len() }),
+        };
+        let mut mtp = MtpTracker::new();
+        mtp.extend(&headers[headers.len().saturating_sub(MEDIAN_TIME_SPAN)..]);
+        Ok(HeaderChain {
+            params: params.clone(),
+            headers,
+            tip_height,
+            tip_hash: tip.block_hash(),
+            chain_work,
+            mtp,
+        })
+    }
+
+    /// Returns the parameters of the network.
+    pub fn params(&self) -> &Params { &self.params }
+
+    /// Returns the header at the tip of the chain.
+    pub fn tip(&self) -> &Header { self.headers.last().expect("chain is never empty") }
+
+    /// Returns the height of the tip of the chain.
+    pub fn tip_height(&self) -> BlockHeight { self.tip_height }
+
+    /// Returns the block hash of the tip of the chain.
+    pub fn tip_hash(&self) -> BlockHash { self.tip_hash }
+
+    /// Returns the total work of the chain, including the genesis block.
+    pub fn chain_work(&self) -> Work { self.chain_work }
+
+    /// Returns the target required of the next header, given its timestamp.
+    pub fn next_work_required(&self, next_time: BlockTime) -> CompactTarget {
+        next_work_required(&self.headers, self.tip_height, next_time, &self.params)
+            .expect("the chain keeps enough headers")
+    }
+
+    /// Returns the median timestamp of the last eleven headers.
+    ///
+    /// The timestamp of the next header must be greater than this.
+    pub fn median_time_past(&self) -> BlockTime {
+        let median_time_past = self.mtp.median_time_past().expect("chain is never empty");
+        BlockTime::from_u32(median_time_past.to_u32())
+    }
+
+    /// Validates `header` and connects it to the tip of the chain.
+    ///
+    /// The timestamp is not checked against the current time, which is up to the caller.
+    pub fn connect(&mut self, header: Header) -> Result<(), ConnectHeaderError> {
+        if header.prev_blockhash != self.tip_hash {
+            return Err(ConnectHeaderError::PrevBlockHash);
+        }
+        let height = BlockHeight::from_u32(self.tip_height.to_u32() + 1);
+
+        let required = self.next_work_required(header.time);
+        if header.bits != required {
+            return Err(ConnectHeaderError::BadTarget { required, actual: header.bits });
+        }
+        let block_hash = header.block_hash();
+        if !header.target().is_met_by(block_hash) {
+            return Err(ConnectHeaderError::BadProofOfWork);
+        }
+
+        let median_time_past = self.median_time_past();
+        if header.time <= median_time_past {
+            return Err(ConnectHeaderError::TimeTooOld { time: header.time, median_time_past });
+        }
+        let interval = self.params.difficulty_adjustment_interval() as u32;
+        if self.params.enforce_bip94
+            && height.to_u32() % interval == 0
+            && header.time.to_u32() < self.tip().time.to_u32().saturating_sub(MAX_TIMEWARP)
+        {
+            return Err(ConnectHeaderError::TimeWarp {
+                time: header.time,
+                prev_time: self.tip().time,
+            });
+        }
+
+        let version = header.version.to_consensus();
+        if (version < 2 && height >= self.params.bip34_height)
+            || (version < 3 && height >= self.params.bip66_height)
+            || (version < 4 && height >= self.params.bip65_height)
+        {
+            return Err(ConnectHeaderError::ObsoleteVersion(version));
+        }
+
+        self.headers.push(header);
+        self.mtp.push(&header);
+        let keep = (interval as usize).max(MEDIAN_TIME_SPAN);
+        if self.headers.len() > 2 * keep {
+            self.headers.drain(..self.headers.len() - keep);
+        }
+        self.tip_height = height;
+        self.tip_hash = block_hash;
+        self.chain_work = self.chain_work + header.work();
+        Ok(())
+    }
+
+    /// Validates and connects a sequence of headers, returning the total work of the chain.
+    ///
+    /// On error the headers before the invalid one stay connected, the invalid header is the one
+    /// at height `tip_height() + 1`.
+    pub fn connect_headers<I>(&mut self, headers: I) -> Result<Work, ConnectHeaderError>
+    where
+        I: IntoIterator<Item = Header>,
+    {
+        for header in headers {
+            self.connect(header)?;
+        }
+        Ok(self.chain_work)
+    }
+}
+
+/// Not enough ancestor headers were provided to compute the next target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MissingAncestorsError {
+    /// The number of headers required.
+    pub required: usize,
+    /// The number of headers provided.
+    pub available: usize,
+}
+
+impl fmt::Display for MissingAncestorsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ancestor headers required but only {} provided",
+            self.required, self.available
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MissingAncestorsError {}
+
+/// An error connecting a header to a [`HeaderChain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectHeaderError {
+    /// The header does not build on the tip of the chain.
+    PrevBlockHash,
+    /// The `bits` field of the header is not the required target.
+    BadTarget {
+        /// The target required by the difficulty adjustment rules.
+        required: CompactTarget,
+        /// The target of the header.
+        actual: CompactTarget,
+    },
+    /// The header hash is not below the target.
+    BadProofOfWork,
+    /// The timestamp is not after the median time past.
+    TimeTooOld {
+        /// The timestamp of the header.
+        time: BlockTime,
+        /// The median timestamp of the previous eleven headers.
+        median_time_past: BlockTime,
+    },
+    /// The first header of a difficulty adjustment period goes back too far (BIP-94).
+    TimeWarp {
+        /// The timestamp of the header.
+        time: BlockTime,
+        /// The timestamp of the previous header.
+        prev_time: BlockTime,
+    },
+    /// The header version was deprecated by BIP-34, BIP-66 or BIP-65.
+    ObsoleteVersion(i32),
+}
+
+impl fmt::Display for ConnectHeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ConnectHeaderError::*;
+
+        match *self {
+            PrevBlockHash => f.write_str("header does not build on the tip of the chain"),
+            BadTarget { required, actual } => write!(
+                f,
+                "header target {:#x} does not match the required target {:#x}",
+                actual, required
+            ),
+            BadProofOfWork => f.write_str("header hash is not below the target"),
+            TimeTooOld { time, median_time_past } => write!(
+                f,
+                "header time {} is not after the median time past {}",
+                time.to_u32(),
+                median_time_past.to_u32()
+            ),
+            TimeWarp { time, prev_time } => write!(
+                f,
+                "header time {} is more than {} seconds before the previous header time {}",
+                time.to_u32(),
+                MAX_TIMEWARP,
+                prev_time.to_u32()
+            ),
+            ObsoleteVersion(version) => write!(f, "obsolete header version {}", version),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConnectHeaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ConnectHeaderError::*;
+
+        match *self {
+            PrevBlockHash
+            | BadTarget { .. }
+            | BadProofOfWork
+            | TimeTooOld { .. }
+            | TimeWarp { .. }
+            | ObsoleteVersion(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Version;
+    use crate::{Network, TestnetVersion, TxMerkleNode};
+
+    fn header(time: u32, bits: u32) -> Header {
+        Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: BlockHash::from_byte_array([0; 32]),
+            merkle_root: TxMerkleNode::from_byte_array([0; 32]),
+            time: BlockTime::from_u32(time),
+            bits: CompactTarget::from_consensus(bits),
+            nonce: 0,
+        }
+    }
+
+    /// Returns the headers of a whole difficulty adjustment period, 10 minutes apart.
+    fn period(start_time: u32, bits: u32) -> Vec<Header> {
+        (0..2016).map(|i| header(start_time + i * 600, bits)).collect()
+    }
+
+    #[test]
+    fn retarget() {
+        let params = Params::new(Network::Signet);
+        let mut headers = period(1598918400, 503543726);
+        // Signet blocks 0 and 2015, retargeting to the target of block 2016.
+        headers[2015].time = BlockTime::from_u32(1599332177);
+        let next = BlockTime::from_u32(1599332844);
+
+        let bits =
+            next_work_required(&headers, BlockHeight::from_u32(2015), next, &params).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(503394215));
+        // No adjustment within a period.
+        let bits =
+            next_work_required(&headers, BlockHeight::from_u32(4030), next, &params).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(503543726));
+
+        assert_eq!(
+            next_work_required(&headers[1..], BlockHeight::from_u32(2015), next, &params),
+            Err(MissingAncestorsError { required: 2016, available: 2015 })
+        );
+        // Only the tip is needed at the start of a period.
+        let bits = next_work_required(&headers[2015..], BlockHeight::from_u32(2016), next, &params);
+        assert_eq!(bits, Ok(CompactTarget::from_consensus(503543726)));
+    }
+
+    #[test]
+    fn testnet3_min_difficulty() {
+        let params = Params::new(Network::Testnet(TestnetVersion::V3));
+        let min_bits = 0x1d00ffff;
+        let mut headers = period(1_700_000_000, 0x1c0ffff0);
+        for header in &mut headers[2000..] {
+            header.bits = CompactTarget::from_consensus(min_bits);
+        }
+        let tip_height = BlockHeight::from_u32(2016 + 2010);
+        let tip_time = headers[2010].time.to_u32();
+
+        // More than 20 minutes after the tip.
+        let next = BlockTime::from_u32(tip_time + 1201);
+        let bits = next_work_required(&headers[..2011], tip_height, next, &params).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(min_bits));
+
+        // Otherwise the last target which is not the minimum difficulty.
+        let next = BlockTime::from_u32(tip_time + 1200);
+        let bits = next_work_required(&headers[..2011], tip_height, next, &params).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(0x1c0ffff0));
+
+        // Or the first target of the period.
+        for header in &mut headers {
+            header.bits = CompactTarget::from_consensus(min_bits);
+        }
+        let bits = next_work_required(&headers[..2011], tip_height, next, &params).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(min_bits));
+    }
+
+    #[test]
+    fn testnet4_retargets_from_first_header() {
+        let testnet3 = Params::new(Network::Testnet(TestnetVersion::V3));
+        let testnet4 = Params::new(Network::Testnet(TestnetVersion::V4));
+        let mut headers = period(1_700_000_000, 0x1c0ffff0);
+        // The last header was mined with the minimum difficulty, exactly two weeks after the first.
+        headers[2015].bits = CompactTarget::from_consensus(0x1d00ffff);
+        headers[2015].time = BlockTime::from_u32(1_700_000_000 + 14 * 24 * 60 * 60);
+        let tip_height = BlockHeight::from_u32(2015);
+        let next = headers[2015].time;
+
+        let bits = next_work_required(&headers, tip_height, next, &testnet4).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(0x1c0ffff0));
+        let bits = next_work_required(&headers, tip_height, next, &testnet3).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(0x1d00ffff));
+    }
+
+    #[test]
+    fn regtest_no_retargeting() {
+        let params = Params::new(Network::Regtest);
+        // Blocks ten times faster than the target spacing.
+        let headers =
+            (0..2016).map(|i| header(1_296_688_602 + i * 60, 0x207fffff)).collect::<Vec<_>>();
+        let next = BlockTime::from_u32(1_296_688_602 + 2016 * 60);
+        let bits =
+            next_work_required(&headers, BlockHeight::from_u32(2015), next, &params).unwrap();
+        assert_eq!(bits, CompactTarget::from_consensus(0x207fffff));
+    }
+
+    /// Mines a header building on the tip of `chain`.
+    fn mine(chain: &HeaderChain, time: u32) -> Header {
+        let mut header = Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: chain.tip_hash(),
+            merkle_root: TxMerkleNode::from_byte_array([0; 32]),
+            time: BlockTime::from_u32(time),
+            bits: chain.next_work_required(BlockTime::from_u32(time)),
+            nonce: 0,
+        };
+        while !header.target().is_met_by(header.block_hash()) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn connect_headers() {
+        // Regtest with retargeting every 8 blocks.
+        let params = Params {
+            bip66_height: BlockHeight::from_u32(1),
+            pow_target_timespan: 8 * 600,
+            allow_min_difficulty_blocks: false,
+            no_pow_retargeting: false,
+            ..Params::REGTEST
+        };
+        let mut chain = HeaderChain::new(&params);
+        let genesis_time = chain.tip().time.to_u32();
+
+        // Blocks twice as fast as the target spacing increase the difficulty.
+        let mut headers = vec![];
+        let mut validator = chain.clone();
+        for i in 1..=10 {
+            let header = mine(&validator, genesis_time + i * 300);
+            validator.connect(header).unwrap();
+            headers.push(header);
+        }
+        // The header at height 8 is the first of the second period.
+        let genesis_bits = chain.tip().bits;
+        assert_eq!(headers[6].bits, genesis_bits);
+        assert_eq!(
+            headers[7].bits,
+            CompactTarget::from_next_work_required(genesis_bits, 7 * 300, &params)
+        );
+        assert!(headers[7].target() < headers[6].target());
+
+        let work = chain.connect_headers(headers.clone()).unwrap();
+        assert_eq!(chain.tip_height(), BlockHeight::from_u32(10));
+        assert_eq!(chain.tip_hash(), headers[9].block_hash());
+        let expected =
+            headers.iter().fold(genesis_block(&params).header().work(), |work, h| work + h.work());
+        assert_eq!(work, expected);
+        assert!(headers[9].work() > headers[0].work());
+
+        // Invalid headers.
+        let mut bad = mine(&chain, genesis_time + 3300);
+        bad.prev_blockhash = headers[8].block_hash();
+        assert_eq!(chain.connect(bad), Err(ConnectHeaderError::PrevBlockHash));
+
+        let mut bad = mine(&chain, genesis_time + 3300);
+        bad.bits = headers[0].bits;
+        assert!(matches!(chain.connect(bad), Err(ConnectHeaderError::BadTarget { .. })));
+
+        let mut bad = mine(&chain, genesis_time + 3300);
+        while bad.target().is_met_by(bad.block_hash()) {
+            bad.nonce += 1;
+        }
+        assert_eq!(chain.connect(bad), Err(ConnectHeaderError::BadProofOfWork));
+
+        let median_time_past = chain.median_time_past();
+        assert_eq!(median_time_past.to_u32(), genesis_time + 5 * 300);
+        let bad = mine(&chain, median_time_past.to_u32());
+        assert_eq!(
+            chain.connect(bad),
+            Err(ConnectHeaderError::TimeTooOld { time: median_time_past, median_time_past })
+        );
+
+        let mut bad = mine(&chain, genesis_time + 3300);
+        bad.version = Version::from_consensus(2);
+        while !bad.target().is_met_by(bad.block_hash()) {
+            bad.nonce += 1;
+        }
+        assert_eq!(chain.connect(bad), Err(ConnectHeaderError::ObsoleteVersion(2)));
+
+        // Nothing was connected.
+        assert_eq!(chain.tip_hash(), headers[9].block_hash());
+        assert_eq!(chain.chain_work(), work);
+    }
+
+    #[test]
+    fn bip94_timewarp() {
+        let params = Params {
+            pow_target_timespan: 8 * 600,
+            allow_min_difficulty_blocks: false,
+            no_pow_retargeting: false,
+            enforce_bip94: true,
+            ..Params::REGTEST
+        };
+        let mut chain = HeaderChain::new(&params);
+        let genesis_time = chain.tip().time.to_u32();
+        for i in 1..8 {
+            chain.connect(mine(&chain, genesis_time + i * 600)).unwrap();
+        }
+        let prev_time = genesis_time + 7 * 600;
+
+        let header = mine(&chain, prev_time - MAX_TIMEWARP - 1);
+        assert_eq!(
+            chain.connect(header),
+            Err(ConnectHeaderError::TimeWarp {
+                time: BlockTime::from_u32(prev_time - MAX_TIMEWARP - 1),
+                prev_time: BlockTime::from_u32(prev_time),
+            })
+        );
+        chain.connect(mine(&chain, prev_time - MAX_TIMEWARP)).unwrap();
+    }
+
+    #[test]
+    fn from_headers() {
+        let params = Params::new(Network::Bitcoin);
+        let headers = period(1_700_000_000, 0x17034219);
+        let work = Work::from_be_bytes([0xff; 32]);
+
+        let chain = HeaderChain::from_headers(
+            headers[..5].to_vec(),
+            BlockHeight::from_u32(4),
+            work,
+            &params,
+        )
+        .unwrap();
+        assert_eq!(chain.tip_hash(), headers[4].block_hash());
+        assert_eq!(
+            HeaderChain::from_headers(
+                headers[..5].to_vec(),
+                BlockHeight::from_u32(2016 * 2 - 1),
+                work,
+                &params
+            )
+            .unwrap_err(),
+            MissingAncestorsError { required: 2016, available: 5 }
+        );
+        // The median time past needs eleven headers.
+        assert_eq!(
+            HeaderChain::from_headers(
+                headers[..5].to_vec(),
+                BlockHeight::from_u32(2016 * 2 + 1),
+                work,
+                &params
+            )
+            .unwrap_err(),
+            MissingAncestorsError { required: 11, available: 5 }
+        );
+    }
+}
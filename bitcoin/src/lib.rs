@@ -97,22 +97,39 @@
 pub mod p2p;
 pub mod address;
 pub mod bip152;
+#[cfg(feature = "std")]
+pub mod bip157;
 pub mod bip158;
+pub mod bip21;
 pub mod bip32;
+pub mod bip322;
+pub mod bip352;
+#[cfg(feature = "std")]
+pub mod bip37;
+pub mod bip39;
+pub mod block_file;
+pub mod block_validation;
 pub mod blockdata;
+pub mod coin_selection;
 pub mod consensus;
 #[cfg(feature = "bitcoinconsensus")]
 pub mod consensus_validation;
 // Private until we either make this a crate or flatten it - still to be decided.
 pub(crate) mod crypto;
 pub mod hash_types;
+pub mod header_chain;
 pub mod merkle_tree;
+pub mod mining;
 pub mod network;
 pub mod policy;
 pub mod pow;
 pub mod psbt;
 pub mod sign_message;
+pub mod signet;
 pub mod taproot;
+pub mod timelock;
+pub mod utxo_snapshot;
+pub mod versionbits;
 
 // Re-export the type from where it is defined but the module from the highest place up the stack
 // that it is available in the event that we add some functionality there.
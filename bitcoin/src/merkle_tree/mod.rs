@@ -113,3 +113,53 @@ fn combine(&self, other: &Self) -> Self {
         Self::from_byte_array(sha256d::Hash::from_engine(encoder).to_byte_array())
     }
 }
+
+/// Computes the Merkle branch of the coinbase, as used by Stratum mining pools.
+///
+/// `txids` are the TXIDs of all transactions of the block, starting with the coinbase whose TXID
+/// is not used. The branch holds the nodes to combine with the coinbase TXID at each level of the
+/// tree, from the leaves up, see [`root_from_coinbase_branch`].
+pub fn coinbase_branch<I: IntoIterator<Item = Txid>>(txids: I) -> Vec<TxMerkleNode> {
+    let mut level: Vec<TxMerkleNode> =
+        txids.into_iter().skip(1).map(TxMerkleNode::from_leaf).collect();
+    let mut branch = Vec::new();
+    // The coinbase is left of `level` at each level and is combined with its first node.
+    while let Some((&first, rest)) = level.split_first() {
+        branch.push(first);
+        level = rest.chunks(2).map(|pair| pair[0].combine(pair.last().expect("chunk"))).collect();
+    }
+    branch
+}
+
+/// Computes the Merkle root of a block from its coinbase TXID and the coinbase Merkle branch.
+///
+/// This is used after changing the coinbase, e.g. rolling its extranonce, to recompute the
+/// Merkle root without the other transactions.
+pub fn root_from_coinbase_branch(coinbase_txid: Txid, branch: &[TxMerkleNode]) -> TxMerkleNode {
+    branch
+        .iter()
+        .fold(TxMerkleNode::from_leaf(coinbase_txid), |node, sibling| node.combine(sibling))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coinbase_branch_root() {
+        for n in 1..=33_u8 {
+            let txids = (0..n).map(|i| Txid::from_byte_array([i; 32]));
+            let branch = coinbase_branch(txids.clone());
+            assert_eq!(branch.len(), usize::from(n).next_power_of_two().trailing_zeros() as usize);
+
+            let root = TxMerkleNode::calculate_root(txids).unwrap();
+            assert_eq!(root_from_coinbase_branch(Txid::from_byte_array([0; 32]), &branch), root);
+
+            let coinbase = Txid::from_byte_array([0xff; 32]);
+            let txids =
+                core::iter::once(coinbase).chain((1..n).map(|i| Txid::from_byte_array([i; 32])));
+            let root = TxMerkleNode::calculate_root(txids).unwrap();
+            assert_eq!(root_from_coinbase_branch(coinbase, &branch), root);
+        }
+    }
+}
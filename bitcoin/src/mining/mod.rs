@@ -0,0 +1,626 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Mining.
+//!
+//! A [`TemplateBuilder`] assembles a [`BlockTemplate`] from the previous block header, a set of
+//! transactions and the payouts of the block reward. The template contains a coinbase transaction
+//! with the [BIP-34] height, an area reserved for the extranonce and, if requested, the [BIP-141]
+//! witness commitment, as well as a header ready for nonce search.
+//!
+//! [BIP-34]: <https://github.com/bitcoin/bips/blob/master/bip-0034.mediawiki>
+//! [BIP-141]: <https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki>
+
+pub mod stratum;
+
+use core::convert::Infallible;
+use core::fmt;
+use core::ops::Range;
+
+use internals::{compact_size, ToU64 as _};
+
+use crate::block::{self, BlockHeight, Checked, Header, Version, WitnessCommitment};
+use crate::constants::MAX_BLOCK_SIGOPS_COST;
+use crate::locktime::absolute;
+use crate::merkle_tree::{self, TxMerkleNode, WitnessMerkleNode};
+use crate::network::Params;
+use crate::opcodes::all::OP_PUSHBYTES_0;
+use crate::pow::CompactTarget;
+use crate::prelude::Vec;
+use crate::script::{self, ScriptBuf};
+use crate::transaction::{self, TransactionExt as _};
+use crate::{
+    Amount, Block, BlockTime, OutPoint, Sequence, Transaction, TxIn, TxOut, Weight, Witness,
+};
+
+/// The maximum size of the coinbase `scriptSig`.
+pub const MAX_COINBASE_SCRIPT_SIG_SIZE: usize = 100;
+
+/// The minimum size of the coinbase `scriptSig`.
+pub const MIN_COINBASE_SCRIPT_SIG_SIZE: usize = 2;
+
+/// The witness reserved value committed to in the witness commitment.
+///
+/// This is the only value defined so far, it is the coinbase input's witness.
+pub const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+
+/// The prefix of the witness commitment script: `OP_RETURN OP_PUSHBYTES_36 0xaa21a9ed`.
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// A transaction to include in a block template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateTransaction {
+    /// The transaction.
+    pub tx: Transaction,
+    /// The fee paid by the transaction.
+    pub fee: Amount,
+    /// The sigop cost of the transaction.
+    ///
+    /// Counting P2SH and witness sigops requires the spent outputs, see
+    /// [`TransactionExt::total_sigop_cost`](crate::transaction::TransactionExt::total_sigop_cost).
+    pub sigop_cost: usize,
+}
+
+/// Builds a [`BlockTemplate`] on top of a previous block.
+///
+/// Transactions are included in the order they are added, which must be such that each
+/// transaction comes after the transactions it spends.
+///
+/// # Examples
+///
+/// ```
+/// use bitcoin::script::ScriptBufExt as _;
+/// use bitcoin::mining::TemplateBuilder;
+/// use bitcoin::{constants, Amount, BlockHeight, Network, ScriptBuf};
+///
+/// let genesis = constants::genesis_block(Network::Regtest);
+/// let template = TemplateBuilder::new(genesis.header(), BlockHeight::from_u32(1), Network::Regtest)
+///     .add_payout(ScriptBuf::new_op_return([]), Amount::ZERO)
+///     .remainder_script(ScriptBuf::new())
+///     .extranonce_size(8)
+///     .build()
+///     .expect("valid template");
+///
+/// assert_eq!(template.coinbase().output[1].value, Amount::from_int_btc(50_u16));
+/// assert_eq!(template.header().prev_blockhash, genesis.block_hash());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TemplateBuilder {
+    header: Header,
+    height: BlockHeight,
+    subsidy: Amount,
+    coinbase_tag: Vec<u8>,
+    extranonce_size: usize,
+    payouts: Vec<TxOut>,
+    remainder_script: Option<ScriptBuf>,
+    witness_commitment: bool,
+    transactions: Vec<TemplateTransaction>,
+}
+
+impl TemplateBuilder {
+    /// Constructs a new builder for the block at `height` on top of `prev`.
+    ///
+    /// The header time and bits default to the ones of `prev` and the version to
+    /// [`Version::NO_SOFT_FORK_SIGNALLING`]. The time must be set to more than the median time
+    /// past and the bits to the ones required for the new block. The subsidy is the one of the
+    /// block at `height` on the network of `params`.
+    pub fn new(prev: &Header, height: BlockHeight, params: impl AsRef<Params>) -> Self {
+        let header = Header {
+            version: Version::NO_SOFT_FORK_SIGNALLING,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: prev.merkle_root,
+            time: prev.time,
+            bits: prev.bits,
+            nonce: 0,
+        };
+        TemplateBuilder {
+            header,
+            height,
+            subsidy: params.as_ref().block_subsidy(height),
+            coinbase_tag: Vec::new(),
+            extranonce_size: 0,
+            payouts: Vec::new(),
+            remainder_script: None,
+            witness_commitment: true,
+            transactions: Vec::new(),
+        }
+    }
+
+    /// Sets the header version.
+    pub fn version(mut self, version: Version) -> Self {
+        self.header.version = version;
+        self
+    }
+
+    /// Sets the header time.
+    pub fn time(mut self, time: BlockTime) -> Self {
+        self.header.time = time;
+        self
+    }
+
+    /// Sets the header bits.
+    pub fn bits(mut self, bits: CompactTarget) -> Self {
+        self.header.bits = bits;
+        self
+    }
+
+    /// Sets the block subsidy, overriding the one of the network.
+    pub fn subsidy(mut self, subsidy: Amount) -> Self {
+        self.subsidy = subsidy;
+        self
+    }
+
+    /// Sets arbitrary bytes to put in the coinbase `scriptSig` after the height.
+    pub fn coinbase_tag(mut self, tag: Vec<u8>) -> Self {
+        self.coinbase_tag = tag;
+        self
+    }
+
+    /// Sets the number of bytes reserved for the extranonce at the end of the coinbase `scriptSig`.
+    pub fn extranonce_size(mut self, size: usize) -> Self {
+        self.extranonce_size = size;
+        self
+    }
+
+    /// Adds a coinbase output paying `amount` to `script_pubkey`.
+    pub fn add_payout(mut self, script_pubkey: ScriptBuf, amount: Amount) -> Self {
+        self.payouts.push(TxOut { value: amount, script_pubkey });
+        self
+    }
+
+    /// Sets the script of a coinbase output receiving the block reward not paid by the payouts.
+    ///
+    /// Without it, any reward left over is not claimed.
+    pub fn remainder_script(mut self, script_pubkey: ScriptBuf) -> Self {
+        self.remainder_script = Some(script_pubkey);
+        self
+    }
+
+    /// Sets whether to add a witness commitment to the coinbase, defaults to `true`.
+    ///
+    /// The commitment is required if any transaction has witness data and valid otherwise, but
+    /// must not be added before SegWit activation.
+    pub fn witness_commitment(mut self, enable: bool) -> Self {
+        self.witness_commitment = enable;
+        self
+    }
+
+    /// Adds a transaction to the block.
+    pub fn add_transaction(mut self, tx: TemplateTransaction) -> Self {
+        self.transactions.push(tx);
+        self
+    }
+
+    /// Adds transactions to the block.
+    pub fn add_transactions<I: IntoIterator<Item = TemplateTransaction>>(mut self, txs: I) -> Self {
+        self.transactions.extend(txs);
+        self
+    }
+
+    /// Builds the template.
+    ///
+    /// # Errors
+    ///
+    /// If the coinbase is invalid or the block exceeds the consensus weight or sigop cost limits.
+    pub fn build(self) -> Result<BlockTemplate, BuildTemplateError> {
+        if let Some(index) = self.transactions.iter().position(|t| t.tx.is_coinbase()) {
+            return Err(BuildTemplateError::UnexpectedCoinbase(index));
+        }
+
+        let fees = self
+            .transactions
+            .iter()
+            .try_fold(Amount::ZERO, |acc, t| acc.checked_add(t.fee))
+            .ok_or(BuildTemplateError::RewardOverflow)?;
+        let reward = self.subsidy.checked_add(fees).ok_or(BuildTemplateError::RewardOverflow)?;
+
+        // The coinbase `scriptSig`: BIP-34 height, padding, tag then extranonce.
+        let mut script_sig = script::Builder::new()
+            .push_int_unchecked(self.height.to_u32().into())
+            .into_script()
+            .into_bytes();
+        let len = script_sig.len() + self.coinbase_tag.len() + self.extranonce_size;
+        if len < MIN_COINBASE_SCRIPT_SIG_SIZE {
+            script_sig.push(OP_PUSHBYTES_0.to_u8());
+        }
+        script_sig.extend_from_slice(&self.coinbase_tag);
+        let extranonce_start = script_sig.len();
+        script_sig.resize(extranonce_start + self.extranonce_size, 0);
+        if script_sig.len() > MAX_COINBASE_SCRIPT_SIG_SIZE {
+            return Err(BuildTemplateError::CoinbaseScriptSigSize(script_sig.len()));
+        }
+
+        let mut output = self.payouts;
+        let payouts = output
+            .iter()
+            .try_fold(Amount::ZERO, |acc, o| acc.checked_add(o.value))
+            .ok_or(BuildTemplateError::RewardOverflow)?;
+        let remainder = reward
+            .checked_sub(payouts)
+            .ok_or(BuildTemplateError::PayoutsExceedReward { payouts, reward })?;
+        if let Some(script_pubkey) = self.remainder_script {
+            output.push(TxOut { value: remainder, script_pubkey });
+        }
+        if output.is_empty() {
+            return Err(BuildTemplateError::NoPayouts);
+        }
+
+        let mut coinbase = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::COINBASE_PREVOUT,
+                script_sig: ScriptBuf::from_bytes(script_sig),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output,
+        };
+
+        let mut transactions = Vec::with_capacity(self.transactions.len() + 1);
+        let mut sigop_cost = 0_usize;
+        // Placeholder for the coinbase, whose wtxid is not part of the witness commitment.
+        transactions.push(Transaction {
+            version: coinbase.version,
+            lock_time: coinbase.lock_time,
+            input: vec![],
+            output: vec![],
+        });
+        for t in self.transactions {
+            sigop_cost = sigop_cost.saturating_add(t.sigop_cost);
+            transactions.push(t.tx);
+        }
+
+        let mut witness_commitment = None;
+        let mut witness_root = None;
+        if self.witness_commitment {
+            let (root, commitment) =
+                block::compute_witness_commitment(&transactions, &WITNESS_RESERVED_VALUE)
+                    .expect("transactions are not empty");
+            let mut script_pubkey = WITNESS_COMMITMENT_PREFIX.to_vec();
+            script_pubkey.extend_from_slice(commitment.as_byte_array());
+            coinbase.output.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::from_bytes(script_pubkey),
+            });
+            coinbase.input[0].witness.push(WITNESS_RESERVED_VALUE);
+            witness_commitment = Some(commitment);
+            witness_root = Some(root);
+        }
+
+        sigop_cost = sigop_cost.saturating_add(coinbase.total_sigop_cost(|_| None));
+        if sigop_cost > MAX_BLOCK_SIGOPS_COST as usize {
+            return Err(BuildTemplateError::SigopCostExceeded(sigop_cost));
+        }
+
+        transactions[0] = coinbase;
+        let weight = block_weight(&transactions);
+        if weight > Weight::MAX_BLOCK {
+            return Err(BuildTemplateError::WeightExceeded(weight));
+        }
+
+        let merkle_branch =
+            merkle_tree::coinbase_branch(transactions.iter().map(|tx| tx.compute_txid()));
+        let mut header = self.header;
+        header.merkle_root =
+            merkle_tree::root_from_coinbase_branch(transactions[0].compute_txid(), &merkle_branch);
+
+        Ok(BlockTemplate {
+            header,
+            transactions,
+            extranonce: extranonce_start..extranonce_start + self.extranonce_size,
+            merkle_branch,
+            witness_commitment,
+            witness_root,
+            fees,
+            sigop_cost,
+            weight,
+        })
+    }
+}
+
+/// Computes the weight of a block containing `transactions`.
+fn block_weight(transactions: &[Transaction]) -> Weight {
+    let tx_count = compact_size::encoded_size(transactions.len()).to_u64();
+    let header = Weight::from_non_witness_data_size(Header::SIZE.to_u64() + tx_count);
+    transactions.iter().map(|tx| tx.weight()).fold(header, |acc, w| acc + w)
+}
+
+/// A block template, ready for extranonce and nonce search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockTemplate {
+    header: Header,
+    // The coinbase followed by the template transactions.
+    transactions: Vec<Transaction>,
+    extranonce: Range<usize>,
+    merkle_branch: Vec<TxMerkleNode>,
+    witness_commitment: Option<WitnessCommitment>,
+    witness_root: Option<WitnessMerkleNode>,
+    fees: Amount,
+    sigop_cost: usize,
+    weight: Weight,
+}
+
+impl BlockTemplate {
+    /// Returns the header with the Merkle root of the current extranonce and a zero nonce.
+    pub fn header(&self) -> Header { self.header }
+
+    /// Returns the coinbase transaction with the current extranonce.
+    pub fn coinbase(&self) -> &Transaction { &self.transactions[0] }
+
+    /// Returns all transactions of the block, starting with the coinbase.
+    pub fn transactions(&self) -> &[Transaction] { &self.transactions }
+
+    /// Returns the position of the extranonce in the coinbase `scriptSig`.
+    pub fn extranonce_range(&self) -> Range<usize> { self.extranonce.clone() }
+
+    /// Returns the Merkle branch of the coinbase.
+    ///
+    /// See [`merkle_tree::coinbase_branch`].
+    pub fn merkle_branch(&self) -> &[TxMerkleNode] { &self.merkle_branch }
+
+    /// Returns the witness commitment, if the coinbase has one.
+    pub fn witness_commitment(&self) -> Option<WitnessCommitment> { self.witness_commitment }
+
+    /// Returns the coinbase output holding the witness commitment, if any.
+    pub fn witness_commitment_output(&self) -> Option<&TxOut> {
+        self.witness_commitment.and_then(|_| self.coinbase().output.last())
+    }
+
+    /// Returns the sum of the fees of the transactions.
+    pub fn fees(&self) -> Amount { self.fees }
+
+    /// Returns the sigop cost of the block, including the coinbase.
+    pub fn sigop_cost(&self) -> usize { self.sigop_cost }
+
+    /// Returns the weight of the block.
+    pub fn weight(&self) -> Weight { self.weight }
+
+    /// Sets the extranonce, updating the coinbase and the header Merkle root.
+    ///
+    /// # Errors
+    ///
+    /// If `extranonce` does not have the size reserved by [`TemplateBuilder::extranonce_size`].
+    pub fn set_extranonce(&mut self, extranonce: &[u8]) -> Result<(), ExtranonceSizeError> {
+        if extranonce.len() != self.extranonce.len() {
+            return Err(ExtranonceSizeError {
+                expected: self.extranonce.len(),
+                actual: extranonce.len(),
+            });
+        }
+        let script_sig = &mut self.transactions[0].input[0].script_sig;
+        let mut bytes = core::mem::take(script_sig).into_bytes();
+        bytes[self.extranonce.clone()].copy_from_slice(extranonce);
+        *script_sig = ScriptBuf::from_bytes(bytes);
+
+        // The witness commitment does not depend on the coinbase.
+        let txid = self.transactions[0].compute_txid();
+        self.header.merkle_root = merkle_tree::root_from_coinbase_branch(txid, &self.merkle_branch);
+        Ok(())
+    }
+
+    /// Consumes the template, returning the block with `nonce` set in the header.
+    pub fn into_block(self, nonce: u32) -> Block<Checked> {
+        let header = Header { nonce, ..self.header };
+        Block::new_unchecked(header, self.transactions).assume_checked(self.witness_root)
+    }
+}
+
+/// Error building a [`BlockTemplate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildTemplateError {
+    /// The transaction at this index of the added transactions is a coinbase.
+    UnexpectedCoinbase(usize),
+    /// The subsidy plus fees or the payouts overflow.
+    RewardOverflow,
+    /// The payouts are more than the subsidy plus fees.
+    PayoutsExceedReward {
+        /// The sum of the payouts.
+        payouts: Amount,
+        /// The subsidy plus fees.
+        reward: Amount,
+    },
+    /// The coinbase has no outputs.
+    NoPayouts,
+    /// The coinbase `scriptSig` is longer than [`MAX_COINBASE_SCRIPT_SIG_SIZE`].
+    CoinbaseScriptSigSize(usize),
+    /// The block exceeds the maximum weight.
+    WeightExceeded(Weight),
+    /// The block exceeds the maximum sigop cost.
+    SigopCostExceeded(usize),
+}
+
+impl From<Infallible> for BuildTemplateError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for BuildTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BuildTemplateError::*;
+
+        match *self {
+            UnexpectedCoinbase(index) => write!(f, "transaction {} is a coinbase", index),
+            RewardOverflow => write!(f, "block reward overflows"),
+            PayoutsExceedReward { payouts, reward } =>
+                write!(f, "payouts of {} exceed the block reward of {}", payouts, reward),
+            NoPayouts => write!(f, "coinbase has no outputs"),
+            CoinbaseScriptSigSize(size) => write!(
+                f,
+                "coinbase scriptSig of {} bytes exceeds the maximum of {}",
+                size, MAX_COINBASE_SCRIPT_SIG_SIZE
+            ),
+            WeightExceeded(weight) =>
+                write!(f, "block weight {} exceeds the maximum of {}", weight, Weight::MAX_BLOCK),
+            SigopCostExceeded(cost) => write!(
+                f,
+                "block sigop cost {} exceeds the maximum of {}",
+                cost, MAX_BLOCK_SIGOPS_COST
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildTemplateError {}
+
+/// The extranonce does not have the reserved size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtranonceSizeError {
+    /// The reserved size.
+    pub expected: usize,
+    /// The size of the extranonce given.
+    pub actual: usize,
+}
+
+impl fmt::Display for ExtranonceSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "extranonce is {} bytes, expected {}", self.actual, self.expected)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExtranonceSizeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlockCheckedExt as _, BlockUncheckedExt as _};
+    use crate::constants::genesis_block;
+    use crate::script::ScriptBufExt as _;
+    use crate::Network;
+
+    fn spending_tx(witness: bool) -> Transaction {
+        let mut input = TxIn {
+            previous_output: OutPoint { txid: crate::Txid::from_byte_array([1; 32]), vout: 0 },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        if witness {
+            input.witness.push([2; 72]);
+        }
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![input],
+            output: vec![TxOut {
+                value: Amount::from_sat_u32(1000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    fn builder() -> TemplateBuilder {
+        let genesis = genesis_block(Network::Regtest);
+        TemplateBuilder::new(genesis.header(), BlockHeight::from_u32(1_000), Network::Regtest)
+            .subsidy(Amount::from_int_btc(50_u16))
+            .remainder_script(ScriptBuf::new_op_return([1]))
+    }
+
+    #[test]
+    fn build_template() {
+        let tx = TemplateTransaction {
+            tx: spending_tx(true),
+            fee: Amount::from_sat_u32(500),
+            sigop_cost: 1,
+        };
+        let mut template = builder()
+            .coinbase_tag(b"/pool/".to_vec())
+            .extranonce_size(4)
+            .add_payout(ScriptBuf::new_op_return([2]), Amount::from_int_btc(1_u16))
+            .add_transaction(tx.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(template.fees(), Amount::from_sat_u32(500));
+        assert_eq!(template.sigop_cost(), 1);
+        assert_eq!(template.transactions().len(), 2);
+
+        let coinbase = template.coinbase();
+        assert_eq!(coinbase.output.len(), 3);
+        assert_eq!(coinbase.output[1].value, Amount::from_sat(4_900_000_500).unwrap());
+        assert_eq!(template.witness_commitment_output(), coinbase.output.last());
+        assert_eq!(template.extranonce_range(), 9..13);
+        assert_eq!(&coinbase.input[0].script_sig.as_bytes()[3..9], b"/pool/");
+
+        let header = template.header();
+        template.set_extranonce(&[1, 2, 3, 4]).unwrap();
+        assert_ne!(template.header().merkle_root, header.merkle_root);
+        assert_eq!(
+            Some(template.header().merkle_root),
+            block::compute_merkle_root(template.transactions())
+        );
+        assert_eq!(template.witness_commitment_output(), template.coinbase().output.last());
+        assert_eq!(
+            template.set_extranonce(&[1]),
+            Err(ExtranonceSizeError { expected: 4, actual: 1 })
+        );
+
+        let weight = template.weight();
+        let block = template.into_block(42);
+        assert_eq!(block.header().nonce, 42);
+        assert_eq!(block.weight(), weight);
+        assert_eq!(block.bip34_block_height().unwrap(), 1_000);
+
+        let block = Block::new_unchecked(*block.header(), block.transactions().to_vec());
+        assert!(block.validate().is_ok());
+    }
+
+    #[test]
+    fn small_height_is_padded() {
+        let genesis = genesis_block(Network::Regtest);
+        let template =
+            TemplateBuilder::new(genesis.header(), BlockHeight::from_u32(1), Network::Regtest)
+                .remainder_script(ScriptBuf::new())
+                .witness_commitment(false)
+                .build()
+                .unwrap();
+        let coinbase = template.coinbase();
+        assert_eq!(coinbase.input[0].script_sig.as_bytes(), &[0x51, 0x00]);
+        assert!(coinbase.input[0].witness.is_empty());
+        assert_eq!(coinbase.output.len(), 1);
+        assert_eq!(coinbase.output[0].value, Amount::FIFTY_BTC);
+        assert_eq!(template.witness_commitment(), None);
+
+        // The subsidy of regtest halves every 150 blocks.
+        let template =
+            TemplateBuilder::new(genesis.header(), BlockHeight::from_u32(150), Network::Regtest)
+                .remainder_script(ScriptBuf::new())
+                .build()
+                .unwrap();
+        assert_eq!(template.coinbase().output[0].value, Amount::from_int_btc(25_u16));
+    }
+
+    #[test]
+    fn limits() {
+        let err = builder().coinbase_tag(vec![0; 98]).build().unwrap_err();
+        assert_eq!(err, BuildTemplateError::CoinbaseScriptSigSize(101));
+
+        let tx = TemplateTransaction {
+            tx: spending_tx(false),
+            fee: Amount::ZERO,
+            sigop_cost: MAX_BLOCK_SIGOPS_COST as usize + 1,
+        };
+        let err = builder().add_transaction(tx).build().unwrap_err();
+        assert_eq!(err, BuildTemplateError::SigopCostExceeded(MAX_BLOCK_SIGOPS_COST as usize + 1));
+
+        let mut tx = spending_tx(false);
+        tx.output[0].script_pubkey = ScriptBuf::from_bytes(vec![0; 1_000_000]);
+        let tx = TemplateTransaction { tx, fee: Amount::ZERO, sigop_cost: 0 };
+        let err = builder().add_transaction(tx).build().unwrap_err();
+        assert!(matches!(err, BuildTemplateError::WeightExceeded(_)));
+
+        let err = builder()
+            .add_payout(ScriptBuf::new(), Amount::from_int_btc(51_u16))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, BuildTemplateError::PayoutsExceedReward { .. }));
+
+        let genesis = genesis_block(Network::Regtest);
+        let err =
+            TemplateBuilder::new(genesis.header(), BlockHeight::from_u32(1), Network::Regtest)
+                .build();
+        assert_eq!(err.unwrap_err(), BuildTemplateError::NoPayouts);
+    }
+}
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Stratum mining protocol support.
+//!
+//! Stratum jobs send the coinbase Merkle branch, see [`BlockTemplate::merkle_branch`], so miners
+//! can recompute the Merkle root with [`root_from_coinbase_branch`] after rolling the extranonce.
+//!
+//! The [BIP-310] `version-rolling` extension additionally allows miners to roll the bits of the
+//! block version selected by a [`VersionRollingMask`].
+//!
+//! [`BlockTemplate::merkle_branch`]: super::BlockTemplate::merkle_branch
+//! [`root_from_coinbase_branch`]: crate::merkle_tree::root_from_coinbase_branch
+//! [BIP-310]: <https://github.com/bitcoin/bips/blob/master/bip-0310.mediawiki>
+
+use core::fmt;
+use core::str::FromStr;
+
+use units::parse::{self, UnprefixedHexError};
+
+use crate::block::Version;
+
+/// The mask of the block version bits a miner is allowed to roll, negotiated with
+/// `mining.configure`.
+///
+/// Formatted and parsed as the unprefixed hex used by the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VersionRollingMask(u32);
+
+impl VersionRollingMask {
+    /// The bits reserved for general purpose use by [BIP-320], bits 13 to 28.
+    ///
+    /// [BIP-320]: <https://github.com/bitcoin/bips/blob/master/bip-0320.mediawiki>
+    pub const BIP320: Self = VersionRollingMask(0x1fff_e000);
+
+    /// Constructs a new mask from its integer value.
+    pub const fn from_u32(mask: u32) -> Self { VersionRollingMask(mask) }
+
+    /// Returns the integer value of the mask.
+    pub const fn to_u32(self) -> u32 { self.0 }
+
+    /// Constructs a new mask from an unprefixed hex string.
+    pub fn from_unprefixed_hex(s: &str) -> Result<Self, UnprefixedHexError> {
+        parse::hex_u32_unprefixed(s).map(VersionRollingMask)
+    }
+
+    /// Returns the mask allowed by both `self` and `other`.
+    ///
+    /// This is how a pool computes the mask it returns to a miner requesting `other`.
+    pub const fn negotiate(self, other: Self) -> Self { VersionRollingMask(self.0 & other.0) }
+
+    /// Returns the number of bits the miner is allowed to roll.
+    ///
+    /// The miner requests a minimum with `version-rolling.min-bit-count`.
+    pub const fn bit_count(self) -> u32 { self.0.count_ones() }
+
+    /// Returns the version of a share, replacing the bits of the job `version` in the mask with
+    /// `version_bits`, as submitted with `mining.submit`.
+    ///
+    /// # Errors
+    ///
+    /// If `version_bits` has bits set outside of the mask.
+    pub fn roll(self, version: Version, version_bits: u32) -> Result<Version, VersionBitsError> {
+        if version_bits & !self.0 != 0 {
+            return Err(VersionBitsError { version_bits, mask: self });
+        }
+        let version = version.to_consensus() as u32;
+        Ok(Version::from_consensus(((version & !self.0) | version_bits) as i32))
+    }
+
+    /// Returns the `version_bits` to submit for a share of `rolled`, if it only differs from the
+    /// job `version` in the bits allowed by the mask.
+    pub fn version_bits(self, version: Version, rolled: Version) -> Option<u32> {
+        let (version, rolled) = (version.to_consensus() as u32, rolled.to_consensus() as u32);
+        if (version ^ rolled) & !self.0 == 0 {
+            Some(rolled & self.0)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for VersionRollingMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::LowerHex::fmt(self, f) }
+}
+
+impl fmt::LowerHex for VersionRollingMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{:08x}", self.0) }
+}
+
+impl FromStr for VersionRollingMask {
+    type Err = UnprefixedHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_unprefixed_hex(s) }
+}
+
+/// Submitted version bits are outside of the negotiated [`VersionRollingMask`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VersionBitsError {
+    /// The submitted version bits.
+    pub version_bits: u32,
+    /// The negotiated mask.
+    pub mask: VersionRollingMask,
+}
+
+impl fmt::Display for VersionBitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "version bits {:08x} are outside of the mask {}", self.version_bits, self.mask)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VersionBitsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_rolling() {
+        // Example exchange of BIP-310.
+        let requested = "ffffffff".parse::<VersionRollingMask>().unwrap();
+        let mask = VersionRollingMask::BIP320.negotiate(requested);
+        assert_eq!(mask.to_string(), "1fffe000");
+        assert_eq!(mask.bit_count(), 16);
+
+        let job = Version::NO_SOFT_FORK_SIGNALLING;
+        let rolled = mask.roll(job, 0x1234_0000).unwrap();
+        assert_eq!(rolled.to_consensus(), 0x3234_0000);
+        assert_eq!(mask.version_bits(job, rolled), Some(0x1234_0000));
+        assert_eq!(mask.version_bits(job, Version::from_consensus(0x2000_0001)), None);
+
+        let err = mask.roll(job, 0x0000_1000).unwrap_err();
+        assert_eq!(err, VersionBitsError { version_bits: 0x1000, mask });
+
+        assert!("0x1fffe000".parse::<VersionRollingMask>().is_err());
+    }
+}
@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Custom networks defined at runtime.
+//!
+//! A [`CustomNetwork`] describes a network that is not one of the built-in [`Network`]s, such as a
+//! custom signet or an altcoin sharing Bitcoin's consensus rules. It bundles the consensus
+//! [`Params`] with the encoding prefixes and network magic, and can be used wherever the built-in
+//! networks are hard coded:
+//!
+//! - [`Address::from_str_custom`] and [`Address::to_string_custom`]
+//! - [`Xpriv::from_str_custom`], [`Xpub::from_str_custom`] and their `to_string_custom`
+//! - [`PrivateKey::from_wif_custom`] and [`PrivateKey::to_wif_custom`]
+//! - [`Magic`] of the P2P messages, converted from `&CustomNetwork`
+//! - [`CustomNetwork::header_chain`], starting at the custom genesis block
+//! - [`SnapshotReader::new_custom`] and [`SnapshotWriter::new_custom`]
+//!
+//! The consensus [`Params`] do not include the genesis block and magic, functions taking them
+//! use the ones of `params.network`.
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::network::CustomNetwork;
+//! use bitcoin::{Address, Network};
+//!
+//! let mut network = CustomNetwork::new(Network::Bitcoin);
+//! network.bech32_hrp = bitcoin::bech32::Hrp::parse("ltc").unwrap();
+//! network.pubkey_address_prefix = 48;
+//! network.script_address_prefix = 50;
+//!
+//! let address = Address::from_str_custom("LM2WMpR1Rp6j3Sa59cMXMs1SPzj9eXpGc1", &network).unwrap();
+//! assert_eq!(address.to_string_custom(&network).unwrap(), "LM2WMpR1Rp6j3Sa59cMXMs1SPzj9eXpGc1");
+//! ```
+//!
+//! [`Address::from_str_custom`]: crate::Address::from_str_custom
+//! [`Address::to_string_custom`]: crate::Address::to_string_custom
+//! [`Xpriv::from_str_custom`]: crate::bip32::Xpriv::from_str_custom
+//! [`Xpub::from_str_custom`]: crate::bip32::Xpub::from_str_custom
+//! [`PrivateKey::from_wif_custom`]: crate::PrivateKey::from_wif_custom
+//! [`PrivateKey::to_wif_custom`]: crate::PrivateKey::to_wif_custom
+//! [`SnapshotReader::new_custom`]: crate::utxo_snapshot::SnapshotReader::new_custom
+//! [`SnapshotWriter::new_custom`]: crate::utxo_snapshot::SnapshotWriter::new_custom
+
+use bech32::Hrp;
+use units::BlockHeight;
+
+use crate::address::KnownHrp;
+use crate::bip32::{
+    VERSION_BYTES_MAINNET_PRIVATE, VERSION_BYTES_MAINNET_PUBLIC, VERSION_BYTES_TESTNETS_PRIVATE,
+    VERSION_BYTES_TESTNETS_PUBLIC,
+};
+use crate::block::{Block, Checked, HeaderExt as _};
+use crate::constants::{
+    genesis_block, ChainHash, PUBKEY_ADDRESS_PREFIX_MAIN, PUBKEY_ADDRESS_PREFIX_TEST,
+    SCRIPT_ADDRESS_PREFIX_MAIN, SCRIPT_ADDRESS_PREFIX_TEST,
+};
+use crate::crypto::key::{WIF_PREFIX_MAIN, WIF_PREFIX_TEST};
+use crate::header_chain::HeaderChain;
+use crate::network::{Network, NetworkKind, Params};
+use crate::p2p::Magic;
+use crate::script::ScriptBuf;
+
+/// A network defined at runtime.
+///
+/// The base network of the consensus parameters, `params.network`, determines the
+/// [`NetworkKind`] keys and addresses of this network are valid for.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct CustomNetwork {
+    /// The consensus parameters.
+    pub params: Params,
+    /// The magic bytes of the P2P messages.
+    pub magic: Magic,
+    /// The human-readable part of bech32 SegWit addresses.
+    pub bech32_hrp: Hrp,
+    /// The prefix byte of base58 P2PKH addresses.
+    pub pubkey_address_prefix: u8,
+    /// The prefix byte of base58 P2SH addresses.
+    pub script_address_prefix: u8,
+    /// The prefix byte of WIF encoded private keys.
+    pub wif_prefix: u8,
+    /// The version bytes of BIP-32 extended public keys.
+    pub xpub_version: [u8; 4],
+    /// The version bytes of BIP-32 extended private keys.
+    pub xpriv_version: [u8; 4],
+    /// The genesis block.
+    pub genesis_block: Block<Checked>,
+    /// The block challenge script, if this is a signet.
+    pub signet_challenge: Option<ScriptBuf>,
+}
+
+impl CustomNetwork {
+    /// Constructs a new custom network with the parameters and prefixes of `network`.
+    ///
+    /// The fields can then be changed to define the custom network.
+    pub fn new(network: Network) -> Self {
+        let params = Params::new(network);
+        let (pubkey_address_prefix, script_address_prefix, wif_prefix, xpub_version, xpriv_version) =
+            match NetworkKind::from(network) {
+                NetworkKind::Main => (
+                    PUBKEY_ADDRESS_PREFIX_MAIN,
+                    SCRIPT_ADDRESS_PREFIX_MAIN,
+                    WIF_PREFIX_MAIN,
+                    VERSION_BYTES_MAINNET_PUBLIC,
+                    VERSION_BYTES_MAINNET_PRIVATE,
+                ),
+                NetworkKind::Test => (
+                    PUBKEY_ADDRESS_PREFIX_TEST,
+                    SCRIPT_ADDRESS_PREFIX_TEST,
+                    WIF_PREFIX_TEST,
+                    VERSION_BYTES_TESTNETS_PUBLIC,
+                    VERSION_BYTES_TESTNETS_PRIVATE,
+                ),
+            };
+        CustomNetwork {
+            genesis_block: genesis_block(&params),
+            params,
+            magic: Magic::from(network),
+            bech32_hrp: KnownHrp::from(network).to_hrp(),
+            pubkey_address_prefix,
+            script_address_prefix,
+            wif_prefix,
+            xpub_version,
+            xpriv_version,
+            signet_challenge: None,
+        }
+    }
+
+    /// Constructs a new custom signet with the block `challenge` script.
+    ///
+    /// The network magic is derived from the challenge as specified by [BIP-325].
+    ///
+    /// [BIP-325]: <https://github.com/bitcoin/bips/blob/master/bip-0325.mediawiki>
+    pub fn signet(challenge: ScriptBuf) -> Self {
+        let mut network = CustomNetwork::new(Network::Signet);
+        network.magic = Magic::from_signet_challenge(&challenge);
+        network.signet_challenge = Some(challenge);
+        network
+    }
+
+    /// Returns the kind of network keys and addresses of this network are valid for.
+    pub fn network_kind(&self) -> NetworkKind { NetworkKind::from(self.params.network) }
+
+    /// Returns the hash of the genesis block.
+    pub fn chain_hash(&self) -> ChainHash {
+        ChainHash::from_genesis_block_hash(self.genesis_block.block_hash())
+    }
+
+    /// Constructs a new header chain consisting of the genesis block header of this network.
+    pub fn header_chain(&self) -> HeaderChain {
+        let genesis = *self.genesis_block.header();
+        HeaderChain::from_headers(vec![genesis], BlockHeight::ZERO, genesis.work(), &self.params)
+            .expect("the genesis block has no ancestors")
+    }
+}
+
+impl From<Network> for CustomNetwork {
+    fn from(network: Network) -> Self { CustomNetwork::new(network) }
+}
+
+impl From<&CustomNetwork> for Magic {
+    fn from(network: &CustomNetwork) -> Self { network.magic }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use super::*;
+    use crate::address::NetworkUnchecked;
+    use crate::bip32::{Xpriv, Xpub};
+    use crate::signet::DEFAULT_CHALLENGE;
+    use crate::{Address, PrivateKey};
+
+    fn litecoin() -> CustomNetwork {
+        let mut network = CustomNetwork::new(Network::Bitcoin);
+        network.bech32_hrp = Hrp::parse("ltc").unwrap();
+        network.pubkey_address_prefix = 48;
+        network.script_address_prefix = 50;
+        network.wif_prefix = 176;
+        network.xpub_version = [0x01, 0x9d, 0xa4, 0x62];
+        network.xpriv_version = [0x01, 0x9d, 0x9c, 0xfe];
+        network
+    }
+
+    #[test]
+    fn builtin_encodings() {
+        let network = CustomNetwork::new(Network::Bitcoin);
+        assert_eq!(network.chain_hash(), ChainHash::BITCOIN);
+        assert_eq!(network.header_chain().tip_height(), BlockHeight::ZERO);
+
+        for s in [
+            "1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY",
+            "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k",
+            "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw",
+        ] {
+            let address = Address::from_str_custom(s, &network).unwrap();
+            assert_eq!(address, Address::from_str(s).unwrap().assume_checked());
+            assert_eq!(address.to_string_custom(&network).unwrap(), s);
+        }
+
+        let xpriv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let xpriv_custom = Xpriv::from_str_custom(xpriv, &network).unwrap();
+        assert_eq!(xpriv_custom, Xpriv::from_str(xpriv).unwrap());
+        assert_eq!(xpriv_custom.to_string_custom(&network), xpriv);
+
+        let wif = "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617";
+        let key = PrivateKey::from_wif_custom(wif, &network).unwrap();
+        assert_eq!(key, PrivateKey::from_wif(wif).unwrap());
+        assert_eq!(key.to_wif_custom(&network), wif);
+    }
+
+    #[test]
+    fn custom_encodings() {
+        let network = litecoin();
+
+        let p2pkh = "LM2WMpR1Rp6j3Sa59cMXMs1SPzj9eXpGc1";
+        let address = Address::from_str_custom(p2pkh, &network).unwrap();
+        assert_eq!(address.to_string_custom(&network).unwrap(), p2pkh);
+        let bitcoin = address.to_string_custom(&CustomNetwork::new(Network::Bitcoin)).unwrap();
+        assert!(bitcoin.starts_with('1'));
+        assert!(address.to_string_custom(&CustomNetwork::new(Network::Regtest)).is_err());
+        assert!(p2pkh.parse::<Address<NetworkUnchecked>>().is_err());
+
+        let p2wpkh = "ltc1qg42tkwuuxefutzxezdkdel39gfstuap288mfea";
+        let address = Address::from_str_custom(p2wpkh, &network).unwrap();
+        assert_eq!(address.to_string_custom(&network).unwrap(), p2wpkh);
+        let bitcoin = address.to_string_custom(&CustomNetwork::new(Network::Bitcoin)).unwrap();
+        let bitcoin = bitcoin.parse::<Address<NetworkUnchecked>>().unwrap().assume_checked();
+        assert_eq!(bitcoin, address);
+        assert!(Address::from_str_custom(&p2wpkh.to_uppercase(), &network).is_ok());
+
+        // Built-in prefixes are not valid on the custom network.
+        assert!(Address::from_str_custom("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY", &network).is_err());
+        assert!(Address::from_str_custom("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw", &network)
+            .is_err());
+
+        let xpriv = Xpriv::new_master(NetworkKind::Main, &[0; 32]);
+        let xpub = Xpub::from_xpriv(&secp256k1::Secp256k1::new(), &xpriv);
+        let s = xpriv.to_string_custom(&network);
+        assert!(s.starts_with("Ltpv"));
+        assert_eq!(Xpriv::from_str_custom(&s, &network).unwrap(), xpriv);
+        assert!(Xpriv::from_str(&s).is_err());
+        let s = xpub.to_string_custom(&network);
+        assert!(s.starts_with("Ltub"));
+        assert_eq!(Xpub::from_str_custom(&s, &network).unwrap(), xpub);
+        assert!(Xpub::from_str_custom(&xpub.to_string(), &network).is_err());
+
+        let key = PrivateKey::from_byte_array([1; 32], NetworkKind::Main).unwrap();
+        let wif = key.to_wif_custom(&network);
+        assert!(wif.starts_with('T'));
+        assert_eq!(PrivateKey::from_wif_custom(&wif, &network).unwrap(), key);
+        assert!(PrivateKey::from_wif(&wif).is_err());
+    }
+
+    #[test]
+    fn signet() {
+        let network = CustomNetwork::signet(DEFAULT_CHALLENGE.to_owned());
+        assert_eq!(Magic::from(&network), Magic::SIGNET);
+        assert_eq!(network.chain_hash(), ChainHash::SIGNET);
+        assert_eq!(network.network_kind(), NetworkKind::Test);
+
+        let network = CustomNetwork::signet(ScriptBuf::from_bytes(vec![0x51]));
+        assert_ne!(network.magic, Magic::SIGNET);
+        assert_eq!(network.bech32_hrp, bech32::hrp::TB);
+    }
+}
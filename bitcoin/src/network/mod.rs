@@ -6,6 +6,7 @@
 //! Bitcoin network we are operating on e.g., signet, regtest. The terms
 //! "network" and "chain" are often used interchangeably for this concept.
 
+pub mod custom;
 pub mod params;
 
 use core::fmt;
@@ -20,7 +21,7 @@
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
-pub use self::params::Params;
+pub use self::{custom::CustomNetwork, params::Params};
 
 /// What kind of network we are on.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -66,7 +66,9 @@
 #[cfg(doc)]
 use crate::pow::CompactTarget;
 use crate::pow::Target;
-use crate::TestnetVersion;
+use crate::prelude::Cow;
+use crate::versionbits::Deployment;
+use crate::{Amount, BlockHash, TestnetVersion};
 
 /// Parameters that influence chain consensus.
 #[non_exhaustive]
@@ -74,6 +76,8 @@
 pub struct Params {
     /// Network for which parameters are valid.
     pub network: Network,
+    /// Number of blocks after which the block subsidy is halved.
+    pub subsidy_halving_interval: BlockHeightInterval,
     /// Time when BIP16 becomes active.
     pub bip16_time: u32,
     /// Block height at which BIP34 becomes active.
@@ -82,6 +86,8 @@ pub struct Params {
     pub bip65_height: BlockHeight,
     /// Block height at which BIP66 becomes active.
     pub bip66_height: BlockHeight,
+    /// Block height at which BIP68, BIP112 and BIP113 (CSV) become active.
+    pub csv_height: BlockHeight,
     /// Minimum blocks including miner confirmation of the total of 2016 blocks in a retargeting period,
     /// (nPowTargetTimespan / nPowTargetSpacing) which is also used for BIP9 deployments.
     /// Examples: 1916 for 95%, 1512 for testchains.
@@ -111,6 +117,21 @@ pub struct Params {
     pub allow_min_difficulty_blocks: bool,
     /// Determines whether retargeting is disabled for this network or not.
     pub no_pow_retargeting: bool,
+    /// Determines whether the BIP-94 timewarp and difficulty adjustment rules are enforced.
+    pub enforce_bip94: bool,
+    /// The blocks at which UTXO snapshots can be loaded, Bitcoin Core's `m_assumeutxo_data`.
+    pub assumeutxo: &'static [AssumeUtxoData],
+    /// The BIP-9 soft fork deployments.
+    pub deployments: Cow<'static, [Deployment]>,
+}
+
+/// A block whose UTXO set snapshot is trusted, see [`crate::utxo_snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssumeUtxoData {
+    /// The height of the snapshot base block.
+    pub height: BlockHeight,
+    /// The hash of the snapshot base block.
+    pub blockhash: BlockHash,
 }
 
 /// The mainnet parameters.
@@ -141,10 +162,12 @@ impl Params {
     /// The mainnet parameters.
     pub const MAINNET: Params = Params {
         network: Network::Bitcoin,
+        subsidy_halving_interval: BlockHeightInterval::from_u32(210_000),
         bip16_time: 1333238400,                      // Apr 1 2012
         bip34_height: BlockHeight::from_u32(227931), // 000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8
         bip65_height: BlockHeight::from_u32(388381), // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
         bip66_height: BlockHeight::from_u32(363725), // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
+        csv_height: BlockHeight::from_u32(419328), // 000000000000000004a1b34462cb8aeebd5799177f7a29cf28f2d1961716b5b5
         rule_change_activation_threshold: BlockHeightInterval::from_u32(1916), // 95%
         miner_confirmation_window: BlockHeightInterval::from_u32(2016),
         pow_limit: Target::MAX_ATTAINABLE_MAINNET,
@@ -153,16 +176,58 @@ impl Params {
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: false,
         no_pow_retargeting: false,
+        enforce_bip94: false,
+        assumeutxo: &[
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(840_000),
+                // 0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5
+                blockhash: BlockHash::from_byte_array([
+                    165, 131, 218, 28, 63, 242, 155, 104, 114, 72, 255, 115, 120, 34, 248, 206, 72,
+                    39, 3, 58, 40, 32, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(880_000),
+                // 000000000000000000010b17283c3c400507969a9c2afd1dcf2082ec5cca2880
+                blockhash: BlockHash::from_byte_array([
+                    128, 40, 202, 92, 236, 130, 32, 207, 29, 253, 42, 156, 154, 150, 7, 5, 64, 60,
+                    60, 40, 23, 11, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(910_000),
+                // 0000000000000000000108970acb9522ffd516eae17acddcb1bd16469194a821
+                blockhash: BlockHash::from_byte_array([
+                    33, 168, 148, 145, 70, 22, 189, 177, 220, 205, 122, 225, 234, 22, 213, 255, 34,
+                    149, 203, 10, 151, 8, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(935_000),
+                // 0000000000000000000147034958af1652b2b91bba607beacc5e72a56f0fb5ee
+                blockhash: BlockHash::from_byte_array([
+                    238, 181, 15, 111, 165, 114, 94, 204, 234, 123, 96, 186, 27, 185, 178, 82, 22,
+                    175, 88, 73, 3, 71, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+        ],
+        deployments: Cow::Borrowed(&[
+            deployment("testdummy", 28, Deployment::NEVER_ACTIVE, Deployment::NO_TIMEOUT, 0),
+            // BIP-341, April 24th 2021 to August 11th 2021.
+            deployment("taproot", 2, 1619222400, 1628640000, 709632),
+        ]),
     };
 
     /// The testnet3 parameters.
     #[deprecated(since = "0.33.0", note = "use `TESTNET3` instead")]
     pub const TESTNET: Params = Params {
         network: Network::Testnet(TestnetVersion::V3),
+        subsidy_halving_interval: BlockHeightInterval::from_u32(210_000),
         bip16_time: 1333238400,                      // Apr 1 2012
         bip34_height: BlockHeight::from_u32(21111), // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
         bip65_height: BlockHeight::from_u32(581885), // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
         bip66_height: BlockHeight::from_u32(330776), // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
+        csv_height: BlockHeight::from_u32(770112), // 00000000025e930139bac5c6c31a403776da130831ab85be56578f3fa75369bb
         rule_change_activation_threshold: BlockHeightInterval::from_u32(1512), // 75%
         miner_confirmation_window: BlockHeightInterval::from_u32(2016),
         pow_limit: Target::MAX_ATTAINABLE_TESTNET,
@@ -171,15 +236,41 @@ impl Params {
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: true,
         no_pow_retargeting: false,
+        enforce_bip94: false,
+        assumeutxo: &[
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(2_500_000),
+                // 0000000000000093bcb68c03a9a168ae252572d348a2eaeba2cdf9231d73206f
+                blockhash: BlockHash::from_byte_array([
+                    111, 32, 115, 29, 35, 249, 205, 162, 235, 234, 162, 72, 211, 114, 37, 37, 174,
+                    104, 161, 169, 3, 140, 182, 188, 147, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(4_840_000),
+                // 00000000000000f4971a7fb37fbdff89315b69a2e1920c467654a382f0d64786
+                blockhash: BlockHash::from_byte_array([
+                    134, 71, 214, 240, 130, 163, 84, 118, 70, 12, 146, 225, 162, 105, 91, 49, 137,
+                    255, 189, 127, 179, 127, 26, 151, 244, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+        ],
+        deployments: Cow::Borrowed(&[
+            deployment("testdummy", 28, Deployment::NEVER_ACTIVE, Deployment::NO_TIMEOUT, 0),
+            // BIP-341, April 24th 2021 to August 11th 2021.
+            deployment("taproot", 2, 1619222400, 1628640000, 0),
+        ]),
     };
 
     /// The testnet3 parameters.
     pub const TESTNET3: Params = Params {
         network: Network::Testnet(TestnetVersion::V3),
+        subsidy_halving_interval: BlockHeightInterval::from_u32(210_000),
         bip16_time: 1333238400,                      // Apr 1 2012
         bip34_height: BlockHeight::from_u32(21111), // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
         bip65_height: BlockHeight::from_u32(581885), // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
         bip66_height: BlockHeight::from_u32(330776), // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
+        csv_height: BlockHeight::from_u32(770112), // 00000000025e930139bac5c6c31a403776da130831ab85be56578f3fa75369bb
         rule_change_activation_threshold: BlockHeightInterval::from_u32(1512), // 75%
         miner_confirmation_window: BlockHeightInterval::from_u32(2016),
         pow_limit: Target::MAX_ATTAINABLE_TESTNET,
@@ -188,15 +279,41 @@ impl Params {
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: true,
         no_pow_retargeting: false,
+        enforce_bip94: false,
+        assumeutxo: &[
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(2_500_000),
+                // 0000000000000093bcb68c03a9a168ae252572d348a2eaeba2cdf9231d73206f
+                blockhash: BlockHash::from_byte_array([
+                    111, 32, 115, 29, 35, 249, 205, 162, 235, 234, 162, 72, 211, 114, 37, 37, 174,
+                    104, 161, 169, 3, 140, 182, 188, 147, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(4_840_000),
+                // 00000000000000f4971a7fb37fbdff89315b69a2e1920c467654a382f0d64786
+                blockhash: BlockHash::from_byte_array([
+                    134, 71, 214, 240, 130, 163, 84, 118, 70, 12, 146, 225, 162, 105, 91, 49, 137,
+                    255, 189, 127, 179, 127, 26, 151, 244, 0, 0, 0, 0, 0, 0, 0,
+                ]),
+            },
+        ],
+        deployments: Cow::Borrowed(&[
+            deployment("testdummy", 28, Deployment::NEVER_ACTIVE, Deployment::NO_TIMEOUT, 0),
+            // BIP-341, April 24th 2021 to August 11th 2021.
+            deployment("taproot", 2, 1619222400, 1628640000, 0),
+        ]),
     };
 
     /// The testnet4 parameters.
     pub const TESTNET4: Params = Params {
         network: Network::Testnet(TestnetVersion::V4),
+        subsidy_halving_interval: BlockHeightInterval::from_u32(210_000),
         bip16_time: 1333238400, // Apr 1 2012
         bip34_height: BlockHeight::from_u32(1),
         bip65_height: BlockHeight::from_u32(1),
         bip66_height: BlockHeight::from_u32(1),
+        csv_height: BlockHeight::from_u32(1),
         rule_change_activation_threshold: BlockHeightInterval::from_u32(1512), // 75%
         miner_confirmation_window: BlockHeightInterval::from_u32(2016),
         pow_limit: Target::MAX_ATTAINABLE_TESTNET,
@@ -205,15 +322,40 @@ impl Params {
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: true,
         no_pow_retargeting: false,
+        enforce_bip94: true,
+        assumeutxo: &[
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(90_000),
+                // 0000000002ebe8bcda020e0dd6ccfbdfac531d2f6a81457191b99fc2df2dbe3b
+                blockhash: BlockHash::from_byte_array([
+                    59, 190, 45, 223, 194, 159, 185, 145, 113, 69, 129, 106, 47, 29, 83, 172, 223,
+                    251, 204, 214, 13, 14, 2, 218, 188, 232, 235, 2, 0, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(120_000),
+                // 000000000bd2317e51b3c5794981c35ba894ce27d3e772d5c39ecd9cbce01dc8
+                blockhash: BlockHash::from_byte_array([
+                    200, 29, 224, 188, 156, 205, 158, 195, 213, 114, 231, 211, 39, 206, 148, 168,
+                    91, 195, 129, 73, 121, 197, 179, 81, 126, 49, 210, 11, 0, 0, 0, 0,
+                ]),
+            },
+        ],
+        deployments: Cow::Borrowed(&[
+            deployment("testdummy", 28, Deployment::NEVER_ACTIVE, Deployment::NO_TIMEOUT, 0),
+            deployment("taproot", 2, Deployment::ALWAYS_ACTIVE, Deployment::NO_TIMEOUT, 0),
+        ]),
     };
 
     /// The signet parameters.
     pub const SIGNET: Params = Params {
         network: Network::Signet,
+        subsidy_halving_interval: BlockHeightInterval::from_u32(210_000),
         bip16_time: 1333238400, // Apr 1 2012
         bip34_height: BlockHeight::from_u32(1),
         bip65_height: BlockHeight::from_u32(1),
         bip66_height: BlockHeight::from_u32(1),
+        csv_height: BlockHeight::from_u32(1),
         rule_change_activation_threshold: BlockHeightInterval::from_u32(1916), // 95%
         miner_confirmation_window: BlockHeightInterval::from_u32(2016),
         pow_limit: Target::MAX_ATTAINABLE_SIGNET,
@@ -222,15 +364,40 @@ impl Params {
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: false,
         no_pow_retargeting: false,
+        enforce_bip94: false,
+        assumeutxo: &[
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(160_000),
+                // 0000003ca3c99aff040f2563c2ad8f8ec88bd0fd6b8f0895cfaf1ef90353a62c
+                blockhash: BlockHash::from_byte_array([
+                    44, 166, 83, 3, 249, 30, 175, 207, 149, 8, 143, 107, 253, 208, 139, 200, 142,
+                    143, 173, 194, 99, 37, 15, 4, 255, 154, 201, 163, 60, 0, 0, 0,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(290_000),
+                // 0000000577f2741bb30cd9d39d6d71b023afbeb9764f6260786a97969d5c9ac0
+                blockhash: BlockHash::from_byte_array([
+                    192, 154, 92, 157, 150, 151, 106, 120, 96, 98, 79, 118, 185, 190, 175, 35, 176,
+                    113, 109, 157, 211, 217, 12, 179, 27, 116, 242, 119, 5, 0, 0, 0,
+                ]),
+            },
+        ],
+        deployments: Cow::Borrowed(&[
+            deployment("testdummy", 28, Deployment::NEVER_ACTIVE, Deployment::NO_TIMEOUT, 0),
+            deployment("taproot", 2, Deployment::ALWAYS_ACTIVE, Deployment::NO_TIMEOUT, 0),
+        ]),
     };
 
     /// The regtest parameters.
     pub const REGTEST: Params = Params {
         network: Network::Regtest,
+        subsidy_halving_interval: BlockHeightInterval::from_u32(150),
         bip16_time: 1333238400,                         // Apr 1 2012
         bip34_height: BlockHeight::from_u32(100000000), // not activated on regtest
         bip65_height: BlockHeight::from_u32(1351),
         bip66_height: BlockHeight::from_u32(1251), // used only in rpc tests
+        csv_height: BlockHeight::from_u32(432),    // used only in rpc tests
         rule_change_activation_threshold: BlockHeightInterval::from_u32(108), // 75%
         miner_confirmation_window: BlockHeightInterval::from_u32(144),
         pow_limit: Target::MAX_ATTAINABLE_REGTEST,
@@ -239,6 +406,37 @@ impl Params {
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: true,
         no_pow_retargeting: true,
+        enforce_bip94: false,
+        assumeutxo: &[
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(110),
+                // 135eec25a6fb277884e5824e7aa7d052c4868161c99a5122170b5266f86c273d
+                blockhash: BlockHash::from_byte_array([
+                    61, 39, 108, 248, 102, 82, 11, 23, 34, 81, 154, 201, 97, 129, 134, 196, 82,
+                    208, 167, 122, 78, 130, 229, 132, 120, 39, 251, 166, 37, 236, 94, 19,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(200),
+                // 385901ccbd69dff6bbd00065d01fb8a9e464dede7cfe0372443884f9b1dcf6b9
+                blockhash: BlockHash::from_byte_array([
+                    185, 246, 220, 177, 249, 132, 56, 68, 114, 3, 254, 124, 222, 222, 100, 228,
+                    169, 184, 31, 208, 101, 0, 208, 187, 246, 223, 105, 189, 204, 1, 89, 56,
+                ]),
+            },
+            AssumeUtxoData {
+                height: BlockHeight::from_u32(299),
+                // 0c552ced4721c249a389eb9b08cb8da261cd46f0e7b5f9d064d48f3113406853
+                blockhash: BlockHash::from_byte_array([
+                    83, 104, 64, 19, 49, 143, 212, 100, 208, 249, 181, 231, 240, 70, 205, 97, 162,
+                    141, 203, 8, 155, 235, 137, 163, 73, 194, 33, 71, 237, 44, 85, 12,
+                ]),
+            },
+        ],
+        deployments: Cow::Borrowed(&[
+            deployment("testdummy", 28, 0, Deployment::NO_TIMEOUT, 0),
+            deployment("taproot", 2, Deployment::ALWAYS_ACTIVE, Deployment::NO_TIMEOUT, 0),
+        ]),
     };
 
     /// Creates parameters set for the given network.
@@ -256,6 +454,65 @@ pub const fn new(network: Network) -> Self {
     pub fn difficulty_adjustment_interval(&self) -> u64 {
         u64::from(self.pow_target_timespan) / self.pow_target_spacing
     }
+
+    /// Returns the new coins issued by the block at `height`.
+    ///
+    /// The subsidy starts at 50 BTC and is halved every [`subsidy_halving_interval`] blocks.
+    ///
+    /// [`subsidy_halving_interval`]: Self::subsidy_halving_interval
+    pub fn block_subsidy(&self, height: BlockHeight) -> Amount {
+        let halvings = height.to_u32().checked_div(self.subsidy_halving_interval.to_u32());
+        match halvings {
+            Some(halvings) if halvings >= 64 => Amount::ZERO,
+            Some(halvings) => Amount::from_sat(Amount::FIFTY_BTC.to_sat() >> halvings)
+                .expect("at most the initial subsidy"),
+            None => Amount::FIFTY_BTC,
+        }
+    }
+
+    /// Returns the total subsidy of the blocks up to and including the block at `height`.
+    ///
+    /// This is the maximum supply of coins at `height`. It includes the unspendable output of the
+    /// genesis block and not the subsidies miners did not claim. Saturates at [`Amount::MAX`].
+    pub fn total_supply(&self, height: BlockHeight) -> Amount {
+        let interval = u64::from(self.subsidy_halving_interval.to_u32());
+        let mut blocks = u64::from(height.to_u32()) + 1;
+        let mut supply = 0_u64;
+        let mut subsidy = Amount::FIFTY_BTC.to_sat();
+        while blocks > 0 && subsidy > 0 {
+            let era = if interval == 0 { blocks } else { core::cmp::min(blocks, interval) };
+            supply = supply.saturating_add(era.saturating_mul(subsidy));
+            blocks -= era;
+            subsidy >>= 1;
+        }
+        Amount::from_sat(supply).unwrap_or(Amount::MAX)
+    }
+
+    /// Returns the BIP-9 deployment called `name`.
+    pub fn deployment(&self, name: &str) -> Option<&Deployment> {
+        self.deployments.iter().find(|deployment| deployment.name() == name)
+    }
+
+    /// Returns the UTXO snapshot data of the block with `blockhash`, if a snapshot can be loaded
+    /// at it.
+    pub fn assumeutxo_for_blockhash(&self, blockhash: BlockHash) -> Option<&AssumeUtxoData> {
+        self.assumeutxo.iter().find(|data| data.blockhash == blockhash)
+    }
+}
+
+/// Constructs a deployment of the built-in parameters.
+const fn deployment(
+    name: &'static str,
+    bit: u8,
+    start_time: i64,
+    timeout: i64,
+    min_activation_height: u32,
+) -> Deployment {
+    let height = BlockHeight::from_u32(min_activation_height);
+    match Deployment::new(name, bit, start_time, timeout, height) {
+        Ok(deployment) => deployment,
+        Err(_) => panic!("invalid version bit"),
+    }
 }
 
 impl From<Network> for Params {
@@ -281,3 +538,65 @@ fn as_ref(&self) -> &Params { self }
 impl AsRef<Params> for Network {
     fn as_ref(&self) -> &Params { Self::params(*self) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_subsidy() {
+        let params = Params::MAINNET;
+        assert_eq!(params.block_subsidy(BlockHeight::ZERO), Amount::FIFTY_BTC);
+        assert_eq!(
+            params.block_subsidy(BlockHeight::from_u32(840_000)),
+            Amount::from_sat_u32(312_500_000)
+        );
+        assert_eq!(params.block_subsidy(BlockHeight::from_u32(6_929_999)), Amount::ONE_SAT);
+        assert_eq!(params.block_subsidy(BlockHeight::from_u32(6_930_000)), Amount::ZERO);
+        assert_eq!(params.block_subsidy(BlockHeight::MAX), Amount::ZERO);
+
+        let params = Params::REGTEST;
+        assert_eq!(params.block_subsidy(BlockHeight::from_u32(149)), Amount::FIFTY_BTC);
+        assert_eq!(
+            params.block_subsidy(BlockHeight::from_u32(150)),
+            Amount::from_sat_u32(2_500_000_000)
+        );
+    }
+
+    #[test]
+    fn total_supply() {
+        let params = Params::MAINNET;
+        assert_eq!(params.total_supply(BlockHeight::ZERO), Amount::FIFTY_BTC);
+        assert_eq!(
+            params.total_supply(BlockHeight::from_u32(209_999)),
+            Amount::from_sat(1_050_000_000_000_000).unwrap()
+        );
+        assert_eq!(
+            params.total_supply(BlockHeight::from_u32(210_000)),
+            Amount::from_sat(1_050_002_500_000_000).unwrap()
+        );
+        assert_eq!(
+            params.total_supply(BlockHeight::MAX),
+            Amount::from_sat(2_099_999_997_690_000).unwrap()
+        );
+
+        let params = Params::REGTEST;
+        assert_eq!(
+            params.total_supply(BlockHeight::from_u32(150)),
+            Amount::from_sat(752_500_000_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn runtime_deployments() {
+        let mut params = Params::new(Network::Regtest);
+        assert!(params.deployment("custom").is_none());
+
+        let custom =
+            Deployment::new("custom", 5, 0, Deployment::NO_TIMEOUT, BlockHeight::ZERO).unwrap();
+        params.deployments.to_mut().push(custom);
+        assert_eq!(params.deployment("custom"), Some(&custom));
+        assert!(params.deployment("testdummy").is_some());
+        assert_eq!(Params::REGTEST.deployments.len(), 2);
+    }
+}
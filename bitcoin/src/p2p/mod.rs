@@ -20,11 +20,14 @@
 #[cfg(feature = "std")]
 pub mod message_network;
 #[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
 mod deser;
 
 use core::str::FromStr;
 use core::{fmt, ops};
 
+use hashes::sha256d;
 use hex::FromHex;
 use internals::{impl_to_hex_from_lower_hex, write_err};
 use io::{BufRead, Write};
@@ -32,6 +35,7 @@
 use crate::consensus::encode::{self, Decodable, Encodable};
 use crate::network::{Network, Params, TestnetVersion};
 use crate::prelude::{Borrow, BorrowMut, String, ToOwned};
+use crate::script::Script;
 
 #[rustfmt::skip]
 #[doc(inline)]
@@ -238,6 +242,17 @@ impl Magic {
 
     /// Returns the magic bytes for the network defined by `params`.
     pub fn from_params(params: impl AsRef<Params>) -> Self { params.as_ref().network.into() }
+
+    /// Returns the magic bytes of the signet with the block `challenge` script.
+    ///
+    /// These are the first four bytes of the double SHA-256 hash of the serialized challenge, for
+    /// the challenge of the default signet ([`signet::DEFAULT_CHALLENGE`]) [`Magic::SIGNET`].
+    ///
+    /// [`signet::DEFAULT_CHALLENGE`]: crate::signet::DEFAULT_CHALLENGE
+    pub fn from_signet_challenge(challenge: &Script) -> Self {
+        let hash = sha256d::Hash::hash(&encode::serialize(challenge)).to_byte_array();
+        Magic([hash[0], hash[1], hash[2], hash[3]])
+    }
 }
 
 impl FromStr for Magic {
@@ -476,6 +491,12 @@ fn service_flags_test() {
         assert_eq!("ServiceFlags(WITNESS|COMPACT_FILTERS|0xb0)", flag.to_string());
     }
 
+    #[test]
+    fn magic_from_signet_challenge() {
+        assert_eq!(Magic::from_signet_challenge(crate::signet::DEFAULT_CHALLENGE), Magic::SIGNET);
+        assert_ne!(Magic::from_signet_challenge(Script::new()), Magic::SIGNET);
+    }
+
     #[test]
     fn magic_from_str() {
         let known_network_magic_strs = [
@@ -0,0 +1,785 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP-324 encrypted transport.
+//!
+//! Implementation of the version 2 p2p transport protocol as defined by
+//! <https://github.com/bitcoin/bips/blob/master/bip-0324.mediawiki>.
+//!
+//! [`V2Transport`] does no I/O itself: bytes read from the peer are passed to
+//! [`V2Transport::receive`] and the bytes to write to the peer are taken with
+//! [`V2Transport::take_outbound`].
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::p2p::message::{NetworkMessage, V2NetworkMessage};
+//! use bitcoin::p2p::transport::{Role, V2Transport};
+//! use bitcoin::p2p::Magic;
+//! use bitcoin::secp256k1::{Secp256k1, SecretKey};
+//!
+//! let secp = Secp256k1::new();
+//! let key = |byte| SecretKey::from_byte_array(&[byte; 32]).unwrap();
+//! let mut alice =
+//!     V2Transport::new(&secp, Role::Initiator, Magic::BITCOIN, key(1), [1; 32], vec![]).unwrap();
+//! let mut bob =
+//!     V2Transport::new(&secp, Role::Responder, Magic::BITCOIN, key(2), [2; 32], vec![]).unwrap();
+//!
+//! // Shuffle bytes between the peers until both completed the handshake.
+//! while !alice.is_established() || !bob.is_established() {
+//!     bob.receive(&alice.take_outbound()).unwrap();
+//!     alice.receive(&bob.take_outbound()).unwrap();
+//! }
+//! assert_eq!(alice.session_id(), bob.session_id());
+//!
+//! alice.send(&V2NetworkMessage::new(NetworkMessage::Ping(42))).unwrap();
+//! bob.receive(&alice.take_outbound()).unwrap();
+//! assert_eq!(bob.next_message().unwrap().into_payload(), NetworkMessage::Ping(42));
+//! ```
+
+use core::fmt;
+use std::collections::VecDeque;
+
+use chacha20_poly1305::chacha20::ChaCha20;
+use chacha20_poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hashes::{hkdf, sha256};
+use internals::write_err;
+use secp256k1::ellswift::{ElligatorSwift, ElligatorSwiftParty};
+use secp256k1::{Secp256k1, SecretKey, Verification};
+
+use crate::consensus::encode::{self, DeserializeError};
+use crate::p2p::message::V2NetworkMessage;
+use crate::p2p::Magic;
+
+/// The maximum number of garbage bytes sent after the public key.
+pub const MAX_GARBAGE_LEN: usize = 4095;
+
+/// The maximum length of a packet's contents, enough for a block of the maximum size.
+pub const MAX_CONTENTS_LEN: usize = 1 + 12 + 4_000_000;
+
+/// Length of an ElligatorSwift encoded public key.
+const ELLSWIFT_LEN: usize = 64;
+
+/// Length of a garbage terminator.
+const GARBAGE_TERMINATOR_LEN: usize = 16;
+
+/// Length of the encrypted packet length.
+const LENGTH_LEN: usize = 3;
+
+/// Length of the packet header.
+const HEADER_LEN: usize = 1;
+
+/// Length of the Poly1305 authentication tag.
+const TAG_LEN: usize = 16;
+
+/// Header bit set on decoy packets, which the receiver ignores.
+const IGNORE_BIT: u8 = 0x80;
+
+/// Number of messages encrypted with a key before the key is replaced.
+const REKEY_INTERVAL: u32 = 224;
+
+/// The side of the connection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// The peer which opened the connection.
+    Initiator,
+    /// The peer which accepted the connection.
+    Responder,
+}
+
+/// The state of the handshake.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    /// Waiting for the peer's public key.
+    ReceivingKey,
+    /// Waiting for the peer's garbage terminator.
+    ReceivingGarbage,
+    /// Waiting for the peer's version packet.
+    ReceivingVersion,
+    /// The handshake is complete.
+    Established,
+}
+
+/// A BIP-324 encrypted connection to a single peer.
+///
+/// Any error returned is fatal, the connection should be closed.
+pub struct V2Transport {
+    role: Role,
+    magic: Magic,
+    secret_key: SecretKey,
+    our_key: ElligatorSwift,
+    state: State,
+    /// Whether our public key and garbage were written to `outbound`.
+    key_sent: bool,
+    /// Our garbage, authenticated by the first packet we send.
+    our_garbage: Vec<u8>,
+    /// The peer's garbage, authenticated by the first packet we receive.
+    their_garbage: Vec<u8>,
+    ciphers: Option<PacketCiphers>,
+    /// Decrypted length of the packet currently being received.
+    packet_len: Option<usize>,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+    messages: VecDeque<V2NetworkMessage>,
+}
+
+impl V2Transport {
+    /// Constructs a new transport.
+    ///
+    /// `entropy` randomizes the encoding of the public key for `secret_key` and `garbage` is sent
+    /// right after it. Both should be random, and fresh for every connection.
+    ///
+    /// # Errors
+    ///
+    /// If `garbage` is longer than [`MAX_GARBAGE_LEN`].
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        role: Role,
+        magic: Magic,
+        secret_key: SecretKey,
+        entropy: [u8; 32],
+        garbage: Vec<u8>,
+    ) -> Result<Self, Error> {
+        if garbage.len() > MAX_GARBAGE_LEN {
+            return Err(Error::GarbageTooLong(garbage.len()));
+        }
+        let mut transport = V2Transport {
+            role,
+            magic,
+            secret_key,
+            our_key: ElligatorSwift::from_seckey(secp, secret_key, Some(entropy)),
+            state: State::ReceivingKey,
+            key_sent: false,
+            our_garbage: garbage,
+            their_garbage: Vec::new(),
+            ciphers: None,
+            packet_len: None,
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+            messages: VecDeque::new(),
+        };
+        // The responder waits until it knows the initiator does not use the v1 protocol.
+        if role == Role::Initiator {
+            transport.send_key();
+        }
+        Ok(transport)
+    }
+
+    /// Constructs a new transport with a random key and random garbage.
+    #[cfg(feature = "rand-std")]
+    pub fn generate<C: Verification>(secp: &Secp256k1<C>, role: Role, magic: Magic) -> Self {
+        use secp256k1::rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+        let secret_key = SecretKey::new(&mut rng);
+        let mut garbage = vec![0; rng.gen_range(0..=MAX_GARBAGE_LEN)];
+        rng.fill(&mut garbage[..]);
+        Self::new(secp, role, magic, secret_key, rng.gen(), garbage)
+            .expect("garbage is not too long")
+    }
+
+    /// Returns the role of this side of the connection.
+    pub fn role(&self) -> Role { self.role }
+
+    /// Returns true if the handshake is complete.
+    pub fn is_established(&self) -> bool { self.state == State::Established }
+
+    /// Returns the session ID, once the public keys were exchanged.
+    ///
+    /// Both peers have the same session ID, comparing it out of band detects a man in the middle.
+    pub fn session_id(&self) -> Option<[u8; 32]> {
+        self.ciphers.as_ref().map(|ciphers| ciphers.session_id)
+    }
+
+    /// Takes the bytes which should be written to the peer.
+    pub fn take_outbound(&mut self) -> Vec<u8> { core::mem::take(&mut self.outbound) }
+
+    /// Returns the next message received from the peer, if any.
+    pub fn next_message(&mut self) -> Option<V2NetworkMessage> { self.messages.pop_front() }
+
+    /// Processes bytes received from the peer.
+    ///
+    /// Handshake data is answered by writing to the outbound buffer and received messages are
+    /// returned by [`V2Transport::next_message`].
+    pub fn receive(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.inbound.extend_from_slice(bytes);
+        loop {
+            match self.state {
+                State::ReceivingKey => {
+                    if !self.key_sent {
+                        let prefix = v1_version_prefix(self.magic);
+                        let len = self.inbound.len().min(prefix.len());
+                        if self.inbound[..len] != prefix[..len] {
+                            self.send_key();
+                        } else if len == prefix.len() {
+                            return Err(Error::V1Protocol);
+                        } else {
+                            return Ok(());
+                        }
+                    }
+                    if self.inbound.len() < ELLSWIFT_LEN {
+                        return Ok(());
+                    }
+                    let mut their_key = [0; ELLSWIFT_LEN];
+                    their_key.copy_from_slice(&self.inbound[..ELLSWIFT_LEN]);
+                    self.inbound.drain(..ELLSWIFT_LEN);
+                    self.complete_key_exchange(ElligatorSwift::from_array(their_key));
+                    self.state = State::ReceivingGarbage;
+                }
+                State::ReceivingGarbage => {
+                    let terminator =
+                        self.ciphers.as_ref().expect("keys are derived").their_garbage_terminator;
+                    let search_len =
+                        self.inbound.len().min(MAX_GARBAGE_LEN + GARBAGE_TERMINATOR_LEN);
+                    match self.inbound[..search_len]
+                        .windows(GARBAGE_TERMINATOR_LEN)
+                        .position(|window| window == terminator)
+                    {
+                        Some(pos) => {
+                            self.their_garbage = self.inbound[..pos].to_vec();
+                            self.inbound.drain(..pos + GARBAGE_TERMINATOR_LEN);
+                            self.state = State::ReceivingVersion;
+                        }
+                        None if search_len == MAX_GARBAGE_LEN + GARBAGE_TERMINATOR_LEN =>
+                            return Err(Error::MissingGarbageTerminator),
+                        None => return Ok(()),
+                    }
+                }
+                State::ReceivingVersion | State::Established => {
+                    let contents = match self.receive_packet()? {
+                        Some(contents) => contents,
+                        None => return Ok(()),
+                    };
+                    if contents[0] & IGNORE_BIT != 0 {
+                        continue;
+                    }
+                    // The version packet's contents are reserved for future extensions.
+                    if self.state == State::ReceivingVersion {
+                        self.state = State::Established;
+                        continue;
+                    }
+                    let message = encode::deserialize(&contents[HEADER_LEN..])
+                        .map_err(Error::InvalidMessage)?;
+                    self.messages.push_back(message);
+                }
+            }
+        }
+    }
+
+    /// Encrypts `message` and writes it to the outbound buffer.
+    ///
+    /// Messages can be sent before the handshake is complete, as soon as the peer's public key was
+    /// received.
+    pub fn send(&mut self, message: &V2NetworkMessage) -> Result<(), Error> {
+        self.send_packet(0, &encode::serialize(message))
+    }
+
+    /// Writes a decoy packet, which the peer ignores, to the outbound buffer.
+    pub fn send_decoy(&mut self, contents: &[u8]) -> Result<(), Error> {
+        self.send_packet(IGNORE_BIT, contents)
+    }
+
+    fn send_packet(&mut self, header: u8, contents: &[u8]) -> Result<(), Error> {
+        if contents.len() > MAX_CONTENTS_LEN {
+            return Err(Error::PacketTooLong(contents.len()));
+        }
+        let ciphers = self.ciphers.as_mut().ok_or(Error::HandshakeIncomplete)?;
+        let aad = core::mem::take(&mut self.our_garbage);
+        self.outbound.extend(ciphers.encrypt(header, contents, &aad));
+        Ok(())
+    }
+
+    /// Decrypts the next packet from the inbound buffer, if it was received completely.
+    fn receive_packet(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let ciphers = self.ciphers.as_mut().expect("keys are derived");
+        let len = match self.packet_len {
+            Some(len) => len,
+            None => {
+                if self.inbound.len() < LENGTH_LEN {
+                    return Ok(None);
+                }
+                let mut length = [0; LENGTH_LEN];
+                length.copy_from_slice(&self.inbound[..LENGTH_LEN]);
+                self.inbound.drain(..LENGTH_LEN);
+                let len = ciphers.decrypt_length(length);
+                if len > MAX_CONTENTS_LEN {
+                    return Err(Error::PacketTooLong(len));
+                }
+                self.packet_len = Some(len);
+                len
+            }
+        };
+        if self.inbound.len() < HEADER_LEN + len + TAG_LEN {
+            return Ok(None);
+        }
+        let mut contents = self.inbound.drain(..HEADER_LEN + len).collect::<Vec<_>>();
+        let mut tag = [0; TAG_LEN];
+        tag.copy_from_slice(&self.inbound[..TAG_LEN]);
+        self.inbound.drain(..TAG_LEN);
+
+        let aad = core::mem::take(&mut self.their_garbage);
+        ciphers.decrypt(&mut contents, tag, &aad)?;
+        self.packet_len = None;
+        Ok(Some(contents))
+    }
+
+    /// Writes our public key and garbage to the outbound buffer.
+    fn send_key(&mut self) {
+        self.outbound.extend_from_slice(&self.our_key.to_array());
+        self.outbound.extend_from_slice(&self.our_garbage);
+        self.key_sent = true;
+    }
+
+    /// Derives the session keys and writes our garbage terminator and version packet.
+    fn complete_key_exchange(&mut self, their_key: ElligatorSwift) {
+        let ciphers = PacketCiphers::new(
+            SessionKeys::derive(self.secret_key, self.our_key, their_key, self.role, self.magic),
+            self.role,
+        );
+        self.outbound.extend_from_slice(&ciphers.our_garbage_terminator);
+        self.ciphers = Some(ciphers);
+        self.send_packet(0, &[]).expect("keys are derived");
+    }
+}
+
+impl fmt::Debug for V2Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("V2Transport")
+            .field("role", &self.role)
+            .field("magic", &self.magic)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the start of a v1 `version` message, which no v2 public key begins with.
+fn v1_version_prefix(magic: Magic) -> [u8; 16] {
+    let mut prefix = [0; 16];
+    prefix[..4].copy_from_slice(&magic.to_bytes());
+    prefix[4..11].copy_from_slice(b"version");
+    prefix
+}
+
+/// The keys derived from the shared secret.
+struct SessionKeys {
+    session_id: [u8; 32],
+    initiator_length: [u8; 32],
+    initiator_packet: [u8; 32],
+    responder_length: [u8; 32],
+    responder_packet: [u8; 32],
+    initiator_garbage_terminator: [u8; GARBAGE_TERMINATOR_LEN],
+    responder_garbage_terminator: [u8; GARBAGE_TERMINATOR_LEN],
+}
+
+impl SessionKeys {
+    fn derive(
+        secret_key: SecretKey,
+        our_key: ElligatorSwift,
+        their_key: ElligatorSwift,
+        role: Role,
+        magic: Magic,
+    ) -> Self {
+        let (initiator_key, responder_key, party) = match role {
+            Role::Initiator => (our_key, their_key, ElligatorSwiftParty::A),
+            Role::Responder => (their_key, our_key, ElligatorSwiftParty::B),
+        };
+        let shared_secret =
+            ElligatorSwift::shared_secret(initiator_key, responder_key, secret_key, party, None);
+
+        let mut salt = b"bitcoin_v2_shared_secret".to_vec();
+        salt.extend_from_slice(&magic.to_bytes());
+        let hkdf = hkdf::Hkdf::<sha256::HashEngine>::new(&salt, &shared_secret.to_secret_bytes());
+        let expand = |info: &[u8]| {
+            let mut okm = [0; 32];
+            hkdf.expand(info, &mut okm).expect("32 bytes is a valid length");
+            okm
+        };
+
+        let garbage_terminators = expand(b"garbage_terminators");
+        let mut initiator_garbage_terminator = [0; GARBAGE_TERMINATOR_LEN];
+        let mut responder_garbage_terminator = [0; GARBAGE_TERMINATOR_LEN];
+        initiator_garbage_terminator.copy_from_slice(&garbage_terminators[..16]);
+        responder_garbage_terminator.copy_from_slice(&garbage_terminators[16..]);
+        SessionKeys {
+            session_id: expand(b"session_id"),
+            initiator_length: expand(b"initiator_L"),
+            initiator_packet: expand(b"initiator_P"),
+            responder_length: expand(b"responder_L"),
+            responder_packet: expand(b"responder_P"),
+            initiator_garbage_terminator,
+            responder_garbage_terminator,
+        }
+    }
+}
+
+/// The ciphers of one side of the connection.
+struct PacketCiphers {
+    session_id: [u8; 32],
+    send_length: FsChaCha20,
+    send_packet: FsChaCha20Poly1305,
+    receive_length: FsChaCha20,
+    receive_packet: FsChaCha20Poly1305,
+    our_garbage_terminator: [u8; GARBAGE_TERMINATOR_LEN],
+    their_garbage_terminator: [u8; GARBAGE_TERMINATOR_LEN],
+}
+
+impl PacketCiphers {
+    fn new(keys: SessionKeys, role: Role) -> Self {
+        let initiator = (
+            FsChaCha20::new(keys.initiator_length),
+            FsChaCha20Poly1305::new(keys.initiator_packet),
+            keys.initiator_garbage_terminator,
+        );
+        let responder = (
+            FsChaCha20::new(keys.responder_length),
+            FsChaCha20Poly1305::new(keys.responder_packet),
+            keys.responder_garbage_terminator,
+        );
+        let (ours, theirs) = match role {
+            Role::Initiator => (initiator, responder),
+            Role::Responder => (responder, initiator),
+        };
+        PacketCiphers {
+            session_id: keys.session_id,
+            send_length: ours.0,
+            send_packet: ours.1,
+            receive_length: theirs.0,
+            receive_packet: theirs.1,
+            our_garbage_terminator: ours.2,
+            their_garbage_terminator: theirs.2,
+        }
+    }
+
+    /// Returns the encrypted length, header and contents followed by the authentication tag.
+    fn encrypt(&mut self, header: u8, contents: &[u8], aad: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(LENGTH_LEN + HEADER_LEN + contents.len() + TAG_LEN);
+        packet.extend_from_slice(&(contents.len() as u32).to_le_bytes()[..LENGTH_LEN]);
+        self.send_length.crypt(&mut packet[..LENGTH_LEN]);
+        packet.push(header);
+        packet.extend_from_slice(contents);
+        let tag = self.send_packet.encrypt(&mut packet[LENGTH_LEN..], aad);
+        packet.extend_from_slice(&tag);
+        packet
+    }
+
+    /// Decrypts the length of the contents of the next packet.
+    fn decrypt_length(&mut self, mut length: [u8; LENGTH_LEN]) -> usize {
+        self.receive_length.crypt(&mut length);
+        u32::from_le_bytes([length[0], length[1], length[2], 0]) as usize
+    }
+
+    /// Decrypts the header and contents of a packet in place.
+    fn decrypt(&mut self, packet: &mut [u8], tag: [u8; TAG_LEN], aad: &[u8]) -> Result<(), Error> {
+        self.receive_packet.decrypt(packet, tag, aad)
+    }
+}
+
+/// Returns the nonce for the `counter`th message encrypted with the `rekey_counter`th key.
+fn nonce(counter: u32, rekey_counter: u64) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[..4].copy_from_slice(&counter.to_le_bytes());
+    nonce[4..].copy_from_slice(&rekey_counter.to_le_bytes());
+    Nonce::new(nonce)
+}
+
+/// ChaCha20 with forward secrecy, used to encrypt packet lengths.
+///
+/// The keystream is continuous, every [`REKEY_INTERVAL`] chunks the key is replaced by the next
+/// 32 bytes of the keystream.
+struct FsChaCha20 {
+    cipher: ChaCha20,
+    chunk_counter: u32,
+    rekey_counter: u64,
+}
+
+impl FsChaCha20 {
+    fn new(key: [u8; 32]) -> Self {
+        FsChaCha20 {
+            cipher: ChaCha20::new(Key::new(key), nonce(0, 0), 0),
+            chunk_counter: 0,
+            rekey_counter: 0,
+        }
+    }
+
+    /// Encrypts or decrypts `chunk` in place.
+    fn crypt(&mut self, chunk: &mut [u8]) {
+        self.cipher.apply_keystream(chunk);
+        self.chunk_counter += 1;
+        if self.chunk_counter == REKEY_INTERVAL {
+            let mut key = [0; 32];
+            self.cipher.apply_keystream(&mut key);
+            self.chunk_counter = 0;
+            self.rekey_counter += 1;
+            self.cipher = ChaCha20::new(Key::new(key), nonce(0, self.rekey_counter), 0);
+        }
+    }
+}
+
+/// ChaCha20Poly1305 with forward secrecy, used to encrypt packets.
+///
+/// Every [`REKEY_INTERVAL`] packets the key is replaced.
+struct FsChaCha20Poly1305 {
+    key: [u8; 32],
+    packet_counter: u32,
+    rekey_counter: u64,
+}
+
+impl FsChaCha20Poly1305 {
+    fn new(key: [u8; 32]) -> Self {
+        FsChaCha20Poly1305 { key, packet_counter: 0, rekey_counter: 0 }
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::new(self.key), nonce(self.packet_counter, self.rekey_counter))
+    }
+
+    /// Encrypts `content` in place, returning the authentication tag.
+    fn encrypt(&mut self, content: &mut [u8], aad: &[u8]) -> [u8; TAG_LEN] {
+        let tag = self.cipher().encrypt(content, Some(aad));
+        self.next_packet();
+        tag
+    }
+
+    /// Decrypts `content` in place if the authentication tag is valid.
+    fn decrypt(&mut self, content: &mut [u8], tag: [u8; TAG_LEN], aad: &[u8]) -> Result<(), Error> {
+        self.cipher().decrypt(content, tag, Some(aad)).map_err(|_| Error::Decryption)?;
+        self.next_packet();
+        Ok(())
+    }
+
+    fn next_packet(&mut self) {
+        self.packet_counter += 1;
+        if self.packet_counter == REKEY_INTERVAL {
+            let mut key = [0; 32];
+            ChaCha20Poly1305::new(Key::new(self.key), nonce(u32::MAX, self.rekey_counter))
+                .encrypt(&mut key, None);
+            self.key = key;
+            self.packet_counter = 0;
+            self.rekey_counter += 1;
+        }
+    }
+}
+
+/// An error in the v2 transport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The garbage is longer than [`MAX_GARBAGE_LEN`].
+    GarbageTooLong(usize),
+    /// The initiator uses the v1 protocol.
+    V1Protocol,
+    /// The garbage terminator was not found after the maximum amount of garbage.
+    MissingGarbageTerminator,
+    /// A packet is longer than [`MAX_CONTENTS_LEN`].
+    PacketTooLong(usize),
+    /// The authentication tag of a packet is invalid.
+    Decryption,
+    /// A packet was sent before the peer's public key was received.
+    HandshakeIncomplete,
+    /// The contents of a packet are not a valid message.
+    InvalidMessage(DeserializeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            GarbageTooLong(len) =>
+                write!(f, "garbage of {} bytes exceeds the maximum of {}", len, MAX_GARBAGE_LEN),
+            V1Protocol => write!(f, "the peer uses the v1 transport protocol"),
+            MissingGarbageTerminator => write!(f, "garbage terminator not found"),
+            PacketTooLong(len) =>
+                write!(f, "packet of {} bytes exceeds the maximum of {}", len, MAX_CONTENTS_LEN),
+            Decryption => write!(f, "packet authentication failed"),
+            HandshakeIncomplete => write!(f, "the peer's public key has not been received"),
+            InvalidMessage(ref e) => write_err!(f, "invalid message"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            InvalidMessage(ref e) => Some(e),
+            GarbageTooLong(_)
+            | V1Protocol
+            | MissingGarbageTerminator
+            | PacketTooLong(_)
+            | Decryption
+            | HandshakeIncomplete => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::{DisplayHex, FromHex};
+
+    use super::*;
+    use crate::p2p::message::NetworkMessage;
+
+    fn ellswift(hex: &str) -> ElligatorSwift {
+        ElligatorSwift::from_array(<[u8; 64]>::from_hex(hex).unwrap())
+    }
+
+    fn secret_key(hex: &str) -> SecretKey {
+        SecretKey::from_byte_array(&<[u8; 32]>::from_hex(hex).unwrap()).unwrap()
+    }
+
+    // Test vectors from the BIP.
+    #[test]
+    fn session_keys() {
+        let keys = SessionKeys::derive(
+            secret_key("61062ea5071d800bbfd59e2e8b53d47d194b095ae5a4df04936b49772ef0d4d7"),
+            ellswift("ec0adff257bbfe500c188c80b4fdd640f6b45a482bbc15fc7cef5931deff0aa186f6eb9bba7b85dc4dcc28b28722de1e3d9108b985e2967045668f66098e475b"),
+            ellswift("a4a94dfce69b4a2a0a099313d10f9f7e7d649d60501c9e1d274c300e0d89aafaffffffffffffffffffffffffffffffffffffffffffffffffffffffff8faf88d5"),
+            Role::Initiator,
+            Magic::BITCOIN,
+        );
+        assert_eq!(
+            keys.initiator_length.to_lower_hex_string(),
+            "9a6478b5fbab1f4dd2f78994b774c03211c78312786e602da75a0d1767fb55cf"
+        );
+        assert_eq!(
+            keys.initiator_packet.to_lower_hex_string(),
+            "7d0c7820ba6a4d29ce40baf2caa6035e04f1e1cefd59f3e7e59e9e5af84f1f51"
+        );
+        assert_eq!(
+            keys.responder_length.to_lower_hex_string(),
+            "17bc726421e4054ac6a1d54915085aaa766f4d3cf67bbd168e6080eac289d15e"
+        );
+        assert_eq!(
+            keys.responder_packet.to_lower_hex_string(),
+            "9f0fc1c0e85fd9a8eee07e6fc41dba2ff54c7729068a239ac97c37c524cca1c0"
+        );
+        assert_eq!(
+            keys.initiator_garbage_terminator.to_lower_hex_string(),
+            "faef555dfcdb936425d84aba524758f3"
+        );
+        assert_eq!(
+            keys.responder_garbage_terminator.to_lower_hex_string(),
+            "02cb8ff24307a6e27de3b4e7ea3fa65b"
+        );
+
+        let mut ciphers = PacketCiphers::new(keys, Role::Initiator);
+        ciphers.encrypt(0, &[0; 100], &[]);
+        assert_eq!(
+            ciphers.encrypt(0, &[0x8e], &[]).to_lower_hex_string(),
+            "7530d2a18720162ac09c25329a60d75adf36eda3c3"
+        );
+    }
+
+    #[test]
+    fn rekeying() {
+        let keys = SessionKeys::derive(
+            secret_key("1f9c581b35231838f0f17cf0c979835baccb7f3abbbb96ffcc318ab71e6e126f"),
+            ellswift("a1855e10e94e00baa23041d916e259f7044e491da6171269694763f018c7e63693d29575dcb464ac816baa1be353ba12e3876cba7628bd0bd8e755e721eb0140"),
+            ellswift("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f0000000000000000000000000000000000000000000000000000000000000000"),
+            Role::Responder,
+            Magic::BITCOIN,
+        );
+        assert_eq!(
+            keys.session_id.to_lower_hex_string(),
+            "9267c54560607de73f18c563b76a2442718879c52dd39852885d4a3c9912c9ea"
+        );
+
+        let mut ciphers = PacketCiphers::new(keys, Role::Responder);
+        for _ in 0..999 {
+            ciphers.encrypt(0, &[], &[]);
+        }
+        let contents = Vec::from_hex("3eb1d4e98035cfd8eeb29bac969ed3824a").unwrap();
+        assert_eq!(
+            ciphers.encrypt(0, &contents, &[]).to_lower_hex_string(),
+            "1da1bcf589f9b61872f45b7fa5371dd3f8bdf5d515b0c5f9fe9f0044afb8dc0aa1cd39a8c4"
+        );
+    }
+
+    fn transport(role: Role, byte: u8, garbage: Vec<u8>) -> V2Transport {
+        V2Transport::new(
+            &Secp256k1::new(),
+            role,
+            Magic::BITCOIN,
+            SecretKey::from_byte_array(&[byte; 32]).unwrap(),
+            [byte; 32],
+            garbage,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn handshake_and_messages() {
+        let mut alice = transport(Role::Initiator, 1, vec![0xaa; 100]);
+        let mut bob = transport(Role::Responder, 2, vec![0xbb; MAX_GARBAGE_LEN]);
+        assert_eq!(
+            alice.send(&V2NetworkMessage::new(NetworkMessage::Verack)),
+            Err(Error::HandshakeIncomplete)
+        );
+
+        // Deliver the bytes one at a time to exercise partial reads.
+        for byte in alice.take_outbound() {
+            bob.receive(&[byte]).unwrap();
+        }
+        alice.receive(&bob.take_outbound()).unwrap();
+        assert!(alice.is_established());
+        assert!(!bob.is_established());
+        assert!(alice.session_id().is_some());
+
+        alice.send_decoy(&[1, 2, 3]).unwrap();
+        alice.send(&V2NetworkMessage::new(NetworkMessage::Verack)).unwrap();
+        alice.send(&V2NetworkMessage::new(NetworkMessage::Ping(7))).unwrap();
+        bob.receive(&alice.take_outbound()).unwrap();
+        assert!(bob.is_established());
+        assert_eq!(alice.session_id(), bob.session_id());
+        assert_eq!(bob.next_message().unwrap().into_payload(), NetworkMessage::Verack);
+        assert_eq!(bob.next_message().unwrap().into_payload(), NetworkMessage::Ping(7));
+        assert_eq!(bob.next_message(), None);
+
+        // Enough messages to rekey both ciphers.
+        for nonce in 0..500 {
+            bob.send(&V2NetworkMessage::new(NetworkMessage::Pong(nonce))).unwrap();
+        }
+        alice.receive(&bob.take_outbound()).unwrap();
+        for nonce in 0..500 {
+            assert_eq!(alice.next_message().unwrap().into_payload(), NetworkMessage::Pong(nonce));
+        }
+    }
+
+    #[test]
+    fn handshake_errors() {
+        let mut bob = transport(Role::Responder, 2, vec![]);
+        let mut v1 = v1_version_prefix(Magic::BITCOIN).to_vec();
+        v1.extend_from_slice(&[0; 100]);
+        assert_eq!(bob.receive(&v1), Err(Error::V1Protocol));
+        assert!(bob.take_outbound().is_empty());
+
+        let mut alice = transport(Role::Initiator, 1, vec![]);
+        let mut bob = transport(Role::Responder, 2, vec![]);
+        bob.receive(&alice.take_outbound()).unwrap();
+        let mut outbound = bob.take_outbound();
+        outbound.truncate(ELLSWIFT_LEN);
+        outbound.extend_from_slice(&[0; MAX_GARBAGE_LEN + GARBAGE_TERMINATOR_LEN]);
+        assert_eq!(alice.receive(&outbound), Err(Error::MissingGarbageTerminator));
+
+        let mut alice = transport(Role::Initiator, 1, vec![]);
+        let mut bob = transport(Role::Responder, 2, vec![]);
+        bob.receive(&alice.take_outbound()).unwrap();
+        let mut outbound = bob.take_outbound();
+        *outbound.last_mut().unwrap() ^= 1;
+        assert_eq!(alice.receive(&outbound), Err(Error::Decryption));
+
+        assert!(matches!(
+            V2Transport::new(
+                &Secp256k1::new(),
+                Role::Initiator,
+                Magic::BITCOIN,
+                secret_key(&"01".repeat(32)),
+                [0; 32],
+                vec![0; MAX_GARBAGE_LEN + 1]
+            ),
+            Err(Error::GarbageTooLong(_))
+        ));
+    }
+}
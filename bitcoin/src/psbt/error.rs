@@ -7,6 +7,7 @@
 
 use crate::bip32::Xpub;
 use crate::consensus::encode;
+use crate::locktime::absolute;
 use crate::prelude::Box;
 use crate::psbt::raw;
 use crate::transaction::Transaction;
@@ -104,6 +105,14 @@ pub enum Error {
     Version(&'static str),
     /// PSBT data is not consumed entirely
     PartialDataConsumption,
+    /// A key that must be excluded from this PSBT version is present.
+    ExcludedKey(raw::Key),
+    /// A field that is required by this PSBT version is missing.
+    MissingRequiredField(&'static str),
+    /// Invalid required lock time for an input of a version 2 PSBT.
+    InvalidLockTime(absolute::ConversionError),
+    /// The inputs of a version 2 PSBT require incompatible lock time types.
+    IncompatibleLockTimes,
     /// I/O error.
     Io(io::Error),
 }
@@ -167,6 +176,11 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             Version(s) => write!(f, "version error {}", s),
             PartialDataConsumption =>
                 f.write_str("data not consumed entirely when explicitly deserializing"),
+            ExcludedKey(ref rkey) => write!(f, "key not allowed in this PSBT version: {}", rkey),
+            MissingRequiredField(s) => write!(f, "missing required field {}", s),
+            InvalidLockTime(ref e) => write_err!(f, "invalid required lock time"; e),
+            IncompatibleLockTimes =>
+                f.write_str("inputs require both a block height and a block time lock time"),
             Io(ref e) => write_err!(f, "I/O error"; e),
         }
     }
@@ -183,6 +197,7 @@ fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
             ConsensusDeserialize(ref e) => Some(e),
             ConsensusParse(ref e) => Some(e),
             Io(ref e) => Some(e),
+            InvalidLockTime(ref e) => Some(e),
             InvalidMagic
             | MissingUtxo
             | InvalidSeparator
@@ -211,7 +226,10 @@ fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
             | TapTree(_)
             | XPubKey(_)
             | Version(_)
-            | PartialDataConsumption => None,
+            | PartialDataConsumption
+            | ExcludedKey(_)
+            | MissingRequiredField(_)
+            | IncompatibleLockTimes => None,
         }
     }
 }
@@ -0,0 +1,322 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The PSBT Input Finalizer role.
+//!
+//! Builds `final_script_sig` and `final_script_witness` from the signatures and scripts collected
+//! in an input, as described in BIP-174. Only common script templates are supported, for anything
+//! more elaborate use a miniscript aware finalizer.
+
+use core::convert::Infallible;
+use core::{fmt, mem};
+
+use internals::write_err;
+
+use super::{IndexOutOfBoundsError, Input, Psbt};
+use crate::address::script_pubkey::{ScriptBufExt as _, ScriptExt as _};
+use crate::crypto::key::{PublicKey, XOnlyPublicKey};
+use crate::opcodes::all::*;
+use crate::prelude::{BTreeMap, Vec};
+use crate::script::{Builder, Instruction, PushBytes, Script, ScriptBuf, ScriptExt as _};
+use crate::taproot::{LeafVersion, TapLeafHash};
+use crate::witness::{Witness, WitnessExt as _};
+
+impl Psbt {
+    /// Finalizes all inputs of this PSBT.
+    ///
+    /// Inputs that are already finalized are left untouched. If an input can not be finalized the
+    /// remaining inputs are still attempted, the PSBT then contains a mix of finalized and
+    /// non-finalized inputs.
+    ///
+    /// # Errors
+    ///
+    /// A map of input index -> the error encountered while finalizing that input.
+    pub fn finalize(&mut self) -> Result<(), FinalizeErrors> {
+        let mut errors = BTreeMap::new();
+        for input_index in 0..self.inputs.len() {
+            if let Err(e) = self.finalize_input(input_index) {
+                errors.insert(input_index, e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Finalizes the input at `input_index`.
+    ///
+    /// Supports the following outputs being spent:
+    ///
+    /// - P2PK, P2PKH and bare multisig.
+    /// - P2WPKH, both native and nested in P2SH.
+    /// - P2SH, P2WSH and P2SH-P2WSH with a P2PK, P2PKH or (sorted or unsorted) multisig script.
+    /// - P2TR key path, and script path using a single `<key> OP_CHECKSIG` leaf. If signatures
+    ///   for several leaves are present the one with the smallest witness is used.
+    ///
+    /// On success `final_script_sig` and `final_script_witness` are set and, as required by
+    /// BIP-174, all other fields except the UTXOs, proprietary and unknown fields are cleared. An
+    /// input that is already finalized is left untouched. On error the input is not modified.
+    pub fn finalize_input(&mut self, input_index: usize) -> Result<(), FinalizeError> {
+        let input = self.checked_input(input_index)?;
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            return Ok(());
+        }
+        let utxo = self.spend_utxo(input_index).map_err(|_| FinalizeError::MissingSpendUtxo)?;
+        let (script_sig, witness) = satisfy(input, &utxo.script_pubkey)?;
+
+        let input = &mut self.inputs[input_index]; // Index checked above.
+        *input = Input {
+            non_witness_utxo: input.non_witness_utxo.take(),
+            witness_utxo: input.witness_utxo.take(),
+            final_script_sig: (!script_sig.is_empty()).then_some(script_sig),
+            final_script_witness: (!witness.is_empty()).then_some(witness),
+            proprietary: mem::take(&mut input.proprietary),
+            unknown: mem::take(&mut input.unknown),
+            ..Default::default()
+        };
+        Ok(())
+    }
+}
+
+/// Returns the `scriptSig` and witness spending `script_pubkey`.
+fn satisfy(input: &Input, script_pubkey: &Script) -> Result<(ScriptBuf, Witness), FinalizeError> {
+    if script_pubkey.is_p2tr() {
+        return Ok((ScriptBuf::new(), satisfy_taproot(input)?));
+    }
+    if script_pubkey.is_p2wpkh() {
+        return Ok((ScriptBuf::new(), satisfy_p2wpkh(input, script_pubkey)?));
+    }
+    if script_pubkey.is_p2wsh() {
+        return Ok((ScriptBuf::new(), satisfy_p2wsh(input, script_pubkey)?));
+    }
+    if script_pubkey.is_witness_program() {
+        return Err(FinalizeError::UnsupportedScript);
+    }
+    if !script_pubkey.is_p2sh() {
+        return Ok((script_sig(satisfy_script(input, script_pubkey)?), Witness::new()));
+    }
+
+    let redeem_script = input.redeem_script.as_ref().ok_or(FinalizeError::MissingRedeemScript)?;
+    if redeem_script.to_p2sh().as_deref() != Ok(script_pubkey) {
+        return Err(FinalizeError::RedeemScriptMismatch);
+    }
+    let redeem_script_push = <&PushBytes>::try_from(redeem_script.as_bytes())
+        .expect("redeem script size was checked by `to_p2sh`");
+    if redeem_script.is_p2wpkh() {
+        let script_sig = Builder::new().push_slice(redeem_script_push).into_script();
+        Ok((script_sig, satisfy_p2wpkh(input, redeem_script)?))
+    } else if redeem_script.is_p2wsh() {
+        let script_sig = Builder::new().push_slice(redeem_script_push).into_script();
+        Ok((script_sig, satisfy_p2wsh(input, redeem_script)?))
+    } else {
+        let mut stack = satisfy_script(input, redeem_script)?;
+        stack.push(redeem_script.to_vec());
+        Ok((script_sig(stack), Witness::new()))
+    }
+}
+
+/// Returns the witness spending the P2WPKH `script_pubkey`.
+fn satisfy_p2wpkh(input: &Input, script_pubkey: &Script) -> Result<Witness, FinalizeError> {
+    let (pk, sig) = input
+        .partial_sigs
+        .iter()
+        .find(|(pk, _)| {
+            pk.wpubkey_hash().map(ScriptBuf::new_p2wpkh).as_deref() == Ok(script_pubkey)
+        })
+        .ok_or(FinalizeError::MissingSignature)?;
+    Ok(Witness::p2wpkh(*sig, pk.inner))
+}
+
+/// Returns the witness spending the P2WSH `script_pubkey`.
+fn satisfy_p2wsh(input: &Input, script_pubkey: &Script) -> Result<Witness, FinalizeError> {
+    let witness_script =
+        input.witness_script.as_ref().ok_or(FinalizeError::MissingWitnessScript)?;
+    if witness_script.to_p2wsh().as_deref() != Ok(script_pubkey) {
+        return Err(FinalizeError::WitnessScriptMismatch);
+    }
+    let mut stack = satisfy_script(input, witness_script)?;
+    stack.push(witness_script.to_vec());
+    Ok(Witness::from_slice(&stack))
+}
+
+/// Returns the stack satisfying a P2PK, P2PKH or multisig `script`.
+fn satisfy_script(input: &Input, script: &Script) -> Result<Vec<Vec<u8>>, FinalizeError> {
+    if let Some(pk) = script.p2pk_public_key() {
+        let sig = input.partial_sigs.get(&pk).ok_or(FinalizeError::MissingSignature)?;
+        return Ok(vec![sig.to_vec()]);
+    }
+    if script.is_p2pkh() {
+        let (pk, sig) = input
+            .partial_sigs
+            .iter()
+            .find(|(pk, _)| ScriptBuf::new_p2pkh(pk.pubkey_hash()) == *script)
+            .ok_or(FinalizeError::MissingSignature)?;
+        return Ok(vec![sig.to_vec(), pk.to_vec()]);
+    }
+    if let Some((required, keys)) = multisig_keys(script) {
+        // One extra element is consumed by `OP_CHECKMULTISIG` due to an off-by-one bug.
+        let mut stack = vec![Vec::new()];
+        stack.extend(
+            keys.iter()
+                .filter_map(|pk| input.partial_sigs.get(pk))
+                .take(required)
+                .map(|sig| sig.to_vec()),
+        );
+        let available = stack.len() - 1;
+        if available < required {
+            return Err(FinalizeError::NotEnoughSignatures { required, available });
+        }
+        return Ok(stack);
+    }
+    Err(FinalizeError::UnsupportedScript)
+}
+
+/// Returns the number of required signatures and the keys of a multisig `script`.
+fn multisig_keys(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let mut instructions = script.instructions();
+    let required = instructions.next()?.ok()?.opcode()?.decode_pushnum()?;
+    let mut keys = Vec::new();
+    let total = loop {
+        match instructions.next()?.ok()? {
+            Instruction::PushBytes(bytes) =>
+                keys.push(PublicKey::from_slice(bytes.as_bytes()).ok()?),
+            Instruction::Op(op) => break op.decode_pushnum()?,
+        }
+    };
+    let is_multisig = required <= total
+        && usize::from(total) == keys.len()
+        && instructions.next()?.ok()?.opcode()? == OP_CHECKMULTISIG
+        && instructions.next().is_none();
+    is_multisig.then_some((usize::from(required), keys))
+}
+
+/// Returns the witness spending a P2TR output.
+fn satisfy_taproot(input: &Input) -> Result<Witness, FinalizeError> {
+    if let Some(sig) = input.tap_key_sig {
+        return Ok(Witness::p2tr_key_spend(&sig));
+    }
+
+    let mut best: Option<Witness> = None;
+    for (control_block, (script, leaf_version)) in &input.tap_scripts {
+        let key = match single_key_leaf(script, *leaf_version) {
+            Some(key) => key,
+            None => continue,
+        };
+        let leaf_hash = TapLeafHash::from_script(script, *leaf_version);
+        if let Some(sig) = input.tap_script_sigs.get(&(key, leaf_hash)) {
+            let witness =
+                Witness::from_slice(&[sig.to_vec(), script.to_vec(), control_block.serialize()]);
+            if best.as_ref().map_or(true, |best| witness.size() < best.size()) {
+                best = Some(witness);
+            }
+        }
+    }
+    best.ok_or(FinalizeError::MissingSignature)
+}
+
+/// Returns the key if `script` is a `<key> OP_CHECKSIG` Tapscript leaf.
+fn single_key_leaf(script: &Script, leaf_version: LeafVersion) -> Option<XOnlyPublicKey> {
+    let bytes = script.as_bytes();
+    if leaf_version != LeafVersion::TapScript
+        || bytes.len() != 34
+        || bytes[0] != OP_PUSHBYTES_32.to_u8()
+        || bytes[33] != OP_CHECKSIG.to_u8()
+    {
+        return None;
+    }
+    XOnlyPublicKey::from_byte_array(bytes[1..33].try_into().expect("length checked above")).ok()
+}
+
+/// Builds a `scriptSig` pushing `stack`.
+fn script_sig(stack: Vec<Vec<u8>>) -> ScriptBuf {
+    stack
+        .iter()
+        .fold(Builder::new(), |builder, item| {
+            let item = <&PushBytes>::try_from(item.as_slice())
+                .expect("signatures, keys and scripts are small");
+            builder.push_slice(item)
+        })
+        .into_script()
+}
+
+/// Map of input index -> the error encountered while attempting to finalize that input.
+pub type FinalizeErrors = BTreeMap<usize, FinalizeError>;
+
+/// Error encountered while finalizing a PSBT input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FinalizeError {
+    /// Input index out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// Missing spending utxo.
+    MissingSpendUtxo,
+    /// Missing redeem script.
+    MissingRedeemScript,
+    /// The redeem script does not hash to the P2SH `scriptPubkey`.
+    RedeemScriptMismatch,
+    /// Missing witness script.
+    MissingWitnessScript,
+    /// The witness script does not hash to the P2WSH program.
+    WitnessScriptMismatch,
+    /// No signature for the key required to spend the input.
+    MissingSignature,
+    /// Not enough signatures to satisfy a multisig script.
+    NotEnoughSignatures {
+        /// The number of signatures required by the script.
+        required: usize,
+        /// The number of signatures available for keys in the script.
+        available: usize,
+    },
+    /// The script being spent is not one of the supported templates.
+    UnsupportedScript,
+}
+
+impl From<Infallible> for FinalizeError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for FinalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FinalizeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            MissingSpendUtxo => write!(f, "missing spend utxo in PSBT"),
+            MissingRedeemScript => write!(f, "missing redeem script"),
+            RedeemScriptMismatch => write!(f, "redeem script does not match the scriptPubkey"),
+            MissingWitnessScript => write!(f, "missing witness script"),
+            WitnessScriptMismatch => write!(f, "witness script does not match the scriptPubkey"),
+            MissingSignature => write!(f, "missing signature"),
+            NotEnoughSignatures { required, available } => write!(
+                f,
+                "multisig requires {} signatures but only {} are available",
+                required, available
+            ),
+            UnsupportedScript => write!(f, "finalizing this script is not supported"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FinalizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use FinalizeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            MissingSpendUtxo
+            | MissingRedeemScript
+            | RedeemScriptMismatch
+            | MissingWitnessScript
+            | WitnessScriptMismatch
+            | MissingSignature
+            | NotEnoughSignatures { .. }
+            | UnsupportedScript => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for FinalizeError {
+    fn from(e: IndexOutOfBoundsError) -> Self { FinalizeError::IndexOutOfBounds(e) }
+}
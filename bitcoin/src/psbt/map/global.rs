@@ -1,42 +1,114 @@
 // SPDX-License-Identifier: CC0-1.0
 
-use internals::ToU64 as _;
+use internals::{compact_size, ToU64 as _};
 use io::{BufRead, Cursor, Read};
 
 use crate::bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub};
-use crate::consensus::encode::MAX_VEC_SIZE;
+use crate::consensus::encode::{ReadExt as _, MAX_VEC_SIZE};
 use crate::consensus::{encode, Decodable};
+use crate::locktime::absolute;
 use crate::prelude::{btree_map, BTreeMap, Vec};
-use crate::psbt::map::Map;
+use crate::psbt::map::{
+    check_excluded, get_unkeyed, is_unkeyed, set_unkeyed, take_unkeyed, unkeyed_pair,
+    unkeyed_pairs, Map,
+};
+use crate::psbt::serialize::Deserialize;
 use crate::psbt::{raw, Error, Psbt};
-use crate::transaction::Transaction;
+use crate::transaction::{self, Transaction};
 
 /// Type: Unsigned Transaction PSBT_GLOBAL_UNSIGNED_TX = 0x00
 const PSBT_GLOBAL_UNSIGNED_TX: u64 = 0x00;
 /// Type: Extended Public Key PSBT_GLOBAL_XPUB = 0x01
 const PSBT_GLOBAL_XPUB: u64 = 0x01;
+/// Type: Transaction Version PSBT_GLOBAL_TX_VERSION = 0x02
+const PSBT_GLOBAL_TX_VERSION: u64 = 0x02;
+/// Type: Fallback Locktime PSBT_GLOBAL_FALLBACK_LOCKTIME = 0x03
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u64 = 0x03;
+/// Type: Input Count PSBT_GLOBAL_INPUT_COUNT = 0x04
+const PSBT_GLOBAL_INPUT_COUNT: u64 = 0x04;
+/// Type: Output Count PSBT_GLOBAL_OUTPUT_COUNT = 0x05
+const PSBT_GLOBAL_OUTPUT_COUNT: u64 = 0x05;
+/// Type: Transaction Modifiable Flags PSBT_GLOBAL_TX_MODIFIABLE = 0x06
+const PSBT_GLOBAL_TX_MODIFIABLE: u64 = 0x06;
 /// Type: Version Number PSBT_GLOBAL_VERSION = 0xFB
 const PSBT_GLOBAL_VERSION: u64 = 0xFB;
 /// Type: Proprietary Use Type PSBT_GLOBAL_PROPRIETARY = 0xFC
 const PSBT_GLOBAL_PROPRIETARY: u64 = 0xFC;
 
+/// The global types only defined by PSBT version 2.
+const V2_FIELDS: [u64; 5] = [
+    PSBT_GLOBAL_TX_VERSION,
+    PSBT_GLOBAL_FALLBACK_LOCKTIME,
+    PSBT_GLOBAL_INPUT_COUNT,
+    PSBT_GLOBAL_OUTPUT_COUNT,
+    PSBT_GLOBAL_TX_MODIFIABLE,
+];
+
+/// The transaction modifiable flags of a version 2 PSBT (`PSBT_GLOBAL_TX_MODIFIABLE`).
+///
+/// Bits not defined by BIP-370 are preserved when parsing and serializing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TxModifiable(u8);
+
+impl TxModifiable {
+    /// Neither inputs nor outputs may be added or removed.
+    pub const NONE: TxModifiable = TxModifiable(0);
+
+    /// Inputs may be added or removed.
+    pub const INPUTS: TxModifiable = TxModifiable(1 << 0);
+
+    /// Outputs may be added or removed.
+    pub const OUTPUTS: TxModifiable = TxModifiable(1 << 1);
+
+    /// The transaction has a `SIGHASH_SINGLE` signature whose input and output pairing must be
+    /// preserved.
+    pub const SIGHASH_SINGLE: TxModifiable = TxModifiable(1 << 2);
+
+    /// Add [`TxModifiable`] flags together.
+    ///
+    /// Returns itself.
+    pub fn add(&mut self, other: TxModifiable) -> TxModifiable {
+        self.0 |= other.0;
+        *self
+    }
+
+    /// Remove [`TxModifiable`] flags from this.
+    ///
+    /// Returns itself.
+    pub fn remove(&mut self, other: TxModifiable) -> TxModifiable {
+        self.0 &= !other.0;
+        *self
+    }
+
+    /// Check whether [`TxModifiable`] flags are included in this one.
+    pub fn has(self, flags: TxModifiable) -> bool { (self.0 | flags.0) == self.0 }
+
+    /// Constructs a new [`TxModifiable`] from its serialized byte.
+    pub fn from_u8(n: u8) -> TxModifiable { TxModifiable(n) }
+
+    /// Gets the serialized byte of this [`TxModifiable`].
+    pub fn to_u8(self) -> u8 { self.0 }
+}
+
 impl Map for Psbt {
     fn get_pairs(&self) -> Vec<raw::Pair> {
         let mut rv: Vec<raw::Pair> = Default::default();
 
-        rv.push(raw::Pair {
-            key: raw::Key { type_value: PSBT_GLOBAL_UNSIGNED_TX, key_data: vec![] },
-            value: {
-                // Manually serialized to ensure 0-input txs are serialized
-                // without witnesses.
-                let mut ret = Vec::new();
-                ret.extend(encode::serialize(&self.unsigned_tx.version));
-                ret.extend(encode::serialize(&self.unsigned_tx.input));
-                ret.extend(encode::serialize(&self.unsigned_tx.output));
-                ret.extend(encode::serialize(&self.unsigned_tx.lock_time));
-                ret
-            },
-        });
+        if !self.is_v2() {
+            rv.push(raw::Pair {
+                key: raw::Key { type_value: PSBT_GLOBAL_UNSIGNED_TX, key_data: vec![] },
+                value: {
+                    // Manually serialized to ensure 0-input txs are serialized
+                    // without witnesses.
+                    let mut ret = Vec::new();
+                    ret.extend(encode::serialize(&self.unsigned_tx.version));
+                    ret.extend(encode::serialize(&self.unsigned_tx.input));
+                    ret.extend(encode::serialize(&self.unsigned_tx.output));
+                    ret.extend(encode::serialize(&self.unsigned_tx.lock_time));
+                    ret
+                },
+            });
+        }
 
         for (xpub, (fingerprint, derivation)) in &self.xpub {
             rv.push(raw::Pair {
@@ -50,6 +122,23 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
             });
         }
 
+        if self.is_v2() {
+            rv.push(unkeyed_pair(
+                PSBT_GLOBAL_TX_VERSION,
+                encode::serialize(&self.unsigned_tx.version),
+            ));
+            rv.extend(unkeyed_pairs(&self.unknown, &[PSBT_GLOBAL_FALLBACK_LOCKTIME]));
+            rv.push(unkeyed_pair(
+                PSBT_GLOBAL_INPUT_COUNT,
+                compact_size::encode(self.unsigned_tx.input.len()).to_vec(),
+            ));
+            rv.push(unkeyed_pair(
+                PSBT_GLOBAL_OUTPUT_COUNT,
+                compact_size::encode(self.unsigned_tx.output.len()).to_vec(),
+            ));
+            rv.extend(unkeyed_pairs(&self.unknown, &[PSBT_GLOBAL_TX_MODIFIABLE]));
+        }
+
         // Serializing version only for non-default value; otherwise test vectors fail
         if self.version > 0 {
             rv.push(raw::Pair {
@@ -62,7 +151,7 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
             rv.push(raw::Pair { key: key.to_key(), value: value.clone() });
         }
 
-        for (key, value) in self.unknown.iter() {
+        for (key, value) in self.unknown.iter().filter(|(k, _)| !is_unkeyed(k, &V2_FIELDS)) {
             rv.push(raw::Pair { key: key.clone(), value: value.clone() });
         }
 
@@ -71,7 +160,39 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
 }
 
 impl Psbt {
-    pub(crate) fn decode_global<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Error> {
+    /// Returns the lock time to use if no input requires one (version 2 PSBTs only).
+    pub fn fallback_lock_time(&self) -> Option<absolute::LockTime> {
+        get_unkeyed(&self.unknown, PSBT_GLOBAL_FALLBACK_LOCKTIME).ok().flatten()
+    }
+
+    /// Sets the lock time to use if no input requires one (version 2 PSBTs only).
+    ///
+    /// The lock time is kept in [`Psbt::unknown`] and not serialized in version 0 PSBTs. The
+    /// lock time of the unsigned transaction is not updated.
+    pub fn set_fallback_lock_time(&mut self, lock_time: Option<absolute::LockTime>) {
+        set_unkeyed(&mut self.unknown, PSBT_GLOBAL_FALLBACK_LOCKTIME, lock_time);
+    }
+
+    /// Returns the flags describing whether inputs and outputs may be added (version 2 PSBTs
+    /// only).
+    pub fn tx_modifiable(&self) -> Option<TxModifiable> {
+        get_unkeyed(&self.unknown, PSBT_GLOBAL_TX_MODIFIABLE).ok().flatten()
+    }
+
+    /// Sets the flags describing whether inputs and outputs may be added (version 2 PSBTs only).
+    ///
+    /// The flags are kept in [`Psbt::unknown`] and not serialized in version 0 PSBTs.
+    pub fn set_tx_modifiable(&mut self, flags: Option<TxModifiable>) {
+        set_unkeyed(&mut self.unknown, PSBT_GLOBAL_TX_MODIFIABLE, flags);
+    }
+
+    /// Decodes the global map, returning the number of input and output maps that follow it.
+    ///
+    /// For version 2 PSBTs the returned unsigned transaction has no inputs or outputs yet, they
+    /// are filled in from the input and output maps.
+    pub(crate) fn decode_global<R: BufRead + ?Sized>(
+        r: &mut R,
+    ) -> Result<(Self, usize, usize), Error> {
         let mut r = r.take(MAX_VEC_SIZE.to_u64());
         let mut tx: Option<Transaction> = None;
         let mut version: Option<u32> = None;
@@ -161,11 +282,11 @@ pub(crate) fn decode_global<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Erro
                                         ));
                                     }
                                     version = Some(Decodable::consensus_decode(&mut decoder)?);
-                                    // We only understand version 0 PSBTs. According to BIP-174 we
-                                    // should throw an error if we see anything other than version 0.
-                                    if version != Some(0) {
+                                    // We understand version 0 (BIP-174) and version 2 (BIP-370)
+                                    // PSBTs, there is no version 1.
+                                    if version != Some(0) && version != Some(2) {
                                         return Err(Error::Version(
-                                            "PSBT versions greater than 0 are not supported",
+                                            "only PSBT versions 0 and 2 are supported",
                                         ));
                                     }
                                 } else {
@@ -198,18 +319,60 @@ pub(crate) fn decode_global<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Erro
             }
         }
 
-        if let Some(tx) = tx {
-            Ok(Psbt {
-                unsigned_tx: tx,
-                version: version.unwrap_or(0),
-                xpub: xpub_map,
-                proprietary,
-                unknown: unknowns,
-                inputs: vec![],
-                outputs: vec![],
-            })
-        } else {
-            Err(Error::MustHaveUnsignedTx)
+        let version = version.unwrap_or(0);
+        let mut psbt = Psbt {
+            unsigned_tx: Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            },
+            version,
+            xpub: xpub_map,
+            proprietary,
+            unknown: unknowns,
+            inputs: vec![],
+            outputs: vec![],
+        };
+
+        if !psbt.is_v2() {
+            check_excluded(&psbt.unknown, &V2_FIELDS)?;
+            let tx = tx.ok_or(Error::MustHaveUnsignedTx)?;
+            let (input_count, output_count) = (tx.input.len(), tx.output.len());
+            psbt.unsigned_tx = tx;
+            return Ok((psbt, input_count, output_count));
+        }
+
+        if tx.is_some() {
+            let key = raw::Key { type_value: PSBT_GLOBAL_UNSIGNED_TX, key_data: vec![] };
+            return Err(Error::ExcludedKey(key));
+        }
+        let unknown = &mut psbt.unknown;
+        psbt.unsigned_tx.version = take_unkeyed(unknown, PSBT_GLOBAL_TX_VERSION)?
+            .ok_or(Error::MissingRequiredField("PSBT_GLOBAL_TX_VERSION"))?;
+        get_unkeyed::<absolute::LockTime>(unknown, PSBT_GLOBAL_FALLBACK_LOCKTIME)?;
+        get_unkeyed::<TxModifiable>(unknown, PSBT_GLOBAL_TX_MODIFIABLE)?;
+        let input_count = take_unkeyed::<Count>(unknown, PSBT_GLOBAL_INPUT_COUNT)?
+            .ok_or(Error::MissingRequiredField("PSBT_GLOBAL_INPUT_COUNT"))?;
+        let output_count = take_unkeyed::<Count>(unknown, PSBT_GLOBAL_OUTPUT_COUNT)?
+            .ok_or(Error::MissingRequiredField("PSBT_GLOBAL_OUTPUT_COUNT"))?;
+
+        Ok((psbt, input_count.0, output_count.0))
+    }
+}
+
+/// The compact size encoded input or output count of a version 2 PSBT.
+struct Count(usize);
+
+impl Deserialize for Count {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let mut decoder = bytes;
+        let count = decoder.read_compact_size()?;
+        if !decoder.is_empty() {
+            return Err(Error::PartialDataConsumption);
         }
+        usize::try_from(count)
+            .map(Count)
+            .map_err(|_| encode::ParseError::ParseFailed("PSBT map count out of range").into())
     }
 }
@@ -8,9 +8,13 @@
 use crate::bip32::KeySource;
 use crate::crypto::key::{PublicKey, XOnlyPublicKey};
 use crate::crypto::{ecdsa, taproot};
+use crate::locktime::absolute;
 use crate::prelude::{btree_map, BTreeMap, Borrow, Box, ToOwned, Vec};
-use crate::psbt::map::Map;
-use crate::psbt::serialize::Deserialize;
+use crate::psbt::map::{
+    check_excluded, get_unkeyed, is_unkeyed, serialize_pairs, set_unkeyed, take_unkeyed,
+    unkeyed_pair, unkeyed_pairs, Map,
+};
+use crate::psbt::serialize::{Deserialize, Serialize};
 use crate::psbt::{error, raw, Error};
 use crate::script::ScriptBuf;
 use crate::sighash::{
@@ -18,8 +22,9 @@
     TapSighashType,
 };
 use crate::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapNodeHash};
-use crate::transaction::{Transaction, TxOut};
+use crate::transaction::{OutPoint, Transaction, TxIn, TxOut};
 use crate::witness::Witness;
+use crate::Sequence;
 
 /// Type: Non-Witness UTXO PSBT_IN_NON_WITNESS_UTXO = 0x00
 const PSBT_IN_NON_WITNESS_UTXO: u64 = 0x00;
@@ -47,6 +52,16 @@
 const PSBT_IN_HASH160: u64 = 0x0c;
 /// Type: HASH256 preimage PSBT_IN_HASH256 = 0x0d
 const PSBT_IN_HASH256: u64 = 0x0d;
+/// Type: Previous TXID PSBT_IN_PREVIOUS_TXID = 0x0e
+const PSBT_IN_PREVIOUS_TXID: u64 = 0x0e;
+/// Type: Spent Output Index PSBT_IN_OUTPUT_INDEX = 0x0f
+const PSBT_IN_OUTPUT_INDEX: u64 = 0x0f;
+/// Type: Sequence Number PSBT_IN_SEQUENCE = 0x10
+const PSBT_IN_SEQUENCE: u64 = 0x10;
+/// Type: Required Time-based Locktime PSBT_IN_REQUIRED_TIME_LOCKTIME = 0x11
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u64 = 0x11;
+/// Type: Required Height-based Locktime PSBT_IN_REQUIRED_HEIGHT_LOCKTIME = 0x12
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u64 = 0x12;
 /// Type: Taproot Signature in Key Spend PSBT_IN_TAP_KEY_SIG = 0x13
 const PSBT_IN_TAP_KEY_SIG: u64 = 0x13;
 /// Type: Taproot Signature in Script Spend PSBT_IN_TAP_SCRIPT_SIG = 0x14
@@ -64,6 +79,15 @@
 /// Type: Proprietary Use Type PSBT_IN_PROPRIETARY = 0xFC
 const PSBT_IN_PROPRIETARY: u64 = 0xFC;
 
+/// The input types only defined by PSBT version 2.
+const V2_FIELDS: [u64; 5] = [
+    PSBT_IN_PREVIOUS_TXID,
+    PSBT_IN_OUTPUT_INDEX,
+    PSBT_IN_SEQUENCE,
+    PSBT_IN_REQUIRED_TIME_LOCKTIME,
+    PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+];
+
 /// A key-value map for an input of the corresponding index in the unsigned
 /// transaction.
 #[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
@@ -411,10 +435,66 @@ pub fn combine(&mut self, other: Self) {
         combine!(tap_internal_key, self, other);
         combine!(tap_merkle_root, self, other);
     }
-}
 
-impl Map for Input {
-    fn get_pairs(&self) -> Vec<raw::Pair> {
+    /// Returns the minimum time-based lock time this input requires (version 2 PSBTs only).
+    pub fn required_time_lock_time(&self) -> Option<absolute::MedianTimePast> {
+        get_unkeyed(&self.unknown, PSBT_IN_REQUIRED_TIME_LOCKTIME).ok().flatten()
+    }
+
+    /// Sets the minimum time-based lock time this input requires (version 2 PSBTs only).
+    ///
+    /// The lock time is kept in [`Input::unknown`] and not serialized in version 0 PSBTs.
+    pub fn set_required_time_lock_time(&mut self, lock_time: Option<absolute::MedianTimePast>) {
+        set_unkeyed(&mut self.unknown, PSBT_IN_REQUIRED_TIME_LOCKTIME, lock_time);
+    }
+
+    /// Returns the minimum height-based lock time this input requires (version 2 PSBTs only).
+    pub fn required_height_lock_time(&self) -> Option<absolute::Height> {
+        get_unkeyed(&self.unknown, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME).ok().flatten()
+    }
+
+    /// Sets the minimum height-based lock time this input requires (version 2 PSBTs only).
+    ///
+    /// The lock time is kept in [`Input::unknown`] and not serialized in version 0 PSBTs.
+    pub fn set_required_height_lock_time(&mut self, lock_time: Option<absolute::Height>) {
+        set_unkeyed(&mut self.unknown, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, lock_time);
+    }
+
+    /// Serializes this input as the input map of a version 2 PSBT spending `txin`.
+    pub(crate) fn serialize_map_v2(&self, txin: &TxIn) -> Vec<u8> {
+        serialize_pairs(self.pairs(Some(txin)))
+    }
+
+    /// Removes the version 2 fields from the unknown key-value pairs of a decoded input map.
+    ///
+    /// The returned [`TxIn`] holds the previous output and sequence number, the required lock
+    /// times are checked and kept in the unknown key-value pairs.
+    pub(crate) fn take_v2_fields(&mut self) -> Result<TxIn, Error> {
+        let unknown = &mut self.unknown;
+        let txid = take_unkeyed(unknown, PSBT_IN_PREVIOUS_TXID)?
+            .ok_or(Error::MissingRequiredField("PSBT_IN_PREVIOUS_TXID"))?;
+        let vout = take_unkeyed(unknown, PSBT_IN_OUTPUT_INDEX)?
+            .ok_or(Error::MissingRequiredField("PSBT_IN_OUTPUT_INDEX"))?;
+        let sequence = take_unkeyed(unknown, PSBT_IN_SEQUENCE)?.unwrap_or(Sequence::MAX);
+        get_unkeyed::<absolute::MedianTimePast>(unknown, PSBT_IN_REQUIRED_TIME_LOCKTIME)?;
+        get_unkeyed::<absolute::Height>(unknown, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)?;
+
+        Ok(TxIn {
+            previous_output: OutPoint { txid, vout },
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        })
+    }
+
+    /// Checks that a decoded input map of a version 0 PSBT has no version 2 fields.
+    pub(crate) fn check_v0_fields(&self) -> Result<(), Error> {
+        check_excluded(&self.unknown, &V2_FIELDS)
+    }
+
+    /// Returns the key-value pairs of this input, including the version 2 fields if `txin` is
+    /// provided.
+    fn pairs(&self, txin: Option<&TxIn>) -> Vec<raw::Pair> {
         let mut rv: Vec<raw::Pair> = Default::default();
 
         impl_psbt_get_pair! {
@@ -469,6 +549,19 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
             rv.push_map(self.hash256_preimages, PSBT_IN_HASH256)
         }
 
+        if let Some(txin) = txin {
+            rv.push(unkeyed_pair(PSBT_IN_PREVIOUS_TXID, txin.previous_output.txid.serialize()));
+            rv.push(unkeyed_pair(PSBT_IN_OUTPUT_INDEX, txin.previous_output.vout.serialize()));
+            if txin.sequence != Sequence::MAX {
+                rv.push(unkeyed_pair(PSBT_IN_SEQUENCE, txin.sequence.serialize()));
+            }
+
+            rv.extend(unkeyed_pairs(
+                &self.unknown,
+                &[PSBT_IN_REQUIRED_TIME_LOCKTIME, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME],
+            ));
+        }
+
         impl_psbt_get_pair! {
             rv.push(self.tap_key_sig, PSBT_IN_TAP_KEY_SIG)
         }
@@ -501,7 +594,7 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
             rv.push(raw::Pair { key: key.to_key(), value: value.clone() });
         }
 
-        for (key, value) in self.unknown.iter() {
+        for (key, value) in self.unknown.iter().filter(|(k, _)| !is_unkeyed(k, &V2_FIELDS)) {
             rv.push(raw::Pair { key: key.clone(), value: value.clone() });
         }
 
@@ -509,6 +602,10 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
     }
 }
 
+impl Map for Input {
+    fn get_pairs(&self) -> Vec<raw::Pair> { self.pairs(None) }
+}
+
 impl_psbtmap_ser_de_serialize!(Input);
 
 #[cfg(test)]
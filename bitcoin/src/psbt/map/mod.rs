@@ -4,13 +4,14 @@
 mod input;
 mod output;
 
-use crate::prelude::Vec;
-use crate::psbt::raw;
-use crate::psbt::serialize::Serialize;
+use crate::prelude::{BTreeMap, Vec};
+use crate::psbt::serialize::{Deserialize, Serialize};
+use crate::psbt::{raw, Error};
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
+    global::TxModifiable,
     input::{Input, PsbtSighashType},
     output::Output,
 };
@@ -27,12 +28,93 @@ pub(super) trait Map {
     /// Why is the separator here 0x00 instead of 0xff? The separator here is used to distinguish between each chunk of data.
     /// A separator of 0x00 would mean that the unserializer can read it as a key length of 0, which would never occur with
     /// actual keys. It can thus be used as a separator and allow for easier unserializer implementation.
-    fn serialize_map(&self) -> Vec<u8> {
-        let mut buf = Vec::new();
-        for pair in Map::get_pairs(self) {
-            buf.extend(&pair.serialize());
+    fn serialize_map(&self) -> Vec<u8> { serialize_pairs(Map::get_pairs(self)) }
+}
+
+/// Serializes `pairs` as a PSBT map, including the terminating separator.
+fn serialize_pairs(pairs: Vec<raw::Pair>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for pair in pairs {
+        buf.extend(&pair.serialize());
+    }
+    buf.push(0x00_u8);
+    buf
+}
+
+/// Constructs a key-value pair with an empty key data.
+fn unkeyed_pair(type_value: u64, value: Vec<u8>) -> raw::Pair {
+    raw::Pair { key: raw::Key { type_value, key_data: vec![] }, value }
+}
+
+/// Returns the key-value pairs of the `type_values` with empty key data in `unknown`.
+fn unkeyed_pairs<'a>(
+    unknown: &'a BTreeMap<raw::Key, Vec<u8>>,
+    type_values: &'a [u64],
+) -> impl Iterator<Item = raw::Pair> + 'a {
+    type_values.iter().filter_map(move |&type_value| {
+        let key = raw::Key { type_value, key_data: vec![] };
+        unknown.get(&key).map(|value| raw::Pair { key, value: value.clone() })
+    })
+}
+
+/// Decodes the value of the key-value pair of type `type_value` and empty key data in `unknown`.
+///
+/// Fields only defined by PSBT version 2 are decoded as unknown key-value pairs. Those that are
+/// not part of the unsigned transaction stay there, so adding them did not change the public
+/// fields of [`Psbt`](crate::Psbt) and [`Input`].
+fn get_unkeyed<T: Deserialize>(
+    unknown: &BTreeMap<raw::Key, Vec<u8>>,
+    type_value: u64,
+) -> Result<Option<T>, Error> {
+    if let Some(key) = unknown.keys().find(|k| k.type_value == type_value && !k.key_data.is_empty())
+    {
+        return Err(Error::InvalidKey(key.clone()));
+    }
+    let key = raw::Key { type_value, key_data: vec![] };
+    unknown.get(&key).map(|value| T::deserialize(value)).transpose()
+}
+
+/// Removes the key-value pair of type `type_value` and empty key data from `unknown`.
+///
+/// Used for the version 2 fields that are moved into the unsigned transaction once the version
+/// is known.
+fn take_unkeyed<T: Deserialize>(
+    unknown: &mut BTreeMap<raw::Key, Vec<u8>>,
+    type_value: u64,
+) -> Result<Option<T>, Error> {
+    let value = get_unkeyed(unknown, type_value)?;
+    unknown.remove(&raw::Key { type_value, key_data: vec![] });
+    Ok(value)
+}
+
+/// Sets, or removes if `value` is `None`, the key-value pair of type `type_value` and empty key
+/// data in `unknown`.
+fn set_unkeyed<T: Serialize>(
+    unknown: &mut BTreeMap<raw::Key, Vec<u8>>,
+    type_value: u64,
+    value: Option<T>,
+) {
+    let key = raw::Key { type_value, key_data: vec![] };
+    match value {
+        Some(value) => {
+            unknown.insert(key, value.serialize());
+        }
+        None => {
+            unknown.remove(&key);
         }
-        buf.push(0x00_u8);
-        buf
+    }
+}
+
+/// Returns whether `key` has empty key data and is of one of the `types`.
+fn is_unkeyed(key: &raw::Key, types: &[u64]) -> bool {
+    key.key_data.is_empty() && types.contains(&key.type_value)
+}
+
+/// Checks that `unknown` has no key of the `excluded` types, as required for the fields of one
+/// PSBT version in a PSBT of another version.
+fn check_excluded(unknown: &BTreeMap<raw::Key, Vec<u8>>, excluded: &[u64]) -> Result<(), Error> {
+    match unknown.keys().find(|k| is_unkeyed(k, excluded)) {
+        Some(key) => Err(Error::ExcludedKey(key.clone())),
+        None => Ok(()),
     }
 }
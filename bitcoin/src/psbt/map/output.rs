@@ -3,10 +3,12 @@
 use crate::bip32::KeySource;
 use crate::crypto::key::XOnlyPublicKey;
 use crate::prelude::{btree_map, BTreeMap, Vec};
-use crate::psbt::map::Map;
+use crate::psbt::map::{check_excluded, serialize_pairs, take_unkeyed, unkeyed_pair, Map};
+use crate::psbt::serialize::Serialize;
 use crate::psbt::{raw, Error};
 use crate::script::ScriptBuf;
 use crate::taproot::{TapLeafHash, TapTree};
+use crate::transaction::TxOut;
 
 /// Type: Redeem ScriptBuf PSBT_OUT_REDEEM_SCRIPT = 0x00
 const PSBT_OUT_REDEEM_SCRIPT: u64 = 0x00;
@@ -14,6 +16,10 @@
 const PSBT_OUT_WITNESS_SCRIPT: u64 = 0x01;
 /// Type: BIP 32 Derivation Path PSBT_OUT_BIP32_DERIVATION = 0x02
 const PSBT_OUT_BIP32_DERIVATION: u64 = 0x02;
+/// Type: Output Amount PSBT_OUT_AMOUNT = 0x03
+const PSBT_OUT_AMOUNT: u64 = 0x03;
+/// Type: Output Script PSBT_OUT_SCRIPT = 0x04
+const PSBT_OUT_SCRIPT: u64 = 0x04;
 /// Type: Taproot Internal Key PSBT_OUT_TAP_INTERNAL_KEY = 0x05
 const PSBT_OUT_TAP_INTERNAL_KEY: u64 = 0x05;
 /// Type: Taproot Tree PSBT_OUT_TAP_TREE = 0x06
@@ -123,10 +129,29 @@ pub fn combine(&mut self, other: Self) {
         combine!(tap_internal_key, self, other);
         combine!(tap_tree, self, other);
     }
-}
 
-impl Map for Output {
-    fn get_pairs(&self) -> Vec<raw::Pair> {
+    /// Serializes this output as the output map of a version 2 PSBT creating `txout`.
+    pub(crate) fn serialize_map_v2(&self, txout: &TxOut) -> Vec<u8> {
+        serialize_pairs(self.pairs(Some(txout)))
+    }
+
+    /// Removes the version 2 fields from the unknown key-value pairs of a decoded output map.
+    pub(crate) fn take_v2_fields(&mut self) -> Result<TxOut, Error> {
+        let value = take_unkeyed(&mut self.unknown, PSBT_OUT_AMOUNT)?
+            .ok_or(Error::MissingRequiredField("PSBT_OUT_AMOUNT"))?;
+        let script_pubkey = take_unkeyed(&mut self.unknown, PSBT_OUT_SCRIPT)?
+            .ok_or(Error::MissingRequiredField("PSBT_OUT_SCRIPT"))?;
+        Ok(TxOut { value, script_pubkey })
+    }
+
+    /// Checks that a decoded output map of a version 0 PSBT has no version 2 fields.
+    pub(crate) fn check_v0_fields(&self) -> Result<(), Error> {
+        check_excluded(&self.unknown, &[PSBT_OUT_AMOUNT, PSBT_OUT_SCRIPT])
+    }
+
+    /// Returns the key-value pairs of this output, including the version 2 fields if `txout` is
+    /// provided.
+    fn pairs(&self, txout: Option<&TxOut>) -> Vec<raw::Pair> {
         let mut rv: Vec<raw::Pair> = Default::default();
 
         impl_psbt_get_pair! {
@@ -141,6 +166,11 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
             rv.push_map(self.bip32_derivation, PSBT_OUT_BIP32_DERIVATION)
         }
 
+        if let Some(txout) = txout {
+            rv.push(unkeyed_pair(PSBT_OUT_AMOUNT, txout.value.serialize()));
+            rv.push(unkeyed_pair(PSBT_OUT_SCRIPT, txout.script_pubkey.serialize()));
+        }
+
         impl_psbt_get_pair! {
             rv.push(self.tap_internal_key, PSBT_OUT_TAP_INTERNAL_KEY)
         }
@@ -169,4 +199,8 @@ fn get_pairs(&self) -> Vec<raw::Pair> {
     }
 }
 
+impl Map for Output {
+    fn get_pairs(&self) -> Vec<raw::Pair> { self.pairs(None) }
+}
+
 impl_psbtmap_ser_de_serialize!(Output);
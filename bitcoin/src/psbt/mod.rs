@@ -5,13 +5,17 @@
 //! Implementation of BIP174 Partially Signed Bitcoin Transaction Format as
 //! defined at <https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki>
 //! except we define PSBTs containing non-standard sighash types as invalid.
+//!
+//! Version 2 PSBTs as defined in BIP370 are supported as well, see [`Psbt::into_v2`].
 
 #[macro_use]
 mod macros;
 mod error;
+mod finalize;
 mod map;
 pub mod raw;
 pub mod serialize;
+mod v2;
 
 use core::convert::Infallible;
 use core::{cmp, fmt};
@@ -35,8 +39,10 @@
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
-    map::{Input, Output, PsbtSighashType},
+    map::{Input, Output, PsbtSighashType, TxModifiable},
     error::Error,
+    finalize::{FinalizeError, FinalizeErrors},
+    v2::ConstructError,
 };
 
 /// A Partially Signed Transaction.
@@ -223,7 +229,7 @@ fn internal_extract_tx_with_fee_rate_limit(
     /// Combines this [`Psbt`] with `other` PSBT as described by BIP 174.
     ///
     /// In accordance with BIP 174 this function is commutative i.e., `A.combine(B) == B.combine(A)`
-    pub fn combine(&mut self, other: Self) -> Result<(), Error> {
+    pub fn combine(&mut self, mut other: Self) -> Result<(), Error> {
         if self.unsigned_tx != other.unsigned_tx {
             return Err(Error::UnexpectedUnsignedTx {
                 expected: Box::new(self.unsigned_tx.clone()),
@@ -236,6 +242,7 @@ pub fn combine(&mut self, other: Self) -> Result<(), Error> {
 
         // Keeping the highest version
         self.version = cmp::max(self.version, other.version);
+        self.combine_v2(&mut other);
 
         // Merging xpubs
         for (xpub, (fingerprint1, derivation1)) in other.xpub {
@@ -317,6 +324,9 @@ pub fn sign<C, K>(
                 Ok(SigningAlgorithm::Ecdsa) =>
                     match self.bip32_sign_ecdsa(k, i, &mut cache, secp) {
                         Ok(v) => {
+                            if !v.is_empty() {
+                                self.update_tx_modifiable(i);
+                            }
                             used.insert(i, SigningKeys::Ecdsa(v));
                         }
                         Err(e) => {
@@ -326,6 +336,9 @@ pub fn sign<C, K>(
                 Ok(SigningAlgorithm::Schnorr) => {
                     match self.bip32_sign_schnorr(k, i, &mut cache, secp) {
                         Ok(v) => {
+                            if !v.is_empty() {
+                                self.update_tx_modifiable(i);
+                            }
                             used.insert(i, SigningKeys::Schnorr(v));
                         }
                         Err(e) => {
@@ -2586,4 +2599,254 @@ fn sign_psbt() {
         assert_eq!(signing_keys.len(), 1);
         assert_eq!(signing_keys[&0], SigningKeys::Ecdsa(vec![pk]));
     }
+
+    #[test]
+    #[cfg(feature = "rand-std")]
+    fn finalize_psbt() {
+        use crate::address::script_pubkey::BuilderExt as _;
+        use crate::script::interpreter::{self, VerifyFlags};
+        use crate::sighash::Prevouts;
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::EMPTY_COINBASE, TxIn::EMPTY_COINBASE],
+            output: vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new() }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+
+        let (priv_key, pk, secp) = gen_keys();
+        let (_, other_pk, _) = gen_keys();
+        let mut key_map = BTreeMap::new();
+        key_map.insert(pk, priv_key);
+
+        // First input is a P2WPKH output we can sign for.
+        let txout_wpkh = TxOut {
+            value: Amount::from_sat_u32(10),
+            script_pubkey: ScriptBuf::new_p2wpkh(pk.wpubkey_hash().unwrap()),
+        };
+        psbt.inputs[0].witness_utxo = Some(txout_wpkh.clone());
+        let mut map = BTreeMap::new();
+        map.insert(pk.inner, (Fingerprint::default(), DerivationPath::default()));
+        psbt.inputs[0].bip32_derivation = map.clone();
+
+        // Second input is a P2WSH 2-of-2 multisig for which we only hold one of the keys.
+        let witness_script = crate::script::Builder::new()
+            .push_int_unchecked(2)
+            .push_key(pk)
+            .push_key(other_pk)
+            .push_int_unchecked(2)
+            .push_opcode(crate::opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        let txout_wsh = TxOut {
+            value: Amount::from_sat_u32(10),
+            script_pubkey: ScriptBuf::new_p2wsh(witness_script.wscript_hash().unwrap()),
+        };
+        psbt.inputs[1].witness_utxo = Some(txout_wsh.clone());
+        psbt.inputs[1].witness_script = Some(witness_script);
+        psbt.inputs[1].bip32_derivation = map;
+
+        psbt.sign(&key_map, &secp).unwrap();
+
+        let errors = psbt.finalize().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[&1], FinalizeError::NotEnoughSignatures { required: 2, available: 1 });
+
+        let input = &psbt.inputs[0];
+        assert!(input.partial_sigs.is_empty());
+        assert!(input.bip32_derivation.is_empty());
+        assert!(input.final_script_sig.is_none());
+        assert_eq!(input.final_script_witness.as_ref().map(Witness::len), Some(2));
+        assert_eq!(input.witness_utxo, Some(txout_wpkh.clone()));
+        // The unfinalized input keeps its signature for a later signer.
+        assert_eq!(psbt.inputs[1].partial_sigs.len(), 1);
+
+        // Finalizing an already finalized input is a no-op.
+        let finalized = psbt.inputs[0].clone();
+        psbt.finalize_input(0).unwrap();
+        assert_eq!(psbt.inputs[0], finalized);
+        assert!(matches!(psbt.finalize_input(2), Err(FinalizeError::IndexOutOfBounds(_))));
+
+        let tx = psbt.extract_tx_unchecked_fee_rate();
+        let spent = [txout_wpkh, txout_wsh];
+        interpreter::verify_input(&secp, &tx, 0, &Prevouts::All(&spent), VerifyFlags::STANDARD)
+            .unwrap();
+    }
+
+    #[test]
+    fn psbt_v2_round_trip() {
+        let mut psbt = psbt_with_values(2_000, 1_000);
+        psbt.unsigned_tx.lock_time = absolute::LockTime::from_height(800_000).unwrap();
+        psbt.outputs = vec![Output::default()];
+
+        let v2 = psbt.clone().into_v2();
+        assert_eq!(v2.version, 2);
+        assert_eq!(v2.fallback_lock_time(), Some(psbt.unsigned_tx.lock_time));
+
+        // No unsigned transaction, the global map starts with PSBT_GLOBAL_TX_VERSION.
+        let ser = v2.serialize();
+        assert_eq!(&ser[..12], hex!("70736274ff01020402000000"));
+        let deser = Psbt::deserialize(&ser).unwrap();
+        assert_eq!(deser, v2);
+        assert_eq!(deser.into_v0(), psbt);
+    }
+
+    #[test]
+    fn psbt_v2_deserialize() {
+        // Global map: tx version 2, one input, one output, PSBT version 2.
+        // Input map: previous txid and output index, required height lock time 10000.
+        // Output map: amount 1000 sat and a P2WPKH script.
+        let hex = concat!(
+            "70736274ff",
+            "010204020000000104010101050101",
+            "01fb040200000000",
+            "010e20f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126",
+            "010f0401000000",
+            "01120410270000",
+            "00",
+            "010308e803000000000000",
+            "0104160014339725ba21efd62ac753a9bcd067d6c7a6a39d05",
+            "00",
+        );
+        let psbt = hex_psbt(hex).unwrap();
+        assert_eq!(psbt.version, 2);
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.version, transaction::Version::TWO);
+        assert_eq!(tx.lock_time, absolute::LockTime::from_height(10_000).unwrap());
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output.vout, 1);
+        assert_eq!(tx.input[0].sequence, Sequence::MAX);
+        assert_eq!(tx.output[0].value, Amount::from_sat_u32(1_000));
+        assert_eq!(
+            psbt.inputs[0].required_height_lock_time(),
+            Some(absolute::Height::from_u32(10_000).unwrap())
+        );
+        assert_eq!(psbt.inputs[0].required_time_lock_time(), None);
+        assert!(psbt.outputs[0].unknown.is_empty());
+
+        assert_eq!(psbt.serialize_hex(), hex);
+    }
+
+    #[test]
+    fn psbt_v2_invalid() {
+        // Version 1 does not exist.
+        assert!(matches!(hex_psbt("70736274ff01fb040100000000").unwrap_err(), Error::Version(_)));
+
+        // A version 2 PSBT must not contain an unsigned transaction.
+        let err = hex_psbt("70736274ff01000a0200000000000000000001fb040200000000").unwrap_err();
+        assert!(matches!(err, Error::ExcludedKey(_)));
+
+        // Missing input and output counts.
+        let err = hex_psbt("70736274ff0102040200000001fb040200000000").unwrap_err();
+        assert!(matches!(err, Error::MissingRequiredField("PSBT_GLOBAL_INPUT_COUNT")));
+
+        // Input without a previous txid.
+        let err =
+            hex_psbt("70736274ff01020402000000010401010105010001fb040200000000010f040000000000")
+                .unwrap_err();
+        assert!(matches!(err, Error::MissingRequiredField("PSBT_IN_PREVIOUS_TXID")));
+
+        // Required height lock time that is actually a time.
+        let err = hex_psbt(concat!(
+            "70736274ff010204020000000104010101050100",
+            "01fb040200000000",
+            "010e20f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126",
+            "010f0400000000",
+            "01120400ca9a3b",
+            "00",
+        ))
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidLockTime(_)));
+
+        // PSBTv2 only keys are not allowed in a version 0 PSBT.
+        let mut v0 = psbt_with_values(2_000, 1_000);
+        v0.inputs = vec![Input::default()];
+        v0.outputs = vec![Output::default()];
+        // The serialization ends with the global, the input and the output map separators.
+        let v0 = v0.serialize();
+        let excluded_key = |pos: usize, key: &[u8]| {
+            let mut ser = v0.clone();
+            ser.splice(pos..pos, key.iter().copied());
+            match Psbt::deserialize(&ser) {
+                Err(Error::ExcludedKey(key)) => key.type_value,
+                res => panic!("unexpected result: {:?}", res),
+            }
+        };
+        // PSBT_GLOBAL_FALLBACK_LOCKTIME
+        assert_eq!(excluded_key(5, &hex!("01030400000000")), 0x03);
+        // PSBT_IN_OUTPUT_INDEX
+        assert_eq!(excluded_key(v0.len() - 2, &hex!("010f0400000000")), 0x0f);
+        // PSBT_OUT_AMOUNT
+        assert_eq!(excluded_key(v0.len() - 1, &hex!("0103080000000000000000")), 0x03);
+    }
+
+    #[test]
+    fn psbt_v2_constructor() {
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        let txin = |vout| TxIn {
+            previous_output: OutPoint {
+                txid: "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126"
+                    .parse()
+                    .unwrap(),
+                vout,
+            },
+            ..TxIn::EMPTY_COINBASE
+        };
+        let txout = TxOut { value: Amount::from_sat_u32(1_000), script_pubkey: ScriptBuf::new() };
+
+        // Only version 2 PSBTs can be modified, and only if the flags allow it.
+        assert_eq!(
+            psbt.add_input(txin(0), Input::default()),
+            Err(ConstructError::UnsupportedVersion(0))
+        );
+        let mut psbt = psbt.into_v2();
+        assert_eq!(
+            psbt.add_output(txout.clone(), Output::default()),
+            Err(ConstructError::OutputsNotModifiable)
+        );
+        let mut flags = TxModifiable::INPUTS;
+        flags.add(TxModifiable::OUTPUTS);
+        psbt.set_tx_modifiable(Some(flags));
+
+        let height = |n| {
+            let mut input = Input::default();
+            input.set_required_height_lock_time(Some(absolute::Height::from_u32(n).unwrap()));
+            input
+        };
+        psbt.add_input(txin(0), height(100)).unwrap();
+        psbt.add_input(txin(1), height(200)).unwrap();
+        psbt.add_output(txout, Output::default()).unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time, absolute::LockTime::from_height(200).unwrap());
+        assert_eq!(
+            psbt.add_input(txin(1), Input::default()),
+            Err(ConstructError::DuplicateInput(txin(1).previous_output))
+        );
+
+        // Time-based lock time on its own is incompatible with the height-based inputs.
+        let mut time = Input::default();
+        time.set_required_time_lock_time(Some(
+            absolute::MedianTimePast::from_u32(600_000_000).unwrap(),
+        ));
+        assert_eq!(psbt.add_input(txin(2), time), Err(ConstructError::IncompatibleLockTime));
+
+        // Once an input is signed the lock time can no longer change.
+        let sig = ecdsa::Signature::from_str("3044022007e06b362e89912abd4661f47945430739b006a85d1b2a16c01dc1a4bd07acab022061576d7aa834988b7ab94ef21d8eebd996ea59ea20529a19b15f0c9cebe3d8ac01").unwrap();
+        let pk = PublicKey::from_str(
+            "025fe7371376d53cf8a2783917c28bf30bd690b0a4d4a207690093ca2b920ee076",
+        )
+        .unwrap();
+        psbt.inputs[0].partial_sigs.insert(pk, sig);
+        assert_eq!(psbt.add_input(txin(3), height(300)), Err(ConstructError::LockTimeChanged));
+        psbt.add_input(txin(3), height(150)).unwrap();
+
+        let deser = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(deser, psbt);
+    }
 }
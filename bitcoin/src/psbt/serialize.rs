@@ -10,20 +10,22 @@
 #[allow(unused)] // MSRV polyfill
 use internals::slice::SliceExt;
 
-use super::map::{Input, Map, Output, PsbtSighashType};
+use super::map::{Input, Map, Output, PsbtSighashType, TxModifiable};
 use crate::bip32::{ChildNumber, Fingerprint, KeySource};
 use crate::consensus::encode::{self, deserialize_partial, serialize, Decodable, Encodable};
 use crate::crypto::key::{PublicKey, XOnlyPublicKey};
 use crate::crypto::{ecdsa, taproot};
 use crate::io::Write;
+use crate::locktime::absolute;
 use crate::prelude::{DisplayHex, String, Vec};
 use crate::psbt::{Error, Psbt};
 use crate::script::ScriptBuf;
 use crate::taproot::{
     ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TapTree, TaprootBuilder,
 };
-use crate::transaction::{Transaction, TxOut};
+use crate::transaction::{self, Transaction, TxOut, Txid};
 use crate::witness::Witness;
+use crate::{Amount, Sequence};
 
 /// A trait for serializing a value as raw data for insertion into PSBT
 /// key-value maps.
@@ -64,6 +66,18 @@ fn write_all(w: &mut impl Write, data: &[u8]) -> io::Result<usize> {
 
         written_len += write_all(w, &self.serialize_map())?;
 
+        if self.is_v2() {
+            for (input, txin) in self.inputs.iter().zip(&self.unsigned_tx.input) {
+                written_len += write_all(w, &input.serialize_map_v2(txin))?;
+            }
+
+            for (output, txout) in self.outputs.iter().zip(&self.unsigned_tx.output) {
+                written_len += write_all(w, &output.serialize_map_v2(txout))?;
+            }
+
+            return Ok(written_len);
+        }
+
         for i in &self.inputs {
             written_len += write_all(w, &i.serialize_map())?;
         }
@@ -95,28 +109,47 @@ pub fn deserialize_from_reader<R: io::BufRead>(r: &mut R) -> Result<Self, Error>
             return Err(Error::InvalidSeparator);
         }
 
-        let mut global = Psbt::decode_global(r)?;
+        let (mut global, inputs_len, outputs_len) = Psbt::decode_global(r)?;
         global.unsigned_tx_checks()?;
 
-        let inputs: Vec<Input> = {
-            let inputs_len: usize = (global.unsigned_tx.input).len();
+        if global.is_v2() {
+            // The counts are not backed by any data yet so we do not preallocate.
+            for _ in 0..inputs_len {
+                let mut input = Input::decode(r)?;
+                global.unsigned_tx.input.push(input.take_v2_fields()?);
+                global.inputs.push(input);
+            }
 
+            for _ in 0..outputs_len {
+                let mut output = Output::decode(r)?;
+                global.unsigned_tx.output.push(output.take_v2_fields()?);
+                global.outputs.push(output);
+            }
+
+            global.unsigned_tx.lock_time =
+                global.determine_lock_time().ok_or(Error::IncompatibleLockTimes)?;
+            return Ok(global);
+        }
+
+        let inputs: Vec<Input> = {
             let mut inputs: Vec<Input> = Vec::with_capacity(inputs_len);
 
             for _ in 0..inputs_len {
-                inputs.push(Input::decode(r)?);
+                let input = Input::decode(r)?;
+                input.check_v0_fields()?;
+                inputs.push(input);
             }
 
             inputs
         };
 
         let outputs: Vec<Output> = {
-            let outputs_len: usize = (global.unsigned_tx.output).len();
-
             let mut outputs: Vec<Output> = Vec::with_capacity(outputs_len);
 
             for _ in 0..outputs_len {
-                outputs.push(Output::decode(r)?);
+                let output = Output::decode(r)?;
+                output.check_v0_fields()?;
+                outputs.push(output);
             }
 
             outputs
@@ -130,6 +163,12 @@ pub fn deserialize_from_reader<R: io::BufRead>(r: &mut R) -> Result<Self, Error>
 impl_psbt_de_serialize!(Transaction);
 impl_psbt_de_serialize!(TxOut);
 impl_psbt_de_serialize!(Witness);
+impl_psbt_de_serialize!(Txid);
+impl_psbt_de_serialize!(u32);
+impl_psbt_de_serialize!(Sequence);
+impl_psbt_de_serialize!(Amount);
+impl_psbt_de_serialize!(absolute::LockTime);
+impl_psbt_de_serialize!(transaction::Version);
 impl_psbt_hash_de_serialize!(ripemd160::Hash);
 impl_psbt_hash_de_serialize!(sha256::Hash);
 impl_psbt_hash_de_serialize!(TapLeafHash);
@@ -274,6 +313,39 @@ fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
     }
 }
 
+impl Serialize for absolute::MedianTimePast {
+    fn serialize(&self) -> Vec<u8> { serialize(&self.to_u32()) }
+}
+
+impl Deserialize for absolute::MedianTimePast {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: u32 = encode::deserialize(bytes)?;
+        Self::from_u32(raw).map_err(Error::InvalidLockTime)
+    }
+}
+
+impl Serialize for absolute::Height {
+    fn serialize(&self) -> Vec<u8> { serialize(&self.to_u32()) }
+}
+
+impl Deserialize for absolute::Height {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: u32 = encode::deserialize(bytes)?;
+        Self::from_u32(raw).map_err(Error::InvalidLockTime)
+    }
+}
+
+impl Serialize for TxModifiable {
+    fn serialize(&self) -> Vec<u8> { vec![self.to_u8()] }
+}
+
+impl Deserialize for TxModifiable {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: u8 = encode::deserialize(bytes)?;
+        Ok(TxModifiable::from_u8(raw))
+    }
+}
+
 // Taproot related ser/deser
 impl Serialize for XOnlyPublicKey {
     fn serialize(&self) -> Vec<u8> { XOnlyPublicKey::serialize(self).to_vec() }
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! PSBT version 2.
+//!
+//! Conversion between PSBT versions and the constructor role as defined by BIP-370
+//! <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>.
+//!
+//! A version 2 PSBT does not contain the unsigned transaction, instead the transaction version,
+//! lock time, inputs and outputs are spread across the global, input and output maps. We still
+//! keep the unsigned transaction in [`Psbt::unsigned_tx`] so that the rest of the API works the
+//! same for both versions, only the serialization differs.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use crate::locktime::absolute;
+use crate::psbt::{Input, Output, Psbt, PsbtSighashType, TxModifiable};
+use crate::sighash::EcdsaSighashType;
+use crate::transaction::{OutPoint, TxIn, TxOut};
+
+impl Psbt {
+    /// Converts this PSBT to version 0 (BIP-174).
+    ///
+    /// The lock time and inputs are already part of the unsigned transaction, the fields only
+    /// defined by version 2 are dropped.
+    pub fn into_v0(mut self) -> Psbt {
+        self.version = 0;
+        self.set_fallback_lock_time(None);
+        self.set_tx_modifiable(None);
+        for input in &mut self.inputs {
+            input.set_required_time_lock_time(None);
+            input.set_required_height_lock_time(None);
+        }
+        self
+    }
+
+    /// Converts this PSBT to version 2 (BIP-370).
+    ///
+    /// The lock time of the unsigned transaction becomes the fallback lock time. The
+    /// transaction is not modifiable, use [`Psbt::set_tx_modifiable`] to allow constructors to
+    /// add inputs or outputs.
+    pub fn into_v2(mut self) -> Psbt {
+        if !self.is_v2() {
+            self.version = 2;
+            self.set_fallback_lock_time(
+                Some(self.unsigned_tx.lock_time)
+                    .filter(|lock_time| *lock_time != absolute::LockTime::ZERO),
+            );
+        }
+        self
+    }
+
+    /// Adds an input to a version 2 PSBT (BIP-370 constructor role).
+    ///
+    /// The input is appended so the pairing of existing inputs and outputs, as required by
+    /// `SIGHASH_SINGLE` signatures, is preserved. The lock time of the unsigned transaction is
+    /// recomputed from the required lock times of all inputs.
+    ///
+    /// # Errors
+    ///
+    /// If the PSBT is not version 2, inputs are not modifiable, `txin` is not unsigned or
+    /// spends an outpoint already spent by this PSBT, or the new input's required lock time is
+    /// incompatible with the other inputs or changes the lock time of already signed inputs.
+    pub fn add_input(&mut self, txin: TxIn, input: Input) -> Result<(), ConstructError> {
+        self.check_modifiable(TxModifiable::INPUTS, ConstructError::InputsNotModifiable)?;
+        if !txin.script_sig.is_empty() || !txin.witness.is_empty() {
+            return Err(ConstructError::SignedInput);
+        }
+        let outpoint = txin.previous_output;
+        if self.unsigned_tx.input.iter().any(|txin| txin.previous_output == outpoint) {
+            return Err(ConstructError::DuplicateInput(outpoint));
+        }
+
+        let lock_time = determine_lock_time(
+            self.fallback_lock_time(),
+            self.inputs.iter().chain(core::iter::once(&input)),
+        )
+        .ok_or(ConstructError::IncompatibleLockTime)?;
+        if lock_time != self.unsigned_tx.lock_time && self.has_signatures() {
+            return Err(ConstructError::LockTimeChanged);
+        }
+
+        self.unsigned_tx.lock_time = lock_time;
+        self.unsigned_tx.input.push(txin);
+        self.inputs.push(input);
+        Ok(())
+    }
+
+    /// Adds an output to a version 2 PSBT (BIP-370 constructor role).
+    ///
+    /// # Errors
+    ///
+    /// If the PSBT is not version 2 or outputs are not modifiable.
+    pub fn add_output(&mut self, txout: TxOut, output: Output) -> Result<(), ConstructError> {
+        self.check_modifiable(TxModifiable::OUTPUTS, ConstructError::OutputsNotModifiable)?;
+        self.unsigned_tx.output.push(txout);
+        self.outputs.push(output);
+        Ok(())
+    }
+
+    /// Returns true if this is a version 2 PSBT.
+    pub(crate) fn is_v2(&self) -> bool { self.version == 2 }
+
+    /// Determines the lock time of this version 2 PSBT from its inputs.
+    pub(crate) fn determine_lock_time(&self) -> Option<absolute::LockTime> {
+        determine_lock_time(self.fallback_lock_time(), &self.inputs)
+    }
+
+    /// Updates the modifiable flags after `input_index` was signed (BIP-370 signer role).
+    ///
+    /// A signature not using `SIGHASH_ANYONECANPAY` commits to all inputs and one not using
+    /// `SIGHASH_NONE` to some outputs, so these can no longer be added.
+    pub(crate) fn update_tx_modifiable(&mut self, input_index: usize) {
+        const SIGHASH_NONE: u32 = 0x02;
+        const SIGHASH_SINGLE: u32 = 0x03;
+        const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+        if !self.is_v2() {
+            return;
+        }
+        let sighash_type = self.inputs[input_index]
+            .sighash_type
+            .map_or(EcdsaSighashType::All.to_u32(), PsbtSighashType::to_u32);
+        if let Some(mut flags) = self.tx_modifiable() {
+            if sighash_type & SIGHASH_ANYONECANPAY == 0 {
+                flags.remove(TxModifiable::INPUTS);
+            }
+            match sighash_type & 0x1f {
+                SIGHASH_NONE => {}
+                SIGHASH_SINGLE => {
+                    flags.remove(TxModifiable::OUTPUTS);
+                    flags.add(TxModifiable::SIGHASH_SINGLE);
+                }
+                _ => {
+                    flags.remove(TxModifiable::OUTPUTS);
+                }
+            }
+            self.set_tx_modifiable(Some(flags));
+        }
+    }
+
+    /// Combines the version 2 global fields of `other` into this PSBT.
+    ///
+    /// The fields are removed from `other` so that combining the unknown key-value pairs does
+    /// not overwrite them. Inputs and outputs stay modifiable only if both PSBTs allow it.
+    pub(crate) fn combine_v2(&mut self, other: &mut Psbt) {
+        if self.fallback_lock_time().is_none() {
+            self.set_fallback_lock_time(other.fallback_lock_time());
+        }
+        other.set_fallback_lock_time(None);
+
+        let tx_modifiable = other.tx_modifiable();
+        other.set_tx_modifiable(None);
+        if self.tx_modifiable().is_none() && tx_modifiable.is_none() {
+            return;
+        }
+        let (ours, theirs) =
+            (self.tx_modifiable().unwrap_or_default(), tx_modifiable.unwrap_or_default());
+        let mut flags = TxModifiable::from_u8(ours.to_u8() & theirs.to_u8());
+        if ours.has(TxModifiable::SIGHASH_SINGLE) || theirs.has(TxModifiable::SIGHASH_SINGLE) {
+            flags.add(TxModifiable::SIGHASH_SINGLE);
+        }
+        self.set_tx_modifiable(Some(flags));
+    }
+
+    /// Checks that this is a version 2 PSBT with the `required` modifiable flags set.
+    fn check_modifiable(
+        &self,
+        required: TxModifiable,
+        error: ConstructError,
+    ) -> Result<(), ConstructError> {
+        if !self.is_v2() {
+            return Err(ConstructError::UnsupportedVersion(self.version));
+        }
+        if !self.tx_modifiable().unwrap_or_default().has(required) {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Returns true if any input carries a signature.
+    fn has_signatures(&self) -> bool {
+        self.inputs.iter().any(|input| {
+            !input.partial_sigs.is_empty()
+                || input.tap_key_sig.is_some()
+                || !input.tap_script_sigs.is_empty()
+                || input.final_script_sig.is_some()
+                || input.final_script_witness.is_some()
+        })
+    }
+}
+
+/// Determines the lock time of a version 2 PSBT as described in BIP-370.
+///
+/// If no input requires a lock time the fallback lock time is used. Otherwise height-based lock
+/// times are preferred if all inputs support them, falling back to time-based lock times.
+/// Returns `None` if the inputs require both kinds of lock time.
+fn determine_lock_time<'a>(
+    fallback: Option<absolute::LockTime>,
+    inputs: impl IntoIterator<Item = &'a Input>,
+) -> Option<absolute::LockTime> {
+    let (mut height, mut time) = (None, None);
+    let (mut constrained, mut all_height, mut all_time) = (false, true, true);
+    for input in inputs {
+        let (required_height, required_time) =
+            (input.required_height_lock_time(), input.required_time_lock_time());
+        if required_height.is_none() && required_time.is_none() {
+            continue;
+        }
+        constrained = true;
+        match required_height {
+            Some(h) => height = height.max(Some(h)),
+            None => all_height = false,
+        }
+        match required_time {
+            Some(t) => time = time.max(Some(t)),
+            None => all_time = false,
+        }
+    }
+
+    if !constrained {
+        Some(fallback.unwrap_or(absolute::LockTime::ZERO))
+    } else if all_height {
+        height.map(absolute::LockTime::Blocks)
+    } else if all_time {
+        time.map(absolute::LockTime::Seconds)
+    } else {
+        None
+    }
+}
+
+/// Error adding an input or output to a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConstructError {
+    /// Inputs and outputs can only be added to version 2 PSBTs.
+    UnsupportedVersion(u32),
+    /// The modifiable flags of the PSBT do not permit adding inputs.
+    InputsNotModifiable,
+    /// The modifiable flags of the PSBT do not permit adding outputs.
+    OutputsNotModifiable,
+    /// The input has a non-empty scriptSig or witness.
+    SignedInput,
+    /// The outpoint is already spent by another input of the PSBT.
+    DuplicateInput(OutPoint),
+    /// The required lock time of the input is incompatible with the other inputs.
+    IncompatibleLockTime,
+    /// Adding the input would change the lock time of a transaction that already has signatures.
+    LockTimeChanged,
+}
+
+impl From<Infallible> for ConstructError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for ConstructError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConstructError::*;
+
+        match *self {
+            UnsupportedVersion(version) =>
+                write!(f, "cannot add inputs or outputs to a version {} PSBT", version),
+            InputsNotModifiable => f.write_str("the PSBT does not allow adding inputs"),
+            OutputsNotModifiable => f.write_str("the PSBT does not allow adding outputs"),
+            SignedInput => f.write_str("the input has a non-empty scriptSig or witness"),
+            DuplicateInput(outpoint) => write!(f, "outpoint {} is already spent", outpoint),
+            IncompatibleLockTime =>
+                f.write_str("the required lock time is incompatible with the other inputs"),
+            LockTimeChanged =>
+                f.write_str("adding the input would change the lock time of signed inputs"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConstructError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ConstructError::*;
+
+        match *self {
+            UnsupportedVersion(_)
+            | InputsNotModifiable
+            | OutputsNotModifiable
+            | SignedInput
+            | DuplicateInput(_)
+            | IncompatibleLockTime
+            | LockTimeChanged => None,
+        }
+    }
+}
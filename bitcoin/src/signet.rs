@@ -0,0 +1,436 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Signet block solutions.
+//!
+//! Implements [BIP-325]. Signet blocks are signed by satisfying the challenge script of the
+//! signet: the solution, a script signature and witness, is pushed after [`SIGNET_HEADER`] in the
+//! witness commitment output of the coinbase. It spends the output of the virtual `to_spend`
+//! transaction, locked with the challenge and committing to the block, by the virtual `to_sign`
+//! transaction.
+//!
+//! [BIP-325]: <https://github.com/bitcoin/bips/blob/master/bip-0325.mediawiki>
+
+use core::convert::Infallible;
+use core::fmt;
+
+use internals::write_err;
+use secp256k1::{Secp256k1, Verification};
+
+use crate::block::{self, BlockHash, Checked, Header};
+use crate::consensus::{encode, Encodable as _};
+use crate::merkle_tree::MerkleNode as _;
+use crate::opcodes::all::{OP_PUSHBYTES_0, OP_RETURN};
+use crate::opcodes::Opcode;
+use crate::prelude::Vec;
+use crate::script::interpreter::{self, VerifyFlags};
+use crate::script::{Builder, Instruction, Script, ScriptBuf, ScriptExt as _};
+use crate::sighash::Prevouts;
+use crate::transaction::{self, OutPointExt as _, Transaction, TxIn, TxOut};
+use crate::{absolute, Amount, Block, OutPoint, Sequence, TxMerkleNode, Witness};
+
+/// The bytes preceding the signet solution in the witness commitment output.
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// The challenge script of the default signet, a 1-of-2 multisig.
+pub const DEFAULT_CHALLENGE: &Script = Script::from_bytes(&[
+    0x51, 0x21, 0x03, 0xad, 0x5e, 0x0e, 0xda, 0xd1, 0x8c, 0xb1, 0xf0, 0xfc, 0x0d, 0x28, 0xa3, 0xd4,
+    0xf1, 0xf3, 0xe4, 0x45, 0x64, 0x03, 0x37, 0x48, 0x9a, 0xbb, 0x10, 0x40, 0x4f, 0x2d, 0x1e, 0x08,
+    0x6b, 0xe4, 0x30, 0x21, 0x03, 0x59, 0xef, 0x50, 0x21, 0x96, 0x4f, 0xe2, 0x2d, 0x6f, 0x8e, 0x05,
+    0xb2, 0x46, 0x3c, 0x95, 0x40, 0xce, 0x96, 0x88, 0x3f, 0xe3, 0xb2, 0x78, 0x76, 0x0f, 0x04, 0x8f,
+    0x51, 0x89, 0xf2, 0xe6, 0xc4, 0x52, 0xae,
+]);
+
+/// The virtual transactions of a signet block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignetTxs {
+    /// The transaction committing to the block, its single output is locked with the challenge.
+    pub to_spend: Transaction,
+    /// The transaction spending `to_spend` with the solution of the block.
+    pub to_sign: Transaction,
+}
+
+impl SignetTxs {
+    /// Constructs the virtual transactions of `block` for the signet with the `challenge` script.
+    ///
+    /// A block without a solution is spent with an empty script signature and witness, so that
+    /// trivial challenges such as `OP_TRUE` need no solution.
+    ///
+    /// # Errors
+    ///
+    /// If the block has no witness commitment or the solution can not be decoded.
+    pub fn new(block: &Block<Checked>, challenge: &Script) -> Result<Self, SolutionError> {
+        let mut coinbase =
+            block.transactions().first().cloned().ok_or(SolutionError::NoWitnessCommitment)?;
+        let index =
+            block::witness_commitment_index(&coinbase).ok_or(SolutionError::NoWitnessCommitment)?;
+
+        let commitment = &mut coinbase.output[index].script_pubkey;
+        let (script_sig, witness) = match take_solution(commitment) {
+            Some((script_pubkey, solution)) => {
+                *commitment = script_pubkey;
+                let (script_sig, consumed) = encode::deserialize_partial::<ScriptBuf>(&solution)?;
+                let solution = &solution[consumed..];
+                let (witness, consumed) = encode::deserialize_partial::<Witness>(solution)?;
+                if consumed != solution.len() {
+                    return Err(SolutionError::TrailingData);
+                }
+                (script_sig, witness)
+            }
+            None => (ScriptBuf::new(), Witness::new()),
+        };
+
+        let to_spend = to_spend(block.header(), &coinbase, block.transactions(), challenge);
+        let to_sign = Transaction {
+            version: transaction::Version::maybe_non_standard(0),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+                script_sig,
+                sequence: Sequence::ZERO,
+                witness,
+            }],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+            }],
+        };
+        Ok(SignetTxs { to_spend, to_sign })
+    }
+}
+
+/// Verifies the solution of `block` against the `challenge` script of its signet.
+///
+/// The genesis block, the only block without a parent, needs no solution.
+///
+/// # Errors
+///
+/// If the solution can not be extracted or does not satisfy the challenge.
+pub fn verify_block_solution<C: Verification>(
+    secp: &Secp256k1<C>,
+    block: &Block<Checked>,
+    challenge: &Script,
+) -> Result<(), SolutionError> {
+    if block.header().prev_blockhash == BlockHash::GENESIS_PREVIOUS_BLOCK_HASH {
+        return Ok(());
+    }
+    let txs = SignetTxs::new(block, challenge)?;
+    // Bitcoin Core's `BLOCK_SCRIPT_VERIFY_FLAGS`.
+    let flags =
+        VerifyFlags::P2SH | VerifyFlags::WITNESS | VerifyFlags::DERSIG | VerifyFlags::NULLDUMMY;
+    interpreter::verify_input(secp, &txs.to_sign, 0, &Prevouts::All(&txs.to_spend.output), flags)
+        .map_err(SolutionError::Script)
+}
+
+/// Removes the solution from the witness commitment `script_pubkey`.
+///
+/// Returns the commitment with only the signet header left in the push of the solution and the
+/// solution, `None` if there is no solution. Like Bitcoin Core, the remaining pushes are
+/// re-encoded and everything after an invalid push is dropped.
+fn take_solution(script_pubkey: &Script) -> Option<(ScriptBuf, Vec<u8>)> {
+    let mut solution = None;
+    let mut builder = Builder::new();
+    for instruction in script_pubkey.instruction_indices() {
+        let (position, instruction) = match instruction {
+            Ok(instruction) => instruction,
+            Err(_) => break,
+        };
+        builder = match instruction {
+            Instruction::PushBytes(bytes) if bytes.is_empty() =>
+                builder.push_opcode(Opcode::from(script_pubkey.as_bytes()[position])),
+            Instruction::PushBytes(bytes)
+                if solution.is_none()
+                    && bytes.len() > SIGNET_HEADER.len()
+                    && bytes.as_bytes().starts_with(&SIGNET_HEADER) =>
+            {
+                solution = Some(bytes.as_bytes()[SIGNET_HEADER.len()..].to_vec());
+                builder.push_slice(SIGNET_HEADER)
+            }
+            Instruction::PushBytes(bytes) => builder.push_slice_non_minimal(bytes),
+            Instruction::Op(op) => builder.push_opcode(op),
+        };
+    }
+    solution.map(|solution| (builder.into_script(), solution))
+}
+
+/// Constructs the `to_spend` transaction, committing to the block with its coinbase replaced by
+/// the `coinbase` without the solution.
+fn to_spend(
+    header: &Header,
+    coinbase: &Transaction,
+    transactions: &[Transaction],
+    challenge: &Script,
+) -> Transaction {
+    let txids = core::iter::once(coinbase.compute_txid())
+        .chain(transactions[1..].iter().map(Transaction::compute_txid));
+    let merkle_root = TxMerkleNode::calculate_root(txids).expect("the coinbase is a leaf");
+
+    let mut block_data = Vec::with_capacity(72);
+    header.version.consensus_encode(&mut block_data).expect("vectors don't error");
+    header.prev_blockhash.consensus_encode(&mut block_data).expect("vectors don't error");
+    merkle_root.consensus_encode(&mut block_data).expect("vectors don't error");
+    header.time.consensus_encode(&mut block_data).expect("vectors don't error");
+    let block_data = <[u8; 72]>::try_from(block_data).expect("header fields are 72 bytes");
+
+    Transaction {
+        version: transaction::Version::maybe_non_standard(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::COINBASE_PREVOUT,
+            script_sig: Builder::new()
+                .push_opcode(OP_PUSHBYTES_0)
+                .push_slice(block_data)
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: challenge.into() }],
+    }
+}
+
+/// An error verifying the solution of a signet block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SolutionError {
+    /// The block has no witness commitment.
+    NoWitnessCommitment,
+    /// The solution in the witness commitment can not be decoded.
+    Decode(encode::ParseError),
+    /// The solution in the witness commitment is followed by extra data.
+    TrailingData,
+    /// The solution does not satisfy the challenge.
+    Script(interpreter::Error),
+}
+
+impl From<Infallible> for SolutionError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl From<encode::ParseError> for SolutionError {
+    fn from(e: encode::ParseError) -> Self { Self::Decode(e) }
+}
+
+impl fmt::Display for SolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SolutionError::*;
+
+        match *self {
+            NoWitnessCommitment => write!(f, "the block has no witness commitment"),
+            Decode(ref e) => write_err!(f, "failed to decode the block solution"; e),
+            TrailingData => write!(f, "the block solution is followed by extra data"),
+            Script(ref e) => write_err!(f, "the block solution does not satisfy the challenge"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SolutionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SolutionError::*;
+
+        match *self {
+            Decode(ref e) => Some(e),
+            Script(ref e) => Some(e),
+            NoWitnessCommitment | TrailingData => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::{Message, SecretKey};
+
+    use super::*;
+    use crate::address::script_pubkey::ScriptBufExt as _;
+    use crate::block::{BlockUncheckedExt as _, Version};
+    use crate::crypto::key::PublicKey;
+    use crate::opcodes::all::OP_PUSHNUM_1;
+    use crate::pow::CompactTarget;
+    use crate::script::PushBytes;
+    use crate::sighash::SighashCache;
+    use crate::witness::WitnessExt as _;
+    use crate::{ecdsa, BlockTime, EcdsaSighashType};
+
+    /// Constructs a block whose coinbase has a witness commitment followed by `push`.
+    fn block(push: Option<&[u8]>) -> Block<Checked> {
+        let mut commitment = Builder::new().push_opcode(OP_RETURN).push_slice([
+            0xaa, 0x21, 0xa9, 0xed, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+        if let Some(push) = push {
+            commitment = commitment.push_slice_non_minimal(<&PushBytes>::try_from(push).unwrap());
+        }
+        let coinbase = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                script_sig: Builder::new()
+                    .push_int_unchecked(1)
+                    .push_int_unchecked(0)
+                    .into_script(),
+                ..TxIn::EMPTY_COINBASE
+            }],
+            output: vec![
+                TxOut { value: Amount::FIFTY_BTC, script_pubkey: ScriptBuf::new() },
+                TxOut { value: Amount::ZERO, script_pubkey: commitment.into_script() },
+            ],
+        };
+        let transactions = vec![coinbase];
+        let header = Header {
+            version: Version::TWO,
+            prev_blockhash: BlockHash::from_byte_array([1; 32]),
+            merkle_root: block::compute_merkle_root(&transactions).unwrap(),
+            time: BlockTime::from_u32(1_700_000_000),
+            bits: CompactTarget::from_consensus(0x1e0377ae),
+            nonce: 0,
+        };
+        Block::new_unchecked(header, transactions).validate().unwrap()
+    }
+
+    /// Returns `SIGNET_HEADER` followed by the serialized solution.
+    fn solution(script_sig: &Script, witness: &Witness) -> Vec<u8> {
+        let mut push = SIGNET_HEADER.to_vec();
+        script_sig.consensus_encode(&mut push).unwrap();
+        witness.consensus_encode(&mut push).unwrap();
+        push
+    }
+
+    #[test]
+    fn block_solution() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_byte_array(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let challenge = ScriptBuf::new_p2wpkh(pk.wpubkey_hash().unwrap());
+
+        // The solution commits to the block without it, where only the header is pushed.
+        let unsigned = block(Some(&SIGNET_HEADER));
+        let txs = SignetTxs::new(&unsigned, &challenge).unwrap();
+        assert!(matches!(
+            verify_block_solution(&secp, &unsigned, &challenge),
+            Err(SolutionError::Script(_))
+        ));
+
+        let sighash = SighashCache::new(&txs.to_sign)
+            .p2wpkh_signature_hash(0, &challenge, Amount::ZERO, EcdsaSighashType::All)
+            .unwrap();
+        let signature = secp.sign_ecdsa(&Message::from(sighash), &sk);
+        let signature = ecdsa::Signature { signature, sighash_type: EcdsaSighashType::All };
+        let witness = Witness::p2wpkh(signature, pk.inner);
+        let signed = block(Some(&solution(Script::new(), &witness)));
+
+        let signed_txs = SignetTxs::new(&signed, &challenge).unwrap();
+        assert_eq!(signed_txs.to_spend, txs.to_spend);
+        assert_eq!(signed_txs.to_sign.input[0].witness, witness);
+        verify_block_solution(&secp, &signed, &challenge).unwrap();
+
+        let other = ScriptBuf::new_p2wpkh(
+            PublicKey::new(SecretKey::from_byte_array(&[2; 32]).unwrap().public_key(&secp))
+                .wpubkey_hash()
+                .unwrap(),
+        );
+        assert!(verify_block_solution(&secp, &signed, &other).is_err());
+
+        // The genesis block has no solution.
+        let header =
+            Header { prev_blockhash: BlockHash::GENESIS_PREVIOUS_BLOCK_HASH, ..*signed.header() };
+        let genesis =
+            Block::new_unchecked(header, signed.transactions().to_vec()).validate().unwrap();
+        verify_block_solution(&secp, &genesis, &other).unwrap();
+    }
+
+    #[test]
+    fn trivial_challenge() {
+        let secp = Secp256k1::new();
+        let challenge = Builder::new().push_opcode(OP_PUSHNUM_1).into_script();
+        verify_block_solution(&secp, &block(None), &challenge).unwrap();
+        verify_block_solution(&secp, &block(Some(&SIGNET_HEADER)), &challenge).unwrap();
+    }
+
+    #[test]
+    fn invalid_solution() {
+        let mut push = solution(Script::new(), &Witness::new());
+        push.push(0);
+        assert_eq!(
+            SignetTxs::new(&block(Some(&push)), Script::new()),
+            Err(SolutionError::TrailingData)
+        );
+        assert!(matches!(
+            SignetTxs::new(
+                &block(Some(&SIGNET_HEADER[..].iter().chain(&[0x05]).copied().collect::<Vec<_>>())),
+                Script::new()
+            ),
+            Err(SolutionError::Decode(_))
+        ));
+
+        let mut transactions = block(None).transactions().to_vec();
+        transactions[0].output.pop();
+        let header = Header {
+            merkle_root: block::compute_merkle_root(&transactions).unwrap(),
+            ..*block(None).header()
+        };
+        let block = Block::new_unchecked(header, transactions).validate().unwrap();
+        assert_eq!(SignetTxs::new(&block, Script::new()), Err(SolutionError::NoWitnessCommitment));
+    }
+
+    #[test]
+    fn default_signet_coinbase() {
+        // The coinbase of default signet block 174359, the only transaction of the block.
+        let hex = include_str!("../tests/data/signet_coinbase_174359.hex");
+        let coinbase: Transaction = encode::deserialize_hex(hex.trim()).unwrap();
+        let index = block::witness_commitment_index(&coinbase).unwrap();
+        let script_pubkey = &coinbase.output[index].script_pubkey;
+        let (commitment, _) = take_solution(script_pubkey).unwrap();
+        assert_eq!(commitment.as_bytes()[..38], script_pubkey.as_bytes()[..38]);
+        assert_eq!(commitment.as_bytes()[38..], [0x04, 0xec, 0xc7, 0xda, 0xa2]);
+
+        let header = Header {
+            merkle_root: block::compute_merkle_root(core::slice::from_ref(&coinbase)).unwrap(),
+            ..*block(None).header()
+        };
+        let block = Block::new_unchecked(header, vec![coinbase.clone()]).validate().unwrap();
+        let txs = SignetTxs::new(&block, DEFAULT_CHALLENGE).unwrap();
+        assert_eq!(txs.to_spend.output[0].script_pubkey, *DEFAULT_CHALLENGE);
+
+        // The 1-of-2 multisig challenge is solved with a dummy element and one signature.
+        let input = &txs.to_sign.input[0];
+        assert!(input.witness.is_empty());
+        let pushes = input
+            .script_sig
+            .instructions()
+            .map(|instruction| instruction.unwrap().push_bytes().unwrap().as_bytes().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(pushes.len(), 2);
+        assert!(pushes[0].is_empty());
+        let signature = ecdsa::Signature::from_slice(&pushes[1]).unwrap();
+        assert_eq!(signature.sighash_type, EcdsaSighashType::All);
+
+        // The signature commits to the header of block 174359, not to this one.
+        assert!(matches!(
+            verify_block_solution(&Secp256k1::new(), &block, DEFAULT_CHALLENGE),
+            Err(SolutionError::Script(_))
+        ));
+
+        // Changing the length of the script signature leaves no bytes for the witness.
+        let mut bytes = script_pubkey.to_vec();
+        let position = bytes.windows(4).position(|w| w == SIGNET_HEADER).unwrap() + 4;
+        bytes[position] += 1;
+        let mut tampered = coinbase;
+        tampered.output[index].script_pubkey = ScriptBuf::from_bytes(bytes);
+        let header = Header {
+            merkle_root: block::compute_merkle_root(core::slice::from_ref(&tampered)).unwrap(),
+            ..header
+        };
+        let block = Block::new_unchecked(header, vec![tampered]).validate().unwrap();
+        assert!(matches!(SignetTxs::new(&block, DEFAULT_CHALLENGE), Err(SolutionError::Decode(_))));
+    }
+
+    #[test]
+    fn take_solution_reencodes_pushes() {
+        // A non-minimal push of the solution and a non-minimal push of other data.
+        let script = ScriptBuf::from_bytes(vec![
+            0x6a, 0x4c, 0x06, 0xec, 0xc7, 0xda, 0xa2, 0x00, 0x00, 0x4c, 0x01, 0xff, 0x4c, 0x00,
+        ]);
+        let (script, solution) = take_solution(&script).unwrap();
+        assert_eq!(solution, [0x00, 0x00]);
+        assert_eq!(script.as_bytes(), [0x6a, 0x04, 0xec, 0xc7, 0xda, 0xa2, 0x01, 0xff, 0x4c]);
+    }
+}
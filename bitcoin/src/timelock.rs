@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Contextual validation of transaction lock times.
+//!
+//! Lock times are checked against the chain a transaction is mined in: absolute lock times with
+//! [`TransactionExt::is_absolute_timelock_satisfied`] against the height and median time past of
+//! the chain tip, tracked by [`MtpTracker`], and [BIP-68] relative lock times with
+//! [`check_sequence_locks`] against the confirmations of the spent coins.
+//!
+//! [`TransactionExt::is_absolute_timelock_satisfied`]: crate::transaction::TransactionExt::is_absolute_timelock_satisfied
+//! [BIP-68]: <https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki>
+
+use core::convert::Infallible;
+use core::fmt;
+
+use units::{BlockHeight, BlockMtp, BlockTime};
+
+use crate::block::Header;
+use crate::relative::{self, NumberOf512Seconds, NumberOfBlocks};
+use crate::transaction::{self, OutPoint, Transaction, TransactionExt as _};
+
+/// The number of headers the median time past is computed over.
+pub(crate) const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Tracks the median time past of a chain as its headers are pushed.
+///
+/// The median time past is exact once the eleven most recent headers, or all headers since the
+/// genesis block, were pushed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MtpTracker {
+    /// The timestamps of the most recent headers, in a ring buffer.
+    times: [u32; MEDIAN_TIME_SPAN],
+    len: usize,
+    next: usize,
+}
+
+impl MtpTracker {
+    /// Constructs a new tracker without headers.
+    pub const fn new() -> Self { MtpTracker { times: [0; MEDIAN_TIME_SPAN], len: 0, next: 0 } }
+
+    /// Pushes the header following the last pushed one.
+    pub fn push(&mut self, header: &Header) { self.push_time(header.time) }
+
+    /// Pushes the timestamp of the header following the last pushed one.
+    pub fn push_time(&mut self, time: BlockTime) {
+        self.times[self.next] = time.to_u32();
+        self.next = (self.next + 1) % MEDIAN_TIME_SPAN;
+        self.len = core::cmp::min(self.len + 1, MEDIAN_TIME_SPAN);
+    }
+
+    /// Returns the number of timestamps the median is computed over, at most eleven.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns `true` if no header was pushed.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Returns the median timestamp of the last pushed headers, `None` if none was pushed.
+    pub fn median_time_past(&self) -> Option<BlockMtp> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut times = self.times;
+        let times = &mut times[..self.len];
+        times.sort_unstable();
+        Some(BlockMtp::from_u32(times[times.len() / 2]))
+    }
+}
+
+impl<'a> Extend<&'a Header> for MtpTracker {
+    fn extend<I: IntoIterator<Item = &'a Header>>(&mut self, headers: I) {
+        for header in headers {
+            self.push(header);
+        }
+    }
+}
+
+/// The confirmation of a coin, from which the relative lock times of its spends start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoinConfirmation {
+    /// The height of the block which confirmed the coin.
+    pub height: BlockHeight,
+    /// The median time past of the parent of the block which confirmed the coin.
+    ///
+    /// For a coin confirmed in the block being validated this is the median time past of the
+    /// chain tip.
+    pub median_time_past: BlockMtp,
+}
+
+/// Checks the [BIP-68] relative lock times of the inputs of `tx` for inclusion in the block
+/// following the chain tip at `tip_height`, whose median time past is `tip_median_time_past`.
+///
+/// `spent` is called with the outpoint of each input and returns the confirmation of the spent
+/// coin. Relative lock times only apply to transactions of version 2 and above, and only once
+/// BIP-68 is active, which is up to the caller.
+///
+/// # Errors
+///
+/// If the confirmation of a spent coin is not known or a lock time is not satisfied, reporting
+/// the first such input.
+///
+/// [BIP-68]: <https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki>
+pub fn check_sequence_locks<S>(
+    tx: &Transaction,
+    tip_height: BlockHeight,
+    tip_median_time_past: BlockMtp,
+    mut spent: S,
+) -> Result<(), SequenceLockError>
+where
+    S: FnMut(&OutPoint) -> Option<CoinConfirmation>,
+{
+    if tx.version < transaction::Version::TWO || tx.is_coinbase() {
+        return Ok(());
+    }
+    for (index, input) in tx.input.iter().enumerate() {
+        let lock_time = match input.sequence.to_relative_lock_time() {
+            Some(lock_time) => lock_time,
+            None => continue,
+        };
+        let coin = spent(&input.previous_output)
+            .ok_or(SequenceLockError::UnknownSpentOutput(input.previous_output))?;
+        match lock_time {
+            relative::LockTime::Blocks(blocks) => {
+                // The lock is satisfied by the block at height `coin.height + blocks`.
+                let elapsed = i64::from(tip_height.to_u32()) + 1 - i64::from(coin.height.to_u32());
+                let remaining = i64::from(blocks.to_height()) - elapsed;
+                if remaining > 0 {
+                    return Err(SequenceLockError::HeightLocked {
+                        index,
+                        lock_time: blocks,
+                        remaining: remaining as u32,
+                    });
+                }
+            }
+            relative::LockTime::Time(time) => {
+                let elapsed = i64::from(tip_median_time_past.to_u32())
+                    - i64::from(coin.median_time_past.to_u32());
+                let remaining = i64::from(time.to_seconds()) - elapsed;
+                if remaining > 0 {
+                    return Err(SequenceLockError::TimeLocked {
+                        index,
+                        lock_time: time,
+                        remaining: remaining as u32,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Error checking the relative lock times of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SequenceLockError {
+    /// The confirmation of the coin spent by an input is not known.
+    UnknownSpentOutput(OutPoint),
+    /// The block height based lock time of an input is not satisfied.
+    HeightLocked {
+        /// The index of the input.
+        index: usize,
+        /// The relative lock time of the input.
+        lock_time: NumberOfBlocks,
+        /// The number of blocks the chain tip has to advance by.
+        remaining: u32,
+    },
+    /// The time based lock time of an input is not satisfied.
+    TimeLocked {
+        /// The index of the input.
+        index: usize,
+        /// The relative lock time of the input.
+        lock_time: NumberOf512Seconds,
+        /// The number of seconds the median time past of the chain tip has to advance by.
+        remaining: u32,
+    },
+}
+
+impl From<Infallible> for SequenceLockError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl fmt::Display for SequenceLockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SequenceLockError::*;
+
+        match *self {
+            UnknownSpentOutput(ref outpoint) => write!(f, "unknown spent output {}", outpoint),
+            HeightLocked { index, lock_time, remaining } => write!(
+                f,
+                "input {} is locked for {} blocks, {} more blocks are required",
+                index, lock_time, remaining
+            ),
+            TimeLocked { index, lock_time, remaining } => write!(
+                f,
+                "input {} is locked for {} seconds, {} more seconds are required",
+                index,
+                lock_time.to_seconds(),
+                remaining
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SequenceLockError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlockHash, Version};
+    use crate::locktime::absolute;
+    use crate::pow::CompactTarget;
+    use crate::{Sequence, TxIn, TxMerkleNode, Txid};
+
+    #[test]
+    fn median_time_past() {
+        let mut tracker = MtpTracker::new();
+        assert_eq!(tracker.median_time_past(), None);
+
+        let header = |time| Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: BlockHash::from_byte_array([0; 32]),
+            merkle_root: TxMerkleNode::from_byte_array([0; 32]),
+            time: BlockTime::from_u32(time),
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        tracker.push(&header(100));
+        assert_eq!(tracker.median_time_past(), Some(BlockMtp::from_u32(100)));
+        tracker.extend(&[header(90), header(300)]);
+        assert_eq!(tracker.median_time_past(), Some(BlockMtp::from_u32(100)));
+
+        // Only the last eleven headers count.
+        let headers = (0..20).map(|i| header(1000 + i * 10)).collect::<Vec<_>>();
+        tracker.extend(&headers);
+        assert_eq!(tracker.len(), 11);
+        assert_eq!(tracker.median_time_past(), Some(BlockMtp::from_u32(1140)));
+    }
+
+    #[test]
+    fn sequence_locks() {
+        let outpoint = |vout| OutPoint { txid: Txid::from_byte_array([1; 32]), vout };
+        let input = |vout, sequence| TxIn {
+            previous_output: outpoint(vout),
+            sequence,
+            ..TxIn::EMPTY_COINBASE
+        };
+        let mut tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![
+                input(0, Sequence::ENABLE_LOCKTIME_NO_RBF),
+                input(1, Sequence::from_height(10)),
+                input(2, Sequence::from_512_second_intervals(2)),
+            ],
+            output: vec![],
+        };
+        let confirmation = CoinConfirmation {
+            height: BlockHeight::from_u32(100),
+            median_time_past: BlockMtp::from_u32(1_000_000),
+        };
+        let spent = |outpoint: &OutPoint| Some(confirmation).filter(|_| outpoint.vout < 3);
+
+        let (height, mtp) = (BlockHeight::from_u32(109), BlockMtp::from_u32(1_001_024));
+        assert_eq!(check_sequence_locks(&tx, height, mtp, spent), Ok(()));
+
+        let err = check_sequence_locks(&tx, BlockHeight::from_u32(107), mtp, spent).unwrap_err();
+        assert_eq!(
+            err,
+            SequenceLockError::HeightLocked {
+                index: 1,
+                lock_time: NumberOfBlocks::from_height(10),
+                remaining: 2,
+            }
+        );
+        let err = check_sequence_locks(&tx, height, BlockMtp::from_u32(1_001_000), spent);
+        assert_eq!(
+            err,
+            Err(SequenceLockError::TimeLocked {
+                index: 2,
+                lock_time: NumberOf512Seconds::from_512_second_intervals(2),
+                remaining: 24,
+            })
+        );
+
+        // Version 1 transactions are not relatively locked.
+        tx.version = transaction::Version::ONE;
+        assert_eq!(check_sequence_locks(&tx, BlockHeight::from_u32(100), mtp, spent), Ok(()));
+
+        tx.version = transaction::Version::TWO;
+        tx.input.push(input(3, Sequence::ZERO));
+        assert_eq!(
+            check_sequence_locks(&tx, height, mtp, spent),
+            Err(SequenceLockError::UnknownSpentOutput(outpoint(3)))
+        );
+    }
+}
@@ -0,0 +1,630 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bitcoin Core UTXO set snapshots.
+//!
+//! Bitcoin Core's `dumptxoutset` writes the UTXO set at a block to a snapshot file, which
+//! `loadtxoutset` loads to use the node before it validated the chain up to that block
+//! ("assumeutxo"). A snapshot starts with a header identifying the network and the base block,
+//! followed by the coins grouped by transaction, with compressed amounts and scripts.
+//!
+//! Snapshots are only accepted for the base blocks listed in [`Params::assumeutxo`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")] {
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! use bitcoin::utxo_snapshot::SnapshotReader;
+//! use bitcoin::Network;
+//!
+//! let file = BufReader::new(File::open("utxo-840000.dat")?);
+//! let reader = SnapshotReader::new(bitcoin::io::from_std(file), Network::Bitcoin)
+//!     .expect("valid snapshot header");
+//! println!("{} coins at block {}", reader.coins_count(), reader.base_blockhash());
+//! for coin in reader {
+//!     let coin = coin.expect("valid coin");
+//!     println!("{}: {}", coin.outpoint, coin.txout.value);
+//! }
+//! # }
+//! # Ok::<_, std::io::Error>(())
+//! ```
+
+use core::convert::Infallible;
+use core::fmt;
+
+use internals::write_err;
+use io::{BufRead, Write};
+
+use crate::block::{BlockHash, BlockHeight};
+#[doc(inline)]
+pub use crate::block_validation::Coin;
+use crate::consensus::encode::{self, Decodable, Encodable, ReadExt as _, WriteExt as _};
+use crate::consensus::{compress, ParseError};
+use crate::network::params::{AssumeUtxoData, Params};
+use crate::network::CustomNetwork;
+use crate::p2p::Magic;
+use crate::prelude::Vec;
+use crate::{OutPoint, Txid};
+
+/// The magic bytes snapshot files start with.
+pub const SNAPSHOT_MAGIC: [u8; 5] = *b"utxo\xff";
+
+/// The version of the snapshot format supported by this module.
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+/// Reads the coins of a snapshot.
+///
+/// The header is read and verified on construction, the coins are then yielded in file order.
+#[derive(Debug)]
+pub struct SnapshotReader<R> {
+    inner: R,
+    base: AssumeUtxoData,
+    coins_count: u64,
+    coins_left: u64,
+    txid: Txid,
+    group_left: u64,
+    finished: bool,
+}
+
+impl<R: BufRead> SnapshotReader<R> {
+    /// Reads the header of a snapshot for the network of `params`.
+    ///
+    /// # Errors
+    ///
+    /// If the header is invalid, is for another network or the base block is not one of
+    /// [`Params::assumeutxo`].
+    pub fn new(reader: R, params: impl AsRef<Params>) -> Result<Self, Error> {
+        let params = params.as_ref();
+        Self::with_magic(reader, params, Magic::from_params(params))
+    }
+
+    /// Reads the header of a snapshot for the custom `network`.
+    ///
+    /// # Errors
+    ///
+    /// If the header is invalid, is for another network or the base block is not one of the
+    /// [`Params::assumeutxo`] of `network`.
+    pub fn new_custom(reader: R, network: &CustomNetwork) -> Result<Self, Error> {
+        Self::with_magic(reader, &network.params, network.magic)
+    }
+
+    fn with_magic(mut reader: R, params: &Params, expected: Magic) -> Result<Self, Error> {
+        let mut magic = [0; 5];
+        reader.read_slice(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+        let version = reader.read_u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let mut network_magic = [0; 4];
+        reader.read_slice(&mut network_magic)?;
+        let actual = Magic::from_bytes(network_magic);
+        if actual != expected {
+            return Err(Error::NetworkMismatch { expected, actual });
+        }
+        let base_blockhash = BlockHash::consensus_decode(&mut reader)?;
+        let base = *params
+            .assumeutxo_for_blockhash(base_blockhash)
+            .ok_or(Error::UnknownBaseBlock(base_blockhash))?;
+        let coins_count = reader.read_u64()?;
+
+        Ok(SnapshotReader {
+            inner: reader,
+            base,
+            coins_count,
+            coins_left: coins_count,
+            txid: Txid::from_byte_array([0; 32]),
+            group_left: 0,
+            finished: false,
+        })
+    }
+
+    /// Returns the hash of the block the snapshot is the UTXO set of.
+    pub fn base_blockhash(&self) -> BlockHash { self.base.blockhash }
+
+    /// Returns the height of the block the snapshot is the UTXO set of.
+    pub fn base_height(&self) -> BlockHeight { self.base.height }
+
+    /// Returns the number of coins in the snapshot.
+    pub fn coins_count(&self) -> u64 { self.coins_count }
+
+    /// Reads the next coin, returns `None` after the last one.
+    ///
+    /// # Errors
+    ///
+    /// If a coin is invalid, or if there is data after the last coin.
+    pub fn next_coin(&mut self) -> Result<Option<Coin>, Error> {
+        if self.coins_left == 0 {
+            if !self.finished {
+                self.finished = true;
+                if !self.inner.fill_buf().map_err(encode::Error::from)?.is_empty() {
+                    return Err(Error::TrailingData);
+                }
+            }
+            return Ok(None);
+        }
+        while self.group_left == 0 {
+            self.txid = Txid::consensus_decode(&mut self.inner)?;
+            self.group_left = self.inner.read_compact_size()?;
+            if self.group_left > self.coins_left {
+                return Err(Error::InvalidCoinCount);
+            }
+        }
+
+        let vout = u32::try_from(self.inner.read_compact_size()?)
+            .ok()
+            .filter(|&vout| vout != u32::MAX)
+            .ok_or(ParseError::ParseFailed("output index out of range"))
+            .map_err(encode::Error::from)?;
+        let code = compress::read_varint(&mut self.inner)?;
+        let height = u32::try_from(code >> 1)
+            .ok()
+            .filter(|&height| height <= self.base.height.to_u32())
+            .ok_or(ParseError::ParseFailed("coin height above the snapshot base block"))
+            .map_err(encode::Error::from)?;
+        let txout = compress::read_compressed_txout(&mut self.inner)?;
+
+        self.group_left -= 1;
+        self.coins_left -= 1;
+        Ok(Some(Coin {
+            outpoint: OutPoint { txid: self.txid, vout },
+            txout,
+            height: BlockHeight::from_u32(height),
+            is_coinbase: code & 1 == 1,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for SnapshotReader<R> {
+    type Item = Result<Coin, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> { self.next_coin().transpose() }
+}
+
+/// Writes a snapshot.
+///
+/// Consecutive coins of the same transaction are grouped, so coins should be written ordered by
+/// outpoint, as in the snapshots written by Bitcoin Core.
+#[derive(Debug)]
+pub struct SnapshotWriter<W> {
+    inner: W,
+    coins_count: u64,
+    coins_written: u64,
+    group: Vec<Coin>,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    /// Writes the header of a snapshot of `coins_count` coins, for the network of `params`, at
+    /// the block `base_blockhash`.
+    pub fn new(
+        writer: W,
+        params: impl AsRef<Params>,
+        base_blockhash: BlockHash,
+        coins_count: u64,
+    ) -> Result<Self, io::Error> {
+        Self::with_magic(writer, Magic::from_params(params), base_blockhash, coins_count)
+    }
+
+    /// Writes the header of a snapshot of `coins_count` coins, for the custom `network`, at the
+    /// block `base_blockhash`.
+    pub fn new_custom(
+        writer: W,
+        network: &CustomNetwork,
+        base_blockhash: BlockHash,
+        coins_count: u64,
+    ) -> Result<Self, io::Error> {
+        Self::with_magic(writer, network.magic, base_blockhash, coins_count)
+    }
+
+    fn with_magic(
+        mut writer: W,
+        magic: Magic,
+        base_blockhash: BlockHash,
+        coins_count: u64,
+    ) -> Result<Self, io::Error> {
+        writer.emit_slice(&SNAPSHOT_MAGIC)?;
+        writer.emit_u16(SNAPSHOT_VERSION)?;
+        writer.emit_slice(&magic.to_bytes())?;
+        base_blockhash.consensus_encode(&mut writer)?;
+        writer.emit_u64(coins_count)?;
+        Ok(SnapshotWriter { inner: writer, coins_count, coins_written: 0, group: Vec::new() })
+    }
+
+    /// Writes a coin.
+    ///
+    /// # Errors
+    ///
+    /// If more coins than announced in the header are written.
+    pub fn write_coin(&mut self, coin: Coin) -> Result<(), WriteError> {
+        if self.coins_written == self.coins_count {
+            return Err(WriteError::CoinCountMismatch {
+                expected: self.coins_count,
+                actual: self.coins_written + 1,
+            });
+        }
+        if self.group.first().map_or(false, |first| first.outpoint.txid != coin.outpoint.txid) {
+            self.write_group()?;
+        }
+        self.group.push(coin);
+        self.coins_written += 1;
+        Ok(())
+    }
+
+    /// Writes the buffered coins and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// If fewer coins than announced in the header were written.
+    pub fn finish(mut self) -> Result<W, WriteError> {
+        self.write_group()?;
+        if self.coins_written != self.coins_count {
+            return Err(WriteError::CoinCountMismatch {
+                expected: self.coins_count,
+                actual: self.coins_written,
+            });
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Writes the coins of the current transaction.
+    fn write_group(&mut self) -> Result<(), io::Error> {
+        let first = match self.group.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        first.outpoint.txid.consensus_encode(&mut self.inner)?;
+        self.inner.emit_compact_size(self.group.len())?;
+        for coin in &self.group {
+            let height = u64::from(coin.height.to_u32());
+            self.inner.emit_compact_size(coin.outpoint.vout)?;
+            compress::emit_varint(&mut self.inner, height * 2 + u64::from(coin.is_coinbase))?;
+            compress::emit_compressed_txout(&mut self.inner, &coin.txout)?;
+        }
+        self.group.clear();
+        Ok(())
+    }
+}
+
+/// Error reading a snapshot.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// I/O error or invalid encoding of a coin.
+    Decode(encode::Error),
+    /// The file does not start with [`SNAPSHOT_MAGIC`].
+    InvalidMagic,
+    /// The snapshot format version is not [`SNAPSHOT_VERSION`].
+    UnsupportedVersion(u16),
+    /// The snapshot is for another network.
+    NetworkMismatch {
+        /// The magic of the expected network.
+        expected: Magic,
+        /// The magic in the snapshot.
+        actual: Magic,
+    },
+    /// The base block is not one of [`Params::assumeutxo`].
+    UnknownBaseBlock(BlockHash),
+    /// A transaction has more coins than are left in the snapshot.
+    InvalidCoinCount,
+    /// There is data after the last coin.
+    TrailingData,
+}
+
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl From<encode::Error> for Error {
+    fn from(e: encode::Error) -> Self { Self::Decode(e) }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            Decode(ref e) => write_err!(f, "invalid snapshot data"; e),
+            InvalidMagic => f.write_str("not a UTXO snapshot, invalid magic bytes"),
+            UnsupportedVersion(version) => write!(f, "unsupported snapshot version {}", version),
+            NetworkMismatch { expected, actual } =>
+                write!(f, "snapshot network magic {} does not match {}", actual, expected),
+            UnknownBaseBlock(hash) => write!(f, "unknown snapshot base block {}", hash),
+            InvalidCoinCount =>
+                f.write_str("transaction has more coins than are left in the snapshot"),
+            TrailingData => f.write_str("data after the last coin of the snapshot"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            Decode(ref e) => Some(e),
+            InvalidMagic
+            | UnsupportedVersion(_)
+            | NetworkMismatch { .. }
+            | UnknownBaseBlock(_)
+            | InvalidCoinCount
+            | TrailingData => None,
+        }
+    }
+}
+
+/// Error writing a snapshot.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WriteError {
+    /// I/O error.
+    Io(io::Error),
+    /// The number of coins written does not match the count in the header.
+    CoinCountMismatch {
+        /// The number of coins in the header.
+        expected: u64,
+        /// The number of coins written.
+        actual: u64,
+    },
+}
+
+impl From<Infallible> for WriteError {
+    fn from(never: Infallible) -> Self { match never {} }
+}
+
+impl From<io::Error> for WriteError {
+    fn from(e: io::Error) -> Self { Self::Io(e) }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use WriteError::*;
+
+        match *self {
+            Io(ref e) => write_err!(f, "I/O error"; e),
+            CoinCountMismatch { expected, actual } =>
+                write!(f, "wrote {} coins but the snapshot header has {}", actual, expected),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use WriteError::*;
+
+        match *self {
+            Io(ref e) => Some(e),
+            CoinCountMismatch { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_lit::hex;
+
+    use super::*;
+    use crate::script::ScriptBufExt as _;
+    use crate::{Amount, ScriptBuf, TxOut};
+
+    const BASE: AssumeUtxoData = AssumeUtxoData {
+        height: BlockHeight::from_u32(110),
+        blockhash: BlockHash::from_byte_array([1; 32]),
+    };
+
+    fn params() -> Params {
+        let mut params = Params::REGTEST;
+        params.assumeutxo = &[BASE];
+        params
+    }
+
+    fn coin(txid: u8, vout: u32, height: u32) -> Coin {
+        Coin {
+            outpoint: OutPoint { txid: Txid::from_byte_array([txid; 32]), vout },
+            txout: TxOut {
+                value: Amount::from_sat_u32(1_000 * vout),
+                script_pubkey: ScriptBuf::from_bytes(
+                    hex!("a914000102030405060708090a0b0c0d0e0f1011121387").to_vec(),
+                ),
+            },
+            height: BlockHeight::from_u32(height),
+            is_coinbase: vout == 0,
+        }
+    }
+
+    fn snapshot(coins: &[Coin]) -> Vec<u8> {
+        let mut writer =
+            SnapshotWriter::new(Vec::new(), params(), BASE.blockhash, coins.len() as u64).unwrap();
+        for coin in coins {
+            writer.write_coin(coin.clone()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let coins = [coin(2, 0, 100), coin(2, 3, 100), coin(3, 1, 110)];
+        let data = snapshot(&coins);
+        // Magic, version, regtest magic, base block hash and coin count.
+        assert_eq!(data[..11], hex!("7574786fff0200fabfb5da"));
+        assert_eq!(data[11..43], [1; 32]);
+        assert_eq!(data[43..51], hex!("0300000000000000"));
+        // First transaction with two coins.
+        assert_eq!(data[51..83], [2; 32]);
+        assert_eq!(data[83], 2);
+
+        let reader = SnapshotReader::new(&data[..], params()).unwrap();
+        assert_eq!(reader.base_blockhash(), BASE.blockhash);
+        assert_eq!(reader.base_height(), BASE.height);
+        assert_eq!(reader.coins_count(), 3);
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), coins);
+    }
+
+    #[test]
+    fn custom_signet() {
+        let mut network = CustomNetwork::signet(ScriptBuf::from_bytes(vec![0x51]));
+        network.params.assumeutxo = &[BASE];
+        let coins = [coin(2, 0, 100), coin(3, 1, 110)];
+        let mut writer =
+            SnapshotWriter::new_custom(Vec::new(), &network, BASE.blockhash, 2).unwrap();
+        for coin in coins.iter().cloned() {
+            writer.write_coin(coin).unwrap();
+        }
+        let data = writer.finish().unwrap();
+        assert_eq!(data[7..11], network.magic.to_bytes());
+
+        let reader = SnapshotReader::new_custom(&data[..], &network).unwrap();
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), coins);
+
+        // The base network of the custom signet has another magic.
+        assert!(matches!(
+            SnapshotReader::new(&data[..], &network.params),
+            Err(Error::NetworkMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_snapshot() {
+        let data = snapshot(&[coin(2, 0, 100)]);
+
+        let err = SnapshotReader::new(&data[..], Params::SIGNET).unwrap_err();
+        assert!(matches!(err, Error::NetworkMismatch { .. }));
+        let err = SnapshotReader::new(&data[..], Params::REGTEST).unwrap_err();
+        assert!(matches!(err, Error::UnknownBaseBlock(hash) if hash == BASE.blockhash));
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        let mut reader = SnapshotReader::new(&trailing[..], params()).unwrap();
+        assert!(reader.next_coin().unwrap().is_some());
+        assert!(matches!(reader.next_coin(), Err(Error::TrailingData)));
+
+        let mut reader = SnapshotReader::new(&data[..data.len() - 1], params()).unwrap();
+        assert!(matches!(reader.next_coin(), Err(Error::Decode(_))));
+
+        let data = snapshot(&[coin(2, 0, 111)]);
+        let mut reader = SnapshotReader::new(&data[..], params()).unwrap();
+        assert!(matches!(reader.next_coin(), Err(Error::Decode(_))));
+
+        let mut writer = SnapshotWriter::new(Vec::new(), params(), BASE.blockhash, 2).unwrap();
+        writer.write_coin(coin(2, 0, 100)).unwrap();
+        let err = writer.finish().unwrap_err();
+        assert!(matches!(err, WriteError::CoinCountMismatch { expected: 2, actual: 1 }));
+    }
+
+    #[test]
+    fn core_snapshot() {
+        // Serialized with Bitcoin Core's `SnapshotMetadata` and `Coin` code as `dumptxoutset` does,
+        // grouped by transaction in the order of its coins database, for the regtest assumeutxo
+        // block at height 110.
+        let data = include_bytes!("../tests/data/utxo_snapshot_regtest_110.dat");
+        let coin = |txid: &str, vout, sat, script: &str, height, is_coinbase| Coin {
+            outpoint: OutPoint { txid: txid.parse().unwrap(), vout },
+            txout: TxOut {
+                value: Amount::from_sat(sat).unwrap(),
+                script_pubkey: ScriptBuf::from_hex_no_length_prefix(script).unwrap(),
+            },
+            height: BlockHeight::from_u32(height),
+            is_coinbase,
+        };
+        let tx = "5d2f6c1b8e9a7d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4";
+        let coins = [
+            // Compressed and uncompressed P2PK coinbase outputs.
+            coin(
+                "3f1c5e4fb2d0a6c7e8b9a0d1c2b3a4958677869504132231f0e1d2c3b4a59687",
+                0,
+                5_000_000_000,
+                "210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+                1,
+                true,
+            ),
+            coin(
+                "a0b1c2d3e4f5061728394a5b6c7d8e9fa0b1c2d3e4f5061728394a5b6c7d8e9f",
+                0,
+                2_500_000_000,
+                "410479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8ac",
+                110,
+                true,
+            ),
+            // P2PKH, P2SH, P2WPKH and P2TR outputs of one transaction.
+            coin(tx, 0, 123_456_789, "76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac", 105, false),
+            coin(tx, 1, 1_000, "a914748284390f9e263a4b766a75d0633c50426eb87587", 105, false),
+            coin(tx, 3, 99_999, "00140000000000000000000000000000000000000000", 105, false),
+            coin(
+                tx,
+                4,
+                330,
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                105,
+                false,
+            ),
+        ];
+
+        let reader = SnapshotReader::new(&data[..], Params::REGTEST).unwrap();
+        assert_eq!(reader.base_height(), BlockHeight::from_u32(110));
+        assert_eq!(
+            reader.base_blockhash(),
+            "135eec25a6fb277884e5824e7aa7d052c4868161c99a5122170b5266f86c273d".parse().unwrap()
+        );
+        assert_eq!(reader.coins_count(), 6);
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), coins);
+
+        let mut writer = SnapshotWriter::new(
+            Vec::new(),
+            Params::REGTEST,
+            Params::REGTEST.assumeutxo[0].blockhash,
+            6,
+        )
+        .unwrap();
+        for coin in coins {
+            writer.write_coin(coin).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn mainnet_assumeutxo() {
+        let data = Params::MAINNET
+            .assumeutxo_for_blockhash(
+                "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5".parse().unwrap(),
+            )
+            .unwrap();
+        assert_eq!(data.height, BlockHeight::from_u32(840_000));
+    }
+
+    #[test]
+    fn test_network_assumeutxo() {
+        let cases = [
+            (
+                Params::TESTNET3,
+                "0000000000000093bcb68c03a9a168ae252572d348a2eaeba2cdf9231d73206f",
+                2_500_000,
+            ),
+            (
+                Params::TESTNET4,
+                "000000000bd2317e51b3c5794981c35ba894ce27d3e772d5c39ecd9cbce01dc8",
+                120_000,
+            ),
+            (
+                Params::SIGNET,
+                "0000003ca3c99aff040f2563c2ad8f8ec88bd0fd6b8f0895cfaf1ef90353a62c",
+                160_000,
+            ),
+            (
+                Params::REGTEST,
+                "385901ccbd69dff6bbd00065d01fb8a9e464dede7cfe0372443884f9b1dcf6b9",
+                200,
+            ),
+        ];
+        for (params, blockhash, height) in cases {
+            let data = params.assumeutxo_for_blockhash(blockhash.parse().unwrap()).unwrap();
+            assert_eq!(data.height, BlockHeight::from_u32(height));
+            assert!(Params::MAINNET.assumeutxo_for_blockhash(data.blockhash).is_none());
+        }
+    }
+}
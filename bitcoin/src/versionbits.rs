@@ -0,0 +1,386 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! [BIP-9] version bits soft fork deployments.
+//!
+//! Miners signal readiness for a soft fork [`Deployment`] by setting its bit in the block
+//! version. The state of a deployment changes at the start of each period of
+//! [`Params::miner_confirmation_window`] blocks, depending on the median time past and on the
+//! number of blocks which signalled in the previous period, see [`DeploymentTracker`].
+//!
+//! # Examples
+//!
+//! ```
+//! use bitcoin::block::Version;
+//! use bitcoin::network::Params;
+//! use bitcoin::versionbits::{DeploymentTracker, ThresholdState};
+//! use bitcoin::BlockMtp;
+//!
+//! let params = Params::REGTEST;
+//! let deployment = params.deployment("testdummy").expect("regtest has a test deployment");
+//! let mut tracker = DeploymentTracker::new(*deployment, &params);
+//! let signalling = Version::from_consensus((0x2000_0000 | deployment.mask()) as i32);
+//!
+//! // All the blocks of the first two periods signal, one every 10 minutes.
+//! let median_time_past = |height: u32| BlockMtp::from_u32(1_700_000_000 + height * 600);
+//! for height in 1..288 {
+//!     tracker.connect(signalling, median_time_past(height));
+//! }
+//! assert_eq!(tracker.state(), ThresholdState::LockedIn);
+//!
+//! // The deployment activates one period after it is locked in.
+//! for height in 288..432 {
+//!     tracker.connect(Version::NO_SOFT_FORK_SIGNALLING, median_time_past(height));
+//! }
+//! assert_eq!(tracker.state(), ThresholdState::Active);
+//! ```
+//!
+//! [BIP-9]: <https://github.com/bitcoin/bips/blob/master/bip-0009.mediawiki>
+
+use core::fmt;
+
+use units::{BlockHeight, BlockMtp};
+
+use crate::block::Version;
+use crate::network::Params;
+
+/// A soft fork deployed with [BIP-9] version bits, Bitcoin Core's `BIP9Deployment`.
+///
+/// [BIP-9]: <https://github.com/bitcoin/bips/blob/master/bip-0009.mediawiki>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Deployment {
+    name: &'static str,
+    bit: u8,
+    start_time: i64,
+    timeout: i64,
+    min_activation_height: BlockHeight,
+}
+
+impl Deployment {
+    /// The [`start_time`](Self::start_time) of deployments which are always active.
+    pub const ALWAYS_ACTIVE: i64 = -1;
+
+    /// The [`start_time`](Self::start_time) of deployments which are disabled.
+    pub const NEVER_ACTIVE: i64 = -2;
+
+    /// The [`timeout`](Self::timeout) of deployments which don't time out.
+    pub const NO_TIMEOUT: i64 = i64::MAX;
+
+    /// The number of version bits available for deployments.
+    ///
+    /// The top three bits of the version are set to `001` to signal, leaving the 29 lower bits.
+    pub const BITS: u8 = 29;
+
+    /// Constructs a new deployment.
+    ///
+    /// # Errors
+    ///
+    /// If `bit` is not one of the [`Deployment::BITS`] lower bits of the version.
+    pub const fn new(
+        name: &'static str,
+        bit: u8,
+        start_time: i64,
+        timeout: i64,
+        min_activation_height: BlockHeight,
+    ) -> Result<Self, InvalidBitError> {
+        if bit >= Self::BITS {
+            return Err(InvalidBitError { bit });
+        }
+        Ok(Deployment { name, bit, start_time, timeout, min_activation_height })
+    }
+
+    /// Returns the name of the deployment, as used by Bitcoin Core's `getdeploymentinfo`.
+    pub const fn name(&self) -> &'static str { self.name }
+
+    /// Returns the version bit miners set to signal for the deployment.
+    pub const fn bit(&self) -> u8 { self.bit }
+
+    /// Returns the median time past from which miners may signal, or
+    /// [`Deployment::ALWAYS_ACTIVE`] or [`Deployment::NEVER_ACTIVE`].
+    pub const fn start_time(&self) -> i64 { self.start_time }
+
+    /// Returns the median time past after which the deployment fails if it is not locked in, or
+    /// [`Deployment::NO_TIMEOUT`].
+    pub const fn timeout(&self) -> i64 { self.timeout }
+
+    /// Returns the height before which the deployment can't activate even if it is locked in.
+    pub const fn min_activation_height(&self) -> BlockHeight { self.min_activation_height }
+
+    /// Returns the mask of the version bit of the deployment.
+    pub const fn mask(&self) -> u32 { 1 << self.bit }
+
+    /// Returns whether a block with `version` signals for the deployment.
+    pub fn is_signalled_by(&self, version: Version) -> bool {
+        version.is_signalling_soft_fork(self.bit)
+    }
+}
+
+/// The state of a [`Deployment`], the same for all blocks of a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThresholdState {
+    /// The deployment has not started yet, the initial state.
+    Defined,
+    /// Miners signal for the deployment.
+    Started,
+    /// Enough miners signalled, the deployment activates in the next period.
+    LockedIn,
+    /// The deployment is active, a final state.
+    Active,
+    /// The deployment timed out before being locked in, a final state.
+    Failed,
+}
+
+impl fmt::Display for ThresholdState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ThresholdState::*;
+
+        // The names used by Bitcoin Core's RPCs.
+        let s = match *self {
+            Defined => "defined",
+            Started => "started",
+            LockedIn => "locked_in",
+            Active => "active",
+            Failed => "failed",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Signalling statistics of the current period, Bitcoin Core's `BIP9Stats`.
+///
+/// Only relevant while the deployment is [`ThresholdState::Started`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Statistics {
+    /// The number of blocks in a period.
+    pub period: u32,
+    /// The number of signalling blocks required to lock in the deployment.
+    pub threshold: u32,
+    /// The number of blocks of the period connected so far.
+    pub elapsed: u32,
+    /// The number of blocks of the period connected so far which signalled.
+    pub count: u32,
+    /// Whether the threshold can still be reached in the period.
+    pub possible: bool,
+}
+
+/// Tracks the state of a [`Deployment`] as the blocks of a chain are connected.
+///
+/// The tracker starts at the genesis block and must be fed every block of the chain, in order.
+#[derive(Debug, Clone)]
+pub struct DeploymentTracker {
+    deployment: Deployment,
+    period: u32,
+    threshold: u32,
+    /// The height of the last connected block.
+    height: BlockHeight,
+    /// The state of the next block.
+    state: ThresholdState,
+    since: BlockHeight,
+    elapsed: u32,
+    count: u32,
+}
+
+impl DeploymentTracker {
+    /// Constructs a new tracker of `deployment` for a chain consisting of the genesis block.
+    pub fn new(deployment: Deployment, params: impl AsRef<Params>) -> Self {
+        let params = params.as_ref();
+        let state = match deployment.start_time {
+            Deployment::ALWAYS_ACTIVE => ThresholdState::Active,
+            Deployment::NEVER_ACTIVE => ThresholdState::Failed,
+            _ => ThresholdState::Defined,
+        };
+        DeploymentTracker {
+            deployment,
+            period: params.miner_confirmation_window.to_u32(),
+            threshold: params.rule_change_activation_threshold.to_u32(),
+            height: BlockHeight::ZERO,
+            state,
+            since: BlockHeight::ZERO,
+            // The genesis block is part of the first period.
+            elapsed: 1,
+            count: 0,
+        }
+    }
+
+    /// Returns the tracked deployment.
+    pub fn deployment(&self) -> &Deployment { &self.deployment }
+
+    /// Returns the height of the last connected block.
+    pub fn height(&self) -> BlockHeight { self.height }
+
+    /// Returns the state of the deployment for the next block.
+    pub fn state(&self) -> ThresholdState { self.state }
+
+    /// Returns the height of the first block in the current state.
+    pub fn state_since(&self) -> BlockHeight { self.since }
+
+    /// Returns the signalling statistics of the period of the next block.
+    pub fn statistics(&self) -> Statistics {
+        let elapsed = if self.elapsed == self.period { 0 } else { self.elapsed };
+        let count = if self.elapsed == self.period { 0 } else { self.count };
+        Statistics {
+            period: self.period,
+            threshold: self.threshold,
+            elapsed,
+            count,
+            possible: self.period - self.threshold >= elapsed - count,
+        }
+    }
+
+    /// Connects the next block of the chain, with `version` and `median_time_past`.
+    ///
+    /// `median_time_past` is the median time past of the connected block, i.e. including its own
+    /// timestamp.
+    pub fn connect(&mut self, version: Version, median_time_past: BlockMtp) {
+        self.height = BlockHeight::from_u32(self.height.to_u32() + 1);
+        if self.elapsed == self.period {
+            self.elapsed = 0;
+            self.count = 0;
+        }
+        self.elapsed += 1;
+        if self.deployment.is_signalled_by(version) {
+            self.count += 1;
+        }
+        if self.elapsed < self.period {
+            return;
+        }
+
+        // The last block of a period determines the state of the next one.
+        let time = i64::from(median_time_past.to_u32());
+        let next_height = self.height.to_u32() + 1;
+        let next = match self.state {
+            ThresholdState::Defined if time >= self.deployment.start_time =>
+                ThresholdState::Started,
+            ThresholdState::Started if self.count >= self.threshold => ThresholdState::LockedIn,
+            ThresholdState::Started if time >= self.deployment.timeout => ThresholdState::Failed,
+            ThresholdState::LockedIn
+                if next_height >= self.deployment.min_activation_height.to_u32() =>
+                ThresholdState::Active,
+            state => state,
+        };
+        if next != self.state {
+            self.state = next;
+            self.since = BlockHeight::from_u32(next_height);
+        }
+    }
+}
+
+/// The version bit of a [`Deployment`] is not available for BIP-9 deployments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InvalidBitError {
+    /// The invalid bit.
+    pub bit: u8,
+}
+
+impl fmt::Display for InvalidBitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "version bit {} is not below {}", self.bit, Deployment::BITS)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidBitError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment(min_activation_height: u32) -> Deployment {
+        let height = BlockHeight::from_u32(min_activation_height);
+        Deployment::new("test", 3, 1_000_000, 2_000_000, height).unwrap()
+    }
+
+    /// Connects a period of blocks, `signalling` of which signal, all with `median_time_past`.
+    fn connect_period(tracker: &mut DeploymentTracker, signalling: u32, median_time_past: u32) {
+        let blocks = tracker.period - tracker.statistics().elapsed;
+        for i in 0..blocks {
+            let version = if i < signalling {
+                Version::from_consensus(0x2000_0008)
+            } else {
+                Version::NO_SOFT_FORK_SIGNALLING
+            };
+            tracker.connect(version, BlockMtp::from_u32(median_time_past));
+        }
+    }
+
+    #[test]
+    fn activation() {
+        let params = Params::REGTEST;
+        let mut tracker = DeploymentTracker::new(deployment(0), &params);
+        assert_eq!(tracker.state(), ThresholdState::Defined);
+
+        // The first period includes the genesis block.
+        connect_period(&mut tracker, 144, 999_999);
+        assert_eq!(tracker.height(), BlockHeight::from_u32(143));
+        assert_eq!(tracker.state(), ThresholdState::Defined);
+
+        connect_period(&mut tracker, 0, 1_000_000);
+        assert_eq!(tracker.state(), ThresholdState::Started);
+        assert_eq!(tracker.state_since(), BlockHeight::from_u32(288));
+
+        for _ in 0..35 {
+            tracker.connect(Version::NO_SOFT_FORK_SIGNALLING, BlockMtp::from_u32(1_000_000));
+        }
+        // Signalling without the version bits top bits doesn't count.
+        tracker.connect(Version::from_consensus(0x0000_0008), BlockMtp::from_u32(1_000_000));
+        let stats = tracker.statistics();
+        assert_eq!(
+            stats,
+            Statistics { period: 144, threshold: 108, elapsed: 36, count: 0, possible: true }
+        );
+        tracker.connect(Version::NO_SOFT_FORK_SIGNALLING, BlockMtp::from_u32(1_000_000));
+        assert!(!tracker.statistics().possible);
+        connect_period(&mut tracker, 144, 1_000_000);
+        assert_eq!(tracker.state(), ThresholdState::Started);
+
+        connect_period(&mut tracker, 108, 1_000_000);
+        assert_eq!(tracker.state(), ThresholdState::LockedIn);
+        connect_period(&mut tracker, 0, 1_000_000);
+        assert_eq!(tracker.state(), ThresholdState::Active);
+        assert_eq!(tracker.state_since(), BlockHeight::from_u32(720));
+        connect_period(&mut tracker, 0, 3_000_000);
+        assert_eq!(tracker.state(), ThresholdState::Active);
+    }
+
+    #[test]
+    fn timeout_and_min_activation_height() {
+        let params = Params::REGTEST;
+        let mut tracker = DeploymentTracker::new(deployment(0), &params);
+        connect_period(&mut tracker, 0, 1_500_000);
+        // Signalling in the period the deployment times out still locks it in.
+        connect_period(&mut tracker, 108, 2_000_000);
+        assert_eq!(tracker.state(), ThresholdState::LockedIn);
+
+        let mut tracker = DeploymentTracker::new(deployment(0), &params);
+        connect_period(&mut tracker, 0, 1_500_000);
+        connect_period(&mut tracker, 107, 2_000_000);
+        assert_eq!(tracker.state(), ThresholdState::Failed);
+
+        let mut tracker = DeploymentTracker::new(deployment(500), &params);
+        connect_period(&mut tracker, 0, 1_500_000);
+        connect_period(&mut tracker, 144, 1_500_000);
+        connect_period(&mut tracker, 0, 1_500_000);
+        assert_eq!(tracker.state(), ThresholdState::LockedIn);
+        connect_period(&mut tracker, 0, 1_500_000);
+        assert_eq!(tracker.state(), ThresholdState::Active);
+        assert_eq!(tracker.state_since(), BlockHeight::from_u32(576));
+
+        let taproot = Params::SIGNET.deployment("taproot").unwrap();
+        let tracker = DeploymentTracker::new(*taproot, &params);
+        assert_eq!(tracker.state(), ThresholdState::Active);
+        let testdummy = Params::MAINNET.deployment("testdummy").unwrap();
+        let tracker = DeploymentTracker::new(*testdummy, &params);
+        assert_eq!(tracker.state(), ThresholdState::Failed);
+    }
+
+    #[test]
+    fn version_bits() {
+        let deployment = Deployment::new("test", 28, 0, Deployment::NO_TIMEOUT, BlockHeight::ZERO);
+        assert_eq!(deployment.unwrap().mask(), 0x1000_0000);
+        for bit in [29, 31, 32, 255] {
+            let deployment =
+                Deployment::new("test", bit, 0, Deployment::NO_TIMEOUT, BlockHeight::ZERO);
+            assert_eq!(deployment, Err(InvalidBitError { bit }));
+        }
+    }
+}
@@ -113,6 +113,16 @@ fn bip174_psbt_workflow() {
     combine_lexicographically();
 }
 
+/// Checks [`Psbt::finalize`] against the reference finalizer of this file.
+#[test]
+fn finalize_matches_reference_finalizer() {
+    let combined = hex_psbt(include_str!("data/combine_psbt_hex"));
+
+    let mut finalized = combined.clone();
+    finalized.finalize().unwrap();
+    assert_eq!(finalized, finalize_psbt(combined));
+}
+
 /// Attempts to build an extended private key from seed and also directly from a string.
 fn build_extended_private_key() -> Xpriv {
     // Strings from BIP 174 test vector.
@@ -7,7 +7,8 @@
 use bitcoin::opcodes::all::OP_CHECKSIG;
 use bitcoin::psbt::{GetKey, Input, KeyRequest, PsbtSighashType, SignError};
 use bitcoin::script::ScriptExt as _;
-use bitcoin::taproot::{LeafVersion, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::sighash::TapSighashType;
+use bitcoin::taproot::{self, LeafVersion, TaprootBuilder, TaprootSpendInfo};
 use bitcoin::transaction::Version;
 use bitcoin::{
     absolute, script, Address, Network, OutPoint, PrivateKey, Psbt, ScriptBuf, Sequence,
@@ -167,6 +168,61 @@ fn get_key<C: Signing>(
     }
 }
 
+/// Checks [`Psbt::finalize`] against the reference finalizers of this file.
+#[test]
+fn finalize_matches_reference_finalizers() {
+    let secp = &Secp256k1::new();
+
+    let sk_path = [
+        ("dff1c8c2c016a572914b4c5adb8791d62b4768ae9d0a61be8ab94cf5038d7d90", "m/86'/1'/0'/0/0"),
+        ("1ede31b0e7e47c2afc65ffd158b1b1b9d3b752bba8fd117dc8b9e944a390e8d9", "m/86'/1'/0'/0/1"),
+        ("1fb777f1a6fb9b76724551f8bc8ad91b77f33b8c456d65d746035391d724922a", "m/86'/1'/0'/0/2"),
+    ];
+    let script1 = create_basic_single_sig_script(secp, sk_path[0].0);
+    let script2 = create_basic_single_sig_script(secp, sk_path[1].0);
+    let script3 = create_basic_single_sig_script(secp, sk_path[2].0);
+    let internal_key = Keypair::from_seckey_str(secp, sk_path[2].0).unwrap().x_only_public_key().0;
+    let tree = create_taproot_tree(secp, script1, script2.clone(), script3, internal_key);
+    let address = create_p2tr_address(tree.clone());
+    let to_address = "tb1pyfv094rr0vk28lf8v9yx3veaacdzg26ztqk4ga84zucqqhafnn5q9my9rz";
+    let to_address = to_address.parse::<Address<_>>().unwrap().assume_checked();
+
+    // The signatures produced by `psbt_sign_taproot`.
+    let signature = |sig: &str| taproot::Signature {
+        signature: sig.parse().unwrap(),
+        sighash_type: TapSighashType::Default,
+    };
+
+    let mut psbt = create_psbt_for_taproot_key_path_spend(address, to_address, tree.clone());
+    psbt.inputs[0].tap_key_sig = Some(signature("92864dc9e56b6260ecbd54ec16b94bb597a2e6be7cca0de89d75e17921e0e1528cba32dd04217175c237e1835b5db1c8b384401718514f9443dce933c6ba9c87"));
+    let mut finalized = psbt.clone();
+    finalized.finalize().unwrap();
+    assert_eq!(
+        finalized.extract_tx().unwrap(),
+        finalize_psbt_for_key_path_spend(psbt).extract_tx().unwrap()
+    );
+
+    let x_only_pubkey = Keypair::from_seckey_str(secp, sk_path[1].0).unwrap().x_only_public_key().0;
+    let mut psbt = create_psbt_for_taproot_script_path_spend(
+        address,
+        to_address,
+        tree,
+        x_only_pubkey,
+        sk_path[1].1,
+        script2.clone(),
+    );
+    psbt.inputs[0].tap_script_sigs.insert(
+        (x_only_pubkey.into(), script2.tapscript_leaf_hash()),
+        signature("9c1466e1631a58c55fcb8642ce5f7896314f4b565d92c5c80b17aa9abf56d22e0b5e5dcbcfe836bbd7d409491f58aa9e1f68a491ef8f05eef62fb50ffac85727"),
+    );
+    let mut finalized = psbt.clone();
+    finalized.finalize().unwrap();
+    assert_eq!(
+        finalized.extract_tx().unwrap(),
+        finalize_psbt_for_script_path_spend(psbt).extract_tx().unwrap()
+    );
+}
+
 fn create_basic_single_sig_script(secp: &Secp256k1<secp256k1::All>, sk: &str) -> ScriptBuf {
     let kp = Keypair::from_seckey_str(secp, sk).expect("failed to create keypair");
     let x_only_pubkey = kp.x_only_public_key().0;